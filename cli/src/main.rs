@@ -84,7 +84,17 @@ fn help(name: &str, opts: &Options) {
     if cfg!(feature = "rpi") {
         println!("    st7735s[:SPEC]      enables the ST7735S LCD console and configures it");
         println!("                        with the settings in SPEC, which is of the form:");
-        println!("                        fg_color=COLOR,bg_color=COLOR,font=NAME");
+        println!("                        fg_color=COLOR,bg_color=COLOR,font=NAME,panel=PANEL,");
+        println!("                        spi_mode=MODE,cs_polarity=POLARITY,fallback,");
+        println!("                        offset_x=N,offset_y=N");
+        println!("                        PANEL can be one of '128x128' (1.44in, the default)");
+        println!("                        or '160x128' (1.8in)");
+        println!("                        offset_x and offset_y override the position of the");
+        println!("                        visible area in the controller's memory");
+        println!("                        MODE can be 0 (the default) to 3 and POLARITY");
+        println!("                        can be 'low' (the default) or 'high'");
+        println!("                        fallback uses the text console if the LCD");
+        println!("                        cannot be set up");
     }
     println!("    text                enables the text-based console");
     println!();
@@ -213,19 +223,47 @@ fn setup_console(
         Err(io::Error::new(io::ErrorKind::InvalidInput, "SDL support not compiled in"))
     }
 
+    /// Creates the ST7735S console when Raspberry Pi support is built in.
+    ///
+    /// If `allow_fallback` is true and the LCD hardware cannot be set up, this returns the text
+    /// console instead of failing and reports which console was chosen.  Errors in `spec` are
+    /// always fatal.
     #[cfg(feature = "rpi")]
-    fn setup_st7735s_console(
+    fn new_rpi_console(
         signals_tx: Sender<Signal>,
         spec: &mut ConsoleSpec,
+        allow_fallback: bool,
     ) -> io::Result<Rc<RefCell<dyn Console>>> {
-        let console = endbasic_st7735s::new_console(
+        let result = endbasic_st7735s::try_new_console(
             endbasic_rpi::RppalPins::default(),
             endbasic_rpi::spi_bus_open,
-            endbasic_terminal::TerminalConsole::from_stdio(signals_tx)?,
+            endbasic_terminal::TerminalConsole::from_stdio(signals_tx.clone())?,
             spec,
             &endbasic_std::gfx::lcd::fonts::all_fonts(),
-        )?;
-        Ok(Rc::from(RefCell::from(console)))
+            signals_tx,
+        );
+        match result {
+            Ok(console) => {
+                if allow_fallback {
+                    eprintln!("Using the ST7735S console");
+                }
+                Ok(Rc::from(RefCell::from(console)))
+            }
+            Err((e, Some(keyboard))) if allow_fallback => {
+                eprintln!("Cannot set up the ST7735S console; using the text console: {}", e);
+                Ok(Rc::from(RefCell::from(keyboard)))
+            }
+            Err((e, _keyboard)) => Err(e),
+        }
+    }
+
+    #[cfg(feature = "rpi")]
+    fn setup_st7735s_console(
+        signals_tx: Sender<Signal>,
+        spec: &mut ConsoleSpec,
+    ) -> io::Result<Rc<RefCell<dyn Console>>> {
+        let allow_fallback = spec.take_flag("fallback");
+        new_rpi_console(signals_tx, spec, allow_fallback)
     }
 
     #[cfg(not(feature = "rpi"))]
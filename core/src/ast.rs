@@ -223,6 +223,31 @@ impl ExprType {
         }
     }
 
+    /// Returns the type denoted by the annotation character `ch`, or `None` if `ch` is not a type
+    /// annotation.
+    ///
+    /// This is the inverse of `annotation`, except that `!` is also accepted as an alias for `#`
+    /// because there is no single-precision type.
+    pub fn from_annotation(ch: char) -> Option<ExprType> {
+        match ch {
+            '?' => Some(ExprType::Boolean),
+            '#' | '!' => Some(ExprType::Double),
+            '%' => Some(ExprType::Integer),
+            '$' => Some(ExprType::Text),
+            _ => None,
+        }
+    }
+
+    /// Returns the keyword that names this type in `AS` clauses.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ExprType::Boolean => "BOOLEAN",
+            ExprType::Double => "DOUBLE",
+            ExprType::Integer => "INTEGER",
+            ExprType::Text => "STRING",
+        }
+    }
+
     /// Returns the default value to assign to this type.
     pub(crate) fn default_value(&self) -> Value {
         match self {
@@ -236,12 +261,7 @@ impl ExprType {
 
 impl fmt::Display for ExprType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            ExprType::Boolean => write!(f, "BOOLEAN"),
-            ExprType::Double => write!(f, "DOUBLE"),
-            ExprType::Integer => write!(f, "INTEGER"),
-            ExprType::Text => write!(f, "STRING"),
-        }
+        f.write_str(self.keyword())
     }
 }
 
@@ -841,6 +861,35 @@ pub enum Statement {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_exprtype_annotation_roundtrip() {
+        for vtype in [ExprType::Boolean, ExprType::Double, ExprType::Integer, ExprType::Text] {
+            assert_eq!(Some(vtype), ExprType::from_annotation(vtype.annotation()));
+        }
+    }
+
+    #[test]
+    fn test_exprtype_from_annotation() {
+        assert_eq!(Some(ExprType::Boolean), ExprType::from_annotation('?'));
+        assert_eq!(Some(ExprType::Double), ExprType::from_annotation('#'));
+        assert_eq!(Some(ExprType::Double), ExprType::from_annotation('!'));
+        assert_eq!(Some(ExprType::Integer), ExprType::from_annotation('%'));
+        assert_eq!(Some(ExprType::Text), ExprType::from_annotation('$'));
+        assert_eq!(None, ExprType::from_annotation('&'));
+        assert_eq!(None, ExprType::from_annotation('a'));
+    }
+
+    #[test]
+    fn test_exprtype_keyword() {
+        assert_eq!("BOOLEAN", ExprType::Boolean.keyword());
+        assert_eq!("DOUBLE", ExprType::Double.keyword());
+        assert_eq!("INTEGER", ExprType::Integer.keyword());
+        assert_eq!("STRING", ExprType::Text.keyword());
+        for vtype in [ExprType::Boolean, ExprType::Double, ExprType::Integer, ExprType::Text] {
+            assert_eq!(vtype.keyword(), format!("{}", vtype));
+        }
+    }
+
     #[test]
     fn test_varref_display() {
         assert_eq!("name", format!("{}", VarRef::new("name", None)));
@@ -14,26 +14,131 @@
 // under the License.
 
 //! Tokenizer for the EndBASIC language.
+//!
+//! The parser is the main consumer of the lexer, but the lexer is also exposed on its own for tools
+//! that need to inspect the source code at the token level, such as documentation generators.
 
 use crate::ast::{ExprType, VarRef};
 use crate::reader::{CharReader, CharSpan, LineCol};
+use std::collections::{HashMap, VecDeque};
 use std::{fmt, io};
 
 /// Result type for the public methods of this module.
 type Result<T> = std::result::Result<T, (LineCol, io::Error)>;
 
+/// Reasons why a token could not be recognized.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexError {
+    /// A character that cannot start any token.
+    UnknownChar(char),
+
+    /// A numeric literal with more than one decimal point.
+    TooManyDots,
+
+    /// An unexpected character within a numeric literal.
+    UnexpectedInNumber(char),
+
+    /// A double literal that cannot be parsed, along with the reason.
+    BadDouble(String, String),
+
+    /// An integer literal that cannot be parsed, along with the reason.
+    BadInteger(String, String),
+
+    /// An integer literal with leading zeros, which are rejected in strict mode.
+    LeadingZeros(String),
+
+    /// A decimal point in an integer literal that specifies a base.
+    NonIntegerBase,
+
+    /// An integer literal that specifies a base but has no digits.
+    NoDigits,
+
+    /// An `&` not followed by the base of an integer literal.
+    MissingBase,
+
+    /// An unknown base in an integer literal.
+    UnknownBase(char),
+
+    /// An integer literal cut short by the end of the input.
+    IncompleteInteger,
+
+    /// An unexpected character within a symbol.
+    UnexpectedInSymbol(char),
+
+    /// A symbol with more than one type annotation.
+    MultipleTypeAnnotations,
+
+    /// A symbol with the `&` annotation of the not yet supported 64-bit integer type.
+    UnsupportedLong,
+
+    /// A string cut short by the end of the input, along with the text read so far.
+    UnterminatedString(String),
+
+    /// A `@` not followed by a label name.
+    EmptyLabel,
+
+    /// An unexpected character within a label.
+    UnexpectedInLabel(char),
+
+    /// A symbol longer than the configured maximum.
+    IdentifierTooLong,
+
+    /// A string longer than the configured maximum.
+    StringTooLong,
+
+    /// A label longer than the configured maximum.
+    LabelTooLong,
+
+    /// A captured comment longer than the configured maximum.
+    CommentTooLong,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnknownChar(ch) => write!(f, "Unknown character: {}", ch),
+            LexError::TooManyDots => write!(f, "Too many dots in numeric literal"),
+            LexError::UnexpectedInNumber(ch) => {
+                write!(f, "Unexpected character in numeric literal: {}", ch)
+            }
+            LexError::BadDouble(s, e) => write!(f, "Bad double {}: {}", s, e),
+            LexError::BadInteger(s, e) => write!(f, "Bad integer {}: {}", s, e),
+            LexError::LeadingZeros(s) => write!(f, "Leading zeros in integer literal: {}", s),
+            LexError::NonIntegerBase => write!(f, "Numbers in base syntax must be integers"),
+            LexError::NoDigits => write!(f, "No digits in integer literal"),
+            LexError::MissingBase => write!(f, "Missing base in integer literal"),
+            LexError::UnknownBase(ch) => write!(f, "Unknown base {} in integer literal", ch),
+            LexError::IncompleteInteger => write!(f, "Incomplete integer due to EOF"),
+            LexError::UnexpectedInSymbol(ch) => write!(f, "Unexpected character in symbol: {}", ch),
+            LexError::MultipleTypeAnnotations => write!(f, "Multiple type annotations"),
+            LexError::UnsupportedLong => write!(f, "LONG integers are not supported yet"),
+            LexError::UnterminatedString(s) => write!(f, "Incomplete string due to EOF: {}", s),
+            LexError::EmptyLabel => write!(f, "Empty label name"),
+            LexError::UnexpectedInLabel(ch) => write!(f, "Unexpected character in label: {}", ch),
+            LexError::IdentifierTooLong => write!(f, "Identifier too long"),
+            LexError::StringTooLong => write!(f, "String too long"),
+            LexError::LabelTooLong => write!(f, "Label too long"),
+            LexError::CommentTooLong => write!(f, "Comment too long"),
+        }
+    }
+}
+
 /// Collection of valid tokens.
 ///
 /// Of special interest are the `Eof` and `Bad` tokens, both of which denote exceptional
 /// conditions and require special care.  `Eof` indicates that there are no more tokens.
 /// `Bad` indicates that a token was bad and contains the reason behind the problem, but the
 /// stream remains valid for extraction of further tokens.
-#[derive(Clone, PartialEq)]
-#[cfg_attr(test, derive(Debug))]
+///
+/// `Comment` is only returned when the lexer has been configured to capture comments with
+/// `Lexer::set_capture_comments` and holds the text that followed `REM` or `'`.
+#[allow(missing_docs)] // The canonical spelling of each token is given by `Display`.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Token {
     Eof,
     Eol,
-    Bad(String),
+    Bad(LexError),
+    Comment(String),
 
     Boolean(bool),
     Double(f64),
@@ -63,6 +168,8 @@ pub enum Token {
     GreaterEqual,
 
     And,
+    Eqv,
+    Imp,
     Not,
     Or,
     Xor,
@@ -95,15 +202,24 @@ pub enum Token {
     Then,
     To,
     Until,
+    Using,
     Wend,
     While,
 
+    /// The `SPC` pseudo-function of `PRINT`.  Not to be confused with the Space key.
+    PrintSpc,
+
+    /// The `TAB` pseudo-function of `PRINT`.  Not to be confused with `Key::Tab` in the console,
+    /// which represents the Tab key.
+    PrintTab,
+
     Dim,
     Shared,
     As,
     BooleanName,
     DoubleName,
     IntegerName,
+    LongName,
     TextName,
 }
 
@@ -117,6 +233,7 @@ impl fmt::Display for Token {
             Token::Eof => write!(f, "<<EOF>>"),
             Token::Eol => write!(f, "<<NEWLINE>>"),
             Token::Bad(s) => write!(f, "<<{}>>", s),
+            Token::Comment(s) => write!(f, "'{}", s),
 
             Token::Boolean(false) => write!(f, "FALSE"),
             Token::Boolean(true) => write!(f, "TRUE"),
@@ -147,6 +264,8 @@ impl fmt::Display for Token {
             Token::GreaterEqual => write!(f, ">="),
 
             Token::And => write!(f, "AND"),
+            Token::Eqv => write!(f, "EQV"),
+            Token::Imp => write!(f, "IMP"),
             Token::Not => write!(f, "NOT"),
             Token::Or => write!(f, "OR"),
             Token::Xor => write!(f, "XOR"),
@@ -179,16 +298,21 @@ impl fmt::Display for Token {
             Token::Then => write!(f, "THEN"),
             Token::To => write!(f, "TO"),
             Token::Until => write!(f, "UNTIL"),
+            Token::Using => write!(f, "USING"),
             Token::Wend => write!(f, "WEND"),
             Token::While => write!(f, "WHILE"),
 
+            Token::PrintSpc => write!(f, "SPC"),
+            Token::PrintTab => write!(f, "TAB"),
+
             Token::Dim => write!(f, "DIM"),
             Token::Shared => write!(f, "SHARED"),
             Token::As => write!(f, "AS"),
-            Token::BooleanName => write!(f, "BOOLEAN"),
-            Token::DoubleName => write!(f, "DOUBLE"),
-            Token::IntegerName => write!(f, "INTEGER"),
-            Token::TextName => write!(f, "STRING"),
+            Token::BooleanName => write!(f, "{}", ExprType::Boolean),
+            Token::DoubleName => write!(f, "{}", ExprType::Double),
+            Token::IntegerName => write!(f, "{}", ExprType::Integer),
+            Token::LongName => write!(f, "LONG"),
+            Token::TextName => write!(f, "{}", ExprType::Text),
         }
     }
 }
@@ -239,13 +363,12 @@ impl CharOps for char {
 #[cfg_attr(test, derive(PartialEq))]
 pub struct TokenSpan {
     /// The token itself.
-    pub(crate) token: Token,
+    pub token: Token,
 
     /// Start position of the token.
-    pub(crate) pos: LineCol,
+    pub pos: LineCol,
 
     /// Length of the token in characters.
-    #[allow(unused)] // TODO(jmmv): Use this in the parser.
     length: usize,
 }
 
@@ -254,30 +377,206 @@ impl TokenSpan {
     fn new(token: Token, pos: LineCol, length: usize) -> Self {
         Self { token, pos, length }
     }
+
+    /// Returns the length of the token in characters.
+    ///
+    /// Together with `pos`, this allows reconstructing the spacing that separated consecutive
+    /// tokens on the same line in the original input.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
+
+/// A non-fatal issue found while lexing.
+///
+/// Warnings point at code that is valid but possibly not what the user intended, and never alter
+/// the token stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexWarning {
+    /// Position of the token that raised the warning.
+    pub pos: LineCol,
+
+    /// Description of the issue.
+    pub message: String,
+}
+
+/// Maximum lengths, in characters, of the tokens that the lexer accepts.
+///
+/// Each limit is unset by default, which means the corresponding tokens can be of any length.
+/// Setting them protects against pathological inputs that would otherwise make the lexer buffer
+/// enormous tokens.
+#[derive(Clone, Copy, Default)]
+pub struct LexerLimits {
+    /// Maximum length of symbols and keywords, excluding their type annotation.
+    pub max_identifier_len: Option<usize>,
+
+    /// Maximum length of the contents of string literals, excluding their delimiters, and of
+    /// unquoted `DATA` items.
+    pub max_string_len: Option<usize>,
+
+    /// Maximum length of label names, excluding their `@` prefix.
+    pub max_label_len: Option<usize>,
+
+    /// Maximum length of captured comments, excluding their `REM` or `'` marker.
+    pub max_comment_len: Option<usize>,
+}
+
+/// Settings that control how the lexer recognizes tokens.
+///
+/// All settings can be combined, and the defaults are those used by `Lexer::from`.
+#[derive(Clone, Copy, Default)]
+pub struct LexerOptions<'a> {
+    /// Additional words to recognize as the tokens they map to, in addition to the built-in
+    /// keywords.
+    ///
+    /// The keys must be in uppercase and are matched case-insensitively, like the built-in
+    /// keywords.  Entries that have the same spelling as a built-in keyword replace it, and words
+    /// that match no keyword are still returned as `Token::Symbol`.  This allows experimenting
+    /// with dialects, such as localized keywords, without changing the built-in table.
+    pub keywords: Option<&'a HashMap<String, Token>>,
+
+    /// Whether to treat newlines between parenthesis as whitespace, which allows splitting long
+    /// argument lists across lines.
+    pub join_paren_lines: bool,
+
+    /// Maximum lengths of the tokens to accept.  Tokens longer than these are rejected as
+    /// `Token::Bad` and skipped in their entirety so that lexing can continue after them.
+    pub limits: LexerLimits,
+}
+
+/// Returns true if `s` has more characters than `limit`, if any.
+fn exceeds_limit(s: &str, limit: Option<usize>) -> bool {
+    match limit {
+        // The byte length is an upper bound of the character count, so check it first to avoid
+        // counting characters on every call.
+        Some(max) => s.len() > max && s.chars().count() > max,
+        None => false,
+    }
 }
 
 /// Iterator over the tokens of the language.
 pub struct Lexer<'a> {
     /// Peekable iterator over the characters to scan.
     input: CharReader<'a>,
+
+    /// Whether to return comments as `Token::Comment` instead of discarding them.
+    capture_comments: bool,
+
+    /// Whether to skip newlines that appear between parenthesis instead of returning `Token::Eol`.
+    join_paren_lines: bool,
+
+    /// Number of currently-open parenthesis.  Only tracked if `join_paren_lines` is true.
+    paren_depth: usize,
+
+    /// Whether to reject integer literals with superfluous leading zeros.
+    strict_numbers: bool,
+
+    /// Whether to record warnings about suspicious tokens in `warnings`.
+    collect_warnings: bool,
+
+    /// Warnings recorded so far.  Only populated if `collect_warnings` is true.
+    warnings: Vec<LexWarning>,
+
+    /// Whether we are reading the items of a `DATA` statement.  Set after returning `Token::Data`
+    /// and cleared at the end of the statement.
+    in_data: bool,
+
+    /// Additional keywords to recognize, keyed by their uppercase spelling, which take precedence
+    /// over the built-in keywords.
+    keywords: Option<&'a HashMap<String, Token>>,
+
+    /// Maximum lengths of the tokens to accept.
+    limits: LexerLimits,
+
+    /// Token already consumed from the input that has to be returned by the next call to `read`.
+    pending: Option<TokenSpan>,
 }
 
 impl<'a> Lexer<'a> {
     /// Creates a new lexer from the given readable.
     pub fn from(input: &'a mut dyn io::Read) -> Self {
-        Self { input: CharReader::from(input) }
+        Self::from_with_options(input, LexerOptions::default())
+    }
+
+    /// Creates a new lexer from the given readable configured with `options`.
+    pub fn from_with_options(input: &'a mut dyn io::Read, options: LexerOptions<'a>) -> Self {
+        Self {
+            input: CharReader::from(input),
+            capture_comments: false,
+            join_paren_lines: options.join_paren_lines,
+            paren_depth: 0,
+            strict_numbers: false,
+            collect_warnings: false,
+            warnings: vec![],
+            in_data: false,
+            keywords: options.keywords,
+            limits: options.limits,
+            pending: None,
+        }
+    }
+
+    /// Configures whether integer literals with superfluous leading zeros, such as `007`, are
+    /// rejected as `Token::Bad` (if `strict` is true) or accepted as decimal numbers (the default).
+    ///
+    /// Leading zeros never denote octal numbers in EndBASIC: octal literals are written with the
+    /// `&o` prefix.  Strict mode exists to catch typos from users that expect otherwise.  A lone
+    /// `0` and double literals such as `0.5` are always valid.
+    pub fn set_strict_numbers(&mut self, strict: bool) {
+        self.strict_numbers = strict;
+    }
+
+    /// Configures whether to record warnings about suspicious but valid tokens (if `collect` is
+    /// true) or not (the default).  Recorded warnings can be queried with `warnings`.
+    pub fn set_collect_warnings(&mut self, collect: bool) {
+        self.collect_warnings = collect;
+    }
+
+    /// Returns the warnings recorded so far, in input order.
+    pub fn warnings(&self) -> &[LexWarning] {
+        &self.warnings
+    }
+
+    /// Records a warning with `message` for the token at `pos` if warnings are being collected.
+    fn warn<S: Into<String>>(&mut self, pos: LineCol, message: S) {
+        if self.collect_warnings {
+            self.warnings.push(LexWarning { pos, message: message.into() });
+        }
+    }
+
+    /// Sets the distance between tab stops used to compute token positions to `length` columns,
+    /// which must be positive.  Defaults to 8.
+    pub fn set_tab_length(&mut self, length: usize) {
+        self.input.set_tab_length(length);
+    }
+
+    /// Returns true if a newline at the current position should be treated as whitespace.
+    fn skip_newline(&self) -> bool {
+        self.join_paren_lines && self.paren_depth > 0
+    }
+
+    /// Configures whether comments are returned as `Token::Comment` tokens (if `capture` is true)
+    /// or discarded (the default).
+    pub fn set_capture_comments(&mut self, capture: bool) {
+        self.capture_comments = capture;
     }
 
     /// Handles an `input.next()` call that returned an unexpected character.
     ///
-    /// This returns a `Token::Bad` with the provided `msg` and skips characters in the input
+    /// This returns a `Token::Bad` with the provided `err` and skips characters in the input
     /// stream until a field separator is found.
-    fn handle_bad_read<S: Into<String>>(
+    fn handle_bad_read(&mut self, err: LexError, first_pos: LineCol) -> io::Result<TokenSpan> {
+        self.skip_bad_token(err, first_pos, 1)
+    }
+
+    /// Same as `handle_bad_read` but for a token of which `consumed` characters have already been
+    /// read, which are accounted for in the length of the returned token.
+    fn skip_bad_token(
         &mut self,
-        msg: S,
+        err: LexError,
         first_pos: LineCol,
+        consumed: usize,
     ) -> io::Result<TokenSpan> {
-        let mut len = 1;
+        let mut len = consumed;
         loop {
             match self.input.peek() {
                 Some(Ok(ch_span)) if ch_span.ch.is_separator() => break,
@@ -289,7 +588,7 @@ impl<'a> Lexer<'a> {
                 None => break,
             }
         }
-        Ok(TokenSpan::new(Token::Bad(msg.into()), first_pos, len))
+        Ok(TokenSpan::new(Token::Bad(err), first_pos, len))
     }
 
     /// Consumes the number at the current position, whose first digit is `first`.
@@ -303,8 +602,7 @@ impl<'a> Lexer<'a> {
                     '.' => {
                         if found_dot {
                             self.input.next().unwrap()?;
-                            return self
-                                .handle_bad_read("Too many dots in numeric literal", first.pos);
+                            return self.handle_bad_read(LexError::TooManyDots, first.pos);
                         }
                         s.push(self.input.next().unwrap()?.ch);
                         found_dot = true;
@@ -313,8 +611,7 @@ impl<'a> Lexer<'a> {
                     ch if ch.is_separator() => break,
                     ch => {
                         self.input.next().unwrap()?;
-                        let msg = format!("Unexpected character in numeric literal: {}", ch);
-                        return self.handle_bad_read(msg, first.pos);
+                        return self.handle_bad_read(LexError::UnexpectedInNumber(ch), first.pos);
                     }
                 },
                 Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
@@ -326,16 +623,34 @@ impl<'a> Lexer<'a> {
                 // TODO(jmmv): Reconsider supporting double literals with a . that is not prefixed
                 // by a number or not followed by a number.  For now, mimic the error we get when
                 // we encounter a dot not prefixed by a number.
-                return self.handle_bad_read("Unknown character: .", first.pos);
+                return self.handle_bad_read(LexError::UnknownChar('.'), first.pos);
             }
             match s.parse::<f64>() {
-                Ok(d) => Ok(TokenSpan::new(Token::Double(d), first.pos, s.len())),
-                Err(e) => self.handle_bad_read(format!("Bad double {}: {}", s, e), first.pos),
+                Ok(d) => {
+                    if d.fract() == 0.0 && d.abs() <= f64::from(i32::MAX) {
+                        self.warn(
+                            first.pos,
+                            format!("Double literal {} could be written as an integer", s),
+                        );
+                    }
+                    Ok(TokenSpan::new(Token::Double(d), first.pos, s.len()))
+                }
+                Err(e) => {
+                    let err = LexError::BadDouble(s, e.to_string());
+                    self.handle_bad_read(err, first.pos)
+                }
             }
         } else {
+            if self.strict_numbers && s.len() > 1 && s.starts_with('0') {
+                let len = s.len();
+                return Ok(TokenSpan::new(Token::Bad(LexError::LeadingZeros(s)), first.pos, len));
+            }
             match s.parse::<i32>() {
                 Ok(i) => Ok(TokenSpan::new(Token::Integer(i), first.pos, s.len())),
-                Err(e) => self.handle_bad_read(format!("Bad integer {}: {}", s, e), first.pos),
+                Err(e) => {
+                    let err = LexError::BadInteger(s, e.to_string());
+                    self.handle_bad_read(err, first.pos)
+                }
             }
         }
     }
@@ -355,16 +670,14 @@ impl<'a> Lexer<'a> {
                 Some(Ok(ch_span)) => match ch_span.ch {
                     '.' => {
                         self.input.next().unwrap()?;
-                        return self
-                            .handle_bad_read("Numbers in base syntax must be integers", pos);
+                        return self.handle_bad_read(LexError::NonIntegerBase, pos);
                     }
                     ch if ch.is_ascii_digit() => s.push(self.input.next().unwrap()?.ch),
                     'a'..='f' | 'A'..='F' => s.push(self.input.next().unwrap()?.ch),
                     ch if ch.is_separator() => break,
                     ch => {
                         self.input.next().unwrap()?;
-                        let msg = format!("Unexpected character in numeric literal: {}", ch);
-                        return self.handle_bad_read(msg, pos);
+                        return self.handle_bad_read(LexError::UnexpectedInNumber(ch), pos);
                     }
                 },
                 Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
@@ -372,12 +685,15 @@ impl<'a> Lexer<'a> {
             }
         }
         if s.is_empty() {
-            return self.handle_bad_read("No digits in integer literal", pos);
+            return self.handle_bad_read(LexError::NoDigits, pos);
         }
 
         match u32::from_str_radix(&s, u32::from(base)) {
             Ok(i) => Ok(TokenSpan::new(Token::Integer(i as i32), pos, s.len() + prefix_len)),
-            Err(e) => self.handle_bad_read(format!("Bad integer {}: {}", s, e), pos),
+            Err(e) => {
+                let err = LexError::BadInteger(s, e.to_string());
+                self.handle_bad_read(err, pos)
+            }
         }
     }
 
@@ -393,14 +709,11 @@ impl<'a> Lexer<'a> {
                     'o' | 'O' => 8,
                     'x' | 'X' => 16,
                     ch if ch.is_separator() => {
-                        return self.handle_bad_read("Missing base in integer literal", pos);
+                        return self.handle_bad_read(LexError::MissingBase, pos);
                     }
                     _ => {
                         let ch_span = self.input.next().unwrap()?;
-                        return self.handle_bad_read(
-                            format!("Unknown base {} in integer literal", ch_span.ch),
-                            pos,
-                        );
+                        return self.handle_bad_read(LexError::UnknownBase(ch_span.ch), pos);
                     }
                 };
                 self.input.next().unwrap()?;
@@ -408,7 +721,7 @@ impl<'a> Lexer<'a> {
             }
             Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
             None => {
-                return self.handle_bad_read("Incomplete integer due to EOF", pos);
+                return self.handle_bad_read(LexError::IncompleteInteger, pos);
             }
         };
         prefix_len += 1; // Count the base.
@@ -420,7 +733,7 @@ impl<'a> Lexer<'a> {
             }
             Some(Ok(_ch_span)) => (),
             Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
-            None => return self.handle_bad_read("Incomplete integer due to EOF", pos),
+            None => return self.handle_bad_read(LexError::IncompleteInteger, pos),
         }
 
         self.consume_integer(base, pos, prefix_len)
@@ -471,36 +784,39 @@ impl<'a> Lexer<'a> {
         loop {
             match self.input.peek() {
                 Some(Ok(ch_span)) => match ch_span.ch {
-                    ch if ch.is_word() => s.push(self.input.next().unwrap()?.ch),
-                    ch if ch.is_separator() => break,
-                    '?' => {
-                        vtype = Some(ExprType::Boolean);
-                        self.input.next().unwrap()?;
-                        token_len += 1;
-                        break;
-                    }
-                    '#' => {
-                        vtype = Some(ExprType::Double);
-                        self.input.next().unwrap()?;
-                        token_len += 1;
-                        break;
+                    ch if ch.is_word() => {
+                        s.push(self.input.next().unwrap()?.ch);
+                        if exceeds_limit(&s, self.limits.max_identifier_len) {
+                            let consumed = s.chars().count();
+                            return self.skip_bad_token(
+                                LexError::IdentifierTooLong,
+                                first.pos,
+                                consumed,
+                            );
+                        }
                     }
-                    '%' => {
-                        vtype = Some(ExprType::Integer);
-                        self.input.next().unwrap()?;
-                        token_len += 1;
-                        break;
+                    ch if ch.is_separator() => break,
+                    '"' if s == "r" || s == "R" => {
+                        let delim = self.input.next().unwrap()?;
+                        return self.consume_raw_text(first, delim);
                     }
-                    '$' => {
-                        vtype = Some(ExprType::Text);
+                    '&' => {
                         self.input.next().unwrap()?;
-                        token_len += 1;
-                        break;
+                        return self.handle_bad_read(LexError::UnsupportedLong, first.pos);
                     }
                     ch => {
                         self.input.next().unwrap()?;
-                        let msg = format!("Unexpected character in symbol: {}", ch);
-                        return self.handle_bad_read(msg, first.pos);
+                        match ExprType::from_annotation(ch) {
+                            Some(annotation) => {
+                                vtype = Some(annotation);
+                                token_len += 1;
+                                break;
+                            }
+                            None => {
+                                return self
+                                    .handle_bad_read(LexError::UnexpectedInSymbol(ch), first.pos)
+                            }
+                        }
                     }
                 },
                 Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
@@ -509,52 +825,79 @@ impl<'a> Lexer<'a> {
         }
         debug_assert!(token_len <= 1);
 
+        if vtype.is_some() {
+            if let Some(Ok(ch_span)) = self.input.peek() {
+                if ch_span.ch == '&' || ExprType::from_annotation(ch_span.ch).is_some() {
+                    self.input.next().unwrap()?;
+                    return self.handle_bad_read(LexError::MultipleTypeAnnotations, first.pos);
+                }
+            }
+        }
+
         token_len += s.len();
-        let token = match s.to_uppercase().as_str() {
-            "AND" => Token::And,
-            "AS" => Token::As,
-            "BOOLEAN" => Token::BooleanName,
-            "CASE" => Token::Case,
-            "DATA" => Token::Data,
-            "DIM" => Token::Dim,
-            "DO" => Token::Do,
-            "DOUBLE" => Token::DoubleName,
-            "ELSE" => Token::Else,
-            "ELSEIF" => Token::Elseif,
-            "END" => Token::End,
-            "ERROR" => Token::Error,
-            "EXIT" => Token::Exit,
-            "FALSE" => Token::Boolean(false),
-            "FOR" => Token::For,
-            "FUNCTION" => Token::Function,
-            "GOSUB" => Token::Gosub,
-            "GOTO" => Token::Goto,
-            "IF" => Token::If,
-            "IS" => Token::Is,
-            "INTEGER" => Token::IntegerName,
-            "LOOP" => Token::Loop,
-            "MOD" => Token::Modulo,
-            "NEXT" => Token::Next,
-            "NOT" => Token::Not,
-            "ON" => Token::On,
-            "OR" => Token::Or,
-            "REM" => return self.consume_rest_of_line(),
-            "RESUME" => Token::Resume,
-            "RETURN" => Token::Return,
-            "SELECT" => Token::Select,
-            "SHARED" => Token::Shared,
-            "STEP" => Token::Step,
-            "STRING" => Token::TextName,
-            "SUB" => Token::Sub,
-            "THEN" => Token::Then,
-            "TO" => Token::To,
-            "TRUE" => Token::Boolean(true),
-            "UNTIL" => Token::Until,
-            "WEND" => Token::Wend,
-            "WHILE" => Token::While,
-            "XOR" => Token::Xor,
-            _ => Token::Symbol(VarRef::new(s, vtype)),
+        let mixed_case = s != s.to_uppercase() && s != s.to_lowercase();
+        let upper = s.to_uppercase();
+        let custom = self.keywords.and_then(|keywords| keywords.get(&upper));
+        let token = match custom {
+            Some(token) => token.clone(),
+            None => match upper.as_str() {
+                "AND" => Token::And,
+                "AS" => Token::As,
+                "BOOLEAN" => Token::BooleanName,
+                "CASE" => Token::Case,
+                "DATA" => Token::Data,
+                "DIM" => Token::Dim,
+                "DO" => Token::Do,
+                "DOUBLE" => Token::DoubleName,
+                "ELSE" => Token::Else,
+                "ELSEIF" => Token::Elseif,
+                "END" => Token::End,
+                "EQV" => Token::Eqv,
+                "ERROR" => Token::Error,
+                "EXIT" => Token::Exit,
+                "FALSE" => Token::Boolean(false),
+                "FOR" => Token::For,
+                "FUNCTION" => Token::Function,
+                "GOSUB" => Token::Gosub,
+                "GOTO" => Token::Goto,
+                "IF" => Token::If,
+                "IMP" => Token::Imp,
+                "IS" => Token::Is,
+                "INTEGER" => Token::IntegerName,
+                "LONG" => Token::LongName,
+                "LOOP" => Token::Loop,
+                "MOD" => Token::Modulo,
+                "NEXT" => Token::Next,
+                "NOT" => Token::Not,
+                "ON" => Token::On,
+                "OR" => Token::Or,
+                "REM" => return self.consume_rest_of_line(first.pos, token_len),
+                "RESUME" => Token::Resume,
+                "RETURN" => Token::Return,
+                "SELECT" => Token::Select,
+                "SHARED" => Token::Shared,
+                "SPC" => Token::PrintSpc,
+                "STEP" => Token::Step,
+                "STRING" => Token::TextName,
+                "SUB" => Token::Sub,
+                "TAB" => Token::PrintTab,
+                "THEN" => Token::Then,
+                "TO" => Token::To,
+                "TRUE" => Token::Boolean(true),
+                "UNTIL" => Token::Until,
+                "USING" => Token::Using,
+                "WEND" => Token::Wend,
+                "WHILE" => Token::While,
+                "XOR" => Token::Xor,
+                // Anything else is a symbol, including the names of builtin commands and functions.
+                // In particular, program management commands like `NEW`, `LOAD` and `SAVE` are just
+                // builtin commands provided by the standard library, not keywords.
+                _ => Token::Symbol(VarRef::new(s, vtype)),
+            },
         };
+        if mixed_case && !matches!(token, Token::Symbol(_)) {
+            self.warn(first.pos, format!("Keyword {} written in mixed case", token));
+        }
         Ok(TokenSpan::new(token, first.pos, token_len))
     }
 
@@ -565,35 +908,95 @@ impl<'a> Lexer<'a> {
     fn consume_text(&mut self, delim: CharSpan) -> io::Result<TokenSpan> {
         let mut s = String::new();
         let mut escaping = false;
+        let mut too_long = false;
+        let mut consumed = 1; // Count the opening delimiter.
         loop {
             match self.input.peek() {
                 Some(Ok(ch_span)) => {
+                    let is_delim = !escaping && ch_span.ch == delim.ch;
+                    let ch_span = self.input.next().unwrap()?;
+                    consumed += 1;
                     if escaping {
-                        s.push(self.input.next().unwrap()?.ch);
+                        s.push(ch_span.ch);
                         escaping = false;
                     } else if ch_span.ch == '\\' {
-                        self.input.next().unwrap()?;
                         escaping = true;
-                    } else if ch_span.ch == delim.ch {
-                        self.input.next().unwrap()?;
+                    } else if is_delim {
                         break;
                     } else {
-                        s.push(self.input.next().unwrap()?.ch);
+                        s.push(ch_span.ch);
                     }
                 }
                 Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
                 None => {
-                    return self.handle_bad_read(
-                        format!("Incomplete string due to EOF: {}", s),
-                        delim.pos,
-                    );
+                    if too_long {
+                        let token = Token::Bad(LexError::StringTooLong);
+                        return Ok(TokenSpan::new(token, delim.pos, consumed));
+                    }
+                    return self.handle_bad_read(LexError::UnterminatedString(s), delim.pos);
                 }
             }
+
+            // Keep scanning for the closing delimiter once the string is too long to resume
+            // lexing after it, but drop the excess text to bound memory usage.
+            if too_long || exceeds_limit(&s, self.limits.max_string_len) {
+                too_long = true;
+                s.clear();
+            }
+        }
+        if too_long {
+            return Ok(TokenSpan::new(Token::Bad(LexError::StringTooLong), delim.pos, consumed));
         }
         let token_len = s.len() + 2;
         Ok(TokenSpan::new(Token::Text(s), delim.pos, token_len))
     }
 
+    /// Consumes the raw string at the current position, whose `r` prefix is `prefix` and whose
+    /// opening quote is `delim`.
+    ///
+    /// Backslashes in raw strings have no special meaning.  The only way to include the delimiter
+    /// in the string is to double it.
+    fn consume_raw_text(&mut self, prefix: CharSpan, delim: CharSpan) -> io::Result<TokenSpan> {
+        let mut s = String::new();
+        let mut token_len = 3; // Count the prefix and the two delimiters.
+        let mut too_long = false;
+        loop {
+            match self.input.next() {
+                Some(Ok(ch_span)) if ch_span.ch == delim.ch => match self.input.peek() {
+                    Some(Ok(ch_span)) if ch_span.ch == delim.ch => {
+                        s.push(self.input.next().unwrap()?.ch);
+                        token_len += 2;
+                    }
+                    Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
+                    _ => break,
+                },
+                Some(Ok(ch_span)) => {
+                    s.push(ch_span.ch);
+                    token_len += 1;
+                }
+                Some(Err(e)) => return Err(e),
+                None => {
+                    if too_long {
+                        // The closing delimiter was counted upfront but is missing.
+                        let token = Token::Bad(LexError::StringTooLong);
+                        return Ok(TokenSpan::new(token, prefix.pos, token_len - 1));
+                    }
+                    return self.handle_bad_read(LexError::UnterminatedString(s), prefix.pos);
+                }
+            }
+
+            // Same as in `consume_text`: drop the excess text but find the end of the string.
+            if too_long || exceeds_limit(&s, self.limits.max_string_len) {
+                too_long = true;
+                s.clear();
+            }
+        }
+        if too_long {
+            return Ok(TokenSpan::new(Token::Bad(LexError::StringTooLong), prefix.pos, token_len));
+        }
+        Ok(TokenSpan::new(Token::Text(s), prefix.pos, token_len))
+    }
+
     /// Consumes the label definition at the current position.
     fn consume_label(&mut self, first: CharSpan) -> io::Result<TokenSpan> {
         let mut s = String::new();
@@ -607,17 +1010,26 @@ impl<'a> Lexer<'a> {
             None => (),
         }
         if s.is_empty() {
-            return Ok(TokenSpan::new(Token::Bad("Empty label name".to_owned()), first.pos, 1));
+            return Ok(TokenSpan::new(Token::Bad(LexError::EmptyLabel), first.pos, 1));
         }
 
         loop {
             match self.input.peek() {
                 Some(Ok(ch_span)) => match ch_span.ch {
-                    ch if ch.is_word() => s.push(self.input.next().unwrap()?.ch),
+                    ch if ch.is_word() => {
+                        s.push(self.input.next().unwrap()?.ch);
+                        if exceeds_limit(&s, self.limits.max_label_len) {
+                            let consumed = s.chars().count() + 1;
+                            return self.skip_bad_token(
+                                LexError::LabelTooLong,
+                                first.pos,
+                                consumed,
+                            );
+                        }
+                    }
                     ch if ch.is_separator() => break,
                     ch => {
-                        let msg = format!("Unexpected character in label: {}", ch);
-                        return self.handle_bad_read(msg, first.pos);
+                        return self.handle_bad_read(LexError::UnexpectedInLabel(ch), first.pos);
                     }
                 },
                 Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
@@ -631,7 +1043,42 @@ impl<'a> Lexer<'a> {
 
     /// Consumes the remainder of the line and returns the token that was encountered at the end
     /// (which may be EOF or end of line).
-    fn consume_rest_of_line(&mut self) -> io::Result<TokenSpan> {
+    ///
+    /// If the lexer is capturing comments, this instead returns the remainder of the line as a
+    /// `Token::Comment` and leaves the end of line in the input.  `first_pos` and `marker_len`
+    /// describe the `REM` or `'` that started the comment.
+    fn consume_rest_of_line(
+        &mut self,
+        first_pos: LineCol,
+        marker_len: usize,
+    ) -> io::Result<TokenSpan> {
+        if self.capture_comments {
+            let mut s = String::new();
+            let mut len = 0;
+            let mut too_long = false;
+            loop {
+                match self.input.peek() {
+                    Some(Ok(ch_span)) if ch_span.ch == '\n' => break,
+                    Some(Ok(_)) => {
+                        let ch = self.input.next().unwrap()?.ch;
+                        len += 1;
+                        if !too_long {
+                            s.push(ch);
+                            if exceeds_limit(&s, self.limits.max_comment_len) {
+                                too_long = true;
+                                s.clear();
+                            }
+                        }
+                    }
+                    Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
+                    None => break,
+                }
+            }
+            let token =
+                if too_long { Token::Bad(LexError::CommentTooLong) } else { Token::Comment(s) };
+            return Ok(TokenSpan::new(token, first_pos, marker_len + len));
+        }
+
         loop {
             match self.input.next() {
                 None => {
@@ -639,7 +1086,10 @@ impl<'a> Lexer<'a> {
                     return Ok(TokenSpan::new(Token::Eof, last_pos, 0));
                 }
                 Some(Ok(ch_span)) if ch_span.ch == '\n' => {
-                    return Ok(TokenSpan::new(Token::Eol, ch_span.pos, 1))
+                    if self.skip_newline() {
+                        return self.read();
+                    }
+                    return Ok(TokenSpan::new(Token::Eol, ch_span.pos, 1));
                 }
                 Some(Err(e)) => return Err(e),
                 Some(Ok(_)) => (),
@@ -647,12 +1097,158 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Consumes the unquoted `DATA` item at the current position, whose first character is
+    /// `first`.
+    ///
+    /// The item extends until the next comma or the end of the statement, excluding trailing
+    /// whitespace.  The item is returned as a literal if it represents a single boolean or a
+    /// possibly-negative number, and as text otherwise.
+    ///
+    /// The words `ELSE` and `REM` also end the item, as they do in any other statement, so that
+    /// `DATA` can appear in the `THEN` branch of an uniline `IF` and be followed by a comment.
+    fn consume_datum(&mut self, first: CharSpan) -> io::Result<TokenSpan> {
+        let mut s = String::new();
+        s.push(first.ch);
+        let mut end_col = first.pos.col + 1;
+
+        // Start offset in `s`, position and `end_col` before the word being read, if any.  Only
+        // tracked for words that follow whitespace, as these are the only ones that can be
+        // keywords.
+        let mut word = if first.ch.is_word() { Some((0, first.pos, first.pos.col)) } else { None };
+        let mut keyword = None;
+        let mut prev_space = false;
+        let mut too_long = false;
+        loop {
+            let next = match self.input.peek() {
+                Some(Ok(ch_span)) => Some(ch_span.ch),
+                Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
+                None => None,
+            };
+
+            if !next.map(|ch| ch.is_word()).unwrap_or(false) {
+                if let Some((start, pos, prev_end_col)) = word.take() {
+                    if next.map(|ch| ch.is_separator()).unwrap_or(true) {
+                        let upper = s[start..].to_uppercase();
+                        if upper == "ELSE" || upper == "REM" {
+                            s.truncate(start);
+                            end_col = prev_end_col;
+                            keyword = Some((upper, pos));
+                            break;
+                        }
+                    }
+                }
+            }
+
+            match next {
+                Some('\n' | ':' | '\'' | ',') | None => break,
+                Some('"') if !too_long && (s == "r" || s == "R") => {
+                    let delim = self.input.next().unwrap()?;
+                    return self.consume_raw_text(first, delim);
+                }
+                Some(_) => {
+                    let ch_span = self.input.next().unwrap()?;
+                    if ch_span.ch.is_word() {
+                        if word.is_none() && prev_space {
+                            word = Some((s.len(), ch_span.pos, end_col));
+                        }
+                    } else {
+                        word = None;
+                    }
+                    prev_space = ch_span.ch.is_space();
+                    if !prev_space {
+                        end_col = ch_span.pos.col + 1;
+                    }
+                    s.push(ch_span.ch);
+
+                    // Keep scanning for the end of the item once it is too long to resume lexing
+                    // after it, but only retain the word that may end it to bound memory usage.
+                    if too_long || exceeds_limit(s.trim_end(), self.limits.max_string_len) {
+                        too_long = true;
+                        match word {
+                            Some((start, pos, prev_end_col)) if s.len() - start <= 4 => {
+                                s.drain(..start);
+                                word = Some((0, pos, prev_end_col));
+                            }
+                            _ => {
+                                s.clear();
+                                word = None;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((keyword, pos)) = keyword {
+            let keyword_span = if keyword == "ELSE" {
+                TokenSpan::new(Token::Else, pos, keyword.len())
+            } else {
+                self.consume_rest_of_line(pos, keyword.len())?
+            };
+            if s.is_empty() && !too_long {
+                return Ok(keyword_span);
+            }
+            self.pending = Some(keyword_span);
+        }
+        if too_long {
+            let token = Token::Bad(LexError::StringTooLong);
+            return Ok(TokenSpan::new(token, first.pos, end_col - first.pos.col));
+        }
+
+        let s = s.trim_end().to_owned();
+
+        let (negative, literal) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.as_str()),
+        };
+        let mut input = literal.as_bytes();
+        let mut lexer = Lexer::from(&mut input);
+        lexer.strict_numbers = self.strict_numbers;
+        let literal = match (lexer.read(), lexer.read()) {
+            (Ok(span), Ok(TokenSpan { token: Token::Eof, .. })) => Some(span.token),
+            _ => None,
+        };
+
+        let token = match (negative, literal) {
+            (false, Some(token @ (Token::Boolean(_) | Token::Double(_) | Token::Integer(_)))) => {
+                token
+            }
+            (true, Some(Token::Double(d))) => Token::Double(-d),
+            (true, Some(Token::Integer(i))) => Token::Integer(-i),
+            _ => Token::Text(s),
+        };
+        Ok(TokenSpan::new(token, first.pos, end_col - first.pos.col))
+    }
+
+    /// Reads the next item of a `DATA` statement from the input stream.
+    ///
+    /// Items are separated by commas and extend until the end of the statement.  Quoted strings
+    /// are processed as in any other context, so they must be used to include commas, colons or
+    /// comment markers in an item.
+    fn read_datum(&mut self) -> io::Result<TokenSpan> {
+        let ch_span = match self.advance_and_read_next()? {
+            Some(ch_span) => ch_span,
+            None => {
+                let last_pos = self.input.next_pos();
+                return Ok(TokenSpan::new(Token::Eof, last_pos, 0));
+            }
+        };
+        match ch_span.ch {
+            '\n' | ':' => Ok(TokenSpan::new(Token::Eol, ch_span.pos, 1)),
+            '\'' => self.consume_rest_of_line(ch_span.pos, 1),
+            '"' => self.consume_text(ch_span),
+            ',' => Ok(TokenSpan::new(Token::Comma, ch_span.pos, 1)),
+            _ => self.consume_datum(ch_span),
+        }
+    }
+
     /// Skips whitespace until it finds the beginning of the next token, and returns its first
     /// character.
     fn advance_and_read_next(&mut self) -> io::Result<Option<CharSpan>> {
         loop {
             match self.input.next() {
                 Some(Ok(ch_span)) if ch_span.ch.is_space() => (),
+                Some(Ok(ch_span)) if ch_span.ch == '\n' && self.skip_newline() => (),
                 Some(Ok(ch_span)) => return Ok(Some(ch_span)),
                 Some(Err(e)) => return Err(e),
                 None => return Ok(None),
@@ -664,7 +1260,26 @@ impl<'a> Lexer<'a> {
     ///
     /// Note that this returns errors only on fatal I/O conditions.  EOF and malformed tokens are
     /// both returned as the special token types `Token::Eof` and `Token::Bad` respectively.
+    ///
+    /// The items that follow a `Token::Data` are not tokenized as expressions: each item up to
+    /// the next comma is returned as a single `Token::Text`, `Token::Boolean`, `Token::Integer` or
+    /// `Token::Double` until the end of the statement.
     pub fn read(&mut self) -> io::Result<TokenSpan> {
+        let token_span = match self.pending.take() {
+            Some(token_span) => token_span,
+            None if self.in_data => self.read_datum()?,
+            None => self.read_token()?,
+        };
+        match token_span.token {
+            Token::Data => self.in_data = true,
+            Token::Eof | Token::Eol | Token::Else => self.in_data = false,
+            _ => (),
+        }
+        Ok(token_span)
+    }
+
+    /// Reads the next token from the input stream outside of a `DATA` statement.
+    fn read_token(&mut self) -> io::Result<TokenSpan> {
         let ch_span = self.advance_and_read_next()?;
         if ch_span.is_none() {
             let last_pos = self.input.next_pos();
@@ -673,15 +1288,25 @@ impl<'a> Lexer<'a> {
         let ch_span = ch_span.unwrap();
         match ch_span.ch {
             '\n' | ':' => Ok(TokenSpan::new(Token::Eol, ch_span.pos, 1)),
-            '\'' => self.consume_rest_of_line(),
+            '\'' => self.consume_rest_of_line(ch_span.pos, 1),
 
             '"' => self.consume_text(ch_span),
 
             ';' => Ok(TokenSpan::new(Token::Semicolon, ch_span.pos, 1)),
             ',' => Ok(TokenSpan::new(Token::Comma, ch_span.pos, 1)),
 
-            '(' => Ok(TokenSpan::new(Token::LeftParen, ch_span.pos, 1)),
-            ')' => Ok(TokenSpan::new(Token::RightParen, ch_span.pos, 1)),
+            '(' => {
+                if self.join_paren_lines {
+                    self.paren_depth += 1;
+                }
+                Ok(TokenSpan::new(Token::LeftParen, ch_span.pos, 1))
+            }
+            ')' => {
+                if self.join_paren_lines {
+                    self.paren_depth = self.paren_depth.saturating_sub(1);
+                }
+                Ok(TokenSpan::new(Token::RightParen, ch_span.pos, 1))
+            }
 
             '+' => Ok(TokenSpan::new(Token::Plus, ch_span.pos, 1)),
             '-' => Ok(TokenSpan::new(Token::Minus, ch_span.pos, 1)),
@@ -694,17 +1319,34 @@ impl<'a> Lexer<'a> {
 
             '@' => self.consume_label(ch_span),
 
+            // A leading `?` is the classic shorthand for `PRINT`.  A `?` that follows a name is a
+            // type annotation and is handled by `consume_symbol` instead.
+            '?' => Ok(TokenSpan::new(Token::Symbol(VarRef::new("PRINT", None)), ch_span.pos, 1)),
+
             '&' => self.consume_integer_with_base(ch_span.pos),
 
             ch if ch.is_ascii_digit() => self.consume_number(ch_span),
             ch if ch.is_word() => self.consume_symbol(ch_span),
-            ch => self.handle_bad_read(format!("Unknown character: {}", ch), ch_span.pos),
+            ch => self.handle_bad_read(LexError::UnknownChar(ch), ch_span.pos),
         }
     }
 
+    /// Discards any state about the current input and starts lexing `input` from line 1, column 1.
+    ///
+    /// All settings (such as the tab length or whether comments are captured) are preserved, and
+    /// the internal buffers are reused so that lexing many small inputs does not reallocate them.
+    /// Any recorded warnings are discarded.
+    pub fn reset(&mut self, input: &'a mut dyn io::Read) {
+        self.input.reset(input);
+        self.paren_depth = 0;
+        self.warnings.clear();
+        self.in_data = false;
+        self.pending = None;
+    }
+
     /// Returns a peekable adaptor for this lexer.
     pub fn peekable(self) -> PeekableLexer<'a> {
-        PeekableLexer { lexer: self, peeked: None }
+        PeekableLexer { lexer: self, peeked: VecDeque::new() }
     }
 }
 
@@ -717,9 +1359,9 @@ pub struct PeekableLexer<'a> {
     /// The wrapped lexer instance.
     lexer: Lexer<'a>,
 
-    /// If not none, contains the character read by `peek`, which will be consumed by the next call
-    /// to `read` or `consume_peeked`.
-    peeked: Option<TokenSpan>,
+    /// Tokens read by `peek` or `peek_nth`, in input order, which will be consumed by the next
+    /// calls to `read` or `consume_peeked`.
+    peeked: VecDeque<TokenSpan>,
 }
 
 impl PeekableLexer<'_> {
@@ -728,8 +1370,8 @@ impl PeekableLexer<'_> {
     /// Because `peek` reports read errors, this assumes that the caller already handled those
     /// errors and is thus not going to call this when an error is present.
     pub fn consume_peeked(&mut self) -> TokenSpan {
-        assert!(self.peeked.is_some());
-        self.peeked.take().unwrap()
+        assert!(!self.peeked.is_empty());
+        self.peeked.pop_front().unwrap()
     }
 
     /// Peeks the upcoming token.
@@ -737,11 +1379,23 @@ impl PeekableLexer<'_> {
     /// It is OK to call this function several times on the same token before extracting it from
     /// the lexer.
     pub fn peek(&mut self) -> Result<&TokenSpan> {
-        if self.peeked.is_none() {
-            let span = self.read()?;
-            self.peeked.replace(span);
+        self.peek_nth(0)
+    }
+
+    /// Peeks the token `n` positions after the upcoming one, so `peek_nth(0)` is the same as
+    /// `peek()`.
+    ///
+    /// All tokens up to the requested one are buffered and are returned in order by subsequent
+    /// calls to `read` or `consume_peeked`.  If reading any of them fails, the error is returned
+    /// and the tokens buffered so far remain available.
+    pub fn peek_nth(&mut self, n: usize) -> Result<&TokenSpan> {
+        while self.peeked.len() <= n {
+            match self.lexer.read() {
+                Ok(span) => self.peeked.push_back(span),
+                Err(e) => return Err((self.lexer.input.next_pos(), e)),
+            }
         }
-        Ok(self.peeked.as_ref().unwrap())
+        Ok(&self.peeked[n])
     }
 
     /// Reads the next token.
@@ -749,7 +1403,7 @@ impl PeekableLexer<'_> {
     /// If the next token is invalid and results in a read error, the stream will remain valid and
     /// further tokens can be obtained with subsequent calls.
     pub fn read(&mut self) -> Result<TokenSpan> {
-        match self.peeked.take() {
+        match self.peeked.pop_front() {
             Some(t) => Ok(t),
             None => match self.lexer.read() {
                 Ok(span) => Ok(span),
@@ -784,8 +1438,12 @@ mod tests {
     /// `exp_token_spans`.
     fn do_ok_test(input: &str, exp_token_spans: &[TokenSpan]) {
         let mut input = input.as_bytes();
-        let mut lexer = Lexer::from(&mut input);
+        do_ok_test_with_lexer(Lexer::from(&mut input), exp_token_spans);
+    }
 
+    /// Runs the given `lexer` until EOF and expects the returned tokens to match
+    /// `exp_token_spans`.
+    fn do_ok_test_with_lexer(mut lexer: Lexer<'_>, exp_token_spans: &[TokenSpan]) {
         let mut token_spans: Vec<TokenSpan> = vec![];
         let mut eof = false;
         while !eof {
@@ -828,56 +1486,186 @@ mod tests {
     }
 
     #[test]
-    fn test_tabs() {
-        do_ok_test("\t33", &[ts(Token::Integer(33), 1, 9, 2), ts(Token::Eof, 1, 11, 0)]);
+    fn test_statement_separator_positions() {
         do_ok_test(
-            "1234567\t8",
+            "a:b",
             &[
-                ts(Token::Integer(1234567), 1, 1, 7),
-                ts(Token::Integer(8), 1, 9, 1),
-                ts(Token::Eof, 1, 10, 0),
+                ts(new_auto_symbol("a"), 1, 1, 1),
+                ts(Token::Eol, 1, 2, 1),
+                ts(new_auto_symbol("b"), 1, 3, 1),
+                ts(Token::Eof, 1, 4, 0),
             ],
         );
-    }
 
-    /// Syntactic sugar to instantiate a `VarRef` without an explicit type annotation.
-    fn new_auto_symbol(name: &str) -> Token {
-        Token::Symbol(VarRef::new(name, None))
-    }
+        do_ok_test(
+            "3:4",
+            &[
+                ts(Token::Integer(3), 1, 1, 1),
+                ts(Token::Eol, 1, 2, 1),
+                ts(Token::Integer(4), 1, 3, 1),
+                ts(Token::Eof, 1, 4, 0),
+            ],
+        );
 
-    #[test]
-    fn test_some_tokens() {
         do_ok_test(
-            "123 45 \n 6 3.012 abc a38z: a=3 with_underscores_1=_2",
+            "abc$:1.5:\"x\":d%",
             &[
-                ts(Token::Integer(123), 1, 1, 3),
-                ts(Token::Integer(45), 1, 5, 2),
-                ts(Token::Eol, 1, 8, 1),
-                ts(Token::Integer(6), 2, 2, 1),
-                ts(Token::Double(3.012), 2, 4, 5),
-                ts(new_auto_symbol("abc"), 2, 10, 3),
-                ts(new_auto_symbol("a38z"), 2, 14, 4),
-                ts(Token::Eol, 2, 18, 1),
-                ts(new_auto_symbol("a"), 2, 20, 1),
-                ts(Token::Equal, 2, 21, 1),
-                ts(Token::Integer(3), 2, 22, 1),
-                ts(new_auto_symbol("with_underscores_1"), 2, 24, 18),
-                ts(Token::Equal, 2, 42, 1),
-                ts(new_auto_symbol("_2"), 2, 43, 2),
-                ts(Token::Eof, 2, 45, 0),
+                ts(Token::Symbol(VarRef::new("abc", Some(ExprType::Text))), 1, 1, 4),
+                ts(Token::Eol, 1, 5, 1),
+                ts(Token::Double(1.5), 1, 6, 3),
+                ts(Token::Eol, 1, 9, 1),
+                ts(Token::Text("x".to_owned()), 1, 10, 3),
+                ts(Token::Eol, 1, 13, 1),
+                ts(Token::Symbol(VarRef::new("d", Some(ExprType::Integer))), 1, 14, 2),
+                ts(Token::Eof, 1, 16, 0),
             ],
         );
     }
 
     #[test]
-    fn test_boolean_literals() {
+    fn test_comma_positions() {
         do_ok_test(
-            "true TRUE yes YES y false FALSE no NO n",
+            "a,b,c",
             &[
-                ts(Token::Boolean(true), 1, 1, 4),
-                ts(Token::Boolean(true), 1, 6, 4),
-                ts(new_auto_symbol("yes"), 1, 11, 3),
-                ts(new_auto_symbol("YES"), 1, 15, 3),
+                ts(new_auto_symbol("a"), 1, 1, 1),
+                ts(Token::Comma, 1, 2, 1),
+                ts(new_auto_symbol("b"), 1, 3, 1),
+                ts(Token::Comma, 1, 4, 1),
+                ts(new_auto_symbol("c"), 1, 5, 1),
+                ts(Token::Eof, 1, 6, 0),
+            ],
+        );
+
+        do_ok_test(
+            "PRINT 12,\"x\",,c$",
+            &[
+                ts(Token::Symbol(VarRef::new("PRINT", None)), 1, 1, 5),
+                ts(Token::Integer(12), 1, 7, 2),
+                ts(Token::Comma, 1, 9, 1),
+                ts(Token::Text("x".to_owned()), 1, 10, 3),
+                ts(Token::Comma, 1, 13, 1),
+                ts(Token::Comma, 1, 14, 1),
+                ts(Token::Symbol(VarRef::new("c", Some(ExprType::Text))), 1, 15, 2),
+                ts(Token::Eof, 1, 17, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let exp_tokens = [
+            Token::Symbol(VarRef::new("a", None)),
+            Token::Eol,
+            Token::Symbol(VarRef::new("b", None)),
+            Token::Eof,
+        ];
+        for input in ["a\rb", "a\r\nb", "a\nb"] {
+            let mut input = input.as_bytes();
+            let mut lexer = Lexer::from(&mut input);
+            let mut tokens = vec![];
+            loop {
+                let token = lexer.read().unwrap().token;
+                let eof = token == Token::Eof;
+                tokens.push(token);
+                if eof {
+                    break;
+                }
+            }
+            assert_eq!(exp_tokens, tokens.as_slice());
+        }
+
+        do_ok_test(
+            "a\rb",
+            &[
+                ts(Token::Symbol(VarRef::new("a", None)), 1, 1, 1),
+                ts(Token::Eol, 1, 2, 1),
+                ts(Token::Symbol(VarRef::new("b", None)), 2, 1, 1),
+                ts(Token::Eof, 2, 2, 0),
+            ],
+        );
+        do_ok_test(
+            "a\r\nb",
+            &[
+                ts(Token::Symbol(VarRef::new("a", None)), 1, 1, 1),
+                ts(Token::Eol, 1, 3, 1),
+                ts(Token::Symbol(VarRef::new("b", None)), 2, 1, 1),
+                ts(Token::Eof, 2, 2, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tabs() {
+        do_ok_test("\t33", &[ts(Token::Integer(33), 1, 9, 2), ts(Token::Eof, 1, 11, 0)]);
+        do_ok_test(
+            "1234567\t8",
+            &[
+                ts(Token::Integer(1234567), 1, 1, 7),
+                ts(Token::Integer(8), 1, 9, 1),
+                ts(Token::Eof, 1, 10, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tabs_custom_length() {
+        let mut input = b"\t33".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+        lexer.set_tab_length(4);
+        do_ok_test_with_lexer(lexer, &[ts(Token::Integer(33), 1, 5, 2), ts(Token::Eof, 1, 7, 0)]);
+
+        let mut input = b"123\t8 \t9".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+        lexer.set_tab_length(4);
+        do_ok_test_with_lexer(
+            lexer,
+            &[
+                ts(Token::Integer(123), 1, 1, 3),
+                ts(Token::Integer(8), 1, 5, 1),
+                ts(Token::Integer(9), 1, 9, 1),
+                ts(Token::Eof, 1, 10, 0),
+            ],
+        );
+    }
+
+    /// Syntactic sugar to instantiate a `VarRef` without an explicit type annotation.
+    fn new_auto_symbol(name: &str) -> Token {
+        Token::Symbol(VarRef::new(name, None))
+    }
+
+    #[test]
+    fn test_some_tokens() {
+        do_ok_test(
+            "123 45 \n 6 3.012 abc a38z: a=3 with_underscores_1=_2",
+            &[
+                ts(Token::Integer(123), 1, 1, 3),
+                ts(Token::Integer(45), 1, 5, 2),
+                ts(Token::Eol, 1, 8, 1),
+                ts(Token::Integer(6), 2, 2, 1),
+                ts(Token::Double(3.012), 2, 4, 5),
+                ts(new_auto_symbol("abc"), 2, 10, 3),
+                ts(new_auto_symbol("a38z"), 2, 14, 4),
+                ts(Token::Eol, 2, 18, 1),
+                ts(new_auto_symbol("a"), 2, 20, 1),
+                ts(Token::Equal, 2, 21, 1),
+                ts(Token::Integer(3), 2, 22, 1),
+                ts(new_auto_symbol("with_underscores_1"), 2, 24, 18),
+                ts(Token::Equal, 2, 42, 1),
+                ts(new_auto_symbol("_2"), 2, 43, 2),
+                ts(Token::Eof, 2, 45, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_boolean_literals() {
+        do_ok_test(
+            "true TRUE yes YES y false FALSE no NO n",
+            &[
+                ts(Token::Boolean(true), 1, 1, 4),
+                ts(Token::Boolean(true), 1, 6, 4),
+                ts(new_auto_symbol("yes"), 1, 11, 3),
+                ts(new_auto_symbol("YES"), 1, 15, 3),
                 ts(new_auto_symbol("y"), 1, 19, 1),
                 ts(Token::Boolean(false), 1, 21, 5),
                 ts(Token::Boolean(false), 1, 27, 5),
@@ -922,17 +1710,41 @@ mod tests {
         do_ok_test(
             "& &_ &__ &i10 &i_10 &d &d10.1 &b2 &da &o8 &xg",
             &[
-                ts(Token::Bad("Missing base in integer literal".to_owned()), 1, 1, 1),
-                ts(Token::Bad("Unknown base _ in integer literal".to_owned()), 1, 3, 1),
-                ts(Token::Bad("Unknown base _ in integer literal".to_owned()), 1, 6, 2),
-                ts(Token::Bad("Unknown base i in integer literal".to_owned()), 1, 10, 3),
-                ts(Token::Bad("Unknown base i in integer literal".to_owned()), 1, 15, 4),
-                ts(Token::Bad("No digits in integer literal".to_owned()), 1, 21, 1),
-                ts(Token::Bad("Numbers in base syntax must be integers".to_owned()), 1, 24, 2),
-                ts(Token::Bad("Bad integer 2: invalid digit found in string".to_owned()), 1, 31, 1),
-                ts(Token::Bad("Bad integer a: invalid digit found in string".to_owned()), 1, 35, 1),
-                ts(Token::Bad("Bad integer 8: invalid digit found in string".to_owned()), 1, 39, 1),
-                ts(Token::Bad("Unexpected character in numeric literal: g".to_owned()), 1, 43, 1),
+                ts(Token::Bad(LexError::MissingBase), 1, 1, 1),
+                ts(Token::Bad(LexError::UnknownBase('_')), 1, 3, 1),
+                ts(Token::Bad(LexError::UnknownBase('_')), 1, 6, 2),
+                ts(Token::Bad(LexError::UnknownBase('i')), 1, 10, 3),
+                ts(Token::Bad(LexError::UnknownBase('i')), 1, 15, 4),
+                ts(Token::Bad(LexError::NoDigits), 1, 21, 1),
+                ts(Token::Bad(LexError::NonIntegerBase), 1, 24, 2),
+                ts(
+                    Token::Bad(LexError::BadInteger(
+                        "2".to_owned(),
+                        "invalid digit found in string".to_owned(),
+                    )),
+                    1,
+                    31,
+                    1,
+                ),
+                ts(
+                    Token::Bad(LexError::BadInteger(
+                        "a".to_owned(),
+                        "invalid digit found in string".to_owned(),
+                    )),
+                    1,
+                    35,
+                    1,
+                ),
+                ts(
+                    Token::Bad(LexError::BadInteger(
+                        "8".to_owned(),
+                        "invalid digit found in string".to_owned(),
+                    )),
+                    1,
+                    39,
+                    1,
+                ),
+                ts(Token::Bad(LexError::UnexpectedInNumber('g')), 1, 43, 1),
                 ts(Token::Eof, 1, 46, 0),
             ],
         );
@@ -941,31 +1753,31 @@ mod tests {
             ">&< >&_< >&__< >&i10< >&i_10< >&d< >&d10.1<",
             &[
                 ts(Token::Greater, 1, 1, 1),
-                ts(Token::Bad("Missing base in integer literal".to_owned()), 1, 2, 1),
+                ts(Token::Bad(LexError::MissingBase), 1, 2, 1),
                 ts(Token::Less, 1, 3, 1),
                 // -
                 ts(Token::Greater, 1, 5, 1),
-                ts(Token::Bad("Unknown base _ in integer literal".to_owned()), 1, 6, 1),
+                ts(Token::Bad(LexError::UnknownBase('_')), 1, 6, 1),
                 ts(Token::Less, 1, 8, 1),
                 // -
                 ts(Token::Greater, 1, 10, 1),
-                ts(Token::Bad("Unknown base _ in integer literal".to_owned()), 1, 11, 2),
+                ts(Token::Bad(LexError::UnknownBase('_')), 1, 11, 2),
                 ts(Token::Less, 1, 14, 1),
                 // -
                 ts(Token::Greater, 1, 16, 1),
-                ts(Token::Bad("Unknown base i in integer literal".to_owned()), 1, 17, 3),
+                ts(Token::Bad(LexError::UnknownBase('i')), 1, 17, 3),
                 ts(Token::Less, 1, 21, 1),
                 // -
                 ts(Token::Greater, 1, 23, 1),
-                ts(Token::Bad("Unknown base i in integer literal".to_owned()), 1, 24, 4),
+                ts(Token::Bad(LexError::UnknownBase('i')), 1, 24, 4),
                 ts(Token::Less, 1, 29, 1),
                 // -
                 ts(Token::Greater, 1, 31, 1),
-                ts(Token::Bad("No digits in integer literal".to_owned()), 1, 32, 1),
+                ts(Token::Bad(LexError::NoDigits), 1, 32, 1),
                 ts(Token::Less, 1, 34, 1),
                 // -
                 ts(Token::Greater, 1, 36, 1),
-                ts(Token::Bad("Numbers in base syntax must be integers".to_owned()), 1, 37, 2),
+                ts(Token::Bad(LexError::NonIntegerBase), 1, 37, 2),
                 ts(Token::Less, 1, 43, 1),
                 // -
                 ts(Token::Eof, 1, 44, 0),
@@ -989,6 +1801,307 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_multiple_type_annotations() {
+        do_ok_test(
+            "a$% b#? 5",
+            &[
+                ts(Token::Bad(LexError::MultipleTypeAnnotations), 1, 1, 1),
+                ts(Token::Bad(LexError::MultipleTypeAnnotations), 1, 5, 1),
+                ts(Token::Integer(5), 1, 9, 1),
+                ts(Token::Eof, 1, 10, 0),
+            ],
+        );
+
+        do_ok_test(
+            "c%%d = 1",
+            &[
+                ts(Token::Bad(LexError::MultipleTypeAnnotations), 1, 1, 2),
+                ts(Token::Equal, 1, 6, 1),
+                ts(Token::Integer(1), 1, 8, 1),
+                ts(Token::Eof, 1, 9, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_single_annotation() {
+        do_ok_test(
+            "x! = 1.5 ! y!#",
+            &[
+                ts(Token::Symbol(VarRef::new("x", Some(ExprType::Double))), 1, 1, 2),
+                ts(Token::Equal, 1, 4, 1),
+                ts(Token::Double(1.5), 1, 6, 3),
+                ts(Token::Bad(LexError::UnknownChar('!')), 1, 10, 1),
+                ts(Token::Bad(LexError::MultipleTypeAnnotations), 1, 12, 1),
+                ts(Token::Eof, 1, 15, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_long_type() {
+        do_ok_test(
+            "DIM n AS LONG",
+            &[
+                ts(Token::Dim, 1, 1, 3),
+                ts(new_auto_symbol("n"), 1, 5, 1),
+                ts(Token::As, 1, 7, 2),
+                ts(Token::LongName, 1, 10, 4),
+                ts(Token::Eof, 1, 14, 0),
+            ],
+        );
+
+        do_ok_test(
+            "n& = 1: a%& &x10",
+            &[
+                ts(Token::Bad(LexError::UnsupportedLong), 1, 1, 1),
+                ts(Token::Equal, 1, 4, 1),
+                ts(Token::Integer(1), 1, 6, 1),
+                ts(Token::Eol, 1, 7, 1),
+                ts(Token::Bad(LexError::MultipleTypeAnnotations), 1, 9, 1),
+                ts(Token::Integer(16), 1, 13, 4),
+                ts(Token::Eof, 1, 17, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_semicolon_positions() {
+        do_ok_test(
+            "PRINT a;b;c",
+            &[
+                ts(new_auto_symbol("PRINT"), 1, 1, 5),
+                ts(new_auto_symbol("a"), 1, 7, 1),
+                ts(Token::Semicolon, 1, 8, 1),
+                ts(new_auto_symbol("b"), 1, 9, 1),
+                ts(Token::Semicolon, 1, 10, 1),
+                ts(new_auto_symbol("c"), 1, 11, 1),
+                ts(Token::Eof, 1, 12, 0),
+            ],
+        );
+
+        let mut input = b"PRINT a;b ;  c".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+
+        let mut spans = vec![];
+        loop {
+            let span = lexer.read().unwrap();
+            if span.token == Token::Eof {
+                break;
+            }
+            spans.push(span);
+        }
+
+        let semicolons = spans
+            .iter()
+            .filter(|span| span.token == Token::Semicolon)
+            .map(|span| (span.pos.col, span.length()))
+            .collect::<Vec<(usize, usize)>>();
+        assert_eq!(vec![(8, 1), (11, 1)], semicolons);
+
+        let gaps = spans
+            .windows(2)
+            .map(|pair| pair[1].pos.col - (pair[0].pos.col + pair[0].length()))
+            .collect::<Vec<usize>>();
+        assert_eq!(vec![1, 0, 0, 1, 2], gaps);
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        do_ok_test(
+            r#"r"a\b" R"C:\temp\n" r"" r"he said ""hi""""#,
+            &[
+                ts(Token::Text(r"a\b".to_owned()), 1, 1, 6),
+                ts(Token::Text(r"C:\temp\n".to_owned()), 1, 8, 12),
+                ts(Token::Text("".to_owned()), 1, 21, 3),
+                ts(Token::Text(r#"he said "hi""#.to_owned()), 1, 25, 17),
+                ts(Token::Eof, 1, 42, 0),
+            ],
+        );
+
+        do_ok_test(
+            r#"r "a\"""#,
+            &[
+                ts(new_auto_symbol("r"), 1, 1, 1),
+                ts(Token::Text("a\"".to_owned()), 1, 3, 4),
+                ts(Token::Eof, 1, 8, 0),
+            ],
+        );
+
+        do_ok_test(
+            r#"r"incomplete"#,
+            &[
+                ts(Token::Bad(LexError::UnterminatedString("incomplete".to_owned())), 1, 1, 1),
+                ts(Token::Eof, 1, 13, 0),
+            ],
+        );
+
+        do_ok_test(
+            r#"ar"x""#,
+            &[ts(Token::Bad(LexError::UnexpectedInSymbol('"')), 1, 1, 3), ts(Token::Eof, 1, 6, 0)],
+        );
+    }
+
+    #[test]
+    fn test_print_shorthand() {
+        do_ok_test(
+            "? \"hi\"\n?a;flag?",
+            &[
+                ts(new_auto_symbol("PRINT"), 1, 1, 1),
+                ts(Token::Text("hi".to_owned()), 1, 3, 4),
+                ts(Token::Eol, 1, 7, 1),
+                ts(new_auto_symbol("PRINT"), 2, 1, 1),
+                ts(new_auto_symbol("a"), 2, 2, 1),
+                ts(Token::Semicolon, 2, 3, 1),
+                ts(Token::Symbol(VarRef::new("flag", Some(ExprType::Boolean))), 2, 4, 5),
+                ts(Token::Eof, 2, 9, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_print_using() {
+        do_ok_test(
+            "PRINT USING \"##\"; n",
+            &[
+                ts(new_auto_symbol("PRINT"), 1, 1, 5),
+                ts(Token::Using, 1, 7, 5),
+                ts(Token::Text("##".to_owned()), 1, 13, 4),
+                ts(Token::Semicolon, 1, 17, 1),
+                ts(new_auto_symbol("n"), 1, 19, 1),
+                ts(Token::Eof, 1, 20, 0),
+            ],
+        );
+
+        do_ok_test("using", &[ts(Token::Using, 1, 1, 5), ts(Token::Eof, 1, 6, 0)]);
+    }
+
+    #[test]
+    fn test_program_commands_are_symbols() {
+        do_ok_test(
+            "NEW\nload \"a.bas\": SAVE",
+            &[
+                ts(new_auto_symbol("NEW"), 1, 1, 3),
+                ts(Token::Eol, 1, 4, 1),
+                ts(new_auto_symbol("load"), 2, 1, 4),
+                ts(Token::Text("a.bas".to_owned()), 2, 6, 7),
+                ts(Token::Eol, 2, 13, 1),
+                ts(new_auto_symbol("SAVE"), 2, 15, 4),
+                ts(Token::Eof, 2, 19, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_warnings() {
+        let mut input = b"IF a THEN b\nif a ThEn b = 1.0 + 1.5\n".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+        lexer.set_collect_warnings(true);
+
+        let mut tokens = vec![];
+        loop {
+            let span = lexer.read().unwrap();
+            if span.token == Token::Eof {
+                break;
+            }
+            tokens.push(span.token);
+        }
+        assert_eq!(14, tokens.len());
+        assert_eq!(Token::Then, tokens[7]);
+
+        assert_eq!(
+            &[
+                LexWarning {
+                    pos: LineCol { line: 2, col: 6 },
+                    message: "Keyword THEN written in mixed case".to_owned()
+                },
+                LexWarning {
+                    pos: LineCol { line: 2, col: 15 },
+                    message: "Double literal 1.0 could be written as an integer".to_owned()
+                },
+            ],
+            lexer.warnings()
+        );
+    }
+
+    #[test]
+    fn test_warnings_disabled() {
+        let mut input = b"if a ThEn b = 1.0".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+        while lexer.read().unwrap().token != Token::Eof {}
+        assert!(lexer.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_remarks_captured() {
+        let mut input = b"REM  The first: comment\nNOT 'Another\n'\nREM".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+        lexer.set_capture_comments(true);
+        do_ok_test_with_lexer(
+            lexer,
+            &[
+                ts(Token::Comment("  The first: comment".to_owned()), 1, 1, 23),
+                ts(Token::Eol, 1, 24, 1),
+                ts(Token::Not, 2, 1, 3),
+                ts(Token::Comment("Another".to_owned()), 2, 5, 8),
+                ts(Token::Eol, 2, 13, 1),
+                ts(Token::Comment("".to_owned()), 3, 1, 1),
+                ts(Token::Eol, 3, 2, 1),
+                ts(Token::Comment("".to_owned()), 4, 1, 3),
+                ts(Token::Eof, 4, 4, 0),
+            ],
+        );
+    }
+
+    /// Returns the lexer options to treat newlines between parenthesis as whitespace.
+    fn paren_lines_options() -> LexerOptions<'static> {
+        LexerOptions { join_paren_lines: true, ..Default::default() }
+    }
+
+    #[test]
+    fn test_join_paren_lines() {
+        let mut input = b"a = f(1,\n  (2 + 3), ' comment\n  4): b\n".as_ref();
+        let lexer = Lexer::from_with_options(&mut input, paren_lines_options());
+        do_ok_test_with_lexer(
+            lexer,
+            &[
+                ts(new_auto_symbol("a"), 1, 1, 1),
+                ts(Token::Equal, 1, 3, 1),
+                ts(new_auto_symbol("f"), 1, 5, 1),
+                ts(Token::LeftParen, 1, 6, 1),
+                ts(Token::Integer(1), 1, 7, 1),
+                ts(Token::Comma, 1, 8, 1),
+                ts(Token::LeftParen, 2, 3, 1),
+                ts(Token::Integer(2), 2, 4, 1),
+                ts(Token::Plus, 2, 6, 1),
+                ts(Token::Integer(3), 2, 8, 1),
+                ts(Token::RightParen, 2, 9, 1),
+                ts(Token::Comma, 2, 10, 1),
+                ts(Token::Integer(4), 3, 3, 1),
+                ts(Token::RightParen, 3, 4, 1),
+                ts(Token::Eol, 3, 5, 1),
+                ts(new_auto_symbol("b"), 3, 7, 1),
+                ts(Token::Eol, 3, 8, 1),
+                ts(Token::Eof, 4, 1, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_paren_lines_not_joined_by_default() {
+        do_ok_test(
+            "(1\n)",
+            &[
+                ts(Token::LeftParen, 1, 1, 1),
+                ts(Token::Integer(1), 1, 2, 1),
+                ts(Token::Eol, 1, 3, 1),
+                ts(Token::RightParen, 2, 1, 1),
+                ts(Token::Eof, 2, 2, 0),
+            ],
+        );
+    }
+
     #[test]
     fn test_remarks() {
         do_ok_test(
@@ -1067,20 +2180,79 @@ mod tests {
 
         do_ok_test("data", &[ts(Token::Data, 1, 1, 4), ts(Token::Eof, 1, 5, 0)]);
 
-        // Common BASIC interprets things like "2 + foo" as a single string but we interpret
-        // separate tokens.  "Fixing" this to read data in the same way requires entering a
-        // separate lexing mode just for DATA statements, which is not very interesting.  We can
-        // ask for strings to always be double-quoted.
+        // Like in common BASIC, unquoted items that are not single literals are read as text.
         do_ok_test(
             "DATA 2 + foo",
             &[
                 ts(Token::Data, 1, 1, 4),
-                ts(Token::Integer(2), 1, 6, 1),
-                ts(Token::Plus, 1, 8, 1),
-                ts(new_auto_symbol("foo"), 1, 10, 3),
+                ts(Token::Text("2 + foo".to_owned()), 1, 6, 7),
                 ts(Token::Eof, 1, 13, 0),
             ],
         );
+
+        do_ok_test(
+            "DATA \"a,b\", 3",
+            &[
+                ts(Token::Data, 1, 1, 4),
+                ts(Token::Text("a,b".to_owned()), 1, 6, 5),
+                ts(Token::Comma, 1, 11, 1),
+                ts(Token::Integer(3), 1, 13, 1),
+                ts(Token::Eof, 1, 14, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_data_items() {
+        do_ok_test(
+            "DATA -3, 5.5, TRUE, foo bar , &x10: PRINT 1+2",
+            &[
+                ts(Token::Data, 1, 1, 4),
+                ts(Token::Integer(-3), 1, 6, 2),
+                ts(Token::Comma, 1, 8, 1),
+                ts(Token::Double(5.5), 1, 10, 3),
+                ts(Token::Comma, 1, 13, 1),
+                ts(Token::Boolean(true), 1, 15, 4),
+                ts(Token::Comma, 1, 19, 1),
+                ts(Token::Text("foo bar".to_owned()), 1, 21, 7),
+                ts(Token::Comma, 1, 29, 1),
+                ts(Token::Integer(16), 1, 31, 4),
+                ts(Token::Eol, 1, 35, 1),
+                ts(new_auto_symbol("PRINT"), 1, 37, 5),
+                ts(Token::Integer(1), 1, 43, 1),
+                ts(Token::Plus, 1, 44, 1),
+                ts(Token::Integer(2), 1, 45, 1),
+                ts(Token::Eof, 1, 46, 0),
+            ],
+        );
+
+        do_ok_test(
+            "DATA - 2.5, -x, 1.2.3, \"a\\\"b\" ' Comment\nfoo",
+            &[
+                ts(Token::Data, 1, 1, 4),
+                ts(Token::Double(-2.5), 1, 6, 5),
+                ts(Token::Comma, 1, 11, 1),
+                ts(Token::Text("-x".to_owned()), 1, 13, 2),
+                ts(Token::Comma, 1, 15, 1),
+                ts(Token::Text("1.2.3".to_owned()), 1, 17, 5),
+                ts(Token::Comma, 1, 22, 1),
+                ts(Token::Text("a\"b".to_owned()), 1, 24, 5),
+                ts(Token::Eol, 1, 40, 1),
+                ts(new_auto_symbol("foo"), 2, 1, 3),
+                ts(Token::Eof, 2, 4, 0),
+            ],
+        );
+
+        do_ok_test(
+            "DATA r\"a\\b\", PRINT",
+            &[
+                ts(Token::Data, 1, 1, 4),
+                ts(Token::Text("a\\b".to_owned()), 1, 6, 6),
+                ts(Token::Comma, 1, 12, 1),
+                ts(Token::Text("PRINT".to_owned()), 1, 14, 5),
+                ts(Token::Eof, 1, 19, 0),
+            ],
+        );
     }
 
     #[test]
@@ -1182,6 +2354,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_end_loop_keywords() {
+        do_ok_test(
+            "END WHILE\nEND FOR",
+            &[
+                ts(Token::End, 1, 1, 3),
+                ts(Token::While, 1, 5, 5),
+                ts(Token::Eol, 1, 10, 1),
+                ts(Token::End, 2, 1, 3),
+                ts(Token::For, 2, 5, 3),
+                ts(Token::Eof, 2, 8, 0),
+            ],
+        );
+
+        do_ok_test(
+            "end  while: End For",
+            &[
+                ts(Token::End, 1, 1, 3),
+                ts(Token::While, 1, 6, 5),
+                ts(Token::Eol, 1, 11, 1),
+                ts(Token::End, 1, 13, 3),
+                ts(Token::For, 1, 17, 3),
+                ts(Token::Eof, 1, 20, 0),
+            ],
+        );
+    }
+
     #[test]
     fn test_for() {
         do_ok_test(
@@ -1361,6 +2560,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_print_functions() {
+        do_ok_test(
+            "TAB(5)",
+            &[
+                ts(Token::PrintTab, 1, 1, 3),
+                ts(Token::LeftParen, 1, 4, 1),
+                ts(Token::Integer(5), 1, 5, 1),
+                ts(Token::RightParen, 1, 6, 1),
+                ts(Token::Eof, 1, 7, 0),
+            ],
+        );
+
+        do_ok_test(
+            "PRINT spc(3); Tab (10)",
+            &[
+                ts(new_auto_symbol("PRINT"), 1, 1, 5),
+                ts(Token::PrintSpc, 1, 7, 3),
+                ts(Token::LeftParen, 1, 10, 1),
+                ts(Token::Integer(3), 1, 11, 1),
+                ts(Token::RightParen, 1, 12, 1),
+                ts(Token::Semicolon, 1, 13, 1),
+                ts(Token::PrintTab, 1, 15, 3),
+                ts(Token::LeftParen, 1, 19, 1),
+                ts(Token::Integer(10), 1, 20, 2),
+                ts(Token::RightParen, 1, 22, 1),
+                ts(Token::Eof, 1, 23, 0),
+            ],
+        );
+
+        do_ok_test(
+            "TABS SPC2",
+            &[
+                ts(new_auto_symbol("TABS"), 1, 1, 4),
+                ts(new_auto_symbol("SPC2"), 1, 6, 4),
+                ts(Token::Eof, 1, 10, 0),
+            ],
+        );
+    }
+
     /// Syntactic sugar to instantiate a test that verifies the parsing of a binary operator.
     fn do_binary_operator_test(op: &str, t: Token) {
         do_ok_test(
@@ -1411,6 +2650,10 @@ mod tests {
     #[test]
     fn test_operator_logical_bitwise_ops() {
         do_binary_operator_test("AND", Token::And);
+        do_binary_operator_test("IMP", Token::Imp);
+        do_binary_operator_test("imp", Token::Imp);
+        do_binary_operator_test("EQV", Token::Eqv);
+        do_binary_operator_test("Eqv", Token::Eqv);
         do_binary_operator_test("OR", Token::Or);
         do_binary_operator_test("XOR", Token::Xor);
         do_unary_operator_test("NOT", Token::Not);
@@ -1474,12 +2717,305 @@ mod tests {
         assert_eq!(Token::Eof, lexer.read().unwrap().token);
     }
 
+    #[test]
+    fn test_peekable_lexer_peek_nth() {
+        let mut input = b"END IF\nx".as_ref();
+        let mut lexer = Lexer::from(&mut input).peekable();
+        assert_eq!(Token::If, lexer.peek_nth(1).unwrap().token);
+        assert_eq!(Token::End, lexer.peek().unwrap().token);
+        assert_eq!(Token::Eol, lexer.peek_nth(2).unwrap().token);
+        assert_eq!(Token::If, lexer.peek_nth(1).unwrap().token);
+        assert_eq!(Token::End, lexer.consume_peeked().token);
+        assert_eq!(Token::If, lexer.read().unwrap().token);
+        assert_eq!(Token::Eol, lexer.peek().unwrap().token);
+        assert_eq!(Token::Eof, lexer.peek_nth(2).unwrap().token);
+        assert_eq!(Token::Eol, lexer.read().unwrap().token);
+        assert_eq!(new_auto_symbol("x"), lexer.read().unwrap().token);
+        assert_eq!(Token::Eof, lexer.read().unwrap().token);
+        assert_eq!(Token::Eof, lexer.read().unwrap().token);
+    }
+
+    /// Reads all tokens from `lexer` until EOF, inclusive.
+    fn read_all(lexer: &mut Lexer) -> Vec<TokenSpan> {
+        let mut tokens = vec![];
+        loop {
+            let span = lexer.read().unwrap();
+            let eof = span.token == Token::Eof;
+            tokens.push(span);
+            if eof {
+                return tokens;
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_keywords() {
+        let mut keywords = HashMap::default();
+        keywords.insert("SI".to_owned(), Token::If);
+        keywords.insert("ENTONCES".to_owned(), Token::Then);
+        keywords.insert("END".to_owned(), new_auto_symbol("END"));
+
+        let mut input = b"SI a entonces End IF b".as_ref();
+        let options = LexerOptions { keywords: Some(&keywords), ..Default::default() };
+        let mut lexer = Lexer::from_with_options(&mut input, options);
+        assert_eq!(
+            vec![
+                ts(Token::If, 1, 1, 2),
+                ts(new_auto_symbol("a"), 1, 4, 1),
+                ts(Token::Then, 1, 6, 8),
+                ts(new_auto_symbol("END"), 1, 15, 3),
+                ts(Token::If, 1, 19, 2),
+                ts(new_auto_symbol("b"), 1, 22, 1),
+                ts(Token::Eof, 1, 23, 0),
+            ],
+            read_all(&mut lexer)
+        );
+    }
+
+    #[test]
+    fn test_custom_keywords_do_not_leak() {
+        let mut input = b"SI".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+        assert_eq!(
+            vec![ts(new_auto_symbol("SI"), 1, 1, 2), ts(Token::Eof, 1, 3, 0)],
+            read_all(&mut lexer)
+        );
+    }
+
+    #[test]
+    fn test_options_combined() {
+        let mut keywords = HashMap::default();
+        keywords.insert("SI".to_owned(), Token::If);
+
+        let mut input = b"SI (a\n+ bbbb)".as_ref();
+        let options = LexerOptions {
+            keywords: Some(&keywords),
+            join_paren_lines: true,
+            limits: LexerLimits { max_identifier_len: Some(3), ..Default::default() },
+        };
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut input, options),
+            &[
+                ts(Token::If, 1, 1, 2),
+                ts(Token::LeftParen, 1, 4, 1),
+                ts(new_auto_symbol("a"), 1, 5, 1),
+                ts(Token::Plus, 2, 1, 1),
+                ts(Token::Bad(LexError::IdentifierTooLong), 2, 3, 4),
+                ts(Token::RightParen, 2, 7, 1),
+                ts(Token::Eof, 2, 8, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_limits_identifier() {
+        let long = "a".repeat(1000);
+        let input = format!("{} = 1", long);
+
+        let limits = LexerLimits { max_identifier_len: Some(64), ..Default::default() };
+        let mut bytes = input.as_bytes();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() }),
+            &[
+                ts(Token::Bad(LexError::IdentifierTooLong), 1, 1, 1000),
+                ts(Token::Equal, 1, 1002, 1),
+                ts(Token::Integer(1), 1, 1004, 1),
+                ts(Token::Eof, 1, 1005, 0),
+            ],
+        );
+
+        let mut bytes = input.as_bytes();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions::default()),
+            &[
+                ts(new_auto_symbol(&long), 1, 1, 1000),
+                ts(Token::Equal, 1, 1002, 1),
+                ts(Token::Integer(1), 1, 1004, 1),
+                ts(Token::Eof, 1, 1005, 0),
+            ],
+        );
+
+        let limits = LexerLimits { max_identifier_len: Some(3), ..Default::default() };
+        let mut bytes = b"abc$ abcd".as_ref();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() }),
+            &[
+                ts(Token::Symbol(VarRef::new("abc", Some(ExprType::Text))), 1, 1, 4),
+                ts(Token::Bad(LexError::IdentifierTooLong), 1, 6, 4),
+                ts(Token::Eof, 1, 10, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_limits_string() {
+        let limits = LexerLimits { max_string_len: Some(3), ..Default::default() };
+        let mut bytes = b"\"abc\" \"a b c d\" 5 \"abcd".as_ref();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() }),
+            &[
+                ts(Token::Text("abc".to_owned()), 1, 1, 5),
+                ts(Token::Bad(LexError::StringTooLong), 1, 7, 9),
+                ts(Token::Integer(5), 1, 17, 1),
+                ts(Token::Bad(LexError::StringTooLong), 1, 19, 5),
+                ts(Token::Eof, 1, 24, 0),
+            ],
+        );
+
+        let mut bytes = b"r\"a\"\"b\" r\"abcd\" 5 r\"abcd".as_ref();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() }),
+            &[
+                ts(Token::Text("a\"b".to_owned()), 1, 1, 7),
+                ts(Token::Bad(LexError::StringTooLong), 1, 9, 7),
+                ts(Token::Integer(5), 1, 17, 1),
+                ts(Token::Bad(LexError::StringTooLong), 1, 19, 6),
+                ts(Token::Eof, 1, 25, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_limits_data() {
+        let limits = LexerLimits { max_string_len: Some(5), ..Default::default() };
+        let mut bytes = b"DATA abc  , a b c d, abcdef ELSE 1".as_ref();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() }),
+            &[
+                ts(Token::Data, 1, 1, 4),
+                ts(Token::Text("abc".to_owned()), 1, 6, 3),
+                ts(Token::Comma, 1, 11, 1),
+                ts(Token::Bad(LexError::StringTooLong), 1, 13, 7),
+                ts(Token::Comma, 1, 20, 1),
+                ts(Token::Bad(LexError::StringTooLong), 1, 22, 6),
+                ts(Token::Else, 1, 29, 4),
+                ts(Token::Integer(1), 1, 34, 1),
+                ts(Token::Eof, 1, 35, 0),
+            ],
+        );
+
+        let mut bytes = b"DATA abcdef REM x".as_ref();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() }),
+            &[
+                ts(Token::Data, 1, 1, 4),
+                ts(Token::Bad(LexError::StringTooLong), 1, 6, 6),
+                ts(Token::Eof, 1, 18, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_limits_comment() {
+        let limits = LexerLimits { max_comment_len: Some(4), ..Default::default() };
+        let mut bytes = b"REM abc\n' abcd".as_ref();
+        let mut lexer =
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() });
+        lexer.set_capture_comments(true);
+        do_ok_test_with_lexer(
+            lexer,
+            &[
+                ts(Token::Comment(" abc".to_owned()), 1, 1, 7),
+                ts(Token::Eol, 1, 8, 1),
+                ts(Token::Bad(LexError::CommentTooLong), 2, 1, 6),
+                ts(Token::Eof, 2, 7, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_limits_label() {
+        let limits = LexerLimits { max_label_len: Some(3), ..Default::default() };
+        let mut bytes = b"@abc @abcd GOTO".as_ref();
+        do_ok_test_with_lexer(
+            Lexer::from_with_options(&mut bytes, LexerOptions { limits, ..Default::default() }),
+            &[
+                ts(Token::Label("abc".to_owned()), 1, 1, 4),
+                ts(Token::Bad(LexError::LabelTooLong), 1, 6, 5),
+                ts(Token::Goto, 1, 12, 4),
+                ts(Token::Eof, 1, 16, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_reset_matches_fresh_lexer() {
+        let inputs: [&[u8]; 3] = [b"a = (1\n+ 2)\nDATA 3, x", b"PRINT 3: c", b"\tIF b THEN"];
+
+        let mut fresh = vec![];
+        for input in inputs {
+            let mut input = input;
+            let mut lexer = Lexer::from_with_options(&mut input, paren_lines_options());
+            lexer.set_tab_length(4);
+            fresh.push(read_all(&mut lexer));
+        }
+
+        let mut first = inputs[0];
+        let mut second = inputs[1];
+        let mut third = inputs[2];
+        let mut lexer = Lexer::from_with_options(&mut first, paren_lines_options());
+        lexer.set_tab_length(4);
+        assert_eq!(fresh[0], read_all(&mut lexer));
+        lexer.reset(&mut second);
+        assert_eq!(fresh[1], read_all(&mut lexer));
+        lexer.reset(&mut third);
+        assert_eq!(fresh[2], read_all(&mut lexer));
+    }
+
+    #[test]
+    fn test_reset_mid_input() {
+        let mut first = b"a = (1\n+ 2)".as_ref();
+        let mut second = b"b\nc".as_ref();
+        let mut lexer = Lexer::from_with_options(&mut first, paren_lines_options());
+        for _ in 0..4 {
+            lexer.read().unwrap();
+        }
+        lexer.reset(&mut second);
+        do_ok_test_with_lexer(
+            lexer,
+            &[
+                ts(new_auto_symbol("b"), 1, 1, 1),
+                ts(Token::Eol, 1, 2, 1),
+                ts(new_auto_symbol("c"), 2, 1, 1),
+                ts(Token::Eof, 2, 2, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_integer_leading_zeros() {
+        do_ok_test(
+            "007 00 0 0.5",
+            &[
+                ts(Token::Integer(7), 1, 1, 3),
+                ts(Token::Integer(0), 1, 5, 2),
+                ts(Token::Integer(0), 1, 8, 1),
+                ts(Token::Double(0.5), 1, 10, 3),
+                ts(Token::Eof, 1, 13, 0),
+            ],
+        );
+
+        let mut input = b"007 00 0 0.5".as_ref();
+        let mut lexer = Lexer::from(&mut input);
+        lexer.set_strict_numbers(true);
+        do_ok_test_with_lexer(
+            lexer,
+            &[
+                ts(Token::Bad(LexError::LeadingZeros("007".to_owned())), 1, 1, 3),
+                ts(Token::Bad(LexError::LeadingZeros("00".to_owned())), 1, 5, 2),
+                ts(Token::Integer(0), 1, 8, 1),
+                ts(Token::Double(0.5), 1, 10, 3),
+                ts(Token::Eof, 1, 13, 0),
+            ],
+        );
+    }
+
     #[test]
     fn test_recoverable_errors() {
         do_ok_test(
             "0.1.28+5",
             &[
-                ts(Token::Bad("Too many dots in numeric literal".to_owned()), 1, 1, 3),
+                ts(Token::Bad(LexError::TooManyDots), 1, 1, 3),
                 ts(Token::Plus, 1, 7, 1),
                 ts(Token::Integer(5), 1, 8, 1),
                 ts(Token::Eof, 1, 9, 0),
@@ -1490,7 +3026,7 @@ mod tests {
             "1 .3",
             &[
                 ts(Token::Integer(1), 1, 1, 1),
-                ts(Token::Bad("Unknown character: .".to_owned()), 1, 3, 2),
+                ts(Token::Bad(LexError::UnknownChar('.')), 1, 3, 2),
                 ts(Token::Eof, 1, 5, 0),
             ],
         );
@@ -1499,7 +3035,7 @@ mod tests {
             "1 3. 2",
             &[
                 ts(Token::Integer(1), 1, 1, 1),
-                ts(Token::Bad("Unknown character: .".to_owned()), 1, 3, 1),
+                ts(Token::Bad(LexError::UnknownChar('.')), 1, 3, 1),
                 ts(Token::Integer(2), 1, 6, 1),
                 ts(Token::Eof, 1, 7, 0),
             ],
@@ -1509,9 +3045,10 @@ mod tests {
             "9999999999+5",
             &[
                 ts(
-                    Token::Bad(
-                        "Bad integer 9999999999: number too large to fit in target type".to_owned(),
-                    ),
+                    Token::Bad(LexError::BadInteger(
+                        "9999999999".to_owned(),
+                        "number too large to fit in target type".to_owned(),
+                    )),
                     1,
                     1,
                     1,
@@ -1526,7 +3063,7 @@ mod tests {
             "\n3!2 1",
             &[
                 ts(Token::Eol, 1, 1, 1),
-                ts(Token::Bad("Unexpected character in numeric literal: !".to_owned()), 2, 1, 2),
+                ts(Token::Bad(LexError::UnexpectedInNumber('!')), 2, 1, 2),
                 ts(Token::Integer(1), 2, 5, 1),
                 ts(Token::Eof, 2, 6, 0),
             ],
@@ -1536,7 +3073,7 @@ mod tests {
             "a b|d 5",
             &[
                 ts(new_auto_symbol("a"), 1, 1, 1),
-                ts(Token::Bad("Unexpected character in symbol: |".to_owned()), 1, 3, 2),
+                ts(Token::Bad(LexError::UnexpectedInSymbol('|')), 1, 3, 2),
                 ts(Token::Integer(5), 1, 7, 1),
                 ts(Token::Eof, 1, 8, 0),
             ],
@@ -1547,7 +3084,7 @@ mod tests {
             &[
                 ts(Token::LeftParen, 1, 1, 1),
                 ts(
-                    Token::Bad("Incomplete string due to EOF: this is incomplete".to_owned()),
+                    Token::Bad(LexError::UnterminatedString("this is incomplete".to_owned())),
                     1,
                     3,
                     1,
@@ -1561,7 +3098,7 @@ mod tests {
             &[
                 ts(Token::Plus, 1, 1, 1),
                 ts(Token::Minus, 1, 3, 1),
-                ts(Token::Bad("Unknown character: !".to_owned()), 1, 5, 1),
+                ts(Token::Bad(LexError::UnknownChar('!')), 1, 5, 1),
                 ts(Token::Multiply, 1, 7, 1),
                 ts(Token::Divide, 1, 9, 1),
                 ts(Token::Modulo, 1, 11, 3),
@@ -1573,7 +3110,7 @@ mod tests {
         do_ok_test(
             "@+",
             &[
-                ts(Token::Bad("Empty label name".to_owned()), 1, 1, 1),
+                ts(Token::Bad(LexError::EmptyLabel), 1, 1, 1),
                 ts(Token::Plus, 1, 2, 1),
                 ts(Token::Eof, 1, 3, 0),
             ],
@@ -1582,7 +3119,7 @@ mod tests {
         do_ok_test(
             "@123",
             &[
-                ts(Token::Bad("Empty label name".to_owned()), 1, 1, 1),
+                ts(Token::Bad(LexError::EmptyLabel), 1, 1, 1),
                 ts(Token::Integer(123), 1, 2, 3),
                 ts(Token::Eof, 1, 5, 0),
             ],
@@ -27,7 +27,7 @@ pub mod ast;
 pub mod bytecode;
 pub mod compiler;
 pub mod exec;
-mod lexer;
+pub mod lexer;
 pub mod parser;
 mod reader;
 pub mod syms;
@@ -413,6 +413,9 @@ impl<'a> Parser<'a> {
             Token::BooleanName => Ok((ExprType::Boolean, token_span.pos)),
             Token::DoubleName => Ok((ExprType::Double, token_span.pos)),
             Token::IntegerName => Ok((ExprType::Integer, token_span.pos)),
+            Token::LongName => {
+                Err(Error::Bad(token_span.pos, "LONG type is not supported yet".to_owned()))
+            }
             Token::TextName => Ok((ExprType::Text, token_span.pos)),
             t => Err(Error::Bad(
                 token_span.pos,
@@ -441,20 +444,6 @@ impl<'a> Parser<'a> {
                 Token::Integer(i) => values.push(Some(Value::Integer(i))),
                 Token::Text(t) => values.push(Some(Value::Text(t))),
 
-                Token::Minus => {
-                    let token_span = self.lexer.read()?;
-                    match token_span.token {
-                        Token::Double(d) => values.push(Some(Value::Double(-d))),
-                        Token::Integer(i) => values.push(Some(Value::Integer(-i))),
-                        _ => {
-                            return Err(Error::Bad(
-                                token_span.pos,
-                                "Expected number after -".to_owned(),
-                            ))
-                        }
-                    }
-                }
-
                 Token::Eof | Token::Eol | Token::Else => {
                     panic!("Should not be consumed here; handled above")
                 }
@@ -464,6 +453,8 @@ impl<'a> Parser<'a> {
                     continue;
                 }
 
+                Token::Bad(e) => return Err(Error::Bad(token_span.pos, e.to_string())),
+
                 t => {
                     return Err(Error::Bad(
                         token_span.pos,
@@ -899,7 +890,13 @@ impl<'a> Parser<'a> {
                     need_operand = true;
                 }
 
-                Token::Bad(e) => return Err(Error::Bad(ts.pos, e)),
+                Token::Bad(e) => return Err(Error::Bad(ts.pos, e.to_string())),
+
+                Token::Comment(_) => panic!("The parser never enables comment capturing"),
+
+                Token::Eqv | Token::Imp => {
+                    return Err(Error::Bad(ts.pos, format!("{} is not supported yet", ts.token)));
+                }
 
                 Token::Eof
                 | Token::Eol
@@ -931,9 +928,12 @@ impl<'a> Parser<'a> {
                 | Token::Is
                 | Token::IntegerName
                 | Token::Label(_)
+                | Token::LongName
                 | Token::Loop
                 | Token::Next
                 | Token::On
+                | Token::PrintSpc
+                | Token::PrintTab
                 | Token::Resume
                 | Token::Return
                 | Token::Select
@@ -941,6 +941,7 @@ impl<'a> Parser<'a> {
                 | Token::Sub
                 | Token::TextName
                 | Token::Until
+                | Token::Using
                 | Token::Wend
                 | Token::While => {
                     return Err(Error::Bad(ts.pos, "Unexpected keyword in expression".to_owned()));
@@ -1799,7 +1800,7 @@ impl<'a> Parser<'a> {
                     Ok(Some(self.parse_array_or_builtin_call(vref, token_span.pos)?))
                 }
             }
-            Token::Bad(msg) => Err(Error::Bad(token_span.pos, msg)),
+            Token::Bad(e) => Err(Error::Bad(token_span.pos, e.to_string())),
             t => Err(Error::Bad(token_span.pos, format!("Unexpected {} in uniline IF branch", t))),
         }
     }
@@ -1905,7 +1906,7 @@ impl<'a> Parser<'a> {
                 }
                 Ok(Some(result?))
             }
-            Token::Bad(msg) => return Err(Error::Bad(token_span.pos, msg)),
+            Token::Bad(e) => return Err(Error::Bad(token_span.pos, e.to_string())),
             t => return Err(Error::Bad(token_span.pos, format!("Unexpected {} in statement", t))),
         };
 
@@ -2184,6 +2185,8 @@ mod tests {
         do_error_test("a = b, 3", "1:6: Unexpected , in assignment");
         do_error_test("a = if 3", "1:5: Unexpected keyword in expression");
         do_error_test("true = 1", "1:1: Unexpected TRUE in statement");
+        do_error_test("a = b imp c", "1:7: IMP is not supported yet");
+        do_error_test("a = b EQV c", "1:7: EQV is not supported yet");
     }
 
     #[test]
@@ -2405,17 +2408,99 @@ mod tests {
                 values: vec![Some(Value::Integer(-3)), Some(Value::Double(-5.1))],
             })],
         );
+
+        do_ok_test(
+            "DATA 2 + foo, ;, -FALSE, \"a,b\"",
+            &[Statement::Data(DataSpan {
+                values: vec![
+                    Some(Value::Text("2 + foo".to_owned())),
+                    Some(Value::Text(";".to_owned())),
+                    Some(Value::Text("-FALSE".to_owned())),
+                    Some(Value::Text("a,b".to_owned())),
+                ],
+            })],
+        );
+    }
+
+    #[test]
+    fn test_data_ends_at_else() {
+        do_ok_test(
+            "IF TRUE THEN DATA 1 ELSE DATA 2",
+            &[Statement::If(IfSpan {
+                branches: vec![
+                    IfBranchSpan {
+                        guard: expr_boolean(true, 1, 4),
+                        body: vec![Statement::Data(DataSpan {
+                            values: vec![Some(Value::Integer(1))],
+                        })],
+                    },
+                    IfBranchSpan {
+                        guard: expr_boolean(true, 1, 21),
+                        body: vec![Statement::Data(DataSpan {
+                            values: vec![Some(Value::Integer(2))],
+                        })],
+                    },
+                ],
+            })],
+        );
+
+        do_ok_test(
+            "IF TRUE THEN DATA a b, ELSE",
+            &[Statement::If(IfSpan {
+                branches: vec![
+                    IfBranchSpan {
+                        guard: expr_boolean(true, 1, 4),
+                        body: vec![Statement::Data(DataSpan {
+                            values: vec![Some(Value::Text("a b".to_owned())), None],
+                        })],
+                    },
+                    IfBranchSpan { guard: expr_boolean(true, 1, 24), body: vec![] },
+                ],
+            })],
+        );
+
+        do_ok_test(
+            "DATA elsewhere, xelse, \"else\"",
+            &[Statement::Data(DataSpan {
+                values: vec![
+                    Some(Value::Text("elsewhere".to_owned())),
+                    Some(Value::Text("xelse".to_owned())),
+                    Some(Value::Text("else".to_owned())),
+                ],
+            })],
+        );
+    }
+
+    #[test]
+    fn test_data_ends_at_rem() {
+        do_ok_test(
+            "DATA 1, foo bar REM a, b\nDATA 2 rem\nDATA REM",
+            &[
+                Statement::Data(DataSpan {
+                    values: vec![Some(Value::Integer(1)), Some(Value::Text("foo bar".to_owned()))],
+                }),
+                Statement::Data(DataSpan { values: vec![Some(Value::Integer(2))] }),
+                Statement::Data(DataSpan { values: vec![None] }),
+            ],
+        );
+
+        do_ok_test(
+            "DATA 1, remark, x-rem",
+            &[Statement::Data(DataSpan {
+                values: vec![
+                    Some(Value::Integer(1)),
+                    Some(Value::Text("remark".to_owned())),
+                    Some(Value::Text("x-rem".to_owned())),
+                ],
+            })],
+        );
     }
 
     #[test]
     fn test_data_errors() {
-        do_error_test("DATA + 2", "1:6: Unexpected + in DATA statement");
-        do_error_test("DATA ;", "1:6: Unexpected ; in DATA statement");
-        do_error_test("DATA 5 + 1", "1:8: Expected comma after datum but found +");
-        do_error_test("DATA 5 ; 1", "1:8: Expected comma after datum but found ;");
-        do_error_test("DATA -FALSE", "1:7: Expected number after -");
-        do_error_test("DATA -\"abc\"", "1:7: Expected number after -");
-        do_error_test("DATA -foo", "1:7: Expected number after -");
+        do_error_test("DATA \"a\" 5", "1:10: Expected comma after datum but found 5");
+        do_error_test("DATA \"a\" \"b\"", "1:10: Expected comma after datum but found b");
+        do_error_test("DATA \"abc", "1:6: Incomplete string due to EOF: abc");
     }
 
     #[test]
@@ -2607,6 +2692,8 @@ mod tests {
         do_error_test("DIM a AS", "1:9: Invalid type name <<EOF>> in AS type definition");
         do_error_test("DIM a$ AS", "1:5: Type annotation not allowed in a$");
         do_error_test("DIM a AS 3", "1:10: Invalid type name 3 in AS type definition");
+        do_error_test("DIM a AS LONG", "1:10: LONG type is not supported yet");
+        do_error_test("a& = 1", "1:1: LONG integers are not supported yet");
         do_error_test("DIM a AS INTEGER 3", "1:18: Unexpected 3 in DIM statement");
 
         do_error_test("DIM a()", "1:6: Arrays require at least one dimension");
@@ -3406,8 +3493,8 @@ mod tests {
     fn test_expr_errors_due_to_keywords() {
         for kw in &[
             "BOOLEAN", "CASE", "DATA", "DIM", "DOUBLE", "ELSEIF", "END", "ERROR", "EXIT", "FOR",
-            "GOSUB", "GOTO", "IF", "IS", "INTEGER", "LOOP", "NEXT", "ON", "RESUME", "RETURN",
-            "SELECT", "STRING", "UNTIL", "WEND", "WHILE",
+            "GOSUB", "GOTO", "IF", "IS", "INTEGER", "LONG", "LOOP", "NEXT", "ON", "RESUME",
+            "RETURN", "SELECT", "SPC", "STRING", "TAB", "UNTIL", "USING", "WEND", "WHILE",
         ] {
             do_expr_error_test(
                 &format!("2 + {} - 1", kw),
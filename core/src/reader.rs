@@ -17,10 +17,14 @@
 
 use std::char;
 use std::fmt;
-use std::io::{self, BufRead};
+use std::io;
 
-/// Tab length used to compute the current position within a line when encountering a tab character.
-const TAB_LENGTH: usize = 8;
+/// Default tab length used to compute the current position within a line when encountering a tab
+/// character.
+const DEFAULT_TAB_LENGTH: usize = 8;
+
+/// Size of the buffer used to read from the underlying reader.
+const BUFFER_SIZE: usize = 8 * 1024;
 
 /// Representation of a position within a stream.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -69,7 +73,14 @@ enum Pending {
 /// Wraps `io::Read` to offer an iterator over characters.
 pub struct CharReader<'a> {
     /// The wrapper reader from which to reach characters.
-    reader: io::BufReader<&'a mut dyn io::Read>,
+    reader: &'a mut dyn io::Read,
+
+    /// Buffer for the data read from `reader`, kept around to reuse its allocation across resets.
+    buffer: Vec<u8>,
+
+    /// Range of `buffer` that holds data not yet consumed.
+    buffer_start: usize,
+    buffer_end: usize,
 
     /// Current state of any buffered data.
     pending: Pending,
@@ -80,28 +91,106 @@ pub struct CharReader<'a> {
 
     /// Line and column number of the next character to be read.
     next_pos: LineCol,
+
+    /// Distance between tab stops, used to compute positions after a tab character.
+    tab_length: usize,
+
+    /// Scratch buffer to read the bytes of lines into, kept around to reuse its allocation across
+    /// lines.
+    line: Vec<u8>,
 }
 
 impl<'a> CharReader<'a> {
     /// Constructs a new character reader from an `io::Read`.
     pub fn from(reader: &'a mut dyn io::Read) -> Self {
         Self {
-            reader: io::BufReader::new(reader),
+            reader,
+            buffer: vec![0; BUFFER_SIZE],
+            buffer_start: 0,
+            buffer_end: 0,
             pending: Pending::Unknown,
             peeked: None,
             next_pos: LineCol { line: 1, col: 1 },
+            tab_length: DEFAULT_TAB_LENGTH,
+            line: vec![],
+        }
+    }
+
+    /// Discards all buffered data and starts reading from `reader` at line 1, column 1.
+    ///
+    /// The configured tab length and the read and line buffers are preserved.
+    pub(crate) fn reset(&mut self, reader: &'a mut dyn io::Read) {
+        self.reader = reader;
+        self.buffer_start = 0;
+        self.buffer_end = 0;
+        self.pending = match std::mem::replace(&mut self.pending, Pending::Unknown) {
+            Pending::Chars(mut chars, _) => {
+                chars.clear();
+                Pending::Chars(chars, 0)
+            }
+            _ => Pending::Unknown,
+        };
+        self.peeked = None;
+        self.next_pos = LineCol { line: 1, col: 1 };
+    }
+
+    /// Sets the distance between tab stops to `length` columns, which must be positive.
+    pub(crate) fn set_tab_length(&mut self, length: usize) {
+        assert!(length > 0, "Tab length must be positive");
+        self.tab_length = length;
+    }
+
+    /// Reads the next line from `reader`, including its terminating newline if any, into `line`.
+    ///
+    /// Returns the text of the line, which is empty at the end of the input.
+    fn read_line(&mut self) -> io::Result<&str> {
+        self.line.clear();
+        loop {
+            if self.buffer_start == self.buffer_end {
+                let n = match self.reader.read(&mut self.buffer) {
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                if n == 0 {
+                    break;
+                }
+                self.buffer_start = 0;
+                self.buffer_end = n;
+            }
+
+            let available = &self.buffer[self.buffer_start..self.buffer_end];
+            match available.iter().position(|b| *b == b'\n') {
+                Some(i) => {
+                    self.line.extend_from_slice(&available[..=i]);
+                    self.buffer_start += i + 1;
+                    break;
+                }
+                None => {
+                    self.line.extend_from_slice(available);
+                    self.buffer_start = self.buffer_end;
+                }
+            }
         }
+        std::str::from_utf8(&self.line).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+        })
     }
 
     /// Replenishes `pending` with the next line to process.
     fn refill_and_next(&mut self) -> Option<io::Result<CharSpan>> {
-        self.pending = {
-            let mut line = String::new();
-            match self.reader.read_line(&mut line) {
-                Ok(0) => Pending::Eof,
-                Ok(_) => Pending::Chars(line.chars().collect(), 0),
-                Err(e) => Pending::Error(Some(e)),
+        let mut chars = match std::mem::replace(&mut self.pending, Pending::Unknown) {
+            Pending::Chars(chars, _) => chars,
+            _ => vec![],
+        };
+        self.pending = match self.read_line() {
+            Ok("") => Pending::Eof,
+            Ok(line) => {
+                chars.clear();
+                chars.extend(line.chars());
+                Pending::Chars(chars, 0)
             }
+            Err(e) => Pending::Error(Some(e)),
         };
         self.next()
     }
@@ -137,9 +226,15 @@ impl Iterator for CharReader<'_> {
                 if *last == chars.len() {
                     self.refill_and_next()
                 } else {
-                    let ch = chars[*last];
+                    let mut ch = chars[*last];
                     *last += 1;
 
+                    // Lines are split at `\n` characters, so a `\r` that is not followed by a
+                    // `\n` in the same line is a standalone line terminator.
+                    if ch == '\r' && chars.get(*last) != Some(&'\n') {
+                        ch = '\n';
+                    }
+
                     let pos = self.next_pos;
                     match ch {
                         '\n' => {
@@ -147,8 +242,8 @@ impl Iterator for CharReader<'_> {
                             self.next_pos.col = 1;
                         }
                         '\t' => {
-                            self.next_pos.col =
-                                (self.next_pos.col - 1 + TAB_LENGTH) / TAB_LENGTH * TAB_LENGTH + 1;
+                            let tab = self.tab_length;
+                            self.next_pos.col = (self.next_pos.col - 1 + tab) / tab * tab + 1;
                         }
                         _ => {
                             self.next_pos.col += 1;
@@ -242,6 +337,30 @@ mod tests {
         assert!(reader.next().is_none());
     }
 
+    #[test]
+    fn test_tabs_custom_length() {
+        let mut input = "1\t9\n123\t8\n1234\t9".as_bytes();
+        let mut reader = CharReader::from(&mut input);
+        reader.set_tab_length(4);
+        assert_eq!(cs('1', 1, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\t', 1, 2), reader.next().unwrap().unwrap());
+        assert_eq!(cs('9', 1, 5), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\n', 1, 6), reader.next().unwrap().unwrap());
+        assert_eq!(cs('1', 2, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('2', 2, 2), reader.next().unwrap().unwrap());
+        assert_eq!(cs('3', 2, 3), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\t', 2, 4), reader.next().unwrap().unwrap());
+        assert_eq!(cs('8', 2, 5), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\n', 2, 6), reader.next().unwrap().unwrap());
+        assert_eq!(cs('1', 3, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('2', 3, 2), reader.next().unwrap().unwrap());
+        assert_eq!(cs('3', 3, 3), reader.next().unwrap().unwrap());
+        assert_eq!(cs('4', 3, 4), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\t', 3, 5), reader.next().unwrap().unwrap());
+        assert_eq!(cs('9', 3, 9), reader.next().unwrap().unwrap());
+        assert!(reader.next().is_none());
+    }
+
     #[test]
     fn test_crlf() {
         let mut input = b"a\r\nb".as_ref();
@@ -253,6 +372,18 @@ mod tests {
         assert!(reader.next().is_none());
     }
 
+    #[test]
+    fn test_lone_cr() {
+        let mut input = b"a\rb\r\r".as_ref();
+        let mut reader = CharReader::from(&mut input);
+        assert_eq!(cs('a', 1, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\n', 1, 2), reader.next().unwrap().unwrap());
+        assert_eq!(cs('b', 2, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\n', 2, 2), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\n', 3, 1), reader.next().unwrap().unwrap());
+        assert!(reader.next().is_none());
+    }
+
     #[test]
     fn test_past_eof_returns_eof() {
         let mut input = b"a".as_ref();
@@ -275,6 +406,70 @@ mod tests {
         assert_eq!(LineCol { line: 1, col: 3 }, reader.next_pos());
     }
 
+    #[test]
+    fn test_reset() {
+        let mut input1 = "ab\ncd".as_bytes();
+        let mut input2 = "\tx".as_bytes();
+        let mut reader = CharReader::from(&mut input1);
+        reader.set_tab_length(4);
+        assert_eq!(cs('a', 1, 1), reader.next().unwrap().unwrap());
+        assert_eq!(&cs('b', 1, 2), reader.peek().unwrap().as_ref().unwrap());
+
+        reader.reset(&mut input2);
+        assert_eq!(LineCol { line: 1, col: 1 }, reader.next_pos());
+        assert_eq!(cs('\t', 1, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('x', 1, 5), reader.next().unwrap().unwrap());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_reset_reuses_buffers() {
+        let mut input1 = "first line\nsecond".as_bytes();
+        let mut input2 = "other\n".as_bytes();
+        let mut reader = CharReader::from(&mut input1);
+        assert_eq!(cs('f', 1, 1), reader.next().unwrap().unwrap());
+        let buffer = reader.buffer.as_ptr();
+        let line = reader.line.as_ptr();
+        let line_capacity = reader.line.capacity();
+
+        reader.reset(&mut input2);
+        let mut text = String::new();
+        for ch_span in &mut reader {
+            text.push(ch_span.unwrap().ch);
+        }
+        assert_eq!("other\n", text);
+        assert_eq!(buffer, reader.buffer.as_ptr());
+        assert_eq!(BUFFER_SIZE, reader.buffer.len());
+        assert_eq!(line, reader.line.as_ptr());
+        assert_eq!(line_capacity, reader.line.capacity());
+    }
+
+    #[test]
+    fn test_line_longer_than_buffer() {
+        let long = "x".repeat(BUFFER_SIZE * 2 + 10);
+        let input = format!("{}\nab", long);
+        let mut input = input.as_bytes();
+        let mut reader = CharReader::from(&mut input);
+        for i in 0..long.len() {
+            assert_eq!(cs('x', 1, i + 1), reader.next().unwrap().unwrap());
+        }
+        assert_eq!(cs('\n', 1, long.len() + 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('a', 2, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('b', 2, 2), reader.next().unwrap().unwrap());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut input = b"ab\n\xff\n".as_ref();
+        let mut reader = CharReader::from(&mut input);
+        assert_eq!(cs('a', 1, 1), reader.next().unwrap().unwrap());
+        assert_eq!(cs('b', 1, 2), reader.next().unwrap().unwrap());
+        assert_eq!(cs('\n', 1, 3), reader.next().unwrap().unwrap());
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, e.kind());
+    }
+
     /// A reader that generates an error only on the Nth read operation.
     ///
     /// All other reads return a line with a single character in them with the assumption that the
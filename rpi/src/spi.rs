@@ -15,8 +15,8 @@
 
 //! SPI bus implementation using rppal.
 
-use endbasic_std::spi::{SpiBus, SpiMode};
-use rppal::spi::{self, Bus, SlaveSelect, Spi};
+use endbasic_std::spi::{SpiBus, SpiMode, SsPolarity};
+use rppal::spi::{self, Bus, Polarity, SlaveSelect, Spi};
 use std::io::Write;
 use std::path::Path;
 use std::{fs, io};
@@ -65,7 +65,13 @@ pub struct RppalSpiBus {
 }
 
 /// Factory function to open an `RppalSpiBus`.
-pub fn spi_bus_open(bus: u8, slave: u8, clock_hz: u32, mode: SpiMode) -> io::Result<RppalSpiBus> {
+pub fn spi_bus_open(
+    bus: u8,
+    slave: u8,
+    clock_hz: u32,
+    mode: SpiMode,
+    ss_polarity: SsPolarity,
+) -> io::Result<RppalSpiBus> {
     let bus = match bus {
         0 => Bus::Spi0,
         _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Only bus 0 is supported")),
@@ -83,7 +89,13 @@ pub fn spi_bus_open(bus: u8, slave: u8, clock_hz: u32, mode: SpiMode) -> io::Res
         SpiMode::Mode3 => spi::Mode::Mode3,
     };
 
+    let ss_polarity = match ss_polarity {
+        SsPolarity::ActiveLow => Polarity::ActiveLow,
+        SsPolarity::ActiveHigh => Polarity::ActiveHigh,
+    };
+
     let spi = Spi::new(bus, slave, clock_hz, mode).map_err(spi_error_to_io_error)?;
+    spi.set_ss_polarity(ss_polarity).map_err(spi_error_to_io_error)?;
 
     let bufsiz = query_spi_bufsiz(None)?;
 
@@ -20,12 +20,15 @@ use async_channel::Sender;
 use async_trait::async_trait;
 use endbasic_core::exec::Signal;
 use endbasic_std::console::{
-    remove_control_chars, CharsXY, ClearType, Console, Key, PixelsXY, Resolution, SizeInPixels,
+    remove_control_chars, CharsXY, ClearType, Console, DrawOp, FontId, GradientDir, Key, PixelsXY,
+    Resolution, SizeInPixels, RGB,
 };
+use std::collections::VecDeque;
 use std::io;
 use std::path::PathBuf;
 use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 /// Implementation of the EndBASIC console on top of an SDL2 window.
 ///
@@ -36,6 +39,7 @@ pub(crate) struct SdlConsole {
     request_tx: SyncSender<Request>,
     response_rx: Receiver<Response>,
     on_key_rx: Receiver<Key>,
+    pending_keys: VecDeque<Key>,
     fg_color: Option<u8>,
     bg_color: Option<u8>,
     alt_backup: Option<(Option<u8>, Option<u8>)>,
@@ -83,6 +87,7 @@ impl SdlConsole {
                 request_tx,
                 response_rx,
                 on_key_rx,
+                pending_keys: VecDeque::default(),
                 fg_color: None,
                 bg_color: None,
                 alt_backup: None,
@@ -153,6 +158,10 @@ impl Console for SdlConsole {
         true
     }
 
+    fn supports_graphics(&self) -> bool {
+        true
+    }
+
     fn leave_alt(&mut self) -> io::Result<()> {
         let alt_backup = match self.alt_backup.take() {
             Some(t) => t,
@@ -170,6 +179,14 @@ impl Console for SdlConsole {
         Ok(())
     }
 
+    async fn cursor_position(&mut self) -> io::Result<CharsXY> {
+        self.request_tx.send(Request::CursorPosition).expect("Channel must be alive");
+        match self.response_rx.recv().expect("Channel must be alive") {
+            Response::CursorPosition(result) => result,
+            _ => panic!("Unexpected response type"),
+        }
+    }
+
     fn locate(&mut self, pos: CharsXY) -> io::Result<()> {
         self.call(Request::Locate(pos))
     }
@@ -184,6 +201,9 @@ impl Console for SdlConsole {
     }
 
     async fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        if let Some(k) = self.pending_keys.pop_front() {
+            return Ok(Some(k));
+        }
         match self.on_key_rx.try_recv() {
             Ok(k) => Ok(Some(k)),
             Err(TryRecvError::Empty) => Ok(None),
@@ -192,9 +212,51 @@ impl Console for SdlConsole {
     }
 
     async fn read_key(&mut self) -> io::Result<Key> {
+        if let Some(k) = self.pending_keys.pop_front() {
+            return Ok(k);
+        }
         Ok(self.on_key_rx.recv().expect("Channel must be alive"))
     }
 
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        // `pending_keys` only ever holds keys that must survive draining so there is no need to
+        // inspect it.
+        let mut discarded = 0;
+        loop {
+            match self.on_key_rx.try_recv() {
+                Ok(k @ (Key::Eof | Key::Interrupt)) => self.pending_keys.push_back(k),
+                Ok(_) => discarded += 1,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => panic!("Channel must be alive"),
+            }
+        }
+        Ok(discarded)
+    }
+
+    fn set_blink_rate(&mut self, period: Duration) -> io::Result<()> {
+        self.call(Request::SetBlinkRate(period))
+    }
+
+    fn set_font(&mut self, font: FontId) -> io::Result<()> {
+        self.call(Request::SetFont(font))
+    }
+
+    fn restore_cursor(&mut self) -> io::Result<()> {
+        self.call(Request::RestoreCursor)
+    }
+
+    fn save_cursor(&mut self) -> io::Result<()> {
+        self.call(Request::SaveCursor)
+    }
+
+    fn scroll_up(&mut self, lines: u16) -> io::Result<()> {
+        self.call(Request::ScrollUp(lines))
+    }
+
+    fn set_scroll_region(&mut self, top: u16, bottom: u16) -> io::Result<()> {
+        self.call(Request::SetScrollRegion(top, bottom))
+    }
+
     fn show_cursor(&mut self) -> io::Result<()> {
         self.call(Request::ShowCursor)
     }
@@ -220,6 +282,16 @@ impl Console for SdlConsole {
         self.call(Request::Write(text))
     }
 
+    fn draw_arc(
+        &mut self,
+        center: PixelsXY,
+        radius: u16,
+        start_deg: u16,
+        end_deg: u16,
+    ) -> io::Result<()> {
+        self.call(Request::DrawArc(center, radius, start_deg, end_deg))
+    }
+
     fn draw_circle(&mut self, center: PixelsXY, radius: u16) -> io::Result<()> {
         self.call(Request::DrawCircle(center, radius))
     }
@@ -228,6 +300,10 @@ impl Console for SdlConsole {
         self.call(Request::DrawCircleFilled(center, radius))
     }
 
+    fn draw_bezier(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        self.call(Request::DrawBezier(points.to_owned()))
+    }
+
     fn draw_line(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
         self.call(Request::DrawLine(x1y1, x2y2))
     }
@@ -236,6 +312,14 @@ impl Console for SdlConsole {
         self.call(Request::DrawPixel(xy))
     }
 
+    fn draw_pixels(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        self.call(Request::DrawPixels(points.to_owned()))
+    }
+
+    fn progress_bar(&mut self, fraction: f64, width: u16) -> io::Result<()> {
+        self.call(Request::ProgressBar(fraction, width))
+    }
+
     fn draw_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
         self.call(Request::DrawRect(x1y1, x2y2))
     }
@@ -244,10 +328,74 @@ impl Console for SdlConsole {
         self.call(Request::DrawRectFilled(x1y1, x2y2))
     }
 
+    fn draw_rounded_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY, radius: u16) -> io::Result<()> {
+        self.call(Request::DrawRoundedRect(x1y1, x2y2, radius))
+    }
+
+    fn draw_rounded_rect_filled(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        radius: u16,
+    ) -> io::Result<()> {
+        self.call(Request::DrawRoundedRectFilled(x1y1, x2y2, radius))
+    }
+
+    fn clear_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
+        self.call(Request::ClearRect(x1y1, x2y2))
+    }
+
+    fn draw_text(&mut self, xy: PixelsXY, text: &str) -> io::Result<()> {
+        self.call(Request::DrawText(xy, text.to_owned()))
+    }
+
+    fn text_pixel_size(&self, text: &str) -> io::Result<SizeInPixels> {
+        self.request_tx
+            .send(Request::TextPixelSize(text.to_owned()))
+            .expect("Channel must be alive");
+        match self.response_rx.recv().expect("Channel must be alive") {
+            Response::TextPixelSize(result) => result,
+            _ => panic!("Unexpected response type"),
+        }
+    }
+
+    fn draw_gradient_rect(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        from: RGB,
+        to: RGB,
+        direction: GradientDir,
+    ) -> io::Result<()> {
+        self.call(Request::DrawGradientRect(x1y1, x2y2, from, to, direction))
+    }
+
+    fn set_draw_op(&mut self, op: DrawOp) -> io::Result<()> {
+        self.call(Request::SetDrawOp(op))
+    }
+
+    fn set_clip(&mut self, rect: Option<(PixelsXY, PixelsXY)>) -> io::Result<()> {
+        self.call(Request::SetClip(rect))
+    }
+
     fn sync_now(&mut self) -> io::Result<()> {
         self.call(Request::SyncNow)
     }
 
+    /// The host thread renders the window on behalf of the caller, so presenting the buffered
+    /// output is the same as syncing it.
+    async fn present(&mut self) -> io::Result<()> {
+        self.call(Request::SyncNow)
+    }
+
+    fn begin_frame(&mut self) -> io::Result<()> {
+        self.call(Request::BeginFrame)
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.call(Request::EndFrame)
+    }
+
     fn set_sync(&mut self, enabled: bool) -> io::Result<bool> {
         self.request_tx.send(Request::SetSync(enabled)).expect("Channel must be alive");
         match self.response_rx.recv().expect("Channel must be alive") {
@@ -25,7 +25,8 @@ use endbasic_core::exec::Signal;
 use endbasic_std::console::drawing::{draw_circle, draw_circle_filled};
 use endbasic_std::console::graphics::{ClampedInto, ClampedMul, InputOps, RasterInfo, RasterOps};
 use endbasic_std::console::{
-    CharsXY, ClearType, Console, GraphicsConsole, Key, PixelsXY, Resolution, SizeInPixels, RGB,
+    CharsXY, ClearType, Console, DrawOp, FontId, GradientDir, GraphicsConsole, Key, PixelsXY,
+    Resolution, SizeInPixels, RGB,
 };
 use sdl2::event::Event;
 use sdl2::keyboard::{Keycode, Mod};
@@ -541,6 +542,7 @@ pub(crate) enum Request {
     EnterAlt,
     HideCursor,
     LeaveAlt,
+    CursorPosition,
     Locate(CharsXY),
     MoveWithinLine(i16),
     Print(String),
@@ -548,13 +550,33 @@ pub(crate) enum Request {
     SizeChars,
     SizePixels,
     Write(String),
+    SetBlinkRate(Duration),
+    SetFont(FontId),
+    RestoreCursor,
+    SaveCursor,
+    ScrollUp(u16),
+    SetScrollRegion(u16, u16),
+    ProgressBar(f64, u16),
+    DrawArc(PixelsXY, u16, u16, u16),
     DrawCircle(PixelsXY, u16),
     DrawCircleFilled(PixelsXY, u16),
+    DrawBezier(Vec<PixelsXY>),
     DrawLine(PixelsXY, PixelsXY),
     DrawPixel(PixelsXY),
+    DrawPixels(Vec<PixelsXY>),
     DrawRect(PixelsXY, PixelsXY),
     DrawRectFilled(PixelsXY, PixelsXY),
+    DrawRoundedRect(PixelsXY, PixelsXY, u16),
+    DrawRoundedRectFilled(PixelsXY, PixelsXY, u16),
+    ClearRect(PixelsXY, PixelsXY),
+    DrawGradientRect(PixelsXY, PixelsXY, RGB, RGB, GradientDir),
+    DrawText(PixelsXY, String),
+    TextPixelSize(String),
+    SetDrawOp(DrawOp),
+    SetClip(Option<(PixelsXY, PixelsXY)>),
     SyncNow,
+    BeginFrame,
+    EndFrame,
     SetSync(bool),
 
     #[cfg(test)]
@@ -569,9 +591,11 @@ pub(crate) enum Request {
 #[derive(Debug)]
 pub(crate) enum Response {
     Empty(io::Result<()>),
+    CursorPosition(io::Result<CharsXY>),
     SizeChars(CharsXY),
     SizePixels(SizeInPixels),
     SetSync(io::Result<bool>),
+    TextPixelSize(io::Result<SizeInPixels>),
 }
 
 /// Implementation of `InputOps` that should never be used.
@@ -589,6 +613,10 @@ impl InputOps for NoopInputOps {
     async fn read_key(&mut self) -> io::Result<Key> {
         unreachable!();
     }
+
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        unreachable!();
+    }
 }
 
 /// Runs the main graphics loop.
@@ -635,6 +663,7 @@ pub(crate) fn run(
                     Request::EnterAlt => Response::Empty(console.enter_alt()),
                     Request::HideCursor => Response::Empty(console.hide_cursor()),
                     Request::LeaveAlt => Response::Empty(console.leave_alt()),
+                    Request::CursorPosition => Response::CursorPosition(Ok(console.cursor_pos())),
                     Request::Locate(pos) => Response::Empty(console.locate(pos)),
                     Request::MoveWithinLine(off) => Response::Empty(console.move_within_line(off)),
                     Request::Print(text) => Response::Empty(console.print(&text)),
@@ -642,19 +671,57 @@ pub(crate) fn run(
                     Request::SizeChars => Response::SizeChars(info.size_chars),
                     Request::SizePixels => Response::SizePixels(info.size_pixels),
                     Request::Write(text) => Response::Empty(console.write(&text)),
+                    Request::SetBlinkRate(period) => {
+                        Response::Empty(console.set_blink_rate(period))
+                    }
+                    Request::SetFont(font) => Response::Empty(console.set_font(font)),
+                    Request::RestoreCursor => Response::Empty(console.restore_cursor()),
+                    Request::SaveCursor => Response::Empty(console.save_cursor()),
+                    Request::ScrollUp(lines) => Response::Empty(console.scroll_up(lines)),
+                    Request::SetScrollRegion(top, bottom) => {
+                        Response::Empty(console.set_scroll_region(top, bottom))
+                    }
+                    Request::ProgressBar(fraction, width) => {
+                        Response::Empty(console.progress_bar(fraction, width))
+                    }
+                    Request::DrawArc(center, radius, start_deg, end_deg) => {
+                        Response::Empty(console.draw_arc(center, radius, start_deg, end_deg))
+                    }
                     Request::DrawCircle(center, radius) => {
                         Response::Empty(console.draw_circle(center, radius))
                     }
                     Request::DrawCircleFilled(center, radius) => {
                         Response::Empty(console.draw_circle_filled(center, radius))
                     }
+                    Request::DrawBezier(points) => Response::Empty(console.draw_bezier(&points)),
                     Request::DrawLine(x1y1, x2y2) => Response::Empty(console.draw_line(x1y1, x2y2)),
                     Request::DrawPixel(xy) => Response::Empty(console.draw_pixel(xy)),
+                    Request::DrawPixels(points) => Response::Empty(console.draw_pixels(&points)),
                     Request::DrawRect(x1y1, x2y2) => Response::Empty(console.draw_rect(x1y1, x2y2)),
+                    Request::DrawRoundedRect(x1y1, x2y2, radius) => {
+                        Response::Empty(console.draw_rounded_rect(x1y1, x2y2, radius))
+                    }
+                    Request::DrawRoundedRectFilled(x1y1, x2y2, radius) => {
+                        Response::Empty(console.draw_rounded_rect_filled(x1y1, x2y2, radius))
+                    }
+                    Request::ClearRect(x1y1, x2y2) => {
+                        Response::Empty(console.clear_rect(x1y1, x2y2))
+                    }
+                    Request::DrawGradientRect(x1y1, x2y2, from, to, direction) => {
+                        Response::Empty(console.draw_gradient_rect(x1y1, x2y2, from, to, direction))
+                    }
+                    Request::SetDrawOp(op) => Response::Empty(console.set_draw_op(op)),
+                    Request::SetClip(rect) => Response::Empty(console.set_clip(rect)),
+                    Request::DrawText(xy, text) => Response::Empty(console.draw_text(xy, &text)),
+                    Request::TextPixelSize(text) => {
+                        Response::TextPixelSize(console.text_pixel_size(&text))
+                    }
                     Request::DrawRectFilled(x1y1, x2y2) => {
                         Response::Empty(console.draw_rect_filled(x1y1, x2y2))
                     }
                     Request::SyncNow => Response::Empty(console.sync_now()),
+                    Request::BeginFrame => Response::Empty(console.begin_frame()),
+                    Request::EndFrame => Response::Empty(console.end_frame()),
                     Request::SetSync(enabled) => Response::SetSync(console.set_sync(enabled)),
 
                     #[cfg(test)]
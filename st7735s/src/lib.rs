@@ -23,17 +23,19 @@
 
 //! Console driver for the ST7735S LCD.
 
-use async_channel::{Receiver, TryRecvError};
+use async_channel::{Receiver, Sender, TryRecvError};
 use async_trait::async_trait;
+use endbasic_core::exec::Signal;
 use endbasic_std::console::graphics::InputOps;
 use endbasic_std::console::{
-    CharsXY, ClearType, Console, ConsoleSpec, GraphicsConsole, Key, ParseError, PixelsXY,
-    SizeInPixels, RGB,
+    CharsXY, ClearType, ColorCapability, Console, ConsoleSpec, DrawOp, FontId, GradientDir,
+    GraphicsConsole, Key, ParseError, PixelsXY, SizeInPixels, RGB,
 };
-use endbasic_std::gfx::lcd::fonts::Fonts;
+use endbasic_std::gfx::lcd::fonts::{Font, Fonts};
 use endbasic_std::gfx::lcd::{to_xy_size, BufferedLcd, Lcd, LcdSize, LcdXY, RGB565Pixel};
 use endbasic_std::gpio::{Pin, PinMode, Pins};
-use endbasic_std::spi::{SpiBus, SpiMode};
+use endbasic_std::spi::{SpiBus, SpiMode, SsPolarity};
+use std::convert::TryFrom;
 use std::io;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -64,18 +66,20 @@ struct ST7735SInput<K> {
 }
 
 impl<K> ST7735SInput<K> {
+    /// Configures the `pins` connected to the buttons as inputs.
+    fn setup_pins<P: Pins>(pins: &Mutex<P>) -> io::Result<()> {
+        let mut pins = pins.lock().unwrap();
+        for (pin, _key) in INPUT_PINS {
+            pins.setup(*pin, PinMode::InPullUp)?;
+        }
+        Ok(())
+    }
+
     /// Constructs a new input handler that reads button presses through `pins` and multiplexes them
-    /// with `keyboard`.
-    fn new<P: Pins + Send + 'static>(pins: Arc<Mutex<P>>, keyboard: K) -> io::Result<Self> {
+    /// with `keyboard`.  The pins must have been configured with `setup_pins` before.
+    fn new<P: Pins + Send + 'static>(pins: Arc<Mutex<P>>, keyboard: K) -> Self {
         let (on_button_tx, on_button_rx) = async_channel::unbounded();
 
-        {
-            let mut pins = pins.lock().unwrap();
-            for (pin, _key) in INPUT_PINS {
-                pins.setup(*pin, PinMode::InPullUp)?;
-            }
-        }
-
         tokio::task::spawn(async move {
             loop {
                 let mut keys = vec![];
@@ -103,7 +107,7 @@ impl<K> ST7735SInput<K> {
             }
         });
 
-        Ok(Self { on_button_rx, keyboard })
+        Self { on_button_rx, keyboard }
     }
 }
 
@@ -128,39 +132,205 @@ impl<K: InputOps> InputOps for ST7735SInput<K> {
             result = self.keyboard.read_key() => result,
         }
     }
+
+    async fn read_key_timeout(&mut self, timeout: Duration) -> io::Result<Option<Key>> {
+        match tokio::time::timeout(timeout, self.read_key()).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        // Buttons never generate `Key::Eof` nor `Key::Interrupt` so all of them can go.
+        let mut discarded = 0;
+        while self.on_button_rx.try_recv().is_ok() {
+            discarded += 1;
+        }
+        Ok(discarded + self.keyboard.drain_keys()?)
+    }
 }
 
+/// Maximum number of consecutive zero-length writes tolerated before giving up on the SPI bus.
+const MAX_ZERO_WRITES: u32 = 5;
+
 /// Writes arbitrary data to the SPI bus.
 ///
 /// The input data is chunked to respect the maximum write size accepted by the SPI bus.
-fn lcd_write<B: SpiBus>(spi_bus: &mut B, data: &[u8]) -> io::Result<()> {
+///
+/// If the bus does not make progress, the write is retried with an exponential backoff and, after
+/// `MAX_ZERO_WRITES` attempts, fails with `io::ErrorKind::WriteZero`.
+///
+/// If `signals_tx` is provided, the write is aborted with `io::ErrorKind::Interrupted` when a
+/// signal is pending delivery to the machine before sending any chunk but the first one.  The
+/// signal is left in the channel for the machine to act on.  An aborted write leaves the LCD with
+/// a partially-updated frame until the affected area is redrawn.
+fn lcd_write<B: SpiBus>(
+    spi_bus: &mut B,
+    data: &[u8],
+    signals_tx: Option<&Sender<Signal>>,
+) -> io::Result<()> {
     // TODO(jmmv): Do we really need to chunk the data ourselves, or can we try to write it
     // all to the bus and then expect the write to return partial results?
-    for chunk in data.chunks(spi_bus.max_size()) {
+    for (n, chunk) in data.chunks(spi_bus.max_size()).enumerate() {
+        if n > 0 && matches!(signals_tx, Some(tx) if !tx.is_empty()) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "LCD transfer interrupted"));
+        }
+
         let mut i = 0;
+        let mut zero_writes = 0;
         loop {
             let n = spi_bus.write(&chunk[i..])?;
             if n == chunk.len() - i {
                 break;
             }
+            if n == 0 {
+                zero_writes += 1;
+                if zero_writes == MAX_ZERO_WRITES {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "SPI bus is not accepting any data",
+                    ));
+                }
+                std::thread::sleep(Duration::from_millis(1 << (zero_writes - 1)));
+            } else {
+                zero_writes = 0;
+            }
             i += n;
         }
     }
     Ok(())
 }
 
+/// Physical characteristics of an ST7735S panel variant.
+#[derive(Clone, Copy)]
+struct Panel {
+    /// Name of the panel variant as given in the console specification.
+    name: &'static str,
+
+    /// Dimensions of the visible area in pixels.
+    size: LcdSize,
+
+    /// Horizontal offset of the visible area within the controller's memory.
+    ///
+    /// The default for each variant matches the boards listed in `PANELS`, but other boards with
+    /// the same controller may need a different value, which can be configured with the
+    /// `offset_x` setting or `ST7735SConsole::set_window_offsets`.
+    adjust_x: usize,
+
+    /// Vertical offset of the visible area within the controller's memory.  See `adjust_x` for
+    /// details.
+    adjust_y: usize,
+
+    /// Value of the memory data access control (MADCTL) register, which sets the scan direction
+    /// (the `MY`, `MX` and `MV` bits, `0x80`, `0x40` and `0x20`) and the color order (the `RGB`
+    /// bit, `0x08`, which selects BGR when set) of the panel.
+    madctl: u8,
+}
+
+/// Supported panel variants.  The first one is the default.
+const PANELS: &[Panel] = &[
+    // Waveshare 1.44" LCD HAT.
+    Panel {
+        name: "128x128",
+        size: LcdSize { width: 128, height: 128 },
+        adjust_x: 1,
+        adjust_y: 2,
+        madctl: 0x40 | 0x20 | 0x08,
+    },
+    // Waveshare 1.8" LCD module.
+    Panel {
+        name: "160x128",
+        size: LcdSize { width: 160, height: 128 },
+        adjust_x: 0,
+        adjust_y: 0,
+        madctl: 0x40 | 0x20 | 0x08,
+    },
+];
+
+/// SPI bus settings used to talk to the panel.
+///
+/// The Waveshare 1.44" and 1.8" boards, which are the ones described in `PANELS`, need SPI mode 0
+/// with an active-low slave select line, which is the default.  Some clone boards that lack a CS
+/// line need mode 3 instead, and modules that wire CS through an inverter need an active-high
+/// slave select.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SpiConfig {
+    /// Clock polarity and phase.
+    mode: SpiMode,
+
+    /// Level of the slave select line that selects the panel.
+    ss_polarity: SsPolarity,
+}
+
+impl Default for SpiConfig {
+    fn default() -> Self {
+        Self { mode: SpiMode::Mode0, ss_polarity: SsPolarity::ActiveLow }
+    }
+}
+
+impl SpiConfig {
+    /// Extracts the SPI settings from the `spi_mode` and `cs_polarity` flags in `spec`.
+    fn from_spec(spec: &mut ConsoleSpec) -> io::Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(mode) = spec.take_keyed_flag::<u8>("spi_mode")? {
+            config.mode = match mode {
+                0 => SpiMode::Mode0,
+                1 => SpiMode::Mode1,
+                2 => SpiMode::Mode2,
+                3 => SpiMode::Mode3,
+                _ => {
+                    return Err(ParseError(format!(
+                        "Invalid SPI mode: {}; valid modes are: 0, 1, 2, 3",
+                        mode
+                    ))
+                    .into())
+                }
+            };
+        }
+
+        if let Some(polarity) = spec.take_keyed_flag_str("cs_polarity") {
+            config.ss_polarity = match polarity {
+                "low" => SsPolarity::ActiveLow,
+                "high" => SsPolarity::ActiveHigh,
+                _ => {
+                    return Err(ParseError(format!(
+                        "Invalid CS polarity: {}; valid values are: high, low",
+                        polarity
+                    ))
+                    .into())
+                }
+            };
+        }
+
+        Ok(config)
+    }
+}
+
 /// LCD handler for the ST7735S console.
+///
+/// The pins and the SPI bus are shared behind locks so that pixel transfers can run on a thread
+/// other than the one that owns the LCD.  The pins are always locked before the bus.
 struct ST7735SLcd<P: Pins, B> {
     pins: Arc<Mutex<P>>,
-    spi_bus: B,
-    size_pixels: LcdSize,
+    spi_bus: Arc<Mutex<B>>,
+    panel: Panel,
+    signals_tx: Sender<Signal>,
 }
 
 impl<P: Pins, B: SpiBus> ST7735SLcd<P, B> {
-    /// Initializes the LCD.
-    pub fn new<F>(pins: Arc<Mutex<P>>, spi_factory: F) -> io::Result<Self>
+    /// Initializes the LCD for the given `panel` variant using the `spi` bus settings.
+    ///
+    /// Pixel transfers are interrupted when a signal is pending in `signals_tx`.
+    pub fn new<F>(
+        pins: Arc<Mutex<P>>,
+        spi_factory: F,
+        spi: SpiConfig,
+        panel: Panel,
+        signals_tx: Sender<Signal>,
+    ) -> io::Result<Self>
     where
-        F: FnOnce(u8, u8, u32, SpiMode) -> io::Result<B>,
+        F: FnOnce(u8, u8, u32, SpiMode, SsPolarity) -> io::Result<B>,
     {
         {
             let mut pins = pins.lock().unwrap();
@@ -169,11 +339,10 @@ impl<P: Pins, B: SpiBus> ST7735SLcd<P, B> {
             }
         }
 
-        let spi_bus = spi_factory(0, 0, 9000000, SpiMode::Mode0)?;
-
-        let size_pixels = LcdSize { width: 128, height: 128 };
+        let spi_bus =
+            Arc::from(Mutex::from(spi_factory(0, 0, 9000000, spi.mode, spi.ss_polarity)?));
 
-        let mut device = Self { pins, spi_bus, size_pixels };
+        let mut device = Self { pins, spi_bus, panel, signals_tx };
 
         device.lcd_init()?;
 
@@ -183,13 +352,25 @@ impl<P: Pins, B: SpiBus> ST7735SLcd<P, B> {
     /// Selects the registers to affect by the next data write.
     fn lcd_write_reg(pins: &mut P, spi_bus: &mut B, regs: &[u8]) -> io::Result<()> {
         pins.write(OUTPUT_PIN_DC, false)?;
-        lcd_write(spi_bus, regs)
+        lcd_write(spi_bus, regs, None)
     }
 
     /// Writes data to the device.  A register should have been selected before.
     fn lcd_write_data(pins: &mut P, spi_bus: &mut B, data: &[u8]) -> io::Result<()> {
         pins.write(OUTPUT_PIN_DC, true)?;
-        lcd_write(spi_bus, data)
+        lcd_write(spi_bus, data, None)
+    }
+
+    /// Writes pixel data to the device, aborting the write if a signal is pending in
+    /// `signals_tx`.  The memory write register should have been selected before.
+    fn lcd_write_pixels(
+        pins: &mut P,
+        spi_bus: &mut B,
+        data: &[u8],
+        signals_tx: &Sender<Signal>,
+    ) -> io::Result<()> {
+        pins.write(OUTPUT_PIN_DC, true)?;
+        lcd_write(spi_bus, data, Some(signals_tx))
     }
 
     /// Resets the LCD.
@@ -273,18 +454,17 @@ impl<P: Pins, B: SpiBus> ST7735SLcd<P, B> {
         Ok(())
     }
 
-    /// Initializes the LCD scan direction and pixel color encoding.
-    fn lcd_set_gram_scan_way(pins: &mut P, spi_bus: &mut B) -> io::Result<()> {
-        Self::lcd_write_reg(pins, spi_bus, &[0x36])?; // MX, MY, RGB mode.
-        let scan_dir = 0x40 | 0x20; // X, Y.
-        let rgb_mode = 0x08; // RGB for 1.44in display.
-        Self::lcd_write_data(pins, spi_bus, &[scan_dir | rgb_mode])?;
+    /// Initializes the LCD scan direction and pixel color encoding as required by `panel`.
+    fn lcd_set_gram_scan_way(pins: &mut P, spi_bus: &mut B, panel: &Panel) -> io::Result<()> {
+        Self::lcd_write_reg(pins, spi_bus, &[0x36])?;
+        Self::lcd_write_data(pins, spi_bus, &[panel.madctl])?;
         Ok(())
     }
 
     /// Initializes the LCD.
     fn lcd_init(&mut self) -> io::Result<()> {
         let mut pins = self.pins.lock().unwrap();
+        let mut spi_bus = self.spi_bus.lock().unwrap();
 
         // I'm not sure what this does.  This does not have an effect on Linux but
         // setting this to high on NetBSD causes the LCD to remain lit up.
@@ -293,41 +473,101 @@ impl<P: Pins, B: SpiBus> ST7735SLcd<P, B> {
         pins.write(OUTPUT_PIN_BL, true)?;
 
         Self::lcd_reset(&mut *pins)?;
-        Self::lcd_init_reg(&mut *pins, &mut self.spi_bus)?;
+        Self::lcd_init_reg(&mut *pins, &mut *spi_bus)?;
 
-        Self::lcd_set_gram_scan_way(&mut *pins, &mut self.spi_bus)?;
+        Self::lcd_set_gram_scan_way(&mut *pins, &mut *spi_bus, &self.panel)?;
         std::thread::sleep(Duration::from_millis(200));
 
-        Self::lcd_write_reg(&mut *pins, &mut self.spi_bus, &[0x11])?;
+        Self::lcd_write_reg(&mut *pins, &mut *spi_bus, &[0x11])?;
         std::thread::sleep(Duration::from_millis(200));
 
         // Turn display on.
-        Self::lcd_write_reg(&mut *pins, &mut self.spi_bus, &[0x29])?;
+        Self::lcd_write_reg(&mut *pins, &mut *spi_bus, &[0x29])?;
 
         Ok(())
     }
 
     /// Configures the LCD so that the next write, which carries pixel data, affects the specified
-    /// region.
-    fn lcd_set_window(pins: &mut P, spi_bus: &mut B, xy: LcdXY, size: LcdSize) -> io::Result<()> {
-        let adjust_x = 1;
-        let adjust_y = 2;
+    /// region of the `panel`.
+    fn lcd_set_window(
+        pins: &mut P,
+        spi_bus: &mut B,
+        panel: &Panel,
+        xy: LcdXY,
+        size: LcdSize,
+    ) -> io::Result<()> {
+        if size.width == 0
+            || size.height == 0
+            || xy.x + size.width > panel.size.width
+            || xy.y + size.height > panel.size.height
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Window {}x{} at {},{} does not fit in the {}x{} panel",
+                    size.width, size.height, xy.x, xy.y, panel.size.width, panel.size.height
+                ),
+            ));
+        }
 
-        let x1 = ((xy.x & 0xff) + adjust_x) as u8;
-        let x2 = (((xy.x + size.width) + adjust_x - 1) & 0xff) as u8;
-        let y1 = ((xy.y & 0xff) + adjust_y) as u8;
-        let y2 = (((xy.y + size.height) + adjust_y - 1) & 0xff) as u8;
+        let [x1h, x1l] = window_coord(xy.x + panel.adjust_x)?;
+        let [x2h, x2l] = window_coord(xy.x + size.width - 1 + panel.adjust_x)?;
+        let [y1h, y1l] = window_coord(xy.y + panel.adjust_y)?;
+        let [y2h, y2l] = window_coord(xy.y + size.height - 1 + panel.adjust_y)?;
 
         Self::lcd_write_reg(pins, spi_bus, &[0x2a])?;
-        Self::lcd_write_data(pins, spi_bus, &[0x00, x1, 0x00, x2])?;
+        Self::lcd_write_data(pins, spi_bus, &[x1h, x1l, x2h, x2l])?;
 
         Self::lcd_write_reg(pins, spi_bus, &[0x2b])?;
-        Self::lcd_write_data(pins, spi_bus, &[0x00, y1, 0x00, y2])?;
+        Self::lcd_write_data(pins, spi_bus, &[y1h, y1l, y2h, y2l])?;
 
         Self::lcd_write_reg(pins, spi_bus, &[0x2c])?;
 
         Ok(())
     }
+
+    /// Sends the pixel `data` for the area between `x1y1` and `x2y2` of the `panel`, holding the
+    /// `pins` and `spi_bus` locks for the whole transfer.
+    fn lcd_send_pixels(
+        pins: &Mutex<P>,
+        spi_bus: &Mutex<B>,
+        panel: &Panel,
+        signals_tx: &Sender<Signal>,
+        x1y1: LcdXY,
+        x2y2: LcdXY,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let (xy, size) = to_xy_size(x1y1, x2y2);
+        let mut pins = pins.lock().unwrap();
+        let mut spi_bus = spi_bus.lock().unwrap();
+        Self::lcd_set_window(&mut *pins, &mut *spi_bus, panel, xy, size)?;
+        Self::lcd_write_pixels(&mut *pins, &mut *spi_bus, data, signals_tx)
+    }
+
+    /// Returns a function that sends the pixel `data` for the area between `x1y1` and `x2y2` when
+    /// called.  The function does not borrow the LCD so it can run on a separate thread.
+    fn set_data_task(
+        &self,
+        x1y1: LcdXY,
+        x2y2: LcdXY,
+        data: Vec<u8>,
+    ) -> impl FnOnce() -> io::Result<()> + Send + 'static
+    where
+        P: Send + 'static,
+        B: Send + 'static,
+    {
+        let pins = self.pins.clone();
+        let spi_bus = self.spi_bus.clone();
+        let panel = self.panel;
+        let signals_tx = self.signals_tx.clone();
+        move || Self::lcd_send_pixels(&pins, &spi_bus, &panel, &signals_tx, x1y1, x2y2, &data)
+    }
+
+    /// Sets the offsets of the visible area within the controller's memory to `dx` and `dy`.
+    fn set_window_offsets(&mut self, dx: u16, dy: u16) {
+        self.panel.adjust_x = usize::from(dx);
+        self.panel.adjust_y = usize::from(dy);
+    }
 }
 
 impl<P: Pins, B> Drop for ST7735SLcd<P, B> {
@@ -337,28 +577,63 @@ impl<P: Pins, B> Drop for ST7735SLcd<P, B> {
     }
 }
 
+/// Converts a window coordinate in the controller's memory to the big-endian bytes expected by the
+/// column and row address commands.
+fn window_coord(value: usize) -> io::Result<[u8; 2]> {
+    match u16::try_from(value) {
+        Ok(value) => Ok(value.to_be_bytes()),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Window coordinate {} is too large", value),
+        )),
+    }
+}
+
+/// Converts an `rgb` color to its RGB565 representation.
+///
+/// Each channel is rounded to the nearest representable value instead of being truncated so that
+/// colors are not biased towards darker tones.
+fn rgb_to_rgb565(rgb: RGB) -> u16 {
+    fn quantize(value: u8, bits: u8) -> u16 {
+        let shift = 8 - bits;
+        let half_step = 1 << (shift - 1);
+        std::cmp::min(u16::from(value) + half_step, 255) >> shift
+    }
+
+    (quantize(rgb.0, 5) << 11) | (quantize(rgb.1, 6) << 5) | quantize(rgb.2, 5)
+}
+
 impl<P: Pins, B: SpiBus> Lcd for ST7735SLcd<P, B> {
     type Pixel = RGB565Pixel;
 
     fn info(&self) -> (LcdSize, usize) {
-        (self.size_pixels, 2)
+        (self.panel.size, 2)
     }
 
     fn encode(&self, rgb: RGB) -> Self::Pixel {
-        let rgb = (u16::from(rgb.0), u16::from(rgb.1), u16::from(rgb.2));
-
-        let pixel: u16 = ((rgb.0 >> 3) << 11) | ((rgb.1 >> 2) << 5) | (rgb.2 >> 3);
+        let pixel = rgb_to_rgb565(rgb);
 
         let high = (pixel >> 8) as u8;
         let low = (pixel & 0xff) as u8;
         RGB565Pixel([high, low])
     }
 
+    fn encode_into(&self, rgb: RGB, out: &mut Vec<u8>) {
+        let pixel = rgb_to_rgb565(rgb);
+        out.push((pixel >> 8) as u8);
+        out.push((pixel & 0xff) as u8);
+    }
+
     fn set_data(&mut self, x1y1: LcdXY, x2y2: LcdXY, data: &[u8]) -> io::Result<()> {
-        let (xy, size) = to_xy_size(x1y1, x2y2);
-        let mut pins = self.pins.lock().unwrap();
-        Self::lcd_set_window(&mut *pins, &mut self.spi_bus, xy, size)?;
-        Self::lcd_write_data(&mut *pins, &mut self.spi_bus, data)
+        Self::lcd_send_pixels(
+            &self.pins,
+            &self.spi_bus,
+            &self.panel,
+            &self.signals_tx,
+            x1y1,
+            x2y2,
+            data,
+        )
     }
 }
 
@@ -369,8 +644,45 @@ pub struct ST7735SConsole<P: Pins + Send, B: SpiBus, K> {
     inner: GraphicsConsole<ST7735SInput<K>, BufferedLcd<ST7735SLcd<P, B>>>,
 }
 
+impl<P, B, K> ST7735SConsole<P, B, K>
+where
+    P: Pins + Send + 'static,
+    B: SpiBus + Send + 'static,
+    K: InputOps,
+{
+    /// Sets the offsets of the visible area within the controller's memory to `dx` and `dy`,
+    /// overriding the defaults of the panel variant and the `offset_x` and `offset_y` settings.
+    ///
+    /// Only subsequent updates to the LCD use the new offsets: the contents already on the screen
+    /// are not moved.  Use `draw_calibration_border` to find the right values.
+    pub fn set_window_offsets(&mut self, dx: u16, dy: u16) {
+        self.inner.raster_ops_mut().lcd_mut().set_window_offsets(dx, dy);
+    }
+
+    /// Clears the screen and draws a one-pixel border around the visible area with the current
+    /// foreground color.
+    ///
+    /// If the window offsets are correct, the four sides of the border are visible at the very
+    /// edges of the panel.  A missing side, or a gap between a side and the edge, indicates that
+    /// the offsets need adjusting.
+    pub fn draw_calibration_border(&mut self) -> io::Result<()> {
+        let size = self.inner.size_pixels()?;
+        self.inner.clear(ClearType::All)?;
+        let x2y2 = PixelsXY::new(
+            i16::try_from(size.width).expect("Panel must fit") - 1,
+            i16::try_from(size.height).expect("Panel must fit") - 1,
+        );
+        self.inner.draw_rect(PixelsXY::new(0, 0), x2y2)
+    }
+}
+
 #[async_trait(?Send)]
-impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K> {
+impl<P, B, K> Console for ST7735SConsole<P, B, K>
+where
+    P: Pins + Send + 'static,
+    B: SpiBus + Send + 'static,
+    K: InputOps,
+{
     fn clear(&mut self, how: ClearType) -> io::Result<()> {
         self.inner.clear(how)
     }
@@ -379,6 +691,10 @@ impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K>
         self.inner.color()
     }
 
+    fn color_capability(&self) -> ColorCapability {
+        self.inner.color_capability()
+    }
+
     fn set_color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()> {
         self.inner.set_color(fg, bg)
     }
@@ -395,10 +711,18 @@ impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K>
         self.inner.is_interactive()
     }
 
+    fn supports_graphics(&self) -> bool {
+        self.inner.supports_graphics()
+    }
+
     fn leave_alt(&mut self) -> io::Result<()> {
         self.inner.leave_alt()
     }
 
+    async fn cursor_position(&mut self) -> io::Result<CharsXY> {
+        self.inner.cursor_position().await
+    }
+
     fn locate(&mut self, pos: CharsXY) -> io::Result<()> {
         self.inner.locate(pos)
     }
@@ -419,6 +743,34 @@ impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K>
         self.inner.read_key().await
     }
 
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        self.inner.drain_keys()
+    }
+
+    fn set_blink_rate(&mut self, period: Duration) -> io::Result<()> {
+        self.inner.set_blink_rate(period)
+    }
+
+    fn set_font(&mut self, font: FontId) -> io::Result<()> {
+        self.inner.set_font(font)
+    }
+
+    fn restore_cursor(&mut self) -> io::Result<()> {
+        self.inner.restore_cursor()
+    }
+
+    fn save_cursor(&mut self) -> io::Result<()> {
+        self.inner.save_cursor()
+    }
+
+    fn scroll_up(&mut self, lines: u16) -> io::Result<()> {
+        self.inner.scroll_up(lines)
+    }
+
+    fn set_scroll_region(&mut self, top: u16, bottom: u16) -> io::Result<()> {
+        self.inner.set_scroll_region(top, bottom)
+    }
+
     fn show_cursor(&mut self) -> io::Result<()> {
         self.inner.show_cursor()
     }
@@ -435,6 +787,20 @@ impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K>
         self.inner.write(text)
     }
 
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_raw(bytes)
+    }
+
+    fn draw_arc(
+        &mut self,
+        center: PixelsXY,
+        radius: u16,
+        start_deg: u16,
+        end_deg: u16,
+    ) -> io::Result<()> {
+        self.inner.draw_arc(center, radius, start_deg, end_deg)
+    }
+
     fn draw_circle(&mut self, center: PixelsXY, radius: u16) -> io::Result<()> {
         self.inner.draw_circle(center, radius)
     }
@@ -443,6 +809,10 @@ impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K>
         self.inner.draw_circle_filled(center, radius)
     }
 
+    fn draw_bezier(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        self.inner.draw_bezier(points)
+    }
+
     fn draw_line(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
         self.inner.draw_line(x1y1, x2y2)
     }
@@ -451,6 +821,14 @@ impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K>
         self.inner.draw_pixel(xy)
     }
 
+    fn draw_pixels(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        self.inner.draw_pixels(points)
+    }
+
+    fn progress_bar(&mut self, fraction: f64, width: u16) -> io::Result<()> {
+        self.inner.progress_bar(fraction, width)
+    }
+
     fn draw_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
         self.inner.draw_rect(x1y1, x2y2)
     }
@@ -459,59 +837,231 @@ impl<P: Pins + Send, B: SpiBus, K: InputOps> Console for ST7735SConsole<P, B, K>
         self.inner.draw_rect_filled(x1y1, x2y2)
     }
 
+    fn draw_rounded_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY, radius: u16) -> io::Result<()> {
+        self.inner.draw_rounded_rect(x1y1, x2y2, radius)
+    }
+
+    fn draw_rounded_rect_filled(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        radius: u16,
+    ) -> io::Result<()> {
+        self.inner.draw_rounded_rect_filled(x1y1, x2y2, radius)
+    }
+
+    fn clear_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
+        self.inner.clear_rect(x1y1, x2y2)
+    }
+
+    fn draw_text(&mut self, xy: PixelsXY, text: &str) -> io::Result<()> {
+        self.inner.draw_text(xy, text)
+    }
+
+    fn text_pixel_size(&self, text: &str) -> io::Result<SizeInPixels> {
+        self.inner.text_pixel_size(text)
+    }
+
+    fn draw_gradient_rect(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        from: RGB,
+        to: RGB,
+        direction: GradientDir,
+    ) -> io::Result<()> {
+        self.inner.draw_gradient_rect(x1y1, x2y2, from, to, direction)
+    }
+
+    fn set_draw_op(&mut self, op: DrawOp) -> io::Result<()> {
+        self.inner.set_draw_op(op)
+    }
+
+    fn set_clip(&mut self, rect: Option<(PixelsXY, PixelsXY)>) -> io::Result<()> {
+        self.inner.set_clip(rect)
+    }
+
     fn sync_now(&mut self) -> io::Result<()> {
         self.inner.sync_now()
     }
 
+    /// Sends any buffered output to the LCD from a blocking task and waits for it to complete.
+    ///
+    /// The damaged area of the framebuffer is copied before the transfer starts and the console
+    /// cannot be drawn on until this returns, so the LCD never receives pixels out of order.  The
+    /// transfer holds the pins and SPI bus locks for its whole duration.  If the transfer fails,
+    /// its area is marked as damaged again so that the next flush resends it.
+    async fn present(&mut self) -> io::Result<()> {
+        let buffered = self.inner.raster_ops_mut();
+        let (x1y1, x2y2, task) = match buffered.take_damage() {
+            None => return Ok(()),
+            Some((x1y1, x2y2, data)) => {
+                (x1y1, x2y2, buffered.lcd().set_data_task(x1y1, x2y2, data))
+            }
+        };
+        let result = match tokio::task::spawn_blocking(task).await {
+            Ok(result) => result,
+            Err(e) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("LCD transfer task failed: {}", e),
+            )),
+        };
+        if result.is_err() {
+            self.inner.raster_ops_mut().restore_damage(x1y1, x2y2);
+        }
+        result
+    }
+
+    fn begin_frame(&mut self) -> io::Result<()> {
+        self.inner.begin_frame()
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.inner.end_frame()
+    }
+
     fn set_sync(&mut self, enabled: bool) -> io::Result<bool> {
         self.inner.set_sync(enabled)
     }
 }
 
+/// Settings of the console obtained from its specification.
+struct Settings {
+    /// Default foreground color, if overridden.
+    default_fg_color: Option<u8>,
+
+    /// Default background color, if overridden.
+    default_bg_color: Option<u8>,
+
+    /// Font to render text with.
+    font: &'static Font,
+
+    /// Physical characteristics of the LCD.
+    panel: Panel,
+
+    /// Settings of the SPI bus connected to the LCD.
+    spi: SpiConfig,
+}
+
+impl Settings {
+    /// Extracts the console settings from `spec`, using `fonts` to look up the font by name.
+    fn from_spec(spec: &mut ConsoleSpec, fonts: &Fonts) -> io::Result<Self> {
+        let default_fg_color = spec.take_keyed_flag::<u8>("fg_color")?;
+        let default_bg_color = spec.take_keyed_flag::<u8>("bg_color")?;
+
+        let font_name = spec.take_keyed_flag_str("font").unwrap_or("5x8");
+        let font = match fonts.get(font_name) {
+            Some(font) => font,
+            None => {
+                let mut valid = fonts.keys().copied().collect::<Vec<&'static str>>();
+                valid.sort();
+                return Err(ParseError(format!(
+                    "Unknown font: {}; valid names are: {}",
+                    font_name,
+                    valid.join(", ")
+                ))
+                .into());
+            }
+        };
+
+        let panel_name = spec.take_keyed_flag_str("panel").unwrap_or(PANELS[0].name);
+        let panel = match PANELS.iter().find(|p| p.name == panel_name) {
+            Some(panel) => *panel,
+            None => {
+                let valid = PANELS.iter().map(|p| p.name).collect::<Vec<&'static str>>();
+                return Err(ParseError(format!(
+                    "Unknown panel: {}; valid names are: {}",
+                    panel_name,
+                    valid.join(", ")
+                ))
+                .into());
+            }
+        };
+
+        let mut panel = panel;
+        if let Some(offset) = spec.take_keyed_flag::<u16>("offset_x")? {
+            panel.adjust_x = usize::from(offset);
+        }
+        if let Some(offset) = spec.take_keyed_flag::<u16>("offset_y")? {
+            panel.adjust_y = usize::from(offset);
+        }
+
+        let spi = SpiConfig::from_spec(spec)?;
+
+        Ok(Self { default_fg_color, default_bg_color, font, panel, spi })
+    }
+}
+
 /// Initializes a new console on a ST7735S LCD.
+///
+/// Long transfers to the LCD are aborted when a signal is pending in `signals_tx`, so that
+/// programs that continuously redraw the screen can be stopped.  Such transfers fail with
+/// `io::ErrorKind::Interrupted` and leave a partially-drawn frame on the LCD.
 pub fn new_console<P, F, B, K>(
     pins: P,
     new_spi: F,
     keyboard: K,
     spec: &mut ConsoleSpec,
     fonts: &Fonts,
+    signals_tx: Sender<Signal>,
 ) -> io::Result<ST7735SConsole<P, B, K>>
 where
     P: Pins + Send + 'static,
-    F: FnOnce(u8, u8, u32, SpiMode) -> io::Result<B>,
+    F: FnOnce(u8, u8, u32, SpiMode, SsPolarity) -> io::Result<B>,
     B: SpiBus,
     K: InputOps,
 {
-    let default_fg_color = spec.take_keyed_flag::<u8>("fg_color")?;
-    let default_bg_color = spec.take_keyed_flag::<u8>("bg_color")?;
-
-    let font_name = spec.take_keyed_flag_str("font").unwrap_or("5x8");
-    let font = match fonts.get(font_name) {
-        Some(font) => font,
-        None => {
-            let mut valid = fonts.keys().copied().collect::<Vec<&'static str>>();
-            valid.sort();
-            return Err(ParseError(format!(
-                "Unknown font: {}; valid names are: {}",
-                font_name,
-                valid.join(", ")
-            ))
-            .into());
-        }
-    };
+    try_new_console(pins, new_spi, keyboard, spec, fonts, signals_tx).map_err(|(e, _keyboard)| e)
+}
+
+/// Initializes a new console on a ST7735S LCD like `new_console` does, but hands `keyboard` back
+/// to the caller on failure.
+///
+/// This allows the caller to fall back to a console that only uses the keyboard when the LCD is
+/// not present.  The keyboard is only returned for errors in the setup of the hardware.  Errors in
+/// the specification are configuration mistakes that a fallback would hide, so they do not return
+/// the keyboard, and neither does a failure to clear the LCD right after it has been successfully
+/// initialized.
+pub fn try_new_console<P, F, B, K>(
+    pins: P,
+    new_spi: F,
+    keyboard: K,
+    spec: &mut ConsoleSpec,
+    fonts: &Fonts,
+    signals_tx: Sender<Signal>,
+) -> Result<ST7735SConsole<P, B, K>, (io::Error, Option<K>)>
+where
+    P: Pins + Send + 'static,
+    F: FnOnce(u8, u8, u32, SpiMode, SsPolarity) -> io::Result<B>,
+    B: SpiBus,
+    K: InputOps,
+{
+    let settings = Settings::from_spec(spec, fonts).map_err(|e| (e, None))?;
 
     let pins = Arc::from(Mutex::from(pins));
-    let lcd = ST7735SLcd::new(pins.clone(), new_spi)?;
-    let input = ST7735SInput::new(pins, keyboard)?;
-    let lcd = BufferedLcd::new(lcd, font);
-    let inner = GraphicsConsole::new(input, lcd, default_fg_color, default_bg_color)?;
+    if let Err(e) = ST7735SInput::<K>::setup_pins(&pins) {
+        return Err((e, Some(keyboard)));
+    }
+    let lcd = match ST7735SLcd::new(pins.clone(), new_spi, settings.spi, settings.panel, signals_tx)
+    {
+        Ok(lcd) => lcd,
+        Err(e) => return Err((e, Some(keyboard))),
+    };
+
+    let input = ST7735SInput::new(pins, keyboard);
+    let lcd = BufferedLcd::new(lcd, settings.font);
+    let inner =
+        GraphicsConsole::new(input, lcd, settings.default_fg_color, settings.default_bg_color)
+            .map_err(|e| (e, None))?;
     Ok(ST7735SConsole { inner })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use endbasic_std::console::ansi_color_to_rgb;
     use std::io::Write;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     #[derive(Default)]
     struct MockSpiBus {
@@ -538,24 +1088,465 @@ mod tests {
         }
     }
 
+    /// SPI bus that never accepts any data.
+    #[derive(Default)]
+    struct StalledSpiBus {
+        attempts: u32,
+    }
+
+    impl Write for StalledSpiBus {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            self.attempts += 1;
+            Ok(0)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SpiBus for StalledSpiBus {
+        fn max_size(&self) -> usize {
+            16
+        }
+    }
+
+    /// SPI bus that records the bytes it accepts and that fails all writes while `fail` is set.
+    #[derive(Clone, Default)]
+    struct FlakySpiBus {
+        fail: Arc<AtomicBool>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for FlakySpiBus {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "Injected failure"));
+            }
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SpiBus for FlakySpiBus {
+        fn max_size(&self) -> usize {
+            4096
+        }
+    }
+
+    /// Pins that accept all operations and do nothing.
+    struct NoopPins;
+
+    impl Pins for NoopPins {
+        fn setup(&mut self, _pin: Pin, _mode: PinMode) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn clear(&mut self, _pin: Pin) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn clear_all(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn read(&mut self, _pin: Pin) -> io::Result<bool> {
+            Ok(false)
+        }
+
+        fn write(&mut self, _pin: Pin, _v: bool) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Keyboard that never delivers any key and that carries an identifier to tell instances
+    /// apart.
+    #[derive(Debug, PartialEq)]
+    struct MockKeyboard(u8);
+
+    #[async_trait(?Send)]
+    impl InputOps for MockKeyboard {
+        async fn poll_key(&mut self) -> io::Result<Option<Key>> {
+            Ok(None)
+        }
+
+        async fn read_key(&mut self) -> io::Result<Key> {
+            Ok(Key::Eof)
+        }
+
+        fn drain_keys(&mut self) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    /// Calls `try_new_console` with `spec` and returns the error and keyboard it yields.
+    fn try_new_console_error<F>(spec: &str, new_spi: F) -> (io::Error, Option<MockKeyboard>)
+    where
+        F: FnOnce(u8, u8, u32, SpiMode, SsPolarity) -> io::Result<MockSpiBus>,
+    {
+        let (signals_tx, _signals_rx) = async_channel::unbounded();
+        let mut spec = ConsoleSpec::init(spec);
+        let fonts = endbasic_std::gfx::lcd::fonts::all_fonts();
+        match try_new_console(NoopPins, new_spi, MockKeyboard(7), &mut spec, &fonts, signals_tx) {
+            Ok(_) => panic!("Console creation should have failed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn test_try_new_console_returns_keyboard_on_hardware_error() {
+        let (e, keyboard) = try_new_console_error("st7735s", |_, _, _, _, _| {
+            Err(io::Error::new(io::ErrorKind::NotFound, "No SPI bus"))
+        });
+        assert_eq!("No SPI bus", e.to_string());
+        assert_eq!(Some(MockKeyboard(7)), keyboard);
+    }
+
+    #[test]
+    fn test_try_new_console_drops_keyboard_on_spec_error() {
+        let (e, keyboard) = try_new_console_error("st7735s:panel=1x1", |_, _, _, _, _| {
+            panic!("The SPI bus should not have been opened")
+        });
+        assert!(e.to_string().starts_with("Unknown panel: 1x1"));
+        assert_eq!(None, keyboard);
+    }
+
+    /// Creates a console that writes to `bus`.
+    fn new_flaky_console(bus: &FlakySpiBus) -> ST7735SConsole<NoopPins, FlakySpiBus, MockKeyboard> {
+        let (signals_tx, _signals_rx) = async_channel::unbounded();
+        let mut spec = ConsoleSpec::init("st7735s");
+        let fonts = endbasic_std::gfx::lcd::fonts::all_fonts();
+        let new_spi = {
+            let bus = bus.clone();
+            move |_, _, _, _, _| Ok(bus)
+        };
+        match try_new_console(NoopPins, new_spi, MockKeyboard(7), &mut spec, &fonts, signals_tx) {
+            Ok(console) => console,
+            Err((e, _keyboard)) => panic!("Console creation failed: {}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_present_retries_failed_transfers() {
+        let bus = FlakySpiBus::default();
+        let mut console = new_flaky_console(&bus);
+
+        console.set_sync(false).unwrap();
+        console.clear(ClearType::All).unwrap();
+        bus.fail.store(true, Ordering::SeqCst);
+        let e = console.present().await.unwrap_err();
+        assert_eq!(io::ErrorKind::Interrupted, e.kind());
+
+        bus.fail.store(false, Ordering::SeqCst);
+        let before = bus.written.lock().unwrap().len();
+        console.present().await.unwrap();
+        assert!(bus.written.lock().unwrap().len() >= before + 128 * 128 * 2);
+
+        let before = bus.written.lock().unwrap().len();
+        console.present().await.unwrap();
+        assert_eq!(before, bus.written.lock().unwrap().len());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_present_completes_before_later_draws() {
+        let bus = FlakySpiBus::default();
+        let mut console = new_flaky_console(&bus);
+
+        console.set_sync(false).unwrap();
+        console.set_color(Some(15), None).unwrap();
+        console.draw_rect_filled(PixelsXY::new(0, 0), PixelsXY::new(127, 127)).unwrap();
+        console.present().await.unwrap();
+
+        console.set_sync(true).unwrap();
+        console.set_color(Some(9), None).unwrap();
+        console.draw_pixel(PixelsXY::new(5, 5)).unwrap();
+
+        let pixel = rgb_to_rgb565(ansi_color_to_rgb(9)).to_be_bytes();
+        let written = bus.written.lock().unwrap();
+        assert_eq!(&pixel, &written[written.len() - 2..]);
+    }
+
+    /// Finds the panel variant called `name`.
+    fn panel(name: &str) -> Panel {
+        *PANELS.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_lcd_set_window_128x128() {
+        let mut bus = MockSpiBus { max_size: 100, ..Default::default() };
+        ST7735SLcd::lcd_set_window(
+            &mut NoopPins,
+            &mut bus,
+            &panel("128x128"),
+            LcdXY { x: 0, y: 0 },
+            LcdSize { width: 128, height: 128 },
+        )
+        .unwrap();
+        assert_eq!(
+            vec![vec![0x2a], vec![0, 1, 0, 128], vec![0x2b], vec![0, 2, 0, 129], vec![0x2c]],
+            bus.writes
+        );
+    }
+
+    #[test]
+    fn test_lcd_set_window_160x128() {
+        let mut bus = MockSpiBus { max_size: 100, ..Default::default() };
+        ST7735SLcd::lcd_set_window(
+            &mut NoopPins,
+            &mut bus,
+            &panel("160x128"),
+            LcdXY { x: 10, y: 20 },
+            LcdSize { width: 150, height: 108 },
+        )
+        .unwrap();
+        assert_eq!(
+            vec![vec![0x2a], vec![0, 10, 0, 159], vec![0x2b], vec![0, 20, 0, 127], vec![0x2c]],
+            bus.writes
+        );
+    }
+
+    #[test]
+    fn test_spi_config_default() {
+        let mut spec = ConsoleSpec::init("st7735s");
+        assert_eq!(SpiConfig::default(), SpiConfig::from_spec(&mut spec).unwrap());
+        spec.finish().unwrap();
+    }
+
+    #[test]
+    fn test_spi_config_custom() {
+        let mut spec = ConsoleSpec::init("st7735s:spi_mode=3,cs_polarity=high");
+        assert_eq!(
+            SpiConfig { mode: SpiMode::Mode3, ss_polarity: SsPolarity::ActiveHigh },
+            SpiConfig::from_spec(&mut spec).unwrap()
+        );
+        spec.finish().unwrap();
+    }
+
+    #[test]
+    fn test_spi_config_errors() {
+        let mut spec = ConsoleSpec::init("st7735s:spi_mode=4");
+        assert_eq!(
+            "Invalid SPI mode: 4; valid modes are: 0, 1, 2, 3",
+            format!("{}", SpiConfig::from_spec(&mut spec).unwrap_err())
+        );
+
+        let mut spec = ConsoleSpec::init("st7735s:cs_polarity=middle");
+        assert_eq!(
+            "Invalid CS polarity: middle; valid values are: high, low",
+            format!("{}", SpiConfig::from_spec(&mut spec).unwrap_err())
+        );
+    }
+
+    #[test]
+    fn test_lcd_set_gram_scan_way() {
+        let mut bus = MockSpiBus { max_size: 100, ..Default::default() };
+        let panel = Panel { madctl: 0xa8, ..panel("160x128") };
+        ST7735SLcd::lcd_set_gram_scan_way(&mut NoopPins, &mut bus, &panel).unwrap();
+        assert_eq!(vec![vec![0x36], vec![0xa8]], bus.writes);
+    }
+
+    #[test]
+    fn test_lcd_set_window_above_255() {
+        let panel = Panel {
+            name: "test",
+            size: LcdSize { width: 320, height: 300 },
+            adjust_x: 10,
+            adjust_y: 0,
+            madctl: 0,
+        };
+        let mut bus = MockSpiBus { max_size: 100, ..Default::default() };
+        ST7735SLcd::lcd_set_window(
+            &mut NoopPins,
+            &mut bus,
+            &panel,
+            LcdXY { x: 250, y: 256 },
+            LcdSize { width: 70, height: 44 },
+        )
+        .unwrap();
+        assert_eq!(
+            vec![vec![0x2a], vec![1, 4, 1, 73], vec![0x2b], vec![1, 0, 1, 43], vec![0x2c]],
+            bus.writes
+        );
+    }
+
+    #[test]
+    fn test_lcd_set_window_out_of_bounds() {
+        for (xy, size) in [
+            (LcdXY { x: 0, y: 0 }, LcdSize { width: 129, height: 1 }),
+            (LcdXY { x: 0, y: 0 }, LcdSize { width: 1, height: 129 }),
+            (LcdXY { x: 127, y: 0 }, LcdSize { width: 2, height: 1 }),
+            (LcdXY { x: 0, y: 127 }, LcdSize { width: 1, height: 2 }),
+            (LcdXY { x: 0, y: 0 }, LcdSize { width: 0, height: 1 }),
+        ] {
+            let mut bus = MockSpiBus { max_size: 100, ..Default::default() };
+            let err =
+                ST7735SLcd::lcd_set_window(&mut NoopPins, &mut bus, &panel("128x128"), xy, size)
+                    .unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+            assert!(bus.writes.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_rgb_to_rgb565_extremes() {
+        assert_eq!(0x0000, rgb_to_rgb565((0, 0, 0)));
+        assert_eq!(0xffff, rgb_to_rgb565((255, 255, 255)));
+        assert_eq!(0xffff, rgb_to_rgb565((254, 253, 252)));
+    }
+
+    #[test]
+    fn test_rgb_to_rgb565_rounds() {
+        assert_eq!((0x10 << 11) | (0x20 << 5) | 0x10, rgb_to_rgb565((0x80, 0x80, 0x80)));
+        assert_eq!((0x10 << 11) | (0x1f << 5) | 0x0f, rgb_to_rgb565((0x7c, 0x7c, 0x7b)));
+        assert_eq!((0x01 << 11) | (0x01 << 5), rgb_to_rgb565((4, 2, 3)));
+    }
+
+    #[test]
+    fn test_settings_default_offsets() {
+        let mut spec = ConsoleSpec::init("st7735s:panel=128x128");
+        let fonts = endbasic_std::gfx::lcd::fonts::all_fonts();
+        let settings = Settings::from_spec(&mut spec, &fonts).unwrap();
+        assert_eq!((1, 2), (settings.panel.adjust_x, settings.panel.adjust_y));
+        spec.finish().unwrap();
+    }
+
+    #[test]
+    fn test_settings_custom_offsets() {
+        let mut spec = ConsoleSpec::init("st7735s:panel=128x128,offset_x=3,offset_y=0");
+        let fonts = endbasic_std::gfx::lcd::fonts::all_fonts();
+        let settings = Settings::from_spec(&mut spec, &fonts).unwrap();
+        assert_eq!((3, 0), (settings.panel.adjust_x, settings.panel.adjust_y));
+        assert_eq!((128, 128), (settings.panel.size.width, settings.panel.size.height));
+        spec.finish().unwrap();
+    }
+
+    /// Creates an LCD for the `name` panel on top of a mock bus, skipping its initialization.
+    fn new_lcd(name: &str) -> ST7735SLcd<NoopPins, MockSpiBus> {
+        ST7735SLcd {
+            pins: Arc::from(Mutex::from(NoopPins)),
+            spi_bus: Arc::from(Mutex::from(MockSpiBus { max_size: 100, ..Default::default() })),
+            panel: panel(name),
+            signals_tx: async_channel::unbounded().0,
+        }
+    }
+
+    #[test]
+    fn test_lcd_set_window_offsets() {
+        let mut lcd = new_lcd("128x128");
+        lcd.set_window_offsets(4, 0);
+        lcd.set_data(LcdXY { x: 0, y: 0 }, LcdXY { x: 127, y: 9 }, &[0; 128 * 10 * 2]).unwrap();
+        lcd.set_window_offsets(0, 300);
+        lcd.set_data(LcdXY { x: 5, y: 6 }, LcdXY { x: 5, y: 6 }, &[0; 2]).unwrap();
+
+        let bus = lcd.spi_bus.lock().unwrap();
+        let windows = bus.writes.iter().filter(|w| w.len() == 4).collect::<Vec<&Vec<u8>>>();
+        assert_eq!(
+            vec![&vec![0, 4, 0, 131], &vec![0, 0, 0, 9], &vec![0, 5, 0, 5], &vec![1, 50, 1, 50],],
+            windows
+        );
+    }
+
+    #[test]
+    fn test_lcd_encode_into_matches_encode() {
+        let lcd = new_lcd("128x128");
+        let mut out = vec![];
+        for rgb in [(0, 0, 0), (255, 255, 255), (0x80, 0x40, 0x20)] {
+            lcd.encode_into(rgb, &mut out);
+        }
+        let mut exp_out = vec![];
+        for rgb in [(0, 0, 0), (255, 255, 255), (0x80, 0x40, 0x20)] {
+            exp_out.extend_from_slice(&lcd.encode(rgb).0);
+        }
+        assert_eq!(exp_out, out);
+    }
+
     #[test]
     fn test_lcd_write_shorter_than_max_size() {
         let mut bus = MockSpiBus { max_size: 100, ..Default::default() };
-        lcd_write(&mut bus, &[0, 1, 2, 3, 4]).unwrap();
+        lcd_write(&mut bus, &[0, 1, 2, 3, 4], None).unwrap();
         assert_eq!(vec![vec![0, 1, 2, 3, 4]], bus.writes);
     }
 
     #[test]
     fn test_lcd_write_equal_to_max_size() {
         let mut bus = MockSpiBus { max_size: 3, ..Default::default() };
-        lcd_write(&mut bus, &[0, 1, 2]).unwrap();
+        lcd_write(&mut bus, &[0, 1, 2], None).unwrap();
         assert_eq!(vec![vec![0, 1, 2]], bus.writes);
     }
 
+    #[test]
+    fn test_lcd_write_stalled_bus() {
+        let mut bus = StalledSpiBus::default();
+        let err = lcd_write(&mut bus, &[0, 1, 2], None).unwrap_err();
+        assert_eq!(io::ErrorKind::WriteZero, err.kind());
+        assert_eq!(MAX_ZERO_WRITES, bus.attempts);
+    }
+
     #[test]
     fn test_lcd_write_greater_than_max_size() {
         let mut bus = MockSpiBus { max_size: 6, ..Default::default() };
-        lcd_write(&mut bus, &[0, 1, 2, 3, 4, 5, 6]).unwrap();
+        lcd_write(&mut bus, &[0, 1, 2, 3, 4, 5, 6], None).unwrap();
         assert_eq!(vec![vec![0, 1, 2, 3, 4, 5], vec![6]], bus.writes);
     }
+
+    #[test]
+    fn test_lcd_write_no_pending_signals() {
+        let (signals_tx, _signals_rx) = async_channel::unbounded();
+        let mut bus = MockSpiBus { max_size: 2, ..Default::default() };
+        lcd_write(&mut bus, &[0, 1, 2, 3, 4], Some(&signals_tx)).unwrap();
+        assert_eq!(vec![vec![0, 1], vec![2, 3], vec![4]], bus.writes);
+    }
+
+    #[test]
+    fn test_lcd_write_interrupted_by_signal() {
+        let (signals_tx, signals_rx) = async_channel::unbounded();
+        signals_tx.try_send(Signal::Break).unwrap();
+        let mut bus = MockSpiBus { max_size: 2, ..Default::default() };
+        let err = lcd_write(&mut bus, &[0, 1, 2, 3, 4], Some(&signals_tx)).unwrap_err();
+        assert_eq!(io::ErrorKind::Interrupted, err.kind());
+        assert_eq!(vec![vec![0, 1]], bus.writes);
+        assert_eq!(Ok(Signal::Break), signals_rx.try_recv());
+    }
+
+    #[test]
+    fn test_lcd_write_single_chunk_not_interrupted() {
+        let (signals_tx, _signals_rx) = async_channel::unbounded();
+        signals_tx.try_send(Signal::Break).unwrap();
+        let mut bus = MockSpiBus { max_size: 10, ..Default::default() };
+        lcd_write(&mut bus, &[0, 1, 2], Some(&signals_tx)).unwrap();
+        assert_eq!(vec![vec![0, 1, 2]], bus.writes);
+    }
+
+    #[tokio::test]
+    async fn test_lcd_set_data_task_on_blocking_thread() {
+        let lcd = new_lcd("128x128");
+        let task = lcd.set_data_task(LcdXY { x: 1, y: 2 }, LcdXY { x: 2, y: 2 }, vec![1, 2, 3, 4]);
+        assert!(lcd.spi_bus.lock().unwrap().writes.is_empty());
+
+        tokio::task::spawn_blocking(task).await.unwrap().unwrap();
+        assert_eq!(
+            vec![
+                vec![0x2a],
+                vec![0, 2, 0, 3],
+                vec![0x2b],
+                vec![0, 4, 0, 4],
+                vec![0x2c],
+                vec![1, 2, 3, 4]
+            ],
+            lcd.spi_bus.lock().unwrap().writes
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lcd_set_data_task_errors() {
+        let lcd = new_lcd("128x128");
+        let task = lcd.set_data_task(LcdXY { x: 0, y: 0 }, LcdXY { x: 128, y: 0 }, vec![0; 258]);
+        let err = tokio::task::spawn_blocking(task).await.unwrap().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert!(lcd.spi_bus.lock().unwrap().writes.is_empty());
+    }
 }
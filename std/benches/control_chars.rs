@@ -0,0 +1,51 @@
+// EndBASIC
+// Copyright 2024 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Micro-benchmark to compare the cost of filtering control characters before printing.
+//!
+//! Run with `cargo bench -p endbasic-std --bench control_chars`.
+
+use endbasic_std::console::{filter_control_chars, remove_control_chars};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Number of times to run each operation.
+const ITERATIONS: u32 = 1_000_000;
+
+/// Runs `f` `ITERATIONS` times on `text` and returns the total elapsed time.
+fn measure<F: Fn(&str) -> usize>(text: &str, f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f(black_box(text)));
+    }
+    start.elapsed()
+}
+
+/// Measures and reports the cost of both filtering approaches on `text`.
+fn compare(name: &str, text: &str) {
+    let alloc = measure(text, |t| remove_control_chars(t.to_owned()).len());
+    let cow = measure(text, |t| filter_control_chars(t).len());
+    println!(
+        "{:<16} remove_control_chars: {:>6} ns/iter; filter_control_chars: {:>6} ns/iter",
+        name,
+        alloc.as_nanos() / u128::from(ITERATIONS),
+        cow.as_nanos() / u128::from(ITERATIONS),
+    );
+}
+
+fn main() {
+    compare("clean ascii", "The quick brown fox jumps over the lazy dog; 0123456789");
+    compare("with controls", "The quick brown fox\tjumps over\rthe lazy dog\n");
+}
@@ -0,0 +1,63 @@
+// EndBASIC
+// Copyright 2024 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Micro-benchmark to measure the cost of filling the whole screen of a buffered LCD.
+//!
+//! Run with `cargo bench -p endbasic-std --bench lcd_fill`.
+
+use endbasic_std::console::graphics::RasterOps;
+use endbasic_std::console::{FontId, PixelsXY, SizeInPixels};
+use endbasic_std::gfx::lcd::fonts::font_by_id;
+use endbasic_std::gfx::lcd::{BufferedLcd, LcdSize, MemoryLcd};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Number of times to run each operation per round.
+const ITERATIONS: u32 = 2_000;
+
+/// Number of rounds to run, of which only the fastest is reported to reduce noise.
+const ROUNDS: u32 = 10;
+
+/// Dimensions of the simulated LCD, which match those of the ST7735S.
+const SIZE: LcdSize = LcdSize { width: 160, height: 128 };
+
+/// Fills the whole screen `ITERATIONS` times per round with syncing set to `sync` and returns
+/// the elapsed time of the fastest round.
+fn measure(sync: bool) -> Duration {
+    let mut lcd = BufferedLcd::new(MemoryLcd::new(SIZE), font_by_id(FontId::Font5x8));
+    lcd.set_sync(sync);
+    let size = SizeInPixels::new(SIZE.width as u16, SIZE.height as u16);
+
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        for i in 0..ITERATIONS {
+            lcd.set_draw_color(((i % 256) as u8, 10, 20));
+            lcd.draw_rect_filled(PixelsXY::new(0, 0), black_box(size)).unwrap();
+        }
+        best = best.min(start.elapsed());
+    }
+    best
+}
+
+fn main() {
+    for sync in [true, false] {
+        println!(
+            "full screen fill (sync={:<5}): {:>8} ns/iter",
+            sync,
+            measure(sync).as_nanos() / u128::from(ITERATIONS),
+        );
+    }
+}
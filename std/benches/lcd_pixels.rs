@@ -0,0 +1,85 @@
+// EndBASIC
+// Copyright 2026 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Micro-benchmark to compare drawing a scatter plot on a buffered LCD pixel by pixel against
+//! drawing all of its points in one batch.
+//!
+//! Run with `cargo bench -p endbasic-std --bench lcd_pixels`.
+
+use endbasic_std::console::graphics::RasterOps;
+use endbasic_std::console::{FontId, PixelsXY};
+use endbasic_std::gfx::lcd::fonts::font_by_id;
+use endbasic_std::gfx::lcd::{BufferedLcd, LcdSize, MemoryLcd};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Number of times to draw the scatter plot per round.
+const ITERATIONS: u32 = 200;
+
+/// Number of rounds to run, of which only the fastest is reported to reduce noise.
+const ROUNDS: u32 = 10;
+
+/// Number of points in the scatter plot.
+const POINTS: usize = 2_000;
+
+/// Dimensions of the simulated LCD, which match those of the ST7735S.
+const SIZE: LcdSize = LcdSize { width: 160, height: 128 };
+
+/// Generates a deterministic set of points spread over the whole screen.
+fn scatter_points() -> Vec<PixelsXY> {
+    (0..POINTS)
+        .map(|i| {
+            let x = (i * 37) % SIZE.width;
+            let y = (i * 91) % SIZE.height;
+            PixelsXY::new(x as i16, y as i16)
+        })
+        .collect()
+}
+
+/// Draws the scatter plot `ITERATIONS` times per round, either with one `draw_pixel` call per
+/// point or with a single `draw_pixels` call if `batch` is true, and returns the elapsed time of
+/// the fastest round.
+fn measure(batch: bool) -> Duration {
+    let mut lcd = BufferedLcd::new(MemoryLcd::new(SIZE), font_by_id(FontId::Font5x8));
+    let points = scatter_points();
+
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        for i in 0..ITERATIONS {
+            lcd.set_draw_color(((i % 256) as u8, 10, 20));
+            if batch {
+                lcd.draw_pixels(black_box(&points)).unwrap();
+            } else {
+                for xy in black_box(&points) {
+                    lcd.draw_pixel(*xy).unwrap();
+                }
+            }
+        }
+        best = best.min(start.elapsed());
+    }
+    best
+}
+
+fn main() {
+    for batch in [false, true] {
+        println!(
+            "scatter plot of {} points (batch={:<5}): {:>8} ns/iter",
+            POINTS,
+            batch,
+            measure(batch).as_nanos() / u128::from(ITERATIONS),
+        );
+    }
+}
@@ -0,0 +1,104 @@
+// EndBASIC
+// Copyright 2024 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Game loop that renders tear-free frames on a slow LCD.
+//!
+//! This example drives a graphical console backed by a fake LCD that counts how many transactions
+//! it receives.  Every frame is drawn between `begin_frame` and `end_frame`, so each frame reaches
+//! the LCD in a single transaction no matter how many drawing operations it contains.
+
+use async_trait::async_trait;
+use endbasic_std::console::graphics::InputOps;
+use endbasic_std::console::{ClearType, Console, FontId, GraphicsConsole, Key, PixelsXY, RGB};
+use endbasic_std::gfx::lcd::fonts::font_by_id;
+use endbasic_std::gfx::lcd::{AsByteSlice, BufferedLcd, Lcd, LcdSize, LcdXY};
+use std::io;
+
+/// Width and height of the fake LCD in pixels.
+const LCD_SIZE: LcdSize = LcdSize { width: 128, height: 128 };
+
+/// Data for one pixel of the fake LCD.
+#[derive(Clone, Copy)]
+struct Pixel([u8; 3]);
+
+impl AsByteSlice for Pixel {
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Fake LCD that prints every transaction it receives.
+struct CountingLcd;
+
+impl Lcd for CountingLcd {
+    type Pixel = Pixel;
+
+    fn info(&self) -> (LcdSize, usize) {
+        (LCD_SIZE, 3)
+    }
+
+    fn encode(&self, rgb: RGB) -> Self::Pixel {
+        Pixel([rgb.0, rgb.1, rgb.2])
+    }
+
+    fn set_data(&mut self, x1y1: LcdXY, x2y2: LcdXY, data: &[u8]) -> io::Result<()> {
+        println!(
+            "  LCD transaction: ({}, {}) to ({}, {}), {} bytes",
+            x1y1.x,
+            x1y1.y,
+            x2y2.x,
+            x2y2.y,
+            data.len()
+        );
+        Ok(())
+    }
+}
+
+/// Input that never delivers any key presses.
+struct NoInput;
+
+#[async_trait(?Send)]
+impl InputOps for NoInput {
+    async fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        Ok(None)
+    }
+
+    async fn read_key(&mut self) -> io::Result<Key> {
+        Ok(Key::Eof)
+    }
+
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+fn main() -> io::Result<()> {
+    let lcd = BufferedLcd::new(CountingLcd, font_by_id(FontId::Font5x8));
+    let mut console = GraphicsConsole::new(NoInput, lcd, None, None)?;
+    console.hide_cursor()?;
+
+    for frame in 0..5 {
+        println!("Frame {}", frame);
+
+        console.begin_frame()?;
+        console.clear(ClearType::All)?;
+        let x = 10 + frame * 20;
+        console.draw_rect_filled(PixelsXY::new(x, 40), PixelsXY::new(x + 16, 56))?;
+        console.draw_circle(PixelsXY::new(64, 100), 10 + frame as u16)?;
+        console.end_frame()?;
+    }
+
+    Ok(())
+}
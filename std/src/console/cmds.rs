@@ -917,6 +917,8 @@ mod tests {
             .expect_output([CapturedOut::Write("                            ".to_owned())])
             .check();
 
+        Tester::default().run("? \"hi\"; 3").expect_prints(["hi 3"]).check();
+
         Tester::default()
             .run("PRINT \"1234567890123\", \"4\"")
             .expect_prints(["1234567890123 4"])
@@ -975,7 +977,9 @@ mod tests {
         for i in 0..1024 {
             let ch = char::from_u32(i).unwrap();
             let ch_var = format!("{}", ch);
-            let exp_ch = if ch.is_control() {
+            let exp_ch = if ch == '\n' {
+                "\n"
+            } else if ch.is_control() {
                 found_any = true;
                 " "
             } else {
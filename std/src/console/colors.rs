@@ -305,3 +305,42 @@ static COLORS: &[RGB] = &[
 pub fn ansi_color_to_rgb(color: u8) -> RGB {
     COLORS[(color as usize) % COLORS.len()]
 }
+
+/// Converts an ANSI color number to the closest color within the basic 16-color palette.
+pub fn ansi_color_to_16(color: u8) -> u8 {
+    if color < 16 {
+        return color;
+    }
+
+    let (r, g, b) = ansi_color_to_rgb(color);
+    let distance = |c: &RGB| {
+        let dr = i32::from(c.0) - i32::from(r);
+        let dg = i32::from(c.1) - i32::from(g);
+        let db = i32::from(c.2) - i32::from(b);
+        dr * dr + dg * dg + db * db
+    };
+
+    let mut best = 0;
+    for (i, c) in COLORS[0..16].iter().enumerate() {
+        if distance(c) < distance(&COLORS[best]) {
+            best = i;
+        }
+    }
+    best as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ansi_color_to_16() {
+        for i in 0..16 {
+            assert_eq!(i, ansi_color_to_16(i));
+        }
+        assert_eq!(AnsiColor::Black as u8, ansi_color_to_16(16));
+        assert_eq!(AnsiColor::BrightBlue as u8, ansi_color_to_16(21));
+        assert_eq!(AnsiColor::BrightWhite as u8, ansi_color_to_16(231));
+        assert_eq!(AnsiColor::BrightBlack as u8, ansi_color_to_16(244));
+    }
+}
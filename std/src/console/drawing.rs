@@ -16,7 +16,7 @@
 //! Drawing algorithms for consoles that don't provide native rendering primitives.
 
 use crate::console::graphics::{ClampedInto, RasterOps};
-use crate::console::{PixelsXY, SizeInPixels};
+use crate::console::{PixelsXY, SizeInPixels, RGB};
 use std::convert::TryFrom;
 use std::io;
 
@@ -121,22 +121,18 @@ where
     }
 }
 
-/// Draws a circle via `rasops` with `center` and `radius`.
+/// Invokes `point` for every pixel on the outline of the circle with `center` and `radius`.
 ///
 /// This implements the [Midpoint circle
 /// algorithm](https://en.wikipedia.org/wiki/Midpoint_circle_algorithm).
-pub fn draw_circle<R>(rasops: &mut R, center: PixelsXY, radius: u16) -> io::Result<()>
+fn for_each_circle_point<F>(center: PixelsXY, radius: u16, mut point: F) -> io::Result<()>
 where
-    R: RasterOps,
+    F: FnMut(i16, i16) -> io::Result<()>,
 {
-    fn point<R: RasterOps>(rasops: &mut R, x: i16, y: i16) -> io::Result<()> {
-        rasops.draw_pixel(PixelsXY { x, y })
-    }
-
     if radius == 0 {
         return Ok(());
     } else if radius == 1 {
-        return rasops.draw_pixel(center);
+        return point(center.x, center.y);
     }
 
     let (diameter, radius): (i16, i16) = match radius.checked_mul(2) {
@@ -154,14 +150,14 @@ where
     let mut e: i16 = tx - diameter;
 
     while x >= y {
-        point(rasops, center.x + x, center.y - y)?;
-        point(rasops, center.x + x, center.y + y)?;
-        point(rasops, center.x - x, center.y - y)?;
-        point(rasops, center.x - x, center.y + y)?;
-        point(rasops, center.x + y, center.y - x)?;
-        point(rasops, center.x + y, center.y + x)?;
-        point(rasops, center.x - y, center.y - x)?;
-        point(rasops, center.x - y, center.y + x)?;
+        point(center.x + x, center.y - y)?;
+        point(center.x + x, center.y + y)?;
+        point(center.x - x, center.y - y)?;
+        point(center.x - x, center.y + y)?;
+        point(center.x + y, center.y - x)?;
+        point(center.x + y, center.y + x)?;
+        point(center.x - y, center.y - x)?;
+        point(center.x - y, center.y + x)?;
 
         if e <= 0 {
             y += 1;
@@ -179,6 +175,54 @@ where
     Ok(())
 }
 
+/// Draws a circle via `rasops` with `center` and `radius`.
+pub fn draw_circle<R>(rasops: &mut R, center: PixelsXY, radius: u16) -> io::Result<()>
+where
+    R: RasterOps + ?Sized,
+{
+    for_each_circle_point(center, radius, |x, y| rasops.draw_pixel(PixelsXY { x, y }))
+}
+
+/// Draws the part of the circle with `center` and `radius` that lies between the `start_deg` and
+/// `end_deg` angles via `rasops`.
+///
+/// Angles are measured in degrees clockwise from 12 o'clock and wrap modulo 360.  An arc whose
+/// start and end are equal is empty, and an arc spanning 360 degrees or more is a full circle.
+pub fn draw_arc<R>(
+    rasops: &mut R,
+    center: PixelsXY,
+    radius: u16,
+    start_deg: u16,
+    end_deg: u16,
+) -> io::Result<()>
+where
+    R: RasterOps + ?Sized,
+{
+    if start_deg == end_deg {
+        return Ok(());
+    } else if end_deg > start_deg && end_deg - start_deg >= 360 {
+        return draw_circle(rasops, center, radius);
+    }
+
+    let start = f64::from(start_deg % 360);
+    let span = f64::from((end_deg % 360 + 360 - start_deg % 360) % 360);
+    if span == 0.0 {
+        return Ok(());
+    }
+
+    for_each_circle_point(center, radius, |x, y| {
+        let dx = f64::from(x) - f64::from(center.x);
+        let dy = f64::from(y) - f64::from(center.y);
+        let angle = dx.atan2(-dy).to_degrees();
+        let offset = (angle - start).rem_euclid(360.0);
+        if offset <= span {
+            rasops.draw_pixel(PixelsXY { x, y })
+        } else {
+            Ok(())
+        }
+    })
+}
+
 /// Draws a circle via `rasops` with `center` and `radius`.
 ///
 /// This implements the [Midpoint circle
@@ -234,6 +278,84 @@ where
     Ok(())
 }
 
+/// Computes the color of step `i` out of `n` steps in a linear gradient from `from` to `to`.
+///
+/// The first step yields `from` and the last step yields `to`.
+pub fn gradient_color(from: RGB, to: RGB, i: usize, n: usize) -> RGB {
+    debug_assert!(i < n);
+    if n <= 1 {
+        return from;
+    }
+
+    let lerp = |a: u8, b: u8| {
+        let a = i64::from(a);
+        let b = i64::from(b);
+        let n = (n - 1) as i64;
+        let i = i as i64;
+        let value = (a * (n - i) + b * i + n / 2) / n;
+        u8::try_from(value).expect("Interpolated value must be within the endpoints")
+    };
+    (lerp(from.0, to.0), lerp(from.1, to.1), lerp(from.2, to.2))
+}
+
+/// Maximum distance in pixels covered by each segment when flattening a Bezier curve.
+const BEZIER_SEGMENT_LENGTH: f64 = 4.0;
+
+/// Evaluates the quadratic (3 `points`) or cubic (4 `points`) Bezier curve at `t`.
+fn bezier_point(points: &[PixelsXY], t: f64) -> (f64, f64) {
+    let p: Vec<(f64, f64)> = points.iter().map(|p| (f64::from(p.x), f64::from(p.y))).collect();
+    let u = 1.0 - t;
+    match p.len() {
+        3 => (
+            u * u * p[0].0 + 2.0 * u * t * p[1].0 + t * t * p[2].0,
+            u * u * p[0].1 + 2.0 * u * t * p[1].1 + t * t * p[2].1,
+        ),
+        4 => (
+            u * u * u * p[0].0
+                + 3.0 * u * u * t * p[1].0
+                + 3.0 * u * t * t * p[2].0
+                + t * t * t * p[3].0,
+            u * u * u * p[0].1
+                + 3.0 * u * u * t * p[1].1
+                + 3.0 * u * t * t * p[2].1
+                + t * t * t * p[3].1,
+        ),
+        _ => unreachable!("Number of points validated by the caller"),
+    }
+}
+
+/// Flattens the Bezier curve defined by `points` into a sequence of points to be joined by lines.
+///
+/// 3 points define a quadratic curve and 4 points define a cubic curve; any other number of points
+/// is an error.  The number of segments adapts to the length of the control polygon so that each
+/// segment spans a few pixels at most.
+pub fn bezier_points(points: &[PixelsXY]) -> io::Result<Vec<PixelsXY>> {
+    if points.len() != 3 && points.len() != 4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Bezier curves need 3 or 4 points",
+        ));
+    }
+
+    let spread: f64 = points
+        .windows(2)
+        .map(|pair| {
+            let dx = f64::from(pair[1].x) - f64::from(pair[0].x);
+            let dy = f64::from(pair[1].y) - f64::from(pair[0].y);
+            (dx * dx + dy * dy).sqrt()
+        })
+        .sum();
+    let segments = (spread / BEZIER_SEGMENT_LENGTH).ceil().clamp(1.0, 1024.0) as usize;
+
+    let to_i16 = |value: f64| value.round().clamp(f64::from(i16::MIN), f64::from(i16::MAX)) as i16;
+    let mut result = Vec::with_capacity(segments + 1);
+    for i in 0..(segments + 1) {
+        let (x, y) = bezier_point(points, i as f64 / segments as f64);
+        result.push(PixelsXY::new(to_i16(x), to_i16(y)));
+    }
+    Ok(result)
+}
+
 /// Draws a rectangle via `rasops` starting at `x1y1` with `size`.
 pub fn draw_rect<R>(rasops: &mut R, x1y1: PixelsXY, size: SizeInPixels) -> io::Result<()>
 where
@@ -250,6 +372,97 @@ where
     Ok(())
 }
 
+/// Computes the corner geometry of a rectangle at `xy` with `size` and corners of `radius`.
+///
+/// Returns the coordinates of the bottom-right corner of the rectangle and the distance from each
+/// side to the centers of the corner arcs, or `None` if the corners are too small to be rounded.
+fn rounded_rect_geometry(
+    xy: PixelsXY,
+    size: SizeInPixels,
+    radius: u16,
+) -> (PixelsXY, Option<(u16, i16)>) {
+    let x2y2 = PixelsXY {
+        x: (i32::from(xy.x) + i32::from(size.width - 1)).clamped_into(),
+        y: (i32::from(xy.y) + i32::from(size.height - 1)).clamped_into(),
+    };
+    let radius = radius.min(size.width / 2).min(size.height / 2);
+    if radius <= 1 {
+        (x2y2, None)
+    } else {
+        let inset = i16::try_from(radius - 1).expect("Radius is bounded by the rectangle size");
+        (x2y2, Some((radius, inset)))
+    }
+}
+
+/// Draws a rectangle via `rasops` starting at `x1y1` with `size` and with its corners rounded by
+/// quarter circles of `radius`.
+///
+/// `radius` is clamped to half of the shorter side of the rectangle.
+pub fn draw_rounded_rect<R>(
+    rasops: &mut R,
+    x1y1: PixelsXY,
+    size: SizeInPixels,
+    radius: u16,
+) -> io::Result<()>
+where
+    R: RasterOps + ?Sized,
+{
+    let (x2y2, corners) = rounded_rect_geometry(x1y1, size, radius);
+    let (radius, inset) = corners.unwrap_or((0, 0));
+
+    let left = x1y1.x + inset;
+    let right = x2y2.x - inset;
+    let top = x1y1.y + inset;
+    let bottom = x2y2.y - inset;
+    rasops.draw_line(PixelsXY { x: left, y: x1y1.y }, PixelsXY { x: right, y: x1y1.y })?;
+    rasops.draw_line(PixelsXY { x: x2y2.x, y: top }, PixelsXY { x: x2y2.x, y: bottom })?;
+    rasops.draw_line(PixelsXY { x: right, y: x2y2.y }, PixelsXY { x: left, y: x2y2.y })?;
+    rasops.draw_line(PixelsXY { x: x1y1.x, y: bottom }, PixelsXY { x: x1y1.x, y: top })?;
+
+    for_each_circle_point(PixelsXY { x: 0, y: 0 }, radius, |dx, dy| {
+        let x = if dx <= 0 { left + dx } else { right + dx };
+        let y = if dy <= 0 { top + dy } else { bottom + dy };
+        rasops.draw_pixel(PixelsXY { x, y })
+    })
+}
+
+/// Draws a filled rectangle via `rasops` starting at `x1y1` with `size` and with its corners
+/// rounded by quarter circles of `radius`.
+///
+/// `radius` is clamped to half of the shorter side of the rectangle.
+pub fn draw_rounded_rect_filled<R>(
+    rasops: &mut R,
+    x1y1: PixelsXY,
+    size: SizeInPixels,
+    radius: u16,
+) -> io::Result<()>
+where
+    R: RasterOps + ?Sized,
+{
+    let (x2y2, corners) = rounded_rect_geometry(x1y1, size, radius);
+    let (radius, inset) = match corners {
+        Some(corners) => corners,
+        None => return rasops.draw_rect_filled(x1y1, size),
+    };
+
+    let left = x1y1.x + inset;
+    let right = x2y2.x - inset;
+    let top = x1y1.y + inset;
+    let bottom = x2y2.y - inset;
+    let middle = SizeInPixels::new(size.width, size.height - 2 * (radius - 1));
+    rasops.draw_rect_filled(PixelsXY { x: x1y1.x, y: top }, middle)?;
+
+    for_each_circle_point(PixelsXY { x: 0, y: 0 }, radius, |dx, dy| {
+        if dx < 0 || dy >= 0 {
+            return Ok(());
+        }
+        for y in [top + dy, bottom - dy] {
+            rasops.draw_line(PixelsXY { x: left - dx, y }, PixelsXY { x: right + dx, y })?;
+        }
+        Ok(())
+    })
+}
+
 #[cfg(test)]
 mod testutils {
     use super::*;
@@ -346,6 +559,66 @@ mod tests {
     use super::testutils::*;
     use super::*;
 
+    #[test]
+    fn test_gradient_color_endpoints() {
+        assert_eq!((10, 20, 30), gradient_color((10, 20, 30), (200, 100, 0), 0, 5));
+        assert_eq!((200, 100, 0), gradient_color((10, 20, 30), (200, 100, 0), 4, 5));
+    }
+
+    #[test]
+    fn test_gradient_color_midpoint() {
+        assert_eq!((100, 60, 15), gradient_color((0, 20, 30), (200, 100, 0), 2, 5));
+        assert_eq!((128, 128, 128), gradient_color((0, 0, 0), (255, 255, 255), 1, 3));
+    }
+
+    #[test]
+    fn test_gradient_color_single_step() {
+        assert_eq!((1, 2, 3), gradient_color((1, 2, 3), (4, 5, 6), 0, 1));
+    }
+
+    #[test]
+    fn test_bezier_points_invalid() {
+        for count in [0, 1, 2, 5] {
+            let points = vec![PixelsXY::new(0, 0); count];
+            let e = bezier_points(&points).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, e.kind());
+        }
+    }
+
+    #[test]
+    fn test_bezier_points_quadratic() {
+        let points =
+            bezier_points(&[PixelsXY::new(0, 0), PixelsXY::new(10, 20), PixelsXY::new(20, 0)])
+                .unwrap();
+        assert_eq!(13, points.len());
+        assert_eq!(PixelsXY::new(0, 0), points[0]);
+        assert_eq!(PixelsXY::new(10, 10), points[6]);
+        assert_eq!(PixelsXY::new(20, 0), points[12]);
+    }
+
+    #[test]
+    fn test_bezier_points_cubic() {
+        let points = bezier_points(&[
+            PixelsXY::new(0, 0),
+            PixelsXY::new(0, 40),
+            PixelsXY::new(40, 40),
+            PixelsXY::new(40, 0),
+        ])
+        .unwrap();
+        assert_eq!(31, points.len());
+        assert_eq!(PixelsXY::new(0, 0), points[0]);
+        assert_eq!(PixelsXY::new(20, 30), points[15]);
+        assert_eq!(PixelsXY::new(40, 0), points[30]);
+    }
+
+    #[test]
+    fn test_bezier_points_degenerate() {
+        let points =
+            bezier_points(&[PixelsXY::new(5, 5), PixelsXY::new(5, 5), PixelsXY::new(5, 5)])
+                .unwrap();
+        assert_eq!([PixelsXY::new(5, 5), PixelsXY::new(5, 5)], points.as_slice());
+    }
+
     #[test]
     fn test_draw_circle_zero() {
         let mut rasops = RecordingRasops::default();
@@ -407,6 +680,99 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_draw_rounded_rect_corners_are_cut() {
+        let mut rasops = RecordingRasops::default();
+        draw_rounded_rect(&mut rasops, PixelsXY::new(10, 20), SizeInPixels::new(10, 8), 4).unwrap();
+        assert_eq!(CapturedRasop::DrawLine(13, 20, 16, 20), rasops.ops[0]);
+        assert_eq!(CapturedRasop::DrawLine(19, 23, 19, 24), rasops.ops[1]);
+        assert_eq!(CapturedRasop::DrawLine(16, 27, 13, 27), rasops.ops[2]);
+        assert_eq!(CapturedRasop::DrawLine(10, 24, 10, 23), rasops.ops[3]);
+        for corner in [(10, 20), (19, 20), (10, 27), (19, 27), (11, 21)] {
+            assert!(!rasops.ops.contains(&CapturedRasop::DrawPixel(corner.0, corner.1)));
+        }
+        for arc in [(10, 21), (11, 20), (18, 20), (19, 21), (10, 26), (19, 26)] {
+            assert!(rasops.ops.contains(&CapturedRasop::DrawPixel(arc.0, arc.1)));
+        }
+    }
+
+    #[test]
+    fn test_draw_rounded_rect_clamps_radius() {
+        let mut clamped = RecordingRasops::default();
+        draw_rounded_rect(&mut clamped, PixelsXY::new(0, 0), SizeInPixels::new(6, 20), 100)
+            .unwrap();
+
+        let mut exact = RecordingRasops::default();
+        draw_rounded_rect(&mut exact, PixelsXY::new(0, 0), SizeInPixels::new(6, 20), 3).unwrap();
+
+        assert_eq!(exact.ops, clamped.ops);
+        assert_eq!(CapturedRasop::DrawLine(2, 0, 3, 0), clamped.ops[0]);
+    }
+
+    #[test]
+    fn test_draw_rounded_rect_small_radius_is_plain_rect() {
+        let mut rasops = RecordingRasops::default();
+        draw_rounded_rect(&mut rasops, PixelsXY::new(1, 2), SizeInPixels::new(3, 4), 1).unwrap();
+        assert_eq!(
+            [
+                CapturedRasop::DrawLine(1, 2, 3, 2),
+                CapturedRasop::DrawLine(3, 2, 3, 5),
+                CapturedRasop::DrawLine(3, 5, 1, 5),
+                CapturedRasop::DrawLine(1, 5, 1, 2),
+            ],
+            rasops.ops.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_draw_arc_empty() {
+        let mut rasops = RecordingRasops::default();
+        draw_arc(&mut rasops, PixelsXY::new(10, 20), 4, 45, 45).unwrap();
+        draw_arc(&mut rasops, PixelsXY::new(10, 20), 4, 400, 40).unwrap();
+        assert!(rasops.ops.is_empty());
+    }
+
+    #[test]
+    fn test_draw_arc_full_circle() {
+        let mut circle = RecordingRasops::default();
+        draw_circle(&mut circle, PixelsXY::new(10, 20), 4).unwrap();
+
+        let mut arc = RecordingRasops::default();
+        draw_arc(&mut arc, PixelsXY::new(10, 20), 4, 0, 360).unwrap();
+
+        assert_eq!(circle.ops, arc.ops);
+    }
+
+    #[test]
+    fn test_draw_arc_quarter() {
+        let mut circle = RecordingRasops::default();
+        draw_circle(&mut circle, PixelsXY::new(10, 20), 4).unwrap();
+        let exp_ops: Vec<CapturedRasop> = circle
+            .ops
+            .into_iter()
+            .filter(|op| match op {
+                CapturedRasop::DrawPixel(x, y) => *x >= 10 && *y <= 20,
+                _ => panic!("Unexpected operation"),
+            })
+            .collect();
+
+        let mut arc = RecordingRasops::default();
+        draw_arc(&mut arc, PixelsXY::new(10, 20), 4, 0, 90).unwrap();
+        assert_eq!(exp_ops, arc.ops);
+        assert!(arc.ops.contains(&CapturedRasop::DrawPixel(10, 17)));
+        assert!(arc.ops.contains(&CapturedRasop::DrawPixel(13, 20)));
+        assert!(!arc.ops.contains(&CapturedRasop::DrawPixel(10, 23)));
+    }
+
+    #[test]
+    fn test_draw_arc_wraps() {
+        let mut arc = RecordingRasops::default();
+        draw_arc(&mut arc, PixelsXY::new(10, 20), 4, 315, 45).unwrap();
+        assert!(arc.ops.contains(&CapturedRasop::DrawPixel(10, 17)));
+        assert!(!arc.ops.contains(&CapturedRasop::DrawPixel(13, 20)));
+        assert!(!arc.ops.contains(&CapturedRasop::DrawPixel(7, 20)));
+    }
+
     #[test]
     fn test_draw_circle_filled_zero() {
         let mut rasops = RecordingRasops::default();
@@ -65,6 +65,85 @@ fn refill(paragraph: &str, width: usize) -> Vec<String> {
     lines
 }
 
+/// Returns the number of columns that `ch` occupies on the screen.
+///
+/// This is an approximation that recognizes the most common East Asian wide characters and
+/// emoji, which take two columns, and treats everything else as taking one.
+pub(crate) fn char_width(ch: char) -> usize {
+    match u32::from(ch) {
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x2fffd
+        | 0x30000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// Returns the number of columns that `s` occupies on the screen.
+fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Wraps `text` at word boundaries so that no line reaches `width` columns, returning the lines.
+///
+/// Explicit newlines in `text` are preserved, and words are separated by a single space.  Unlike
+/// `refill`, words that do not fit in a line on their own are split across lines.
+pub(crate) fn wrap(text: &str, width: usize) -> Vec<String> {
+    // Leave the last column empty for consistency with `refill`, which avoids consoles wrapping
+    // the cursor on their own after a line that fills the screen.
+    let width = width.saturating_sub(1).max(1);
+
+    let mut lines = vec![];
+    for input_line in text.split('\n') {
+        let mut line = String::new();
+        let mut line_width = 0;
+        for word in input_line.split_whitespace() {
+            let word_width = str_width(word);
+            if line_width > 0 {
+                if line_width + 1 + word_width <= width {
+                    line.push(' ');
+                    line.push_str(word);
+                    line_width += 1 + word_width;
+                    continue;
+                }
+                lines.push(line);
+                line = String::new();
+                line_width = 0;
+            }
+
+            if word_width <= width {
+                line.push_str(word);
+                line_width = word_width;
+                continue;
+            }
+
+            for ch in word.chars() {
+                let ch_width = char_width(ch);
+                if line_width > 0 && line_width + ch_width > width {
+                    lines.push(line);
+                    line = String::new();
+                    line_width = 0;
+                }
+                line.push(ch);
+                line_width += ch_width;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
 /// Refills a collection of paragraphs, prefixing them with an optional `indent`.
 fn refill_many<S: AsRef<str>, P: IntoIterator<Item = S>>(
     paragraphs: P,
@@ -173,6 +252,53 @@ mod tests {
         assert_eq!(&["[some .. range]"], refill("[some .. range]", 100).as_slice());
     }
 
+    #[test]
+    fn test_char_width() {
+        assert_eq!(1, char_width('a'));
+        assert_eq!(1, char_width('é'));
+        assert_eq!(2, char_width('훌'));
+        assert_eq!(2, char_width('日'));
+        assert_eq!(2, char_width('Ａ'));
+    }
+
+    #[test]
+    fn test_wrap_empty() {
+        assert_eq!(&[""], wrap("", 10).as_slice());
+        assert_eq!(&["", ""], wrap("\n", 10).as_slice());
+    }
+
+    #[test]
+    fn test_wrap_words() {
+        assert_eq!(
+            &["this is a", "piece of", "text"],
+            wrap("this is a   piece of text", 10).as_slice()
+        );
+        assert_eq!(&["123456789"], wrap("123456789", 10).as_slice());
+    }
+
+    #[test]
+    fn test_wrap_preserves_newlines() {
+        assert_eq!(
+            &["first line", "", "second", "line"],
+            wrap("first line\n\nsecond line", 11).as_slice()
+        );
+    }
+
+    #[test]
+    fn test_wrap_splits_long_words() {
+        assert_eq!(
+            &["a", "abcdefghi", "jklmnopqr", "st b"],
+            wrap("a abcdefghijklmnopqrst b", 10).as_slice()
+        );
+        assert_eq!(&["a", "b", "c"], wrap("abc", 0).as_slice());
+    }
+
+    #[test]
+    fn test_wrap_wide_chars() {
+        assert_eq!(&["훌리", "오 ab"], wrap("훌리 오 ab", 6).as_slice());
+        assert_eq!(&["훌리", "오a"], wrap("훌리오a", 6).as_slice());
+    }
+
     #[test]
     fn test_refill_and_print_empty() {
         let mut console = MockConsole::default();
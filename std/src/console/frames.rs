@@ -0,0 +1,178 @@
+// EndBASIC
+// Copyright 2024 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Fixed-timestep frame loops for animations and games.
+
+use super::{Console, Key};
+use std::io;
+use std::time::Duration;
+use tokio::time::{self, MissedTickBehavior};
+
+/// Decision returned by the per-frame function of `run_frame_loop`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FrameControl {
+    /// Keep running the loop.
+    Continue,
+
+    /// Exit the loop after this frame.
+    Stop,
+}
+
+/// Runs `frame_fn` on `console` at a fixed rate of `fps` frames per second.
+///
+/// Each call to `frame_fn` receives the time elapsed since the previous frame (zero for the first
+/// one) and the keys that were pressed in the meantime, and is wrapped in a `begin_frame` and
+/// `end_frame` pair so that its output is presented in one go.  Frames that take longer than the
+/// frame period cause the missed ticks to be skipped rather than bursted, and the elapsed time
+/// passed to the next frame accounts for the delay.
+///
+/// The loop ends when `frame_fn` returns `FrameControl::Stop` or when `Key::Eof` is received.
+///
+/// This relies on Tokio's timer and must run within a runtime that has it enabled.
+pub async fn run_frame_loop<F>(
+    console: &mut dyn Console,
+    fps: u32,
+    mut frame_fn: F,
+) -> io::Result<()>
+where
+    F: FnMut(&mut dyn Console, Duration, &[Key]) -> io::Result<FrameControl>,
+{
+    if fps == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Frame rate must be positive"));
+    }
+    let period = Duration::from_secs(1) / fps;
+    if period.is_zero() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Frame rate must not exceed one frame per nanosecond",
+        ));
+    }
+
+    let mut interval = time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut keys = vec![];
+    let mut last_tick = interval.tick().await;
+    let mut elapsed = Duration::ZERO;
+    loop {
+        keys.clear();
+        while let Some(key) = console.poll_key().await? {
+            if key == Key::Eof {
+                return Ok(());
+            }
+            keys.push(key);
+        }
+
+        console.begin_frame()?;
+        let result = frame_fn(console, elapsed, &keys);
+        console.end_frame()?;
+        if result? == FrameControl::Stop {
+            return Ok(());
+        }
+
+        let tick = interval.tick().await;
+        elapsed = tick - last_tick;
+        last_tick = tick;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+
+    #[tokio::test]
+    async fn test_run_frame_loop_until_stop() {
+        let mut console = MockConsole::default();
+        console.add_input_keys(&[Key::Char('a'), Key::ArrowUp]);
+
+        let mut frames = vec![];
+        run_frame_loop(&mut console, 500, |console, elapsed, keys| {
+            console.print(&format!("frame {}", frames.len()))?;
+            frames.push((elapsed, keys.to_vec()));
+            if frames.len() == 3 {
+                Ok(FrameControl::Stop)
+            } else {
+                Ok(FrameControl::Continue)
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(3, frames.len());
+        assert_eq!((Duration::ZERO, vec![Key::Char('a'), Key::ArrowUp]), frames[0]);
+        for (elapsed, keys) in &frames[1..] {
+            assert!(*elapsed >= Duration::from_millis(2));
+            assert!(keys.is_empty());
+        }
+        assert_eq!(
+            [
+                CapturedOut::SetSync(false),
+                CapturedOut::Print("frame 0".to_owned()),
+                CapturedOut::SetSync(true),
+                CapturedOut::SetSync(false),
+                CapturedOut::Print("frame 1".to_owned()),
+                CapturedOut::SetSync(true),
+                CapturedOut::SetSync(false),
+                CapturedOut::Print("frame 2".to_owned()),
+                CapturedOut::SetSync(true),
+            ],
+            console.captured_out()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_frame_loop_until_eof() {
+        let mut console = MockConsole::default();
+        console.add_input_keys(&[Key::Char('a'), Key::Eof]);
+
+        let mut count = 0;
+        run_frame_loop(&mut console, 500, |_console, _elapsed, _keys| {
+            count += 1;
+            Ok(FrameControl::Continue)
+        })
+        .await
+        .unwrap();
+        assert_eq!(0, count);
+    }
+
+    #[tokio::test]
+    async fn test_run_frame_loop_errors() {
+        let mut console = MockConsole::default();
+
+        let err = run_frame_loop(&mut console, 0, |_, _, _| Ok(FrameControl::Continue))
+            .await
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!("Frame rate must be positive", err.to_string());
+
+        let err = run_frame_loop(&mut console, u32::MAX, |_, _, _| Ok(FrameControl::Continue))
+            .await
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!("Frame rate must not exceed one frame per nanosecond", err.to_string());
+
+        let err = run_frame_loop(&mut console, 500, |_, _, _| {
+            Err(io::Error::new(io::ErrorKind::Other, "Frame failed"))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!("Frame failed", err.to_string());
+        assert_eq!(
+            [CapturedOut::SetSync(false), CapturedOut::SetSync(true)],
+            console.captured_out()
+        );
+    }
+}
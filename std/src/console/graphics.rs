@@ -15,13 +15,15 @@
 
 //! Support to implement graphical consoles.
 
+use super::drawing;
 use super::{
-    ansi_color_to_rgb, remove_control_chars, AnsiColor, CharsXY, ClearType, Console, Key,
-    LineBuffer, PixelsXY, SizeInPixels, RGB,
+    ansi_color_to_rgb, progress_bar_filled, remove_control_chars, AnsiColor, CharsXY, ClearType,
+    Console, DrawOp, FontId, GradientDir, Key, LineBuffer, PixelsXY, SizeInPixels, RGB,
 };
 use async_trait::async_trait;
 use std::convert::TryFrom;
 use std::io;
+use std::time::Duration;
 
 /// Default foreground color, used at console creation time and when requesting the default color
 /// via the `COLOR` command.
@@ -199,6 +201,53 @@ pub trait RasterOps {
     /// Sets the drawing color for subsequent operations.
     fn set_draw_color(&mut self, color: RGB);
 
+    /// Draws a filled rectangle at `_xy` with `_size` whose color transitions linearly from `_from`
+    /// to `_to` along `_direction`.
+    ///
+    /// Backends without gradient support fail with `io::ErrorKind::Unsupported`.
+    fn draw_gradient_rect(
+        &mut self,
+        _xy: PixelsXY,
+        _size: SizeInPixels,
+        _from: RGB,
+        _to: RGB,
+        _direction: GradientDir,
+    ) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No gradient support in this console"))
+    }
+
+    /// Restricts subsequent drawing operations to the area described by the origin and size in
+    /// `_clip`, or lifts the restriction if `_clip` is `None`.
+    ///
+    /// Backends without clipping support fail with `io::ErrorKind::Unsupported`.
+    fn set_clip(&mut self, _clip: Option<(PixelsXY, SizeInPixels)>) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No clipping support in this console"))
+    }
+
+    /// Sets how subsequent `draw_*` operations combine the drawing color with existing pixels.
+    ///
+    /// While in `DrawOp::Xor` mode, each pixel must be combined at most once until the next call
+    /// to this method, so that shapes made of overlapping parts, such as the corners of a
+    /// rectangle, are fully visible.  Backends that do not keep their pixels around can rely on
+    /// the default, which only supports `DrawOp::Copy`.
+    fn set_draw_op(&mut self, op: DrawOp) -> io::Result<()> {
+        match op {
+            DrawOp::Copy => Ok(()),
+            DrawOp::Xor => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "No XOR drawing support in this console",
+            )),
+        }
+    }
+
+    /// Sets the font to use for subsequent text rendering.
+    ///
+    /// Callers must query `get_info` afterwards to learn about the new glyph and console sizes.
+    /// Backends that cannot change fonts fail with `io::ErrorKind::Unsupported`.
+    fn set_font(&mut self, _font: FontId) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No font selection support in this console"))
+    }
+
     /// Clears the whole console with the given color.
     fn clear(&mut self) -> io::Result<()>;
 
@@ -229,6 +278,18 @@ pub trait RasterOps {
     /// Writes `text` starting at `xy` with the current drawing color.
     fn write_text(&mut self, xy: PixelsXY, text: &str) -> io::Result<()>;
 
+    /// Draws the part of the circle at `center` with `radius` between the `start_deg` and
+    /// `end_deg` angles using the current drawing color.
+    fn draw_arc(
+        &mut self,
+        center: PixelsXY,
+        radius: u16,
+        start_deg: u16,
+        end_deg: u16,
+    ) -> io::Result<()> {
+        drawing::draw_arc(self, center, radius, start_deg, end_deg)
+    }
+
     /// Draws the outline of a circle at `center` with `radius` using the current drawing color.
     fn draw_circle(&mut self, center: PixelsXY, radius: u16) -> io::Result<()>;
 
@@ -241,11 +302,44 @@ pub trait RasterOps {
     /// Draws a single pixel at `xy` using the current drawing color.
     fn draw_pixel(&mut self, xy: PixelsXY) -> io::Result<()>;
 
+    /// Draws a single pixel at each of the `points` using the current drawing color.
+    ///
+    /// Backends that buffer their contents should override this to update the screen once for
+    /// all points instead of once per point.
+    fn draw_pixels(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        for xy in points {
+            self.draw_pixel(*xy)?;
+        }
+        Ok(())
+    }
+
     /// Draws the outline of a rectangle from `x1y1` to `x2y2` using the current drawing color.
     fn draw_rect(&mut self, xy: PixelsXY, size: SizeInPixels) -> io::Result<()>;
 
     /// Draws a filled rectangle from `x1y1` to `x2y2` using the current drawing color.
     fn draw_rect_filled(&mut self, xy: PixelsXY, size: SizeInPixels) -> io::Result<()>;
+
+    /// Draws the outline of a rectangle at `xy` with `size` and corners rounded by `radius` using
+    /// the current drawing color.
+    fn draw_rounded_rect(
+        &mut self,
+        xy: PixelsXY,
+        size: SizeInPixels,
+        radius: u16,
+    ) -> io::Result<()> {
+        drawing::draw_rounded_rect(self, xy, size, radius)
+    }
+
+    /// Draws a filled rectangle at `xy` with `size` and corners rounded by `radius` using the
+    /// current drawing color.
+    fn draw_rounded_rect_filled(
+        &mut self,
+        xy: PixelsXY,
+        size: SizeInPixels,
+        radius: u16,
+    ) -> io::Result<()> {
+        drawing::draw_rounded_rect_filled(self, xy, size, radius)
+    }
 }
 
 /// Primitive graphical console input operations.
@@ -256,6 +350,17 @@ pub trait InputOps {
 
     /// Waits for and returns the next key press.
     async fn read_key(&mut self) -> io::Result<Key>;
+
+    /// Waits for and returns the next key press, or returns `None` if `_timeout` elapses first.
+    ///
+    /// Implementations without access to a timer can rely on the default, which never times out.
+    async fn read_key_timeout(&mut self, _timeout: Duration) -> io::Result<Option<Key>> {
+        self.read_key().await.map(Some)
+    }
+
+    /// Discards all pending key presses without blocking except for `Key::Eof` and
+    /// `Key::Interrupt`, and returns how many were discarded.
+    fn drain_keys(&mut self) -> io::Result<usize>;
 }
 
 /// Implementation of a console that renders to a backing surface.
@@ -283,6 +388,9 @@ where
     /// Whether the cursor is visible or not.
     cursor_visible: bool,
 
+    /// Location of the cursor as recorded by the last call to `save_cursor`.
+    saved_cursor_pos: CharsXY,
+
     /// Raw pixels at the cursor position before the cursor was drawn.  Used to restore the previous
     /// contents when the cursor moves.
     cursor_backup: Option<RO::ID>,
@@ -311,6 +419,18 @@ where
 
     /// Whether video syncing is enabled or not.
     sync_enabled: bool,
+
+    /// First and last rows, both inclusive, of the region that scrolls when opening new lines.
+    scroll_region: (u16, u16),
+
+    /// Period of the cursor blinking, or zero if the cursor does not blink.
+    blink_period: Duration,
+
+    /// State of video syncing before the frame being drawn started, if any.
+    frame_sync_backup: Option<bool>,
+
+    /// How drawing primitives combine the foreground color with the existing pixels.
+    draw_op: DrawOp,
 }
 
 impl<IO, RO> GraphicsConsole<IO, RO>
@@ -338,6 +458,7 @@ where
             size_chars: info.size_chars,
             cursor_pos: CharsXY::default(),
             cursor_visible: true,
+            saved_cursor_pos: CharsXY::default(),
             cursor_backup: None,
             default_fg_color,
             default_bg_color,
@@ -347,6 +468,10 @@ where
             fg_color: ansi_color_to_rgb(default_fg_color),
             alt_backup: None,
             sync_enabled: true,
+            scroll_region: (0, info.size_chars.y.saturating_sub(1)),
+            blink_period: Duration::ZERO,
+            frame_sync_backup: None,
+            draw_op: DrawOp::Copy,
         };
 
         console.set_color(console.ansi_fg_color, console.ansi_bg_color)?;
@@ -355,6 +480,59 @@ where
         Ok(console)
     }
 
+    /// Returns the current position of the cursor.
+    ///
+    /// This is the synchronous counterpart of `Console::cursor_position`, which never fails for
+    /// this console.
+    pub fn cursor_pos(&self) -> CharsXY {
+        self.cursor_pos
+    }
+
+    /// Returns the operations used to render to the console.
+    pub fn raster_ops(&self) -> &RO {
+        &self.raster_ops
+    }
+
+    /// Returns the operations used to render to the console, mutably.
+    pub fn raster_ops_mut(&mut self) -> &mut RO {
+        &mut self.raster_ops
+    }
+
+    /// Runs the drawing primitive `draw` with the foreground color and the current drawing
+    /// operation, and then renders the changes.
+    ///
+    /// The drawing operation is only set on the raster ops for the duration of `draw` so that
+    /// console output, such as text and the cursor, always overwrites what is on the screen.
+    fn draw_with<F>(&mut self, draw: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut RO) -> io::Result<()>,
+    {
+        self.draw_with_op(self.draw_op, draw)
+    }
+
+    /// Same as `draw_with` but uses the drawing operation `op` instead of the current one.
+    ///
+    /// The cursor is cleared while `draw` runs and redrawn afterwards so that restoring the pixels
+    /// under it later on does not overwrite the new drawing.
+    fn draw_with_op<F>(&mut self, op: DrawOp, draw: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut RO) -> io::Result<()>,
+    {
+        self.clear_cursor()?;
+        self.raster_ops.set_draw_color(self.fg_color);
+        let result = if op == DrawOp::Copy {
+            draw(&mut self.raster_ops)
+        } else {
+            self.raster_ops.set_draw_op(op)?;
+            let result = draw(&mut self.raster_ops);
+            self.raster_ops.set_draw_op(DrawOp::Copy)?;
+            result
+        };
+        self.draw_cursor()?;
+        result?;
+        self.present_canvas()
+    }
+
     /// Renders any buffered changes to the backing surface.
     fn present_canvas(&mut self) -> io::Result<()> {
         if self.sync_enabled {
@@ -402,33 +580,90 @@ where
         Ok(())
     }
 
+    /// Flips the cursor between its drawn and cleared states to implement blinking.
+    ///
+    /// Does nothing while video syncing is disabled so that blinking does not flush partially drawn
+    /// frames to the screen.
+    fn toggle_blink(&mut self) -> io::Result<()> {
+        if !self.cursor_visible || !self.sync_enabled {
+            return Ok(());
+        }
+
+        if self.cursor_backup.is_some() {
+            self.clear_cursor()?;
+        } else {
+            self.draw_cursor()?;
+        }
+        self.raster_ops.present_canvas()
+    }
+
     /// Moves the cursor to beginning of the next line, scrolling the console if necessary.
     ///
     /// Does not clear nor draw the cursor.
     fn open_line(&mut self) -> io::Result<()> {
-        if self.cursor_pos.y < self.size_chars.y - 1 {
+        let bottom = self.scroll_region.1;
+        if self.cursor_pos.y != bottom && self.cursor_pos.y < self.size_chars.y - 1 {
             self.cursor_pos.x = 0;
             self.cursor_pos.y += 1;
             return Ok(());
         }
 
-        let x1y1 = PixelsXY::new(0, self.glyph_size.height.clamped_into());
-        let x2y2 = PixelsXY::new(0, 0);
-        let size = SizeInPixels::new(
-            self.size_pixels.width,
-            self.size_pixels.height - self.glyph_size.height,
-        );
-
-        self.raster_ops.set_draw_color(self.bg_color);
-        self.raster_ops.move_pixels(x1y1, x2y2, size)?;
+        if self.cursor_pos.y == bottom {
+            self.raw_scroll_up(1)?;
+        }
 
         self.cursor_pos.x = 0;
         Ok(())
     }
 
+    /// Moves the rows within the scroll region up by `lines` and clears the rows that become
+    /// vacant at the bottom of the region.
+    ///
+    /// Does not clear nor draw the cursor.
+    fn raw_scroll_up(&mut self, lines: u16) -> io::Result<()> {
+        let (top, bottom) = self.scroll_region;
+        if lines == 0 {
+            return Ok(());
+        }
+
+        // Extend the region to the bottom of the console if the region's last row is also the
+        // console's last row so that any partial row of pixels that does not fit a full glyph
+        // also scrolls.
+        let top_y: u16 = top.clamped_mul(self.glyph_size.height);
+        let bottom_y: u16 = if bottom == self.size_chars.y - 1 {
+            self.size_pixels.height
+        } else {
+            (bottom + 1).clamped_mul(self.glyph_size.height)
+        };
+
+        self.raster_ops.set_draw_color(self.bg_color);
+
+        if lines > bottom - top {
+            let xy = PixelsXY::new(0, top_y.clamped_into());
+            let size = SizeInPixels::new(self.size_pixels.width, bottom_y - top_y);
+            return self.raster_ops.draw_rect_filled(xy, size);
+        }
+
+        let src_y: u16 = (top + lines).clamped_mul(self.glyph_size.height);
+        let x1y1 = PixelsXY::new(0, src_y.clamped_into());
+        let x2y2 = PixelsXY::new(0, top_y.clamped_into());
+        let size = SizeInPixels::new(self.size_pixels.width, bottom_y - src_y);
+        self.raster_ops.move_pixels(x1y1, x2y2, size)
+    }
+
     /// Renders the given text at the current cursor position, with wrapping and
-    /// scrolling if necessary.
+    /// scrolling if necessary.  Embedded newlines start new lines.
     fn raw_write_wrapped(&mut self, text: String) -> io::Result<()> {
+        if text.contains('\n') {
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    self.open_line()?;
+                }
+                self.raw_write_wrapped(line.to_owned())?;
+            }
+            return Ok(());
+        }
+
         let mut line_buffer = LineBuffer::from(text);
 
         loop {
@@ -512,6 +747,47 @@ where
                 self.raster_ops.set_draw_color(self.bg_color);
                 self.raster_ops.draw_rect_filled(pos, size)?;
             }
+            ClearType::UntilEndOfScreen => {
+                self.clear_cursor()?;
+                let pos = self.cursor_pos.clamped_mul(self.glyph_size);
+                debug_assert!(pos.x >= 0, "Inputs to pos are unsigned");
+                debug_assert!(pos.y >= 0, "Inputs to pos are unsigned");
+                let size = SizeInPixels::new(
+                    (i32::from(self.size_pixels.width) - i32::from(pos.x)).clamped_into(),
+                    self.glyph_size.height,
+                );
+                self.raster_ops.set_draw_color(self.bg_color);
+                self.raster_ops.draw_rect_filled(pos, size)?;
+
+                if self.cursor_pos.y + 1 < self.size_chars.y {
+                    let next_y = (self.cursor_pos.y + 1).clamped_mul(self.glyph_size.height);
+                    let xy = PixelsXY::new(0, next_y);
+                    let size = SizeInPixels::new(
+                        self.size_pixels.width,
+                        (i32::from(self.size_pixels.height) - i32::from(next_y)).clamped_into(),
+                    );
+                    self.raster_ops.draw_rect_filled(xy, size)?;
+                }
+            }
+            ClearType::UntilBeginningOfScreen => {
+                self.clear_cursor()?;
+                self.raster_ops.set_draw_color(self.bg_color);
+
+                if self.cursor_pos.y > 0 {
+                    let size = SizeInPixels::new(
+                        self.size_pixels.width,
+                        self.cursor_pos.y.clamped_mul(self.glyph_size.height),
+                    );
+                    self.raster_ops.draw_rect_filled(PixelsXY::new(0, 0), size)?;
+                }
+
+                let xy = PixelsXY::new(0, self.cursor_pos.y.clamped_mul(self.glyph_size.height));
+                let size = SizeInPixels::new(
+                    (self.cursor_pos.x + 1).clamped_mul(self.glyph_size.width),
+                    self.glyph_size.height,
+                );
+                self.raster_ops.draw_rect_filled(xy, size)?;
+            }
         }
         self.draw_cursor()?;
         self.present_canvas()
@@ -560,6 +836,10 @@ where
         true
     }
 
+    fn supports_graphics(&self) -> bool {
+        true
+    }
+
     fn leave_alt(&mut self) -> io::Result<()> {
         let (pixels, cursor_pos, ansi_fg_color, ansi_bg_color, fg_color, bg_color) =
             match self.alt_backup.take() {
@@ -588,9 +868,20 @@ where
         Ok(())
     }
 
+    async fn cursor_position(&mut self) -> io::Result<CharsXY> {
+        Ok(self.cursor_pos)
+    }
+
     fn locate(&mut self, pos: CharsXY) -> io::Result<()> {
-        debug_assert!(pos.x < self.size_chars.x);
-        debug_assert!(pos.y < self.size_chars.y);
+        if pos.x >= self.size_chars.x || pos.y >= self.size_chars.y {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Position {},{} is out of the {}x{} console",
+                    pos.x, pos.y, self.size_chars.x, self.size_chars.y
+                ),
+            ));
+        }
 
         let previous = self.set_sync(false)?;
         self.clear_cursor()?;
@@ -601,13 +892,41 @@ where
     }
 
     fn move_within_line(&mut self, off: i16) -> io::Result<()> {
+        if off == 0 {
+            return Ok(());
+        }
+
         let previous = self.set_sync(false)?;
         self.clear_cursor()?;
-        if off < 0 {
-            self.cursor_pos.x -= -off as u16;
-        } else {
-            self.cursor_pos.x += off as u16;
+        let last = i32::from(self.size_chars.x.saturating_sub(1));
+        let x = (i32::from(self.cursor_pos.x) + i32::from(off)).clamp(0, last);
+        self.cursor_pos.x = u16::try_from(x).expect("Clamped to the console width");
+        self.draw_cursor()?;
+        self.set_sync(previous)?;
+        Ok(())
+    }
+
+    fn progress_bar(&mut self, fraction: f64, width: u16) -> io::Result<()> {
+        // Occupy the same cells as the textual bar, including the brackets, but clip the bar to
+        // the current line.
+        let cells = width.saturating_add(2).min(self.size_chars.x - self.cursor_pos.x);
+        if cells == 0 {
+            return Ok(());
+        }
+        let xy = self.cursor_pos.clamped_mul(self.glyph_size);
+        let size =
+            SizeInPixels::new(cells.clamped_mul(self.glyph_size.width), self.glyph_size.height);
+        let filled = progress_bar_filled(fraction, size.width);
+
+        let previous = self.set_sync(false)?;
+        self.clear_cursor()?;
+        self.raster_ops.set_draw_color(self.bg_color);
+        self.raster_ops.draw_rect_filled(xy, size)?;
+        self.raster_ops.set_draw_color(self.fg_color);
+        if filled > 0 {
+            self.raster_ops.draw_rect_filled(xy, SizeInPixels::new(filled, size.height))?;
         }
+        self.raster_ops.draw_rect(xy, size)?;
         self.draw_cursor()?;
         self.set_sync(previous)?;
         Ok(())
@@ -629,8 +948,90 @@ where
         self.input_ops.poll_key().await
     }
 
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        self.input_ops.drain_keys()
+    }
+
     async fn read_key(&mut self) -> io::Result<Key> {
-        self.input_ops.read_key().await
+        if self.blink_period.is_zero() {
+            return self.input_ops.read_key().await;
+        }
+
+        let key = loop {
+            match self.input_ops.read_key_timeout(self.blink_period).await? {
+                Some(key) => break key,
+                None => self.toggle_blink()?,
+            }
+        };
+
+        // Make sure the cursor is left visible once the user has pressed a key.
+        if self.cursor_visible && self.cursor_backup.is_none() {
+            self.draw_cursor()?;
+            self.present_canvas()?;
+        }
+        Ok(key)
+    }
+
+    fn set_blink_rate(&mut self, period: Duration) -> io::Result<()> {
+        self.blink_period = period;
+        Ok(())
+    }
+
+    fn set_font(&mut self, font: FontId) -> io::Result<()> {
+        let previous = self.set_sync(false)?;
+        self.clear_cursor()?;
+        if let Err(e) = self.raster_ops.set_font(font) {
+            self.draw_cursor()?;
+            self.set_sync(previous)?;
+            return Err(e);
+        }
+
+        let info = self.raster_ops.get_info();
+        self.glyph_size = info.glyph_size;
+        self.size_chars = info.size_chars;
+
+        let clamp = |pos: CharsXY, size: CharsXY| {
+            CharsXY::new(
+                std::cmp::min(pos.x, size.x.saturating_sub(1)),
+                std::cmp::min(pos.y, size.y.saturating_sub(1)),
+            )
+        };
+        self.cursor_pos = clamp(self.cursor_pos, self.size_chars);
+        self.saved_cursor_pos = clamp(self.saved_cursor_pos, self.size_chars);
+        self.scroll_region = (0, self.size_chars.y.saturating_sub(1));
+
+        self.draw_cursor()?;
+        self.set_sync(previous)?;
+        Ok(())
+    }
+
+    fn restore_cursor(&mut self) -> io::Result<()> {
+        let previous = self.set_sync(false)?;
+        self.clear_cursor()?;
+        self.cursor_pos = self.saved_cursor_pos;
+        self.draw_cursor()?;
+        self.set_sync(previous)?;
+        Ok(())
+    }
+
+    fn save_cursor(&mut self) -> io::Result<()> {
+        self.saved_cursor_pos = self.cursor_pos;
+        Ok(())
+    }
+
+    fn scroll_up(&mut self, lines: u16) -> io::Result<()> {
+        self.clear_cursor()?;
+        self.raw_scroll_up(lines)?;
+        self.draw_cursor()?;
+        self.present_canvas()
+    }
+
+    fn set_scroll_region(&mut self, top: u16, bottom: u16) -> io::Result<()> {
+        if top >= bottom || bottom >= self.size_chars.y {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid scroll region"));
+        }
+        self.scroll_region = (top, bottom);
+        Ok(())
     }
 
     fn show_cursor(&mut self) -> io::Result<()> {
@@ -663,44 +1064,169 @@ where
         Ok(())
     }
 
+    fn draw_arc(
+        &mut self,
+        center: PixelsXY,
+        radius: u16,
+        start_deg: u16,
+        end_deg: u16,
+    ) -> io::Result<()> {
+        self.draw_with(|ops| ops.draw_arc(center, radius, start_deg, end_deg))
+    }
+
     fn draw_circle(&mut self, center: PixelsXY, radius: u16) -> io::Result<()> {
-        self.raster_ops.set_draw_color(self.fg_color);
-        self.raster_ops.draw_circle(center, radius)?;
-        self.present_canvas()
+        self.draw_with(|ops| ops.draw_circle(center, radius))
     }
 
     fn draw_circle_filled(&mut self, center: PixelsXY, radius: u16) -> io::Result<()> {
-        self.raster_ops.set_draw_color(self.fg_color);
-        self.raster_ops.draw_circle_filled(center, radius)?;
-        self.present_canvas()
+        self.draw_with(|ops| ops.draw_circle_filled(center, radius))
+    }
+
+    fn draw_bezier(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        let points = drawing::bezier_points(points)?;
+        self.draw_with(|ops| {
+            for pair in points.windows(2) {
+                ops.draw_line(pair[0], pair[1])?;
+            }
+            Ok(())
+        })
     }
 
     fn draw_line(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
-        self.raster_ops.set_draw_color(self.fg_color);
-        self.raster_ops.draw_line(x1y1, x2y2)?;
-        self.present_canvas()
+        if x1y1 != x2y2 && (x1y1.x == x2y2.x || x1y1.y == x2y2.y) {
+            // Axis-aligned lines are one-pixel wide rectangles, which backends can fill with a
+            // single update instead of pixel by pixel.  Zero-length lines are left to the backend
+            // because some of them deliberately paint nothing in that case.
+            let xy = PixelsXY::new(x1y1.x.min(x2y2.x), x1y1.y.min(x2y2.y));
+            let width = (i32::from(x1y1.x) - i32::from(x2y2.x)).abs() + 1;
+            let height = (i32::from(x1y1.y) - i32::from(x2y2.y)).abs() + 1;
+            let size = SizeInPixels::new(width.clamped_into(), height.clamped_into());
+            return self.draw_with(|ops| ops.draw_rect_filled(xy, size));
+        }
+        self.draw_with(|ops| ops.draw_line(x1y1, x2y2))
     }
 
     fn draw_pixel(&mut self, xy: PixelsXY) -> io::Result<()> {
-        self.raster_ops.set_draw_color(self.fg_color);
-        self.raster_ops.draw_pixel(xy)?;
-        self.present_canvas()
+        self.draw_with(|ops| ops.draw_pixel(xy))
+    }
+
+    fn draw_pixels(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        self.draw_with(|ops| ops.draw_pixels(points))
     }
 
     fn draw_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
         let (xy, size) = rect_points(x1y1, x2y2);
-        self.raster_ops.set_draw_color(self.fg_color);
-        self.raster_ops.draw_rect(xy, size)?;
-        self.present_canvas()
+        self.draw_with(|ops| ops.draw_rect(xy, size))
     }
 
     fn draw_rect_filled(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
         let (xy, size) = rect_points(x1y1, x2y2);
-        self.raster_ops.set_draw_color(self.fg_color);
+        self.draw_with(|ops| ops.draw_rect_filled(xy, size))
+    }
+
+    fn draw_rounded_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY, radius: u16) -> io::Result<()> {
+        let (xy, size) = rect_points(x1y1, x2y2);
+        self.draw_with(|ops| ops.draw_rounded_rect(xy, size, radius))
+    }
+
+    fn draw_rounded_rect_filled(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        radius: u16,
+    ) -> io::Result<()> {
+        let (xy, size) = rect_points(x1y1, x2y2);
+        self.draw_with(|ops| ops.draw_rounded_rect_filled(xy, size, radius))
+    }
+
+    fn clear_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
+        let (xy, size) = rect_points(x1y1, x2y2);
+        self.clear_cursor()?;
+        self.raster_ops.set_draw_color(self.bg_color);
         self.raster_ops.draw_rect_filled(xy, size)?;
+        self.draw_cursor()?;
         self.present_canvas()
     }
 
+    fn draw_gradient_rect(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        from: RGB,
+        to: RGB,
+        direction: GradientDir,
+    ) -> io::Result<()> {
+        let (xy, size) = rect_points(x1y1, x2y2);
+        self.draw_with_op(DrawOp::Copy, |ops| ops.draw_gradient_rect(xy, size, from, to, direction))
+    }
+
+    /// Writes `text` with its top-left corner at pixel `xy`.
+    ///
+    /// Characters are clipped to the right and bottom edges of the screen.  Characters that would
+    /// start above or to the left of the screen are not drawn at all.
+    fn draw_text(&mut self, xy: PixelsXY, text: &str) -> io::Result<()> {
+        let glyph_width = i32::from(self.glyph_size.width);
+        let width = i32::from(self.size_pixels.width);
+        let height = i32::from(self.size_pixels.height);
+
+        let mut x = i32::from(xy.x);
+        let y = i32::from(xy.y);
+        let skip = if x < 0 { (-x + glyph_width - 1) / glyph_width } else { 0 };
+        x += skip * glyph_width;
+        if y < 0 || x >= width || y >= height {
+            return Ok(());
+        }
+        let fit = (width - x + glyph_width - 1) / glyph_width;
+
+        let text = remove_control_chars(text)
+            .chars()
+            .map(|ch| if ch == '\n' { ' ' } else { ch })
+            .skip(usize::try_from(skip).expect("Must be positive"))
+            .take(usize::try_from(fit).expect("Must be positive"))
+            .collect::<String>();
+        let len = match u16::try_from(text.chars().count()) {
+            Ok(len) if len > 0 => len,
+            Ok(_) => return Ok(()),
+            Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Text too long")),
+        };
+
+        let xy = PixelsXY::new(x.clamped_into(), y.clamped_into());
+        let size =
+            SizeInPixels::new(len.clamped_mul(self.glyph_size.width), self.glyph_size.height);
+        let (fg_color, bg_color) = (self.fg_color, self.bg_color);
+        self.draw_with_op(DrawOp::Copy, |ops| {
+            ops.set_draw_color(bg_color);
+            ops.draw_rect_filled(xy, size)?;
+            ops.set_draw_color(fg_color);
+            ops.write_text(xy, &text)
+        })
+    }
+
+    fn text_pixel_size(&self, text: &str) -> io::Result<SizeInPixels> {
+        let len = remove_control_chars(text).chars().count();
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot measure empty text"));
+        }
+        let width = u16::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_mul(self.glyph_size.width))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Text too long"))?;
+        Ok(SizeInPixels::new(width, self.glyph_size.height))
+    }
+
+    fn set_draw_op(&mut self, op: DrawOp) -> io::Result<()> {
+        // Validate that the backend supports the operation right away instead of failing on the
+        // next drawing primitive.
+        self.raster_ops.set_draw_op(op)?;
+        self.raster_ops.set_draw_op(DrawOp::Copy)?;
+        self.draw_op = op;
+        Ok(())
+    }
+
+    fn set_clip(&mut self, rect: Option<(PixelsXY, PixelsXY)>) -> io::Result<()> {
+        self.raster_ops.set_clip(rect.map(|(x1y1, x2y2)| rect_points(x1y1, x2y2)))
+    }
+
     fn sync_now(&mut self) -> io::Result<()> {
         if self.sync_enabled {
             Ok(())
@@ -718,29 +1244,224 @@ where
         self.raster_ops.set_sync(enabled);
         Ok(previous)
     }
+
+    fn begin_frame(&mut self) -> io::Result<()> {
+        if self.frame_sync_backup.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Already drawing a frame"));
+        }
+
+        // Flush changes from before the frame so that the frame's damage only covers its own
+        // drawing operations.
+        self.raster_ops.present_canvas()?;
+        self.frame_sync_backup = Some(self.set_sync(false)?);
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        let previous = match self.frame_sync_backup.take() {
+            Some(previous) => previous,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Not drawing a frame")),
+        };
+
+        if previous {
+            // Re-enabling syncing flushes the whole frame.
+            self.set_sync(true)?;
+            Ok(())
+        } else {
+            self.raster_ops.present_canvas()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_clamped_into_u16_i16() {
-        assert_eq!(0i16, 0u16.clamped_into());
-        assert_eq!(10i16, 10u16.clamped_into());
-        assert_eq!(i16::MAX - 1, u16::try_from(i16::MAX - 1).unwrap().clamped_into());
-        assert_eq!(i16::MAX, u16::try_from(i16::MAX).unwrap().clamped_into());
-        assert_eq!(i16::MAX, u16::MAX.clamped_into());
+    use crate::console::DrawCommand;
+    use crate::gfx::lcd::fonts::font_by_id;
+    use crate::gfx::lcd::{BufferedLcd, LcdSize, LcdXY, MemoryLcd};
+    use std::collections::VecDeque;
+
+    /// An implementation of `InputOps` that returns keys from a predefined queue.
+    #[derive(Default)]
+    struct MockInputOps {
+        keys: VecDeque<Key>,
+        timeouts: usize,
     }
 
-    #[test]
-    fn test_clamped_into_u16_i32() {
-        assert_eq!(0i16, 0i32.clamped_into());
-        assert_eq!(10i16, 10i32.clamped_into());
-        assert_eq!(i16::MIN + 1, i32::from(i16::MIN + 1).clamped_into());
-        assert_eq!(i16::MIN, i32::from(i16::MIN).clamped_into());
-        assert_eq!(i16::MIN, i32::MIN.clamped_into());
-        assert_eq!(i16::MAX - 1, i32::from(i16::MAX - 1).clamped_into());
+    #[async_trait(?Send)]
+    impl InputOps for MockInputOps {
+        async fn poll_key(&mut self) -> io::Result<Option<Key>> {
+            Ok(self.keys.pop_front())
+        }
+
+        async fn read_key(&mut self) -> io::Result<Key> {
+            Ok(self.keys.pop_front().unwrap_or(Key::Eof))
+        }
+
+        async fn read_key_timeout(&mut self, _timeout: Duration) -> io::Result<Option<Key>> {
+            if self.timeouts > 0 {
+                self.timeouts -= 1;
+                Ok(None)
+            } else {
+                self.read_key().await.map(Some)
+            }
+        }
+
+        fn drain_keys(&mut self) -> io::Result<usize> {
+            let before = self.keys.len();
+            self.keys.retain(|k| matches!(k, Key::Eof | Key::Interrupt));
+            Ok(before - self.keys.len())
+        }
+    }
+
+    /// An implementation of `RasterOps` that captures calls as textual descriptions for later
+    /// validation.
+    struct RecordingRasops {
+        info: RasterInfo,
+        ops: Vec<String>,
+    }
+
+    impl RasterOps for RecordingRasops {
+        type ID = (PixelsXY, SizeInPixels);
+
+        fn get_info(&self) -> RasterInfo {
+            RasterInfo {
+                size_pixels: self.info.size_pixels,
+                glyph_size: self.info.glyph_size,
+                size_chars: self.info.size_chars,
+            }
+        }
+
+        fn set_draw_color(&mut self, color: RGB) {
+            self.ops.push(format!("set_draw_color: {:?}", color));
+        }
+
+        fn set_clip(&mut self, clip: Option<(PixelsXY, SizeInPixels)>) -> io::Result<()> {
+            self.ops.push(format!("set_clip: {:?}", clip));
+            Ok(())
+        }
+
+        fn set_font(&mut self, font: FontId) -> io::Result<()> {
+            self.ops.push(format!("set_font: {:?}", font));
+            let glyph_size = match font {
+                FontId::Font5x8 => SizeInPixels::new(5, 8),
+                FontId::Font16x16 => SizeInPixels::new(16, 16),
+            };
+            self.info.glyph_size = glyph_size;
+            self.info.size_chars = CharsXY::new(
+                self.info.size_pixels.width / glyph_size.width,
+                self.info.size_pixels.height / glyph_size.height,
+            );
+            Ok(())
+        }
+
+        fn clear(&mut self) -> io::Result<()> {
+            self.ops.push("clear".to_owned());
+            Ok(())
+        }
+
+        fn present_canvas(&mut self) -> io::Result<()> {
+            self.ops.push("present_canvas".to_owned());
+            Ok(())
+        }
+
+        fn read_pixels(&mut self, xy: PixelsXY, size: SizeInPixels) -> io::Result<Self::ID> {
+            self.ops.push(format!("read_pixels: {:?}, {:?}", xy, size));
+            Ok((xy, size))
+        }
+
+        fn put_pixels(&mut self, xy: PixelsXY, data: &Self::ID) -> io::Result<()> {
+            self.ops.push(format!("put_pixels: {:?}, {:?}", xy, data));
+            Ok(())
+        }
+
+        fn move_pixels(
+            &mut self,
+            x1y1: PixelsXY,
+            x2y2: PixelsXY,
+            size: SizeInPixels,
+        ) -> io::Result<()> {
+            self.ops.push(format!("move_pixels: {:?}, {:?}, {:?}", x1y1, x2y2, size));
+            Ok(())
+        }
+
+        fn write_text(&mut self, xy: PixelsXY, text: &str) -> io::Result<()> {
+            self.ops.push(format!("write_text: {:?}, {:?}", xy, text));
+            Ok(())
+        }
+
+        fn draw_circle(&mut self, center: PixelsXY, radius: u16) -> io::Result<()> {
+            self.ops.push(format!("draw_circle: {:?}, {}", center, radius));
+            Ok(())
+        }
+
+        fn draw_circle_filled(&mut self, center: PixelsXY, radius: u16) -> io::Result<()> {
+            self.ops.push(format!("draw_circle_filled: {:?}, {}", center, radius));
+            Ok(())
+        }
+
+        fn draw_line(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
+            self.ops.push(format!("draw_line: {:?}, {:?}", x1y1, x2y2));
+            Ok(())
+        }
+
+        fn draw_pixel(&mut self, xy: PixelsXY) -> io::Result<()> {
+            self.ops.push(format!("draw_pixel: {:?}", xy));
+            Ok(())
+        }
+
+        fn draw_rect(&mut self, xy: PixelsXY, size: SizeInPixels) -> io::Result<()> {
+            self.ops.push(format!("draw_rect: {:?}, {:?}", xy, size));
+            Ok(())
+        }
+
+        fn draw_rect_filled(&mut self, xy: PixelsXY, size: SizeInPixels) -> io::Result<()> {
+            self.ops.push(format!("draw_rect_filled: {:?}, {:?}", xy, size));
+            Ok(())
+        }
+    }
+
+    /// Creates a new graphical console backed by a `RecordingRasops` of 10x4 characters with
+    /// glyphs of 8x16 pixels.
+    ///
+    /// The cursor is hidden and the recorded operations are discarded so that tests only see the
+    /// effects of the operations they execute.
+    fn new_console() -> GraphicsConsole<MockInputOps, RecordingRasops> {
+        let info = RasterInfo {
+            size_pixels: SizeInPixels::new(80, 64),
+            glyph_size: SizeInPixels::new(8, 16),
+            size_chars: CharsXY::new(10, 4),
+        };
+        let raster_ops = RecordingRasops { info, ops: vec![] };
+        let mut console =
+            GraphicsConsole::new(MockInputOps::default(), raster_ops, None, None).unwrap();
+        console.hide_cursor().unwrap();
+        console.raster_ops.ops.clear();
+        console
+    }
+
+    /// Returns and clears the operations recorded so far by `console`.
+    fn take_ops(console: &mut GraphicsConsole<MockInputOps, RecordingRasops>) -> Vec<String> {
+        std::mem::take(&mut console.raster_ops.ops)
+    }
+
+    #[test]
+    fn test_clamped_into_u16_i16() {
+        assert_eq!(0i16, 0u16.clamped_into());
+        assert_eq!(10i16, 10u16.clamped_into());
+        assert_eq!(i16::MAX - 1, u16::try_from(i16::MAX - 1).unwrap().clamped_into());
+        assert_eq!(i16::MAX, u16::try_from(i16::MAX).unwrap().clamped_into());
+        assert_eq!(i16::MAX, u16::MAX.clamped_into());
+    }
+
+    #[test]
+    fn test_clamped_into_u16_i32() {
+        assert_eq!(0i16, 0i32.clamped_into());
+        assert_eq!(10i16, 10i32.clamped_into());
+        assert_eq!(i16::MIN + 1, i32::from(i16::MIN + 1).clamped_into());
+        assert_eq!(i16::MIN, i32::from(i16::MIN).clamped_into());
+        assert_eq!(i16::MIN, i32::MIN.clamped_into());
+        assert_eq!(i16::MAX - 1, i32::from(i16::MAX - 1).clamped_into());
         assert_eq!(i16::MAX, i32::from(i16::MAX).clamped_into());
         assert_eq!(i16::MAX, i32::MAX.clamped_into());
     }
@@ -860,4 +1581,836 @@ mod tests {
             rect_points(PixelsXY { x: 31000, y: 32000 }, PixelsXY { x: -31000, y: -32000 })
         );
     }
+
+    #[test]
+    fn test_clear_until_end_of_screen() {
+        let mut console = new_console();
+        console.locate(CharsXY::new(3, 1)).unwrap();
+        take_ops(&mut console);
+
+        console.clear(ClearType::UntilEndOfScreen).unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "draw_rect_filled: PixelsXY { x: 24, y: 16 }, SizeInPixels { width: 56, height: 16 }",
+                "draw_rect_filled: PixelsXY { x: 0, y: 32 }, SizeInPixels { width: 80, height: 32 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(3, 1), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_clear_until_end_of_screen_last_line() {
+        let mut console = new_console();
+        console.locate(CharsXY::new(0, 3)).unwrap();
+        take_ops(&mut console);
+
+        console.clear(ClearType::UntilEndOfScreen).unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "draw_rect_filled: PixelsXY { x: 0, y: 48 }, SizeInPixels { width: 80, height: 16 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(0, 3), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_clear_until_beginning_of_screen() {
+        let mut console = new_console();
+        console.locate(CharsXY::new(3, 2)).unwrap();
+        take_ops(&mut console);
+
+        console.clear(ClearType::UntilBeginningOfScreen).unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "draw_rect_filled: PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 80, height: 32 }",
+                "draw_rect_filled: PixelsXY { x: 0, y: 32 }, SizeInPixels { width: 32, height: 16 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(3, 2), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_clear_until_beginning_of_screen_first_line() {
+        let mut console = new_console();
+        console.locate(CharsXY::new(1, 0)).unwrap();
+        take_ops(&mut console);
+
+        console.clear(ClearType::UntilBeginningOfScreen).unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "draw_rect_filled: PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 16, height: 16 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(1, 0), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_clear_with_cursor_restores_and_redraws_it() {
+        let mut console = new_console();
+        console.show_cursor().unwrap();
+        take_ops(&mut console);
+
+        console.clear(ClearType::UntilEndOfScreen).unwrap();
+        let ops = take_ops(&mut console);
+        assert_eq!(
+            "put_pixels: PixelsXY { x: 0, y: 0 }, (PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 8, height: 16 })",
+            ops[0]
+        );
+        assert_eq!(
+            "read_pixels: PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 8, height: 16 }",
+            ops[ops.len() - 4]
+        );
+    }
+
+    #[test]
+    fn test_print_scrolls_whole_screen_by_default() {
+        let mut console = new_console();
+        console.locate(CharsXY::new(0, 3)).unwrap();
+        take_ops(&mut console);
+
+        console.print("").unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "move_pixels: PixelsXY { x: 0, y: 16 }, PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 80, height: 48 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(0, 3), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_print_scrolls_within_scroll_region() {
+        let mut console = new_console();
+        console.set_scroll_region(1, 2).unwrap();
+        console.locate(CharsXY::new(0, 2)).unwrap();
+        take_ops(&mut console);
+
+        console.print("").unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "move_pixels: PixelsXY { x: 0, y: 32 }, PixelsXY { x: 0, y: 16 }, SizeInPixels { width: 80, height: 16 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(0, 2), console.cursor_pos);
+
+        console.locate(CharsXY::new(0, 3)).unwrap();
+        take_ops(&mut console);
+        console.print("").unwrap();
+        assert_eq!(["present_canvas"], take_ops(&mut console).as_slice());
+        assert_eq!(CharsXY::new(0, 3), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let mut console = new_console();
+        console.set_scroll_region(1, 3).unwrap();
+
+        console.scroll_up(2).unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "move_pixels: PixelsXY { x: 0, y: 48 }, PixelsXY { x: 0, y: 16 }, SizeInPixels { width: 80, height: 16 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+
+        console.scroll_up(0).unwrap();
+        assert_eq!(["present_canvas"], take_ops(&mut console).as_slice());
+    }
+
+    #[test]
+    fn test_scroll_up_whole_region() {
+        let mut console = new_console();
+        console.set_scroll_region(0, 1).unwrap();
+
+        console.scroll_up(5).unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (0, 0, 0)",
+                "draw_rect_filled: PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 80, height: 32 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+    }
+
+    #[test]
+    fn test_set_scroll_region_errors() {
+        let mut console = new_console();
+        for (top, bottom) in [(2, 2), (3, 1), (0, 4)] {
+            assert_eq!(
+                io::ErrorKind::InvalidInput,
+                console.set_scroll_region(top, bottom).unwrap_err().kind()
+            );
+        }
+        assert_eq!((0, 3), console.scroll_region);
+
+        console.set_scroll_region(1, 2).unwrap();
+        console.set_scroll_region(0, 3).unwrap();
+        assert_eq!((0, 3), console.scroll_region);
+    }
+
+    #[test]
+    fn test_save_and_restore_cursor() {
+        let mut console = new_console();
+        console.restore_cursor().unwrap();
+        assert_eq!(CharsXY::new(0, 0), console.cursor_pos);
+
+        console.locate(CharsXY::new(3, 2)).unwrap();
+        console.save_cursor().unwrap();
+        console.locate(CharsXY::new(5, 1)).unwrap();
+        console.write("abc").unwrap();
+        console.restore_cursor().unwrap();
+        assert_eq!(CharsXY::new(3, 2), console.cursor_pos);
+
+        console.locate(CharsXY::new(1, 1)).unwrap();
+        console.restore_cursor().unwrap();
+        assert_eq!(CharsXY::new(3, 2), console.cursor_pos);
+
+        console.locate(CharsXY::new(1, 0)).unwrap();
+        console.save_cursor().unwrap();
+        console.locate(CharsXY::new(2, 3)).unwrap();
+        console.restore_cursor().unwrap();
+        assert_eq!(CharsXY::new(1, 0), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_set_font_updates_size_chars() {
+        let mut console = new_console();
+        assert_eq!(CharsXY::new(10, 4), console.size_chars().unwrap());
+        console.locate(CharsXY::new(9, 3)).unwrap();
+        console.set_scroll_region(1, 2).unwrap();
+
+        console.set_font(FontId::Font16x16).unwrap();
+        assert_eq!(CharsXY::new(5, 4), console.size_chars().unwrap());
+        assert_eq!(SizeInPixels::new(80, 64), console.size_pixels().unwrap());
+        assert_eq!(CharsXY::new(4, 3), console.cursor_pos);
+        assert_eq!((0, 3), console.scroll_region);
+
+        console.set_font(FontId::Font5x8).unwrap();
+        assert_eq!(CharsXY::new(16, 8), console.size_chars().unwrap());
+        assert_eq!(CharsXY::new(4, 3), console.cursor_pos);
+        assert_eq!((0, 7), console.scroll_region);
+    }
+
+    #[test]
+    fn test_set_clip_normalizes_corners() {
+        let mut console = new_console();
+
+        console.set_clip(Some((PixelsXY::new(30, 20), PixelsXY::new(10, 5)))).unwrap();
+        console.set_clip(None).unwrap();
+        assert_eq!(
+            [
+                "set_clip: Some((PixelsXY { x: 10, y: 5 }, SizeInPixels { width: 20, height: 15 }))",
+                "set_clip: None",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_key_without_blink() {
+        let mut console = new_console();
+        console.input_ops.keys.push_back(Key::Char('a'));
+        console.input_ops.timeouts = 2;
+        console.show_cursor().unwrap();
+        take_ops(&mut console);
+
+        assert_eq!(Key::Char('a'), console.read_key().await.unwrap());
+        assert!(take_ops(&mut console).is_empty());
+        assert_eq!(2, console.input_ops.timeouts);
+    }
+
+    #[tokio::test]
+    async fn test_read_key_with_blink() {
+        let mut console = new_console();
+        console.input_ops.keys.push_back(Key::Char('a'));
+        console.input_ops.timeouts = 3;
+        console.show_cursor().unwrap();
+        console.set_blink_rate(Duration::from_millis(500)).unwrap();
+        take_ops(&mut console);
+
+        assert_eq!(Key::Char('a'), console.read_key().await.unwrap());
+        let draw = [
+            "read_pixels: PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 8, height: 16 }",
+            "set_draw_color: (192, 192, 192)",
+            "draw_rect_filled: PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 8, height: 16 }",
+            "present_canvas",
+        ];
+        let clear = [
+            "put_pixels: PixelsXY { x: 0, y: 0 }, (PixelsXY { x: 0, y: 0 }, SizeInPixels { width: 8, height: 16 })",
+            "present_canvas",
+        ];
+        let exp_ops: Vec<&str> = clear
+            .iter()
+            .chain(draw.iter())
+            .chain(clear.iter())
+            .chain(draw.iter())
+            .copied()
+            .collect();
+        assert_eq!(exp_ops, take_ops(&mut console));
+    }
+
+    #[tokio::test]
+    async fn test_read_key_blink_paused_without_sync() {
+        let mut console = new_console();
+        console.input_ops.keys.push_back(Key::Char('a'));
+        console.input_ops.timeouts = 3;
+        console.show_cursor().unwrap();
+        console.set_blink_rate(Duration::from_millis(500)).unwrap();
+        console.set_sync(false).unwrap();
+        take_ops(&mut console);
+
+        assert_eq!(Key::Char('a'), console.read_key().await.unwrap());
+        assert!(take_ops(&mut console).is_empty());
+    }
+
+    #[test]
+    fn test_frame_presents_once() {
+        let mut console = new_console();
+
+        console.begin_frame().unwrap();
+        console.draw_pixel(PixelsXY::new(1, 2)).unwrap();
+        console.draw_pixel(PixelsXY::new(3, 4)).unwrap();
+        console.end_frame().unwrap();
+        assert_eq!(
+            [
+                "present_canvas",
+                "set_draw_color: (192, 192, 192)",
+                "draw_pixel: PixelsXY { x: 1, y: 2 }",
+                "set_draw_color: (192, 192, 192)",
+                "draw_pixel: PixelsXY { x: 3, y: 4 }",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert!(console.sync_enabled);
+    }
+
+    #[test]
+    fn test_frame_restores_sync() {
+        let mut console = new_console();
+        console.set_sync(false).unwrap();
+
+        console.begin_frame().unwrap();
+        console.end_frame().unwrap();
+        assert!(!console.sync_enabled);
+    }
+
+    #[test]
+    fn test_frame_errors() {
+        let mut console = new_console();
+
+        assert_eq!(io::ErrorKind::InvalidInput, console.end_frame().unwrap_err().kind());
+        console.begin_frame().unwrap();
+        assert_eq!(io::ErrorKind::InvalidInput, console.begin_frame().unwrap_err().kind());
+        console.end_frame().unwrap();
+    }
+
+    #[test]
+    fn test_locate_out_of_range() {
+        let mut console = new_console();
+        console.locate(CharsXY::new(2, 1)).unwrap();
+        take_ops(&mut console);
+
+        for pos in [CharsXY::new(10, 0), CharsXY::new(0, 4), CharsXY::new(u16::MAX, u16::MAX)] {
+            let e = console.locate(pos).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, e.kind());
+        }
+        assert_eq!(
+            "Position 10,0 is out of the 10x4 console",
+            console.locate(CharsXY::new(10, 0)).unwrap_err().to_string()
+        );
+        assert_eq!(CharsXY::new(2, 1), console.cursor_pos);
+        assert!(take_ops(&mut console).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cursor_position_tracks_output() {
+        let mut console = new_console();
+        assert_eq!(CharsXY::new(0, 0), console.cursor_position().await.unwrap());
+
+        console.locate(CharsXY::new(3, 2)).unwrap();
+        assert_eq!(CharsXY::new(3, 2), console.cursor_position().await.unwrap());
+
+        console.write("ab").unwrap();
+        assert_eq!(CharsXY::new(5, 2), console.cursor_position().await.unwrap());
+
+        console.print("").unwrap();
+        assert_eq!(CharsXY::new(0, 3), console.cursor_position().await.unwrap());
+        assert_eq!(CharsXY::new(0, 3), console.cursor_pos());
+    }
+
+    #[test]
+    fn test_move_within_line_clamps_to_row() {
+        let mut console = new_memory_console();
+        assert_eq!(CharsXY::new(8, 3), console.size_chars().unwrap());
+
+        console.locate(CharsXY::new(2, 1)).unwrap();
+        console.move_within_line(-3).unwrap();
+        assert_eq!(CharsXY::new(0, 1), console.cursor_pos());
+        console.move_within_line(i16::MIN).unwrap();
+        assert_eq!(CharsXY::new(0, 1), console.cursor_pos());
+
+        console.move_within_line(5).unwrap();
+        assert_eq!(CharsXY::new(5, 1), console.cursor_pos());
+        console.move_within_line(3).unwrap();
+        assert_eq!(CharsXY::new(7, 1), console.cursor_pos());
+        console.move_within_line(i16::MAX).unwrap();
+        assert_eq!(CharsXY::new(7, 1), console.cursor_pos());
+
+        console.move_within_line(-1).unwrap();
+        assert_eq!(CharsXY::new(6, 1), console.cursor_pos());
+    }
+
+    #[test]
+    fn test_draw_line_zero_length_uses_backend() {
+        let mut console = new_console();
+        take_ops(&mut console);
+        console.draw_line(PixelsXY::new(8, 9), PixelsXY::new(8, 9)).unwrap();
+        let ops = take_ops(&mut console);
+        assert!(
+            ops.contains(&"draw_line: PixelsXY { x: 8, y: 9 }, PixelsXY { x: 8, y: 9 }".to_owned())
+        );
+        assert!(!ops.iter().any(|op| op.starts_with("draw_rect_filled: PixelsXY { x: 8, y: 9 }")));
+    }
+
+    #[test]
+    fn test_draw_line_axis_aligned_matches_general_algorithm() {
+        for (x1y1, x2y2) in [
+            (PixelsXY::new(3, 4), PixelsXY::new(20, 4)),
+            (PixelsXY::new(20, 7), PixelsXY::new(3, 7)),
+            (PixelsXY::new(5, 2), PixelsXY::new(5, 25)),
+            (PixelsXY::new(6, 25), PixelsXY::new(6, 2)),
+            (PixelsXY::new(-10, 29), PixelsXY::new(100, 29)),
+            (PixelsXY::new(39, -5), PixelsXY::new(39, 50)),
+            (PixelsXY::new(-5, -5), PixelsXY::new(-5, 5)),
+        ] {
+            let mut fast = new_memory_console();
+            fast.draw_line(x1y1, x2y2).unwrap();
+
+            let mut general = new_memory_console();
+            general.draw_with(|ops| drawing::draw_line(ops, x1y1, x2y2)).unwrap();
+
+            assert!(
+                fast.raster_ops().lcd().as_rgb_buffer()
+                    == general.raster_ops().lcd().as_rgb_buffer(),
+                "Line from {:?} to {:?} differs",
+                x1y1,
+                x2y2
+            );
+        }
+    }
+
+    #[test]
+    fn test_draw_text_at_pixel() {
+        let mut console = new_console();
+        take_ops(&mut console);
+        console.draw_text(PixelsXY::new(5, 3), "ab").unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (192, 192, 192)",
+                "set_draw_color: (0, 0, 0)",
+                "draw_rect_filled: PixelsXY { x: 5, y: 3 }, SizeInPixels { width: 16, height: 16 }",
+                "set_draw_color: (192, 192, 192)",
+                "write_text: PixelsXY { x: 5, y: 3 }, \"ab\"",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(0, 0), console.cursor_pos());
+    }
+
+    #[test]
+    fn test_draw_text_clips() {
+        let mut console = new_console();
+        take_ops(&mut console);
+        console.draw_text(PixelsXY::new(-10, 0), "abcdefghijklmn").unwrap();
+        assert_eq!(
+            [
+                "set_draw_color: (192, 192, 192)",
+                "set_draw_color: (0, 0, 0)",
+                "draw_rect_filled: PixelsXY { x: 6, y: 0 }, SizeInPixels { width: 80, height: 16 }",
+                "set_draw_color: (192, 192, 192)",
+                "write_text: PixelsXY { x: 6, y: 0 }, \"cdefghijkl\"",
+                "present_canvas",
+            ],
+            take_ops(&mut console).as_slice()
+        );
+
+        console.draw_text(PixelsXY::new(80, 0), "a").unwrap();
+        console.draw_text(PixelsXY::new(0, 64), "a").unwrap();
+        console.draw_text(PixelsXY::new(0, -1), "a").unwrap();
+        console.draw_text(PixelsXY::new(0, 0), "").unwrap();
+        assert!(take_ops(&mut console).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drain_keys_keeps_eof_and_interrupt() {
+        let mut console = new_console();
+        console.input_ops.keys.extend([
+            Key::Char('a'),
+            Key::Interrupt,
+            Key::ArrowUp,
+            Key::Eof,
+            Key::Char('b'),
+        ]);
+
+        assert_eq!(3, console.drain_keys().unwrap());
+        assert_eq!(Some(Key::Interrupt), console.poll_key().await.unwrap());
+        assert_eq!(Some(Key::Eof), console.poll_key().await.unwrap());
+        assert_eq!(None, console.poll_key().await.unwrap());
+
+        assert_eq!(0, console.drain_keys().unwrap());
+    }
+
+    #[test]
+    fn test_write_and_print_embedded_newlines() {
+        let mut console = new_console();
+        take_ops(&mut console);
+
+        console.write("a\nbc").unwrap();
+        assert_eq!(CharsXY::new(2, 1), console.cursor_pos);
+
+        console.print("d\r\ne\tf\x08").unwrap();
+        assert_eq!(CharsXY::new(0, 3), console.cursor_pos);
+
+        let texts: Vec<String> =
+            take_ops(&mut console).into_iter().filter(|op| op.starts_with("write_text")).collect();
+        assert_eq!(
+            [
+                "write_text: PixelsXY { x: 0, y: 0 }, \"a\"",
+                "write_text: PixelsXY { x: 0, y: 16 }, \"bc\"",
+                "write_text: PixelsXY { x: 16, y: 16 }, \"d\"",
+                "write_text: PixelsXY { x: 0, y: 32 }, \"e f \"",
+            ],
+            texts.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_draw_rect_on_memory_lcd() {
+        let mut console = new_memory_console();
+
+        console.set_color(Some(10), None).unwrap();
+        console.draw_rect(PixelsXY::new(5, 10), PixelsXY::new(34, 19)).unwrap();
+
+        let fg = ansi_color_to_rgb(10);
+        let bg = ansi_color_to_rgb(4);
+        let lcd = console.raster_ops().lcd();
+        for (x, y) in [(5, 10), (33, 10), (5, 18), (33, 18)] {
+            assert_eq!(fg, lcd.pixel_at(LcdXY { x, y }));
+        }
+        for (x, y) in [(0, 0), (20, 15), (4, 10), (34, 18), (5, 19)] {
+            assert_eq!(bg, lcd.pixel_at(LcdXY { x, y }));
+        }
+    }
+
+    /// Creates a 40x30 console backed by a `MemoryLcd` with a hidden cursor.
+    fn new_memory_console() -> GraphicsConsole<MockInputOps, BufferedLcd<MemoryLcd>> {
+        let lcd = MemoryLcd::new(LcdSize { width: 40, height: 30 });
+        let raster_ops = BufferedLcd::new(lcd, font_by_id(FontId::Font5x8));
+        let mut console =
+            GraphicsConsole::new(MockInputOps::default(), raster_ops, Some(15), Some(4)).unwrap();
+        console.hide_cursor().unwrap();
+        console
+    }
+
+    /// Renders a shape with `draw` on a console backed by a `MemoryLcd` and returns the resulting
+    /// pixels.
+    fn render_on_memory_lcd<F>(draw: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut GraphicsConsole<MockInputOps, BufferedLcd<MemoryLcd>>),
+    {
+        let mut console = new_memory_console();
+        draw(&mut console);
+        console.raster_ops().lcd().as_rgb_buffer().to_vec()
+    }
+
+    #[test]
+    fn test_draw_rect_reversed_corners() {
+        let exp_pixels = render_on_memory_lcd(|console| {
+            console.draw_rect(PixelsXY::new(5, 10), PixelsXY::new(34, 19)).unwrap()
+        });
+        for (x1y1, x2y2) in [
+            (PixelsXY::new(34, 19), PixelsXY::new(5, 10)),
+            (PixelsXY::new(34, 10), PixelsXY::new(5, 19)),
+            (PixelsXY::new(5, 19), PixelsXY::new(34, 10)),
+        ] {
+            let pixels = render_on_memory_lcd(|console| console.draw_rect(x1y1, x2y2).unwrap());
+            assert!(exp_pixels == pixels, "Corners {:?} and {:?} differ", x1y1, x2y2);
+        }
+    }
+
+    #[test]
+    fn test_draw_rect_filled_reversed_corners() {
+        let exp_pixels = render_on_memory_lcd(|console| {
+            console.draw_rect_filled(PixelsXY::new(-5, 3), PixelsXY::new(20, 50)).unwrap()
+        });
+        for (x1y1, x2y2) in [
+            (PixelsXY::new(20, 50), PixelsXY::new(-5, 3)),
+            (PixelsXY::new(20, 3), PixelsXY::new(-5, 50)),
+            (PixelsXY::new(-5, 50), PixelsXY::new(20, 3)),
+        ] {
+            let pixels =
+                render_on_memory_lcd(|console| console.draw_rect_filled(x1y1, x2y2).unwrap());
+            assert!(exp_pixels == pixels, "Corners {:?} and {:?} differ", x1y1, x2y2);
+        }
+    }
+
+    #[test]
+    fn test_clear_rect_only_clears_region() {
+        let pixels = render_on_memory_lcd(|console| {
+            console.draw_rect_filled(PixelsXY::new(0, 0), PixelsXY::new(40, 30)).unwrap();
+            console.clear_rect(PixelsXY::new(15, 10), PixelsXY::new(5, 20)).unwrap();
+        });
+        let fg = ansi_color_to_rgb(15);
+        let bg = ansi_color_to_rgb(4);
+        for y in 0..30 {
+            for x in 0..40 {
+                let offset = (y * 40 + x) * 3;
+                let pixel = (pixels[offset], pixels[offset + 1], pixels[offset + 2]);
+                let exp_pixel = if (5..15).contains(&x) && (10..20).contains(&y) { bg } else { fg };
+                assert_eq!(exp_pixel, pixel, "Pixel at ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_clear_rect_sends_one_block() {
+        let mut console = new_console();
+        console.set_color(Some(1), Some(2)).unwrap();
+        take_ops(&mut console);
+        console.clear_rect(PixelsXY::new(3, 4), PixelsXY::new(10, 20)).unwrap();
+        assert_eq!(
+            [
+                format!("set_draw_color: {:?}", ansi_color_to_rgb(2)),
+                "draw_rect_filled: PixelsXY { x: 3, y: 4 }, SizeInPixels { width: 7, height: 16 }"
+                    .to_owned(),
+                "present_canvas".to_owned(),
+            ],
+            take_ops(&mut console).as_slice()
+        );
+    }
+
+    #[test]
+    fn test_progress_bar_draws_rects() {
+        let mut console = new_console();
+        console.set_color(Some(1), Some(2)).unwrap();
+        console.locate(CharsXY::new(2, 1)).unwrap();
+        take_ops(&mut console);
+        console.progress_bar(0.5, 3).unwrap();
+        assert_eq!(
+            [
+                format!("set_draw_color: {:?}", ansi_color_to_rgb(2)),
+                "draw_rect_filled: PixelsXY { x: 16, y: 16 }, SizeInPixels { width: 40, height: 16 }"
+                    .to_owned(),
+                format!("set_draw_color: {:?}", ansi_color_to_rgb(1)),
+                "draw_rect_filled: PixelsXY { x: 16, y: 16 }, SizeInPixels { width: 20, height: 16 }"
+                    .to_owned(),
+                "draw_rect: PixelsXY { x: 16, y: 16 }, SizeInPixels { width: 40, height: 16 }"
+                    .to_owned(),
+                "present_canvas".to_owned(),
+            ],
+            take_ops(&mut console).as_slice()
+        );
+        assert_eq!(CharsXY::new(2, 1), console.cursor_pos);
+    }
+
+    #[test]
+    fn test_progress_bar_clipped_to_line() {
+        let mut console = new_console();
+        console.locate(CharsXY::new(8, 0)).unwrap();
+        take_ops(&mut console);
+        console.progress_bar(1.0, 20).unwrap();
+        let ops = take_ops(&mut console);
+        assert!(ops.contains(
+            &"draw_rect: PixelsXY { x: 64, y: 0 }, SizeInPixels { width: 16, height: 16 }"
+                .to_owned()
+        ));
+    }
+
+    #[test]
+    fn test_draw_op_xor_twice_restores_pixels() {
+        let mut console = new_memory_console();
+        console.set_color(Some(12), None).unwrap();
+        console.draw_circle_filled(PixelsXY::new(20, 15), 8).unwrap();
+        let original = console.raster_ops().lcd().as_rgb_buffer().to_vec();
+
+        console.set_color(Some(10), None).unwrap();
+        console.set_draw_op(DrawOp::Xor).unwrap();
+        console.draw_rect(PixelsXY::new(5, 10), PixelsXY::new(34, 19)).unwrap();
+        let lcd = console.raster_ops().lcd();
+        let xor = |rgb1: RGB, rgb2: RGB| (rgb1.0 ^ rgb2.0, rgb1.1 ^ rgb2.1, rgb1.2 ^ rgb2.2);
+        let fg = ansi_color_to_rgb(10);
+        let bg = ansi_color_to_rgb(4);
+        for (x, y) in [(5, 10), (33, 10), (5, 18), (33, 18)] {
+            assert_eq!(xor(fg, bg), lcd.pixel_at(LcdXY { x, y }));
+        }
+        assert_eq!(xor(fg, ansi_color_to_rgb(12)), lcd.pixel_at(LcdXY { x: 20, y: 10 }));
+        assert_ne!(original.as_slice(), lcd.as_rgb_buffer());
+
+        console.draw_rect(PixelsXY::new(5, 10), PixelsXY::new(34, 19)).unwrap();
+        assert_eq!(original.as_slice(), console.raster_ops().lcd().as_rgb_buffer());
+
+        console.set_draw_op(DrawOp::Copy).unwrap();
+        console.draw_rect(PixelsXY::new(5, 10), PixelsXY::new(34, 19)).unwrap();
+        assert_eq!(fg, console.raster_ops().lcd().pixel_at(LcdXY { x: 20, y: 10 }));
+    }
+
+    #[test]
+    fn test_draw_op_xor_does_not_affect_text() {
+        let mut console = new_memory_console();
+        console.print("ab").unwrap();
+        let exp_pixels = console.raster_ops().lcd().as_rgb_buffer().to_vec();
+
+        console.clear(ClearType::All).unwrap();
+        console.set_draw_op(DrawOp::Xor).unwrap();
+        console.print("ab").unwrap();
+        assert_eq!(exp_pixels.as_slice(), console.raster_ops().lcd().as_rgb_buffer());
+    }
+
+    #[test]
+    fn test_draw_over_visible_cursor_leaves_no_garbage() {
+        let draw = |console: &mut GraphicsConsole<MockInputOps, BufferedLcd<MemoryLcd>>| {
+            console
+                .draw_gradient_rect(
+                    PixelsXY::new(0, 0),
+                    PixelsXY::new(39, 29),
+                    (10, 20, 30),
+                    (200, 100, 50),
+                    GradientDir::Vertical,
+                )
+                .unwrap();
+            console.draw_text(PixelsXY::new(2, 1), "ab").unwrap();
+            console.draw_line(PixelsXY::new(0, 3), PixelsXY::new(9, 7)).unwrap();
+        };
+        let exp_pixels = render_on_memory_lcd(draw);
+
+        let mut console = new_memory_console();
+        console.show_cursor().unwrap();
+        draw(&mut console);
+        console.hide_cursor().unwrap();
+        assert!(
+            exp_pixels.as_slice() == console.raster_ops().lcd().as_rgb_buffer(),
+            "Cursor left stale pixels behind"
+        );
+    }
+
+    #[test]
+    fn test_draw_gradient_rect_unsupported() {
+        let mut console = new_console();
+        let e = console
+            .draw_gradient_rect(
+                PixelsXY::new(0, 0),
+                PixelsXY::new(5, 5),
+                (0, 0, 0),
+                (255, 255, 255),
+                GradientDir::Horizontal,
+            )
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, e.kind());
+        assert_eq!("No gradient support in this console", e.to_string());
+    }
+
+    #[test]
+    fn test_draw_op_unsupported() {
+        let mut console = new_console();
+        console.set_draw_op(DrawOp::Copy).unwrap();
+        let e = console.set_draw_op(DrawOp::Xor).unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, e.kind());
+        assert_eq!("No XOR drawing support in this console", e.to_string());
+        assert_eq!(DrawOp::Copy, console.draw_op);
+    }
+
+    #[test]
+    fn test_draw_batch_matches_sequential_calls() {
+        let xy = PixelsXY::new;
+        let ops = [
+            DrawCommand::RectFilled(xy(0, 0), xy(39, 29)),
+            DrawCommand::Circle(xy(20, 15), 10),
+            DrawCommand::CircleFilled(xy(8, 8), 4),
+            DrawCommand::Line(xy(0, 29), xy(39, 0)),
+            DrawCommand::Pixel(xy(38, 28)),
+            DrawCommand::Rect(xy(2, 20), xy(15, 27)),
+            DrawCommand::Text(xy(20, 20), "Hi".to_owned()),
+        ];
+
+        let exp_pixels = render_on_memory_lcd(|console| {
+            console.draw_rect_filled(xy(0, 0), xy(39, 29)).unwrap();
+            console.draw_circle(xy(20, 15), 10).unwrap();
+            console.draw_circle_filled(xy(8, 8), 4).unwrap();
+            console.draw_line(xy(0, 29), xy(39, 0)).unwrap();
+            console.draw_pixel(xy(38, 28)).unwrap();
+            console.draw_rect(xy(2, 20), xy(15, 27)).unwrap();
+            console.draw_text(xy(20, 20), "Hi").unwrap();
+        });
+        let pixels = render_on_memory_lcd(|console| console.draw_batch(&ops).unwrap());
+        assert!(exp_pixels == pixels, "Batched drawing differs from sequential drawing");
+    }
+
+    #[test]
+    fn test_draw_batch_presents_once() {
+        let mut console = new_console();
+        console
+            .draw_batch(&[
+                DrawCommand::Pixel(PixelsXY::new(1, 2)),
+                DrawCommand::Line(PixelsXY::new(3, 4), PixelsXY::new(5, 6)),
+            ])
+            .unwrap();
+        assert!(console.sync_enabled);
+        let ops = take_ops(&mut console);
+        assert_eq!(1, ops.iter().filter(|op| *op == "present_canvas").count());
+        assert_eq!(Some("present_canvas"), ops.last().map(String::as_str));
+    }
+
+    #[test]
+    fn test_text_pixel_size_on_memory_lcd() {
+        let font = font_by_id(FontId::Font5x8);
+        let console = new_memory_console();
+
+        let glyph_width = u16::try_from(font.glyph_size.width).unwrap();
+        let glyph_height = u16::try_from(font.glyph_size.height).unwrap();
+        assert_eq!(
+            SizeInPixels::new(glyph_width * 5, glyph_height),
+            console.text_pixel_size("Hello").unwrap()
+        );
+        assert_eq!(
+            SizeInPixels::new(glyph_width * 3, glyph_height),
+            console.text_pixel_size("a\tb").unwrap()
+        );
+        // Text longer than the screen is measured in full.
+        assert_eq!(
+            SizeInPixels::new(glyph_width * 100, glyph_height),
+            console.text_pixel_size(&"x".repeat(100)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_text_pixel_size_too_long() {
+        let console = new_console();
+        assert_eq!(
+            io::ErrorKind::InvalidInput,
+            console.text_pixel_size(&"x".repeat(70000)).unwrap_err().kind()
+        );
+        assert_eq!(io::ErrorKind::InvalidInput, console.text_pixel_size("").unwrap_err().kind());
+    }
 }
@@ -18,21 +18,26 @@
 use async_trait::async_trait;
 use endbasic_core::exec::Clearable;
 use endbasic_core::syms::Symbols;
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::env;
 use std::io;
 use std::rc::Rc;
 use std::str;
+use std::time::Duration;
 
 mod cmds;
 pub(crate) use cmds::add_all;
 mod colors;
-pub use colors::{ansi_color_to_rgb, AnsiColor, RGB};
+pub use colors::{ansi_color_to_16, ansi_color_to_rgb, AnsiColor, RGB};
 pub mod drawing;
 mod format;
 pub(crate) use format::refill_and_page;
 pub use format::refill_and_print;
+mod frames;
+pub use frames::{run_frame_loop, FrameControl};
 pub mod graphics;
 pub use graphics::GraphicsConsole;
 mod linebuffer;
@@ -40,7 +45,7 @@ pub use linebuffer::LineBuffer;
 mod pager;
 pub(crate) use pager::Pager;
 mod readline;
-pub use readline::{read_line, read_line_secure};
+pub use readline::{read_line, read_line_secure, read_line_with};
 mod spec;
 pub use spec::{ConsoleSpec, ParseError, Resolution};
 mod trivial;
@@ -104,6 +109,101 @@ pub enum Key {
     Unknown,
 }
 
+/// Names of all the keys that do not carry a parameter, in alphabetical order.
+const NAMED_KEYS: &[(&str, Key)] = &[
+    ("ArrowDown", Key::ArrowDown),
+    ("ArrowLeft", Key::ArrowLeft),
+    ("ArrowRight", Key::ArrowRight),
+    ("ArrowUp", Key::ArrowUp),
+    ("Backspace", Key::Backspace),
+    ("CarriageReturn", Key::CarriageReturn),
+    ("End", Key::End),
+    ("Eof", Key::Eof),
+    ("Escape", Key::Escape),
+    ("Home", Key::Home),
+    ("Interrupt", Key::Interrupt),
+    ("NewLine", Key::NewLine),
+    ("PageDown", Key::PageDown),
+    ("PageUp", Key::PageUp),
+    ("Tab", Key::Tab),
+    ("Unknown", Key::Unknown),
+];
+
+impl Key {
+    /// Returns a human-readable name for the key.
+    ///
+    /// Keys without parameters are named after their variant, such as `ArrowUp`.  Characters are
+    /// named by themselves, except for the control characters that can be typed with the Ctrl key
+    /// and a letter, which are named like `Ctrl+X`.  ETX and EOT are named by themselves too
+    /// because `Ctrl+C` and `Ctrl+D` stand for `Key::Interrupt` and `Key::Eof` in `from_name`.
+    pub fn name(&self) -> Cow<'static, str> {
+        let name = match self {
+            Key::ArrowDown => "ArrowDown",
+            Key::ArrowLeft => "ArrowLeft",
+            Key::ArrowRight => "ArrowRight",
+            Key::ArrowUp => "ArrowUp",
+            Key::Backspace => "Backspace",
+            Key::CarriageReturn => "CarriageReturn",
+            Key::Char(ch @ ('\u{1}'..='\u{2}' | '\u{5}'..='\u{1a}')) => {
+                let letter = char::from(b'A' + (*ch as u8) - 1);
+                return Cow::Owned(format!("Ctrl+{}", letter));
+            }
+            Key::Char(ch) => return Cow::Owned(ch.to_string()),
+            Key::End => "End",
+            Key::Eof => "Eof",
+            Key::Escape => "Escape",
+            Key::Home => "Home",
+            Key::Interrupt => "Interrupt",
+            Key::NewLine => "NewLine",
+            Key::PageDown => "PageDown",
+            Key::PageUp => "PageUp",
+            Key::Tab => "Tab",
+            Key::Unknown => "Unknown",
+        };
+        Cow::Borrowed(name)
+    }
+
+    /// Returns the names of all the keys that do not carry a parameter along with the keys they
+    /// represent, in alphabetical order.
+    ///
+    /// The names match those returned by `name`.  There are no function keys because `Key` does
+    /// not represent them.
+    pub fn all_named() -> &'static [(&'static str, Key)] {
+        NAMED_KEYS
+    }
+
+    /// Parses a key from its human-readable name `s`, returning `None` if the name is unknown.
+    ///
+    /// This accepts the names returned by `name` and any single character.  Named keys and the
+    /// `Ctrl+` (or `Ctrl-`) prefix are matched case-insensitively.  `Ctrl+C` and `Ctrl+D` map to
+    /// `Key::Interrupt` and `Key::Eof` because that is what consoles deliver for them, and other
+    /// letters prefixed by `Ctrl+` map to their control characters.
+    pub fn from_name(s: &str) -> Option<Key> {
+        let mut chars = s.chars();
+        if let (Some(ch), None) = (chars.next(), chars.next()) {
+            return Some(Key::Char(ch));
+        }
+
+        let has_ctrl = matches!(
+            s.get(..5),
+            Some(p) if p.eq_ignore_ascii_case("ctrl+") || p.eq_ignore_ascii_case("ctrl-")
+        );
+        if has_ctrl {
+            let mut chars = s[5..].chars();
+            return match (chars.next(), chars.next()) {
+                (Some(ch), None) if ch.is_ascii_alphabetic() => match ch.to_ascii_uppercase() {
+                    'C' => Some(Key::Interrupt),
+                    'D' => Some(Key::Eof),
+                    upper => Some(Key::Char(char::from(upper as u8 - b'A' + 1))),
+                },
+                _ => None,
+            };
+        }
+
+        NAMED_KEYS.iter().find(|(name, _key)| name.eq_ignore_ascii_case(s)).map(|(_name, key)| *key)
+    }
+}
+
 /// Indicates what part of the console to clear on a `Console::clear()` call.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ClearType {
@@ -118,6 +218,86 @@ pub enum ClearType {
 
     /// Clears from the cursor position to the end of the line without moving the cursor.
     UntilNewLine,
+
+    /// Clears from the cursor position to the end of the screen without moving the cursor.
+    UntilEndOfScreen,
+
+    /// Clears from the beginning of the screen to the cursor position without moving the cursor.
+    UntilBeginningOfScreen,
+}
+
+/// Indicates the direction in which a gradient progresses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GradientDir {
+    /// The color changes from the left edge to the right edge.
+    Horizontal,
+
+    /// The color changes from the top edge to the bottom edge.
+    Vertical,
+}
+
+/// Indicates how drawing operations combine their color with the pixels already on the screen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DrawOp {
+    /// The drawing color replaces the existing pixels.  This is the default.
+    Copy,
+
+    /// The drawing color is XORed with the existing pixels, so drawing the same shape twice
+    /// restores the original contents of the screen.
+    Xor,
+}
+
+/// A drawing operation that can be executed as part of a `Console::draw_batch` call.
+///
+/// Each variant mirrors the `Console` method of the same name and takes the same arguments.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DrawCommand {
+    /// Draws the outline of a circle.  See `Console::draw_circle`.
+    Circle(PixelsXY, u16),
+
+    /// Draws a filled circle.  See `Console::draw_circle_filled`.
+    CircleFilled(PixelsXY, u16),
+
+    /// Draws a line.  See `Console::draw_line`.
+    Line(PixelsXY, PixelsXY),
+
+    /// Draws a single pixel.  See `Console::draw_pixel`.
+    Pixel(PixelsXY),
+
+    /// Draws the outline of a rectangle.  See `Console::draw_rect`.
+    Rect(PixelsXY, PixelsXY),
+
+    /// Draws a filled rectangle.  See `Console::draw_rect_filled`.
+    RectFilled(PixelsXY, PixelsXY),
+
+    /// Draws text at a pixel position.  See `Console::draw_text`.
+    Text(PixelsXY, String),
+}
+
+/// Indicates the level of color support offered by a console.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorCapability {
+    /// The console does not support colors at all.
+    None,
+
+    /// The console supports the basic 16 ANSI colors.
+    Ansi16,
+
+    /// The console supports the 256 ANSI colors.
+    Ansi256,
+
+    /// The console supports arbitrary RGB colors.
+    TrueColor,
+}
+
+/// Identifiers for the bitmap fonts bundled with graphical consoles.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FontId {
+    /// A small font with glyphs of 5x8 pixels.
+    Font5x8,
+
+    /// A large font with glyphs of 16x16 pixels.
+    Font16x16,
 }
 
 /// Represents a coordinate for character-based console operations.
@@ -199,11 +379,48 @@ pub trait Console {
     /// Gets the console's current foreground and background colors.
     fn color(&self) -> (Option<u8>, Option<u8>);
 
+    /// Returns the level of color support offered by the console.
+    ///
+    /// Colors passed to `set_color` that exceed this capability are downgraded or ignored.
+    fn color_capability(&self) -> ColorCapability {
+        ColorCapability::Ansi256
+    }
+
     /// Sets the console's foreground and background colors to `fg` and `bg`.
     ///
-    /// If any of the colors is `None`, the color is left unchanged.
+    /// If any of the colors is `None`, that color is reset to the console's default.  To leave a
+    /// color unchanged, pass the value currently returned by `color`.
     fn set_color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()>;
 
+    /// Resets the console's background color to its default, leaving the foreground untouched.
+    fn reset_background(&mut self) -> io::Result<()> {
+        let (fg, _bg) = self.color();
+        self.set_color(fg, None)
+    }
+
+    /// Resets the console's foreground color to its default, leaving the background untouched.
+    fn reset_foreground(&mut self) -> io::Result<()> {
+        let (_fg, bg) = self.color();
+        self.set_color(None, bg)
+    }
+
+    /// Draws a progress bar of `width` cells at the cursor position, such as `[####----]`, where
+    /// the proportion of filled cells is given by `fraction` clamped to the `0..=1` range.
+    ///
+    /// The bar is surrounded by brackets that are not part of `width`, and the cursor is left where
+    /// the bar starts so that calling this again redraws the bar in place.  The bar must fit in
+    /// the current line.
+    fn progress_bar(&mut self, fraction: f64, width: u16) -> io::Result<()> {
+        let filled = usize::from(progress_bar_filled(fraction, width));
+        let mut bar = String::with_capacity(usize::from(width) + 2);
+        bar.push('[');
+        bar.push_str(&"#".repeat(filled));
+        bar.push_str(&"-".repeat(usize::from(width) - filled));
+        bar.push(']');
+        self.write(&bar)?;
+        self.move_within_line(-i16::try_from(bar.len()).unwrap_or(i16::MAX))
+    }
+
     /// Enters the alternate console.
     // TODO(jmmv): This API leads to misuse as callers can forget to leave the alternate console.
     fn enter_alt(&mut self) -> io::Result<()>;
@@ -219,25 +436,141 @@ pub trait Console {
     /// Leaves the alternate console.
     fn leave_alt(&mut self) -> io::Result<()>;
 
+    /// Switches the console between raw input, where keys are delivered as soon as they are
+    /// pressed, and line-buffered input, where the system collects a whole line before delivering
+    /// it.  Returns the previous state.
+    ///
+    /// Consoles that do not support switching input modes fail with `io::ErrorKind::Unsupported`.
+    fn set_raw_mode(&mut self, _enabled: bool) -> io::Result<bool> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Cannot change the input mode of this console",
+        ))
+    }
+
+    /// Enables or disables the echoing of typed characters by the system while the console is in
+    /// line-buffered input mode.  Returns the previous state.
+    ///
+    /// Consoles that cannot control echoing fail with `io::ErrorKind::Unsupported`.
+    fn set_echo(&mut self, _enabled: bool) -> io::Result<bool> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "Cannot change the echo of this console"))
+    }
+
+    /// Returns true if the console can render graphics.
+    ///
+    /// Consoles that return false fail `size_pixels` and all `draw_*` operations with
+    /// `io::ErrorKind::Unsupported`.
+    fn supports_graphics(&self) -> bool {
+        false
+    }
+
+    /// Returns the current position of the cursor.
+    ///
+    /// Consoles that cannot determine the cursor position fail with `io::ErrorKind::Unsupported`.
+    /// This is asynchronous because some consoles have to ask their backend and wait for a reply.
+    async fn cursor_position(&mut self) -> io::Result<CharsXY> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Cannot query the cursor position of this console",
+        ))
+    }
+
     /// Moves the cursor to the given position, which must be within the screen.
     fn locate(&mut self, pos: CharsXY) -> io::Result<()>;
 
     /// Moves the cursor within the line.  Positive values move right, negative values move left.
+    ///
+    /// The movement is clamped to the current line: the cursor stops at the first or the last
+    /// column instead of wrapping to the previous or the next row.
     fn move_within_line(&mut self, off: i16) -> io::Result<()>;
 
     /// Writes `text` to the console, followed by a newline or CRLF pair depending on the needs of
     /// the console to advance a line.
     ///
-    /// The input `text` is not supposed to contain any control characters, such as CR or LF.
+    /// The input `text` may contain newlines (LF), each of which starts a new line just like the
+    /// final one does, but it is not supposed to contain any other control characters, such as CR.
     // TODO(jmmv): Remove this in favor of write?
     fn print(&mut self, text: &str) -> io::Result<()>;
 
+    /// Writes `text` to the console like `print` does, but wraps it at word boundaries to fit the
+    /// width of the console.
+    ///
+    /// Explicit newlines in `text` are preserved and words that are too long to fit in a line are
+    /// split.  Wide characters count as two columns.
+    fn print_wrapped(&mut self, text: &str) -> io::Result<()> {
+        let size = self.size_chars()?;
+        for line in format::wrap(text, usize::from(size.x)) {
+            self.print(&line)?;
+        }
+        Ok(())
+    }
+
     /// Returns the next key press if any is available.
     async fn poll_key(&mut self) -> io::Result<Option<Key>>;
 
     /// Waits for and returns the next key press.
     async fn read_key(&mut self) -> io::Result<Key>;
 
+    /// Discards all pending key presses without blocking and returns how many were discarded.
+    ///
+    /// `Key::Eof` and `Key::Interrupt` are never discarded: they stay queued so that the next
+    /// `poll_key` or `read_key` call returns them.
+    ///
+    /// Consoles that cannot access their input queue fail with `io::ErrorKind::Unsupported`.
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "Cannot drain the input of this console"))
+    }
+
+    /// Makes the cursor blink with the given `_period` while waiting for a key press in
+    /// `read_key`.  A zero period disables blinking.
+    ///
+    /// Consoles whose cursor is rendered by other means, such as terminals, ignore this.
+    ///
+    /// Blinking is paused while video syncing is disabled so that partially-drawn frames are not
+    /// flushed to the screen behind the program's back.  `sync_now` does not flush blink changes
+    /// because none happen in that state.
+    fn set_blink_rate(&mut self, _period: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Selects the font `_font` to render text.
+    ///
+    /// Changing the font changes the size of the character cells and thus the dimensions returned
+    /// by `size_chars`.  Consoles that do not render their own text, such as terminals, ignore it.
+    fn set_font(&mut self, _font: FontId) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Moves the cursor to the position recorded by the last call to `save_cursor`.
+    ///
+    /// If the position was never saved, the cursor moves to the top-left corner.  Consoles that
+    /// cannot save the cursor position fail with `io::ErrorKind::Unsupported`.
+    fn restore_cursor(&mut self) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No cursor saving support in this console"))
+    }
+
+    /// Records the current position of the cursor so that `restore_cursor` can return to it.
+    ///
+    /// There is a single save slot so calling this again overwrites the previously-saved position.
+    fn save_cursor(&mut self) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No cursor saving support in this console"))
+    }
+
+    /// Scrolls the contents of the scroll region up by `_lines` lines, filling the vacated lines
+    /// at the bottom of the region with the background color.
+    fn scroll_up(&mut self, _lines: u16) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No scroll region support in this console"))
+    }
+
+    /// Restricts scrolling to the rows between `_top` and `_bottom`, both inclusive.
+    ///
+    /// Passing `0` and the last row of the console resets the scroll region to the full screen.
+    /// Consoles without scroll regions fail with `io::ErrorKind::Unsupported`, as does
+    /// `scroll_up`.
+    fn set_scroll_region(&mut self, _top: u16, _bottom: u16) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No scroll region support in this console"))
+    }
+
     /// Shows the cursor.
     fn show_cursor(&mut self) -> io::Result<()>;
 
@@ -248,41 +581,184 @@ pub trait Console {
 
     /// Queries the size of the graphical console.
     fn size_pixels(&self) -> io::Result<SizeInPixels> {
-        Err(io::Error::new(io::ErrorKind::Other, "No graphics support in this console"))
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
     }
 
     /// Writes the text into the console at the position of the cursor.
     ///
+    /// As with `print`, each newline (LF) in `text` starts a new line.
     fn write(&mut self, text: &str) -> io::Result<()>;
 
+    /// Writes `bytes` verbatim into the console, bypassing any control character filtering.
+    ///
+    /// This is intended to emit pre-rendered content such as ANSI art.  The console does not track
+    /// the effects of the written bytes so this can corrupt the state of the console (e.g. its
+    /// idea of the cursor position or colors), and it is the caller's responsibility to restore
+    /// it.  Consoles that cannot interpret raw bytes, such as graphical ones, ignore them.
+    fn write_raw(&mut self, _bytes: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Draws the part of the circle at `_center` with `_radius` between the `_start_deg` and
+    /// `_end_deg` angles using the current drawing color.
+    ///
+    /// Angles are measured in degrees clockwise from 12 o'clock and wrap modulo 360.
+    fn draw_arc(
+        &mut self,
+        _center: PixelsXY,
+        _radius: u16,
+        _start_deg: u16,
+        _end_deg: u16,
+    ) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
     /// Draws the outline of a circle at `_center` with `_radius` using the current drawing color.
     fn draw_circle(&mut self, _center: PixelsXY, _radius: u16) -> io::Result<()> {
-        Err(io::Error::new(io::ErrorKind::Other, "No graphics support in this console"))
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
     }
 
     /// Draws a filled circle at `_center` with `_radius` using the current drawing color.
     fn draw_circle_filled(&mut self, _center: PixelsXY, _radius: u16) -> io::Result<()> {
-        Err(io::Error::new(io::ErrorKind::Other, "No graphics support in this console"))
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
+    /// Draws the quadratic (3 points) or cubic (4 points) Bezier curve defined by `points` using
+    /// the current drawing color.
+    ///
+    /// The curve is flattened into line segments and drawn via `draw_line`.
+    fn draw_bezier(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        for pair in drawing::bezier_points(points)?.windows(2) {
+            self.draw_line(pair[0], pair[1])?;
+        }
+        Ok(())
     }
 
     /// Draws a line from `_x1y1` to `_x2y2` using the current drawing color.
     fn draw_line(&mut self, _x1y1: PixelsXY, _x2y2: PixelsXY) -> io::Result<()> {
-        Err(io::Error::new(io::ErrorKind::Other, "No graphics support in this console"))
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
     }
 
     /// Draws a single pixel at `_xy` using the current drawing color.
     fn draw_pixel(&mut self, _xy: PixelsXY) -> io::Result<()> {
-        Err(io::Error::new(io::ErrorKind::Other, "No graphics support in this console"))
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
+    /// Draws a single pixel at each of the `points` using the current drawing color.
+    ///
+    /// This is equivalent to calling `draw_pixel` for every point, which is what the default
+    /// implementation does, but consoles backed by slow displays batch the points into a single
+    /// update.  Points outside of the console are ignored.
+    fn draw_pixels(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        for xy in points {
+            self.draw_pixel(*xy)?;
+        }
+        Ok(())
     }
 
     /// Draws the outline of a rectangle from `_x1y1` to `_x2y2` using the current drawing color.
+    ///
+    /// The corners can be given in any order.
     fn draw_rect(&mut self, _x1y1: PixelsXY, _x2y2: PixelsXY) -> io::Result<()> {
-        Err(io::Error::new(io::ErrorKind::Other, "No graphics support in this console"))
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
     }
 
     /// Draws a filled rectangle from `_x1y1` to `_x2y2` using the current drawing color.
+    ///
+    /// The corners can be given in any order.
     fn draw_rect_filled(&mut self, _x1y1: PixelsXY, _x2y2: PixelsXY) -> io::Result<()> {
-        Err(io::Error::new(io::ErrorKind::Other, "No graphics support in this console"))
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
+    /// Draws the outline of a rectangle from `x1y1` to `x2y2` with its corners rounded by quarter
+    /// circles of `_radius` using the current drawing color.
+    ///
+    /// The corners can be given in any order and `_radius` is clamped to half of the shorter side
+    /// of the rectangle.  Consoles that cannot draw rounded corners fall back to `draw_rect`,
+    /// which is what the default implementation does.
+    fn draw_rounded_rect(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        _radius: u16,
+    ) -> io::Result<()> {
+        self.draw_rect(x1y1, x2y2)
+    }
+
+    /// Draws a filled rectangle from `x1y1` to `x2y2` with its corners rounded by quarter circles
+    /// of `_radius` using the current drawing color.
+    ///
+    /// The same considerations as for `draw_rounded_rect` apply, and the default implementation
+    /// falls back to `draw_rect_filled`.
+    fn draw_rounded_rect_filled(
+        &mut self,
+        x1y1: PixelsXY,
+        x2y2: PixelsXY,
+        _radius: u16,
+    ) -> io::Result<()> {
+        self.draw_rect_filled(x1y1, x2y2)
+    }
+
+    /// Clears the rectangle from `_x1y1` to `_x2y2` by filling it with the current background
+    /// color, leaving the rest of the console untouched.
+    ///
+    /// The corners can be given in any order.
+    fn clear_rect(&mut self, _x1y1: PixelsXY, _x2y2: PixelsXY) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
+    /// Writes `_text` with its top-left corner at pixel `_xy` using the current font and colors.
+    ///
+    /// Text that does not fit on the screen is clipped.
+    fn draw_text(&mut self, _xy: PixelsXY, _text: &str) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
+    /// Computes the size of the area that `_text` covers when written with `draw_text` using the
+    /// current font, ignoring clipping.  This is useful to align text before drawing it.
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` if `_text` is empty.
+    fn text_pixel_size(&self, _text: &str) -> io::Result<SizeInPixels> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
+    /// Draws a filled rectangle from `_x1y1` to `_x2y2` whose color transitions linearly from
+    /// `_from` to `_to` along `_direction`.
+    fn draw_gradient_rect(
+        &mut self,
+        _x1y1: PixelsXY,
+        _x2y2: PixelsXY,
+        _from: RGB,
+        _to: RGB,
+        _direction: GradientDir,
+    ) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No graphics support in this console"))
+    }
+
+    /// Sets how subsequent drawing primitives combine the foreground color with the pixels on the
+    /// screen, which is `DrawOp::Copy` by default.
+    ///
+    /// The operation applies to the `draw_*` primitives except for `draw_gradient_rect` and
+    /// `draw_text`.  Console output is not affected.
+    ///
+    /// Consoles that cannot read back their contents only support `DrawOp::Copy` and fail with
+    /// `io::ErrorKind::Unsupported` otherwise.
+    fn set_draw_op(&mut self, op: DrawOp) -> io::Result<()> {
+        match op {
+            DrawOp::Copy => Ok(()),
+            DrawOp::Xor => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "No XOR drawing support in this console",
+            )),
+        }
+    }
+
+    /// Restricts all subsequent drawing operations to the rectangle defined by the `_rect`
+    /// corners, or restores drawing to the whole screen if `_rect` is `None`.
+    ///
+    /// Consoles without graphics support ignore this.
+    fn set_clip(&mut self, _rect: Option<(PixelsXY, PixelsXY)>) -> io::Result<()> {
+        Ok(())
     }
 
     /// Causes any buffered output to be synced.
@@ -290,6 +766,17 @@ pub trait Console {
     /// This is a no-op when video syncing is enabled because output is never buffered in that case.
     fn sync_now(&mut self) -> io::Result<()>;
 
+    /// Causes any buffered output to be synced.
+    ///
+    /// This is the asynchronous counterpart of `sync_now` for consoles whose flushes are slow,
+    /// such as those backed by hardware LCDs.  Such consoles transfer the buffered output on a
+    /// separate blocking thread and resolve once the transfer completes, which keeps the executor
+    /// free to run other tasks in the meantime.  If the transfer fails, the affected area is
+    /// resent by the next flush.  The default implementation delegates to `sync_now`.
+    async fn present(&mut self) -> io::Result<()> {
+        self.sync_now()
+    }
+
     /// Enables or disables video syncing.
     ///
     /// When enabled, all graphical operations immediately updated the rendering target, which is
@@ -301,6 +788,46 @@ pub trait Console {
     ///
     /// Returns the previous status of the video syncing flag.
     fn set_sync(&mut self, _enabled: bool) -> io::Result<bool>;
+
+    /// Starts drawing a frame off-screen.
+    ///
+    /// All output is buffered until the matching `end_frame` call, which presents the frame in one
+    /// go to avoid tearing.  Unlike `set_sync(false)`, any changes pending from before the frame
+    /// are flushed first so that the frame's damage tracking starts clean.
+    ///
+    /// The default implementation just disables video syncing.
+    fn begin_frame(&mut self) -> io::Result<()> {
+        self.set_sync(false).map(|_| ())
+    }
+
+    /// Finishes drawing the frame started by `begin_frame` and presents it.
+    ///
+    /// The default implementation just enables video syncing.
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.set_sync(true).map(|_| ())
+    }
+
+    /// Executes the drawing commands in `ops` in order with video syncing disabled, and then
+    /// restores the previous syncing state.
+    ///
+    /// If syncing was enabled, the results of all commands are flushed at once at the end, which
+    /// is faster than issuing the commands individually on consoles with slow flushes.  Execution
+    /// stops at the first command that fails, but the syncing state is restored regardless.
+    fn draw_batch(&mut self, ops: &[DrawCommand]) -> io::Result<()> {
+        let previous = self.set_sync(false)?;
+        let result = ops.iter().try_for_each(|op| match op {
+            DrawCommand::Circle(center, radius) => self.draw_circle(*center, *radius),
+            DrawCommand::CircleFilled(center, radius) => self.draw_circle_filled(*center, *radius),
+            DrawCommand::Line(x1y1, x2y2) => self.draw_line(*x1y1, *x2y2),
+            DrawCommand::Pixel(xy) => self.draw_pixel(*xy),
+            DrawCommand::Rect(x1y1, x2y2) => self.draw_rect(*x1y1, *x2y2),
+            DrawCommand::RectFilled(x1y1, x2y2) => self.draw_rect_filled(*x1y1, *x2y2),
+            DrawCommand::Text(xy, text) => self.draw_text(*xy, text),
+        });
+        let restored = self.set_sync(previous);
+        result?;
+        restored.map(|_| ())
+    }
 }
 
 /// Resets the state of a console in a best-effort manner.
@@ -335,20 +862,34 @@ pub fn has_control_chars(s: &str) -> bool {
     false
 }
 
+/// Checks if a given string has control characters that `remove_control_chars` would replace.
+fn has_removable_control_chars(s: &str) -> bool {
+    s.chars().any(|ch| ch.is_control() && ch != '\n')
+}
+
 /// Removes control characters from a string to make it suitable for printing.
+///
+/// Newlines are preserved so that consoles can break the text into multiple lines, and `\r\n`
+/// sequences are collapsed into a single `\n`.  All other control characters are replaced by
+/// spaces.
 pub fn remove_control_chars<S: Into<String>>(s: S) -> String {
     let s = s.into();
 
     // Handle the expected common case first.  We use this function to strip control characters
     // before printing them to the console, and thus we expect such input strings to rarely include
     // control characters.
-    if !has_control_chars(&s) {
+    if !has_removable_control_chars(&s) {
         return s;
     }
 
     let mut o = String::with_capacity(s.len());
-    for ch in s.chars() {
-        if ch.is_control() {
+    let mut chars = s.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\n' {
+            o.push(ch);
+        } else if ch == '\r' && chars.peek() == Some(&'\n') {
+            // Dropped in favor of the newline that follows.
+        } else if ch.is_control() {
             o.push(' ');
         } else {
             o.push(ch);
@@ -357,15 +898,31 @@ pub fn remove_control_chars<S: Into<String>>(s: S) -> String {
     o
 }
 
-/// Gets the value of the environment variable `name` and interprets it as a `u16`.  Returns
-/// `None` if the variable is not set or if its contents are invalid.
-pub fn get_env_var_as_u16(name: &str) -> Option<u16> {
-    match env::var_os(name) {
-        Some(value) => value.as_os_str().to_string_lossy().parse::<u16>().map(Some).unwrap_or(None),
-        None => None,
+/// Removes control characters from a string to make it suitable for printing, like
+/// `remove_control_chars` does, but without allocating a new string if `s` contains no control
+/// characters.
+pub fn filter_control_chars(s: &str) -> Cow<'_, str> {
+    if has_removable_control_chars(s) {
+        Cow::Owned(remove_control_chars(s))
+    } else {
+        Cow::Borrowed(s)
     }
 }
 
+/// Interprets `value` as a console dimension, such as the contents of the `LINES` or `COLUMNS`
+/// environment variables.  Returns `None` if `value` is not a positive `u16` so that callers can
+/// fall back to the real size of the console instead of operating on an empty one.
+pub fn parse_dimension(value: &str) -> Option<u16> {
+    value.trim().parse::<u16>().ok().filter(|v| *v > 0)
+}
+
+/// Gets the value of the environment variable `name` and interprets it as a console dimension
+/// with `parse_dimension`.  Returns `None` if the variable is not set or if its contents are not a
+/// valid dimension.
+pub fn get_env_var_as_dimension(name: &str) -> Option<u16> {
+    env::var_os(name).and_then(|value| parse_dimension(&value.to_string_lossy()))
+}
+
 /// Converts a line of text into a collection of keys.
 fn line_to_keys(s: String) -> VecDeque<Key> {
     let mut keys = VecDeque::default();
@@ -405,6 +962,38 @@ pub fn read_key_from_stdin(buffer: &mut VecDeque<Key>) -> io::Result<Key> {
     }
 }
 
+/// Computes how many of the `total` units of a progress bar are filled for `fraction`, which is
+/// clamped to the `0..=1` range.
+///
+/// The result is rounded down so that the bar only appears full once the operation is complete.
+fn progress_bar_filled(fraction: f64, total: u16) -> u16 {
+    if fraction.is_nan() {
+        return 0;
+    }
+    (fraction.clamp(0.0, 1.0) * f64::from(total)).floor() as u16
+}
+
+/// Sets the colors of `console` to `fg` and `bg`, runs `f`, and then restores the colors that
+/// were in effect before the call.
+///
+/// The colors are restored even if `f` fails, in which case the error from `f` is returned.
+pub fn with_color<R, F>(
+    console: &mut dyn Console,
+    fg: Option<u8>,
+    bg: Option<u8>,
+    f: F,
+) -> io::Result<R>
+where
+    F: FnOnce(&mut dyn Console) -> io::Result<R>,
+{
+    let (old_fg, old_bg) = console.color();
+    console.set_color(fg, bg)?;
+    let result = f(console);
+    let restored = console.set_color(old_fg, old_bg);
+    let value = result?;
+    restored.map(|_| value)
+}
+
 /// Returns true if the console is too narrow for the standard interface.
 ///
 /// A narrow console is defined as one that cannot fit the welcome message.
@@ -418,6 +1007,166 @@ pub fn is_narrow(console: &dyn Console) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutils::{CapturedOut, MockConsole};
+
+    #[test]
+    fn test_text_only_console_has_no_graphics() {
+        let mut console = TrivialConsole::default();
+        assert!(!console.supports_graphics());
+        assert_eq!(io::ErrorKind::Unsupported, console.size_pixels().unwrap_err().kind());
+        assert_eq!(
+            io::ErrorKind::Unsupported,
+            console.draw_pixel(PixelsXY::new(0, 0)).unwrap_err().kind()
+        );
+        assert_eq!(
+            io::ErrorKind::Unsupported,
+            console
+                .draw_bezier(&[PixelsXY::new(0, 0), PixelsXY::new(1, 1), PixelsXY::new(2, 0)])
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    #[test]
+    fn test_text_only_console_has_no_cursor_saving_or_scroll_regions() {
+        let mut console = TrivialConsole::default();
+        assert_eq!(io::ErrorKind::Unsupported, console.save_cursor().unwrap_err().kind());
+        assert_eq!(io::ErrorKind::Unsupported, console.restore_cursor().unwrap_err().kind());
+        assert_eq!(io::ErrorKind::Unsupported, console.scroll_up(1).unwrap_err().kind());
+        assert_eq!(io::ErrorKind::Unsupported, console.set_scroll_region(0, 1).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_with_color_restores_on_success() {
+        let mut console = MockConsole::default();
+        console.set_color(Some(1), Some(2)).unwrap();
+        let value = with_color(&mut console, Some(3), None, |console| {
+            assert_eq!((Some(3), None), console.color());
+            console.print("x")?;
+            Ok(5)
+        })
+        .unwrap();
+        assert_eq!(5, value);
+        assert_eq!((Some(1), Some(2)), console.color());
+        assert_eq!(
+            &[
+                CapturedOut::SetColor(Some(1), Some(2)),
+                CapturedOut::SetColor(Some(3), None),
+                CapturedOut::Print("x".to_owned()),
+                CapturedOut::SetColor(Some(1), Some(2)),
+            ],
+            console.captured_out()
+        );
+    }
+
+    #[test]
+    fn test_with_color_restores_on_error() {
+        let mut console = MockConsole::default();
+        console.set_color(Some(1), Some(2)).unwrap();
+        let err = with_color(&mut console, Some(3), Some(4), |_console| -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::Other, "Inner failure"))
+        })
+        .unwrap_err();
+        assert_eq!("Inner failure", err.to_string());
+        assert_eq!((Some(1), Some(2)), console.color());
+    }
+
+    #[test]
+    fn test_progress_bar_filled() {
+        assert_eq!(0, progress_bar_filled(0.0, 10));
+        assert_eq!(2, progress_bar_filled(0.25, 10));
+        assert_eq!(9, progress_bar_filled(0.999, 10));
+        assert_eq!(10, progress_bar_filled(1.0, 10));
+        assert_eq!(0, progress_bar_filled(-3.0, 10));
+        assert_eq!(10, progress_bar_filled(7.5, 10));
+        assert_eq!(0, progress_bar_filled(f64::NAN, 10));
+        assert_eq!(0, progress_bar_filled(0.5, 0));
+    }
+
+    #[test]
+    fn test_progress_bar_text() {
+        for (fraction, exp_bar) in [
+            (0.0, "[--------]"),
+            (0.5, "[####----]"),
+            (0.9, "[#######-]"),
+            (1.0, "[########]"),
+            (2.0, "[########]"),
+        ] {
+            let mut console = MockConsole::default();
+            console.progress_bar(fraction, 8).unwrap();
+            assert_eq!(
+                &[CapturedOut::Write(exp_bar.to_owned()), CapturedOut::MoveWithinLine(-10)],
+                console.captured_out()
+            );
+        }
+    }
+
+    #[test]
+    fn test_draw_batch_error_restores_sync() {
+        let mut console = TrivialConsole::default();
+        console.set_sync(true).unwrap();
+        let err = console
+            .draw_batch(&[
+                DrawCommand::Pixel(PixelsXY::new(1, 2)),
+                DrawCommand::Line(PixelsXY::new(0, 0), PixelsXY::new(3, 4)),
+            ])
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, err.kind());
+        assert!(console.set_sync(true).unwrap());
+    }
+
+    #[test]
+    fn test_set_raw_mode_unsupported_by_default() {
+        let mut console = TrivialConsole::default();
+        assert_eq!(io::ErrorKind::Unsupported, console.set_raw_mode(true).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_print_wrapped() {
+        let mut console = MockConsole::default();
+        console.set_size_chars(CharsXY::new(12, 5));
+        console.print_wrapped("Some words that wrap\nand a verylongwordthatsplits").unwrap();
+        assert_eq!(
+            &[
+                CapturedOut::Print("Some words".to_owned()),
+                CapturedOut::Print("that wrap".to_owned()),
+                CapturedOut::Print("and a".to_owned()),
+                CapturedOut::Print("verylongwor".to_owned()),
+                CapturedOut::Print("dthatsplits".to_owned()),
+            ],
+            console.captured_out()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cursor_position_unsupported_by_default() {
+        let mut console = TrivialConsole::default();
+        assert_eq!(io::ErrorKind::Unsupported, console.cursor_position().await.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_reset_background_keeps_foreground() {
+        let mut console = MockConsole::default();
+        console.set_color(Some(3), Some(4)).unwrap();
+        console.reset_background().unwrap();
+        assert_eq!((Some(3), None), console.color());
+        assert_eq!(
+            &[CapturedOut::SetColor(Some(3), Some(4)), CapturedOut::SetColor(Some(3), None)],
+            console.captured_out()
+        );
+    }
+
+    #[test]
+    fn test_reset_foreground_keeps_background() {
+        let mut console = MockConsole::default();
+        console.set_color(Some(3), Some(4)).unwrap();
+        console.reset_foreground().unwrap();
+        assert_eq!((None, Some(4)), console.color());
+        assert_eq!(
+            &[CapturedOut::SetColor(Some(3), Some(4)), CapturedOut::SetColor(None, Some(4))],
+            console.captured_out()
+        );
+    }
 
     #[test]
     fn test_has_control_chars() {
@@ -433,6 +1182,90 @@ mod tests {
     fn test_remove_control_chars() {
         assert_eq!("", remove_control_chars(""));
         assert_eq!("foo bar", remove_control_chars("foo bar"));
-        assert_eq!("foo  bar baz ", remove_control_chars("foo\r\nbar\rbaz\n"));
+        assert_eq!("foo\nbar baz\n", remove_control_chars("foo\r\nbar\rbaz\n"));
+    }
+
+    #[test]
+    fn test_remove_control_chars_keeps_newlines() {
+        assert_eq!("a\nb", remove_control_chars("a\nb"));
+        assert_eq!("\n\n", remove_control_chars("\n\r\n"));
+        assert_eq!("a \nb  c \n", remove_control_chars("a\t\nb\x1b\x08c\r\r\n"));
+    }
+
+    #[test]
+    fn test_filter_control_chars() {
+        assert!(matches!(filter_control_chars(""), Cow::Borrowed("")));
+        assert!(matches!(filter_control_chars("foo bar"), Cow::Borrowed("foo bar")));
+        assert!(matches!(filter_control_chars("foo\nbar"), Cow::Borrowed("foo\nbar")));
+        assert_eq!(
+            Cow::<str>::Owned("foo\nbar baz\n".to_owned()),
+            filter_control_chars("foo\r\nbar\rbaz\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_dimension() {
+        assert_eq!(Some(80), parse_dimension("80"));
+        assert_eq!(Some(24), parse_dimension(" 24\n"));
+        assert_eq!(Some(65535), parse_dimension("65535"));
+        assert_eq!(None, parse_dimension("0"));
+        assert_eq!(None, parse_dimension(""));
+        assert_eq!(None, parse_dimension("abc"));
+        assert_eq!(None, parse_dimension("-5"));
+        assert_eq!(None, parse_dimension("65536"));
+    }
+
+    #[test]
+    fn test_key_name() {
+        assert_eq!("ArrowUp", Key::ArrowUp.name());
+        assert_eq!("PageDown", Key::PageDown.name());
+        assert_eq!("a", Key::Char('a').name());
+        assert_eq!("ñ", Key::Char('ñ').name());
+        assert_eq!("Ctrl+A", Key::Char('\u{1}').name());
+        assert_eq!("Ctrl+Z", Key::Char('\u{1a}').name());
+        assert_eq!("\u{1b}", Key::Char('\u{1b}').name());
+        assert_eq!("\u{3}", Key::Char('\u{3}').name());
+    }
+
+    #[test]
+    fn test_key_from_name() {
+        assert_eq!(Some(Key::ArrowUp), Key::from_name("ArrowUp"));
+        assert_eq!(Some(Key::ArrowUp), Key::from_name("arrowup"));
+        assert_eq!(Some(Key::Char('a')), Key::from_name("a"));
+        assert_eq!(Some(Key::Char('+')), Key::from_name("+"));
+        assert_eq!(Some(Key::Interrupt), Key::from_name("Ctrl+C"));
+        assert_eq!(Some(Key::Eof), Key::from_name("ctrl-d"));
+        assert_eq!(Some(Key::Char('\u{1}')), Key::from_name("Ctrl+a"));
+        assert_eq!(Some(Key::Char('\u{1a}')), Key::from_name("CTRL+Z"));
+    }
+
+    #[test]
+    fn test_key_from_name_unknown() {
+        for name in ["", "F3", "Arrow", "Ctrl+", "Ctrl+1", "Ctrl+AB", "Ctrl+ñ", "Shift+A", "añ"] {
+            assert_eq!(None, Key::from_name(name), "{} should not be known", name);
+        }
+    }
+
+    #[test]
+    fn test_key_name_round_trip() {
+        let mut keys: Vec<Key> = Key::all_named().iter().map(|(_name, key)| *key).collect();
+        for ch in ['a', 'Z', ' ', '+', 'ñ', '\u{0}', '\u{1b}', '\u{7f}'] {
+            keys.push(Key::Char(ch));
+        }
+        for code in 1..=26u8 {
+            keys.push(Key::Char(char::from(code)));
+        }
+        for key in keys {
+            assert_eq!(Some(key), Key::from_name(&key.name()), "{:?} does not round-trip", key);
+        }
+    }
+
+    #[test]
+    fn test_key_all_named_matches_name() {
+        for (name, key) in Key::all_named() {
+            assert_eq!(*name, key.name());
+        }
+        let found = Key::all_named().iter().find(|(name, _key)| *name == Key::ArrowUp.name());
+        assert_eq!(Some(&("ArrowUp", Key::ArrowUp)), found);
     }
 }
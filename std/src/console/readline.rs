@@ -47,15 +47,20 @@ fn update_line(
     console.show_cursor()
 }
 
+/// Function to compute the completions for the given prefix of the input line.
+type Completer<'a> = &'a dyn Fn(&str) -> Vec<String>;
+
 /// Reads a line of text interactively from the console, using the given `prompt` and pre-filling
 /// the input with `previous`.  If `history` is not `None`, then this appends the newly entered line
-/// into the history and allows navigating through it.
+/// into the history and allows navigating through it.  If `completer` is not `None`, then pressing
+/// the Tab key completes the input up to the cursor position.
 async fn read_line_interactive(
     console: &mut dyn Console,
     prompt: &str,
     previous: &str,
     mut history: Option<&mut Vec<String>>,
     echo: bool,
+    completer: Option<Completer<'_>>,
 ) -> io::Result<String> {
     let console_width = {
         let console_size = console.size_chars()?;
@@ -247,7 +252,44 @@ async fn read_line_interactive(
             }
 
             Key::Tab => {
-                // TODO(jmmv): Would be nice to have some form of auto-completion.
+                let completer = match completer {
+                    Some(completer) => completer,
+                    None => continue,
+                };
+
+                let mut candidates = completer(&line.start(pos));
+                if candidates.len() == 1 {
+                    let rest = line.end(pos);
+                    let candidate = LineBuffer::from(candidates.remove(0));
+                    let candidate_len = candidate.len();
+
+                    let mut new_line = candidate;
+                    new_line.push_str(&LineBuffer::from(rest));
+                    if new_line.len() >= width {
+                        // TODO(jmmv): Implement support for lines that exceed the width of the
+                        // input field (the width of the screen).
+                        continue;
+                    }
+
+                    update_line(console, pos, line.len(), &new_line)?;
+                    if new_line.len() > candidate_len {
+                        console.move_within_line(-((new_line.len() - candidate_len) as i16))?;
+                    }
+
+                    line = new_line;
+                    pos = candidate_len;
+                } else if candidates.len() > 1 {
+                    console.print("")?;
+                    for candidate in candidates {
+                        console.print(&candidate)?;
+                    }
+
+                    console.write(&format!("{}{}", prompt, line))?;
+                    if pos < line.len() {
+                        console.move_within_line(-((line.len() - pos) as i16))?;
+                    }
+                    console.sync_now()?;
+                }
             }
 
             // TODO(jmmv): Should do something smarter with unknown keys.
@@ -309,7 +351,30 @@ pub async fn read_line(
     history: Option<&mut Vec<String>>,
 ) -> io::Result<String> {
     if console.is_interactive() {
-        read_line_interactive(console, prompt, previous, history, true).await
+        read_line_interactive(console, prompt, previous, history, true, None).await
+    } else {
+        read_line_raw(console).await
+    }
+}
+
+/// Reads a line from the console like `read_line` does but offers completion of the input via
+/// `completer`.
+///
+/// When the user presses the Tab key, `completer` receives the text to the left of the cursor and
+/// returns the list of possible completions for it, each of which replaces the given text in full.
+/// A single candidate is inserted into the line while multiple candidates are listed below the
+/// prompt, one per line, after which the prompt and the input are redrawn.
+///
+/// Completion is not available if the console is not interactive.
+pub async fn read_line_with(
+    console: &mut dyn Console,
+    prompt: &str,
+    previous: &str,
+    history: Option<&mut Vec<String>>,
+    completer: &dyn Fn(&str) -> Vec<String>,
+) -> io::Result<String> {
+    if console.is_interactive() {
+        read_line_interactive(console, prompt, previous, history, true, Some(completer)).await
     } else {
         read_line_raw(console).await
     }
@@ -326,7 +391,7 @@ pub async fn read_line_secure(console: &mut dyn Console, prompt: &str) -> io::Re
             "Cannot read secure strings from a raw console".to_owned(),
         ));
     }
-    read_line_interactive(console, prompt, "", None, false).await
+    read_line_interactive(console, prompt, "", None, false, None).await
 }
 
 #[cfg(test)]
@@ -345,6 +410,7 @@ mod tests {
         previous: &'static str,
         history: Option<Vec<String>>,
         echo: bool,
+        completer: Option<fn(&str) -> Vec<String>>,
         exp_line: &'static str,
         exp_output: Vec<CapturedOut>,
         exp_history: Option<Vec<String>>,
@@ -361,6 +427,7 @@ mod tests {
                 previous: "",
                 history: None,
                 echo: true,
+                completer: None,
                 exp_line: "",
                 exp_output: vec![],
                 exp_history: None,
@@ -440,6 +507,12 @@ mod tests {
             self
         }
 
+        /// Sets the function to compute completions when pressing the Tab key.
+        fn set_completer(mut self, completer: fn(&str) -> Vec<String>) -> Self {
+            self.completer = Some(completer);
+            self
+        }
+
         /// Adds a final return key to the golden input, a newline to the expected output, and
         /// executes the test.
         fn accept(mut self) {
@@ -449,6 +522,7 @@ mod tests {
             let mut console = MockConsole::default();
             console.add_input_keys(&self.keys);
             console.set_size_chars(self.size_chars);
+            let completer = self.completer.as_ref().map(|c| c as Completer);
             let line = match self.history.as_mut() {
                 Some(history) => block_on(read_line_interactive(
                     &mut console,
@@ -456,6 +530,7 @@ mod tests {
                     self.previous,
                     Some(history),
                     self.echo,
+                    completer,
                 ))
                 .unwrap(),
                 None => block_on(read_line_interactive(
@@ -464,6 +539,7 @@ mod tests {
                     self.previous,
                     None,
                     self.echo,
+                    completer,
                 ))
                 .unwrap(),
             };
@@ -1125,6 +1201,104 @@ mod tests {
             .accept();
     }
 
+    /// Stub completer that returns the keywords that start with `prefix`.
+    fn complete_keywords(prefix: &str) -> Vec<String> {
+        ["INPUT", "PRINT", "PROGRAM"]
+            .iter()
+            .filter(|k| k.starts_with(prefix))
+            .map(|k| k.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_read_line_completion_single_candidate() {
+        ReadLineInteractiveTest::default()
+            .set_completer(complete_keywords)
+            .add_key_chars("IN")
+            .add_output_bytes("IN")
+            // -
+            .add_key(Key::Tab)
+            .add_output(CapturedOut::HideCursor)
+            .add_output(CapturedOut::MoveWithinLine(-2))
+            .add_output(CapturedOut::Write("INPUT".to_string()))
+            .add_output(CapturedOut::ShowCursor)
+            // -
+            .add_key_chars("X")
+            .add_output_bytes("X")
+            // -
+            .set_line("INPUTX")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_completion_single_candidate_middle_of_line() {
+        ReadLineInteractiveTest::default()
+            .set_completer(complete_keywords)
+            .set_previous("IN x")
+            .add_output(CapturedOut::Write("IN x".to_string()))
+            .add_output(CapturedOut::SyncNow)
+            // -
+            .add_key(Key::ArrowLeft)
+            .add_output(CapturedOut::MoveWithinLine(-1))
+            .add_key(Key::ArrowLeft)
+            .add_output(CapturedOut::MoveWithinLine(-1))
+            // -
+            .add_key(Key::Tab)
+            .add_output(CapturedOut::HideCursor)
+            .add_output(CapturedOut::MoveWithinLine(-2))
+            .add_output(CapturedOut::Write("INPUT x".to_string()))
+            .add_output(CapturedOut::ShowCursor)
+            .add_output(CapturedOut::MoveWithinLine(-2))
+            // -
+            .add_key_chars("S")
+            .add_output(CapturedOut::HideCursor)
+            .add_output_bytes("S")
+            .add_output(CapturedOut::Write(" x".to_string()))
+            .add_output(CapturedOut::MoveWithinLine(-2))
+            .add_output(CapturedOut::ShowCursor)
+            // -
+            .set_line("INPUTS x")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_completion_multiple_candidates() {
+        ReadLineInteractiveTest::default()
+            .set_completer(complete_keywords)
+            .set_prompt("> ")
+            .add_output(CapturedOut::Write("> ".to_string()))
+            .add_output(CapturedOut::SyncNow)
+            // -
+            .add_key_chars("P")
+            .add_output_bytes("P")
+            // -
+            .add_key(Key::Tab)
+            .add_output(CapturedOut::Print("".to_string()))
+            .add_output(CapturedOut::Print("PRINT".to_string()))
+            .add_output(CapturedOut::Print("PROGRAM".to_string()))
+            .add_output(CapturedOut::Write("> P".to_string()))
+            .add_output(CapturedOut::SyncNow)
+            // -
+            .add_key_chars("R")
+            .add_output_bytes("R")
+            // -
+            .set_line("PR")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_completion_no_candidates() {
+        ReadLineInteractiveTest::default()
+            .set_completer(complete_keywords)
+            .add_key_chars("Z")
+            .add_output_bytes("Z")
+            // -
+            .add_key(Key::Tab)
+            // -
+            .set_line("Z")
+            .accept();
+    }
+
     #[test]
     fn test_read_line_without_echo() {
         ReadLineInteractiveTest::default()
@@ -16,7 +16,8 @@
 //! Trivial stdio-based console implementation for when we have nothing else.
 
 use crate::console::{
-    get_env_var_as_u16, read_key_from_stdin, remove_control_chars, CharsXY, ClearType, Console, Key,
+    filter_control_chars, get_env_var_as_dimension, read_key_from_stdin, CharsXY, ClearType,
+    ColorCapability, Console, Key,
 };
 use async_trait::async_trait;
 use std::collections::VecDeque;
@@ -59,6 +60,10 @@ impl Console for TrivialConsole {
         (None, None)
     }
 
+    fn color_capability(&self) -> ColorCapability {
+        ColorCapability::None
+    }
+
     fn set_color(&mut self, _fg: Option<u8>, _bg: Option<u8>) -> io::Result<()> {
         Ok(())
     }
@@ -95,7 +100,7 @@ impl Console for TrivialConsole {
     }
 
     fn print(&mut self, text: &str) -> io::Result<()> {
-        let text = remove_control_chars(text);
+        let text = filter_control_chars(text);
 
         let stdout = io::stdout();
         let mut stdout = stdout.lock();
@@ -116,14 +121,16 @@ impl Console for TrivialConsole {
         Ok(())
     }
 
+    /// The size comes from the `LINES` and `COLUMNS` environment variables.  Variables that are
+    /// unset, malformed or zero are replaced by the defaults.
     fn size_chars(&self) -> io::Result<CharsXY> {
-        let lines = get_env_var_as_u16("LINES").unwrap_or(DEFAULT_LINES);
-        let columns = get_env_var_as_u16("COLUMNS").unwrap_or(DEFAULT_COLUMNS);
+        let lines = get_env_var_as_dimension("LINES").unwrap_or(DEFAULT_LINES);
+        let columns = get_env_var_as_dimension("COLUMNS").unwrap_or(DEFAULT_COLUMNS);
         Ok(CharsXY::new(columns, lines))
     }
 
     fn write(&mut self, text: &str) -> io::Result<()> {
-        let text = remove_control_chars(text);
+        let text = filter_control_chars(text);
 
         let stdout = io::stdout();
         let mut stdout = stdout.lock();
@@ -131,6 +138,13 @@ impl Console for TrivialConsole {
         self.maybe_flush(stdout)
     }
 
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        stdout.write_all(bytes)?;
+        self.maybe_flush(stdout)
+    }
+
     fn sync_now(&mut self) -> io::Result<()> {
         if self.sync_enabled {
             Ok(())
@@ -17,16 +17,17 @@
 
 use crate::console::drawing;
 use crate::console::graphics::{RasterInfo, RasterOps};
-use crate::console::{CharsXY, PixelsXY, SizeInPixels, RGB};
-use crate::gfx::lcd::fonts::Font;
+use crate::console::{CharsXY, DrawOp, FontId, GradientDir, PixelsXY, SizeInPixels, RGB};
+use crate::gfx::lcd::fonts::{font_by_id, Font};
 use crate::gfx::lcd::{to_xy_size, AsByteSlice, Lcd, LcdSize, LcdXY};
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::io;
 
 #[cfg(test)]
 mod tests;
 #[cfg(test)]
-mod testutils;
+pub(super) mod testutils;
 
 /// Implements buffering for a backing slow LCD `L` that renders text with the font `F`.
 ///
@@ -45,8 +46,11 @@ pub struct BufferedLcd<L: Lcd> {
 
     size_pixels: LcdSize,
     size_chars: CharsXY,
+    clip: Option<(PixelsXY, SizeInPixels)>,
 
     draw_color: L::Pixel,
+    draw_op: DrawOp,
+    xored: HashSet<(usize, usize)>,
     row_buffer: Vec<u8>,
 }
 
@@ -63,10 +67,7 @@ where
             vec![0; pixels * stride]
         };
 
-        let size_chars = CharsXY::new(
-            u16::try_from(size.width / font.glyph_size.width).expect("Must fit"),
-            u16::try_from(size.height / font.glyph_size.height).expect("Must fit"),
-        );
+        let size_chars = Self::size_chars_for(size, font);
 
         let draw_color = lcd.encode((255, 255, 255));
         let row_buffer = Vec::with_capacity(size.width * stride);
@@ -80,11 +81,53 @@ where
             damage: None,
             size_pixels: size,
             size_chars,
+            clip: None,
             draw_color,
+            draw_op: DrawOp::Copy,
+            xored: HashSet::default(),
             row_buffer,
         }
     }
 
+    /// Returns the backing LCD.
+    ///
+    /// The LCD only reflects the contents of the framebuffer that have been synced to it.
+    pub fn lcd(&self) -> &L {
+        &self.lcd
+    }
+
+    /// Returns the backing LCD for modification.
+    ///
+    /// This is meant to adjust settings of the LCD.  Drawing on it directly bypasses the
+    /// framebuffer, so the drawn contents are lost on the next sync.
+    pub fn lcd_mut(&mut self) -> &mut L {
+        &mut self.lcd
+    }
+
+    /// Returns the current contents of the framebuffer, including any damage not yet synced.
+    ///
+    /// The bytes are in the native encoding of the backing LCD (e.g. RGB565 for the ST7735S),
+    /// laid out in row-major order, so consumers must decode them as described by
+    /// `frame_dimensions`.
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.fb
+    }
+
+    /// Returns the dimensions of the framebuffer returned by `frame_buffer` and the number of
+    /// bytes per pixel.
+    pub fn frame_dimensions(&self) -> (LcdSize, usize) {
+        (self.size_pixels, self.stride)
+    }
+
+    /// Computes the size of the console in characters for an LCD of `size` pixels rendering text
+    /// with `font`.
+    fn size_chars_for(size: LcdSize, font: &Font) -> CharsXY {
+        CharsXY::new(
+            u16::try_from(size.width / font.glyph_size.width).expect("Must fit"),
+            u16::try_from(size.height / font.glyph_size.height).expect("Must fit"),
+        )
+    }
+
     /// Executes mutations on the buffered LCD via `ops` while ensuring that syncing is disabled.
     fn without_sync<O>(&mut self, ops: O) -> io::Result<()>
     where
@@ -180,6 +223,52 @@ where
         }
     }
 
+    /// Returns true if the pixel at `x` and `y` falls within the clipping area, if any.
+    fn in_clip(&self, x: i32, y: i32) -> bool {
+        match self.clip {
+            None => true,
+            Some((xy, size)) => {
+                let (x1, y1) = (i32::from(xy.x), i32::from(xy.y));
+                let (x2, y2) = (x1 + i32::from(size.width), y1 + i32::from(size.height));
+                x >= x1 && x < x2 && y >= y1 && y < y2
+            }
+        }
+    }
+
+    /// Intersects the user-supplied area at `xy` with `size` with the clipping area, if any.
+    /// Returns `None` if the intersection is empty.
+    fn clip_rect(&self, xy: PixelsXY, size: SizeInPixels) -> Option<(PixelsXY, SizeInPixels)> {
+        let (cxy, csize) = match self.clip {
+            None => return Some((xy, size)),
+            Some(clip) => clip,
+        };
+
+        let x1 = std::cmp::max(i32::from(xy.x), i32::from(cxy.x));
+        let y1 = std::cmp::max(i32::from(xy.y), i32::from(cxy.y));
+        let x2 = std::cmp::min(
+            i32::from(xy.x) + i32::from(size.width),
+            i32::from(cxy.x) + i32::from(csize.width),
+        );
+        let y2 = std::cmp::min(
+            i32::from(xy.y) + i32::from(size.height),
+            i32::from(cxy.y) + i32::from(csize.height),
+        );
+        if x2 <= x1 || y2 <= y1 {
+            return None;
+        }
+
+        Some((
+            PixelsXY::new(
+                i16::try_from(x1).expect("Must fit within the original coordinates"),
+                i16::try_from(y1).expect("Must fit within the original coordinates"),
+            ),
+            SizeInPixels::new(
+                u16::try_from(x2 - x1).expect("Must fit within the original size"),
+                u16::try_from(y2 - y1).expect("Must fit within the original size"),
+            ),
+        ))
+    }
+
     /// Make sure that the coordinates are within the LCD space.
     ///
     /// This is only used to validate input parameters for those functions that are internal to the
@@ -233,6 +322,12 @@ where
     /// that need to be flushed to the LCD once syncing is enabled again.
     fn damage(&mut self, x1y1: LcdXY, x2y2: LcdXY) {
         debug_assert!(!self.sync);
+        self.extend_damage(x1y1, x2y2);
+    }
+
+    /// Extends the current damage area to include the area between between `x1y1` and `x2y2`
+    /// (inclusive) regardless of the syncing status.
+    fn extend_damage(&mut self, x1y1: LcdXY, x2y2: LcdXY) {
         debug_assert!(x2y2.x >= x1y1.x);
         debug_assert!(x2y2.y >= x1y1.y);
 
@@ -298,20 +393,66 @@ where
         Ok(())
     }
 
-    /// Flushes any pending damaged area to the LCD.
-    fn force_present_canvas(&mut self) -> io::Result<()> {
-        let (x1y1, x2y2) = match self.damage {
-            None => return Ok(()),
-            Some(damage) => damage,
-        };
-
-        let mut data = LcdSize::between(x1y1, x2y2).new_buffer(self.stride);
+    /// XORs the area contained between `x1y1` and `x2y2` (inclusive) with the current drawing
+    /// color, skipping the pixels already XORed since the drawing operation was last set.
+    ///
+    /// The XOR is applied to the encoded pixel data, which for LCDs that pack color channels into
+    /// separate bits is the same as XORing the color channels.
+    fn xor(&mut self, x1y1: LcdXY, x2y2: LcdXY) -> io::Result<()> {
+        let color = self.draw_color;
         for y in x1y1.y..(x2y2.y + 1) {
             for x in x1y1.x..(x2y2.x + 1) {
+                if !self.xored.insert((x, y)) {
+                    continue;
+                }
                 let offset = self.fb_addr(x, y);
-                data.extend_from_slice(&self.fb[offset..offset + self.stride]);
+                for (dst, src) in
+                    self.fb[offset..offset + self.stride].iter_mut().zip(color.as_slice())
+                {
+                    *dst ^= src;
+                }
             }
         }
+
+        if self.sync {
+            let data = self.fb_data(x1y1, x2y2);
+            self.lcd.set_data(x1y1, x2y2, &data)
+        } else {
+            self.damage(x1y1, x2y2);
+            Ok(())
+        }
+    }
+
+    /// Draws the area contained between `x1y1` and `x2y2` (inclusive) with the current drawing
+    /// color and operation.
+    fn draw_area(&mut self, x1y1: LcdXY, x2y2: LcdXY) -> io::Result<()> {
+        match self.draw_op {
+            DrawOp::Copy => self.fill(x1y1, x2y2),
+            DrawOp::Xor => self.xor(x1y1, x2y2),
+        }
+    }
+
+    /// Returns a copy of the framebuffer contents between `x1y1` and `x2y2` (inclusive) as
+    /// expected by `Lcd::set_data`.
+    fn fb_data(&self, x1y1: LcdXY, x2y2: LcdXY) -> Vec<u8> {
+        let mut data = LcdSize::between(x1y1, x2y2).new_buffer(self.stride);
+        for y in x1y1.y..(x2y2.y + 1) {
+            let offset = self.fb_addr(x1y1.x, y);
+            let rowlen = (x2y2.x - x1y1.x + 1) * self.stride;
+            data.extend_from_slice(&self.fb[offset..offset + rowlen]);
+        }
+        data
+    }
+
+    /// Takes the pending damaged area, if any, and returns its corners along with a copy of its
+    /// pixel data as expected by `Lcd::set_data`.
+    ///
+    /// The damage is cleared, so the caller becomes responsible for sending the returned data to
+    /// the LCD.  This allows flushing the framebuffer without holding a reference to it.
+    pub fn take_damage(&mut self) -> Option<(LcdXY, LcdXY, Vec<u8>)> {
+        let (x1y1, x2y2) = self.damage.take()?;
+
+        let data = self.fb_data(x1y1, x2y2);
         debug_assert_eq!(
             {
                 let (_xy, size) = to_xy_size(x1y1, x2y2);
@@ -320,9 +461,28 @@ where
             data.len()
         );
 
-        self.lcd.set_data(x1y1, x2y2, &data)?;
+        Some((x1y1, x2y2, data))
+    }
+
+    /// Marks the area between `x1y1` and `x2y2` (inclusive) as damaged again after a failed attempt
+    /// to send the data returned by `take_damage` to the LCD.
+    ///
+    /// The framebuffer still holds the latest contents of the area, so the next flush resends them.
+    pub fn restore_damage(&mut self, x1y1: LcdXY, x2y2: LcdXY) {
+        self.extend_damage(x1y1, x2y2);
+    }
 
-        self.damage = None;
+    /// Flushes any pending damaged area to the LCD.
+    fn force_present_canvas(&mut self) -> io::Result<()> {
+        let (x1y1, x2y2, data) = match self.take_damage() {
+            None => return Ok(()),
+            Some(damage) => damage,
+        };
+
+        if let Err(e) = self.lcd.set_data(x1y1, x2y2, &data) {
+            self.damage(x1y1, x2y2);
+            return Err(e);
+        }
 
         Ok(())
     }
@@ -347,6 +507,10 @@ where
                             continue;
                         }
 
+                        if !self.in_clip(x as i32, y as i32) {
+                            continue;
+                        }
+
                         let xy = LcdXY { x, y };
                         // TODO(jmmv): This is very inefficent on a pixel basis.
                         self.fill(xy, xy)?;
@@ -387,6 +551,23 @@ where
         self.draw_color = self.lcd.encode(color);
     }
 
+    fn set_clip(&mut self, clip: Option<(PixelsXY, SizeInPixels)>) -> io::Result<()> {
+        self.clip = clip;
+        Ok(())
+    }
+
+    fn set_draw_op(&mut self, op: DrawOp) -> io::Result<()> {
+        self.draw_op = op;
+        self.xored.clear();
+        Ok(())
+    }
+
+    fn set_font(&mut self, font: FontId) -> io::Result<()> {
+        self.font = font_by_id(font);
+        self.size_chars = Self::size_chars_for(self.size_pixels, self.font);
+        Ok(())
+    }
+
     fn clear(&mut self) -> io::Result<()> {
         self.fill(
             LcdXY { x: 0, y: 0 },
@@ -467,8 +648,13 @@ where
 
         let data = self.read_pixels(x1y1, size)?;
 
+        // Clear the source area without going through draw_rect_filled: this is an internal
+        // operation and must not be subject to clipping.
+        let src_x1y1 = self.clip_xy(x1y1).expect("Internal ops must receive valid coordinates");
+        let src_x2y2 =
+            self.clip_x2y2(x1y1, size).expect("Internal ops must receive valid coordinates");
         self.without_sync(|self2| {
-            self2.draw_rect_filled(x1y1, size)?;
+            self2.fill(src_x1y1, src_x2y2)?;
             self2.put_pixels(x2y2, &data)
         })?;
 
@@ -490,6 +676,16 @@ where
         })
     }
 
+    fn draw_arc(
+        &mut self,
+        center: PixelsXY,
+        radius: u16,
+        start_deg: u16,
+        end_deg: u16,
+    ) -> io::Result<()> {
+        self.without_sync(|self2| drawing::draw_arc(self2, center, radius, start_deg, end_deg))
+    }
+
     fn draw_circle(&mut self, center: PixelsXY, radius: u16) -> io::Result<()> {
         self.without_sync(|self2| drawing::draw_circle(self2, center, radius))
     }
@@ -503,22 +699,125 @@ where
     }
 
     fn draw_pixel(&mut self, xy: PixelsXY) -> io::Result<()> {
+        if !self.in_clip(i32::from(xy.x), i32::from(xy.y)) {
+            return Ok(());
+        }
+
         let xy = self.clip_xy(xy);
         match xy {
-            Some(xy) => self.fill(xy, xy),
+            Some(xy) => self.draw_area(xy, xy),
             None => Ok(()),
         }
     }
 
+    fn draw_pixels(&mut self, points: &[PixelsXY]) -> io::Result<()> {
+        self.without_sync(|self2| {
+            for xy in points {
+                self2.draw_pixel(*xy)?;
+            }
+            Ok(())
+        })
+    }
+
     fn draw_rect(&mut self, xy: PixelsXY, size: SizeInPixels) -> io::Result<()> {
         self.without_sync(|self2| drawing::draw_rect(self2, xy, size))
     }
 
+    fn draw_rounded_rect(
+        &mut self,
+        xy: PixelsXY,
+        size: SizeInPixels,
+        radius: u16,
+    ) -> io::Result<()> {
+        self.without_sync(|self2| drawing::draw_rounded_rect(self2, xy, size, radius))
+    }
+
+    fn draw_rounded_rect_filled(
+        &mut self,
+        xy: PixelsXY,
+        size: SizeInPixels,
+        radius: u16,
+    ) -> io::Result<()> {
+        self.without_sync(|self2| drawing::draw_rounded_rect_filled(self2, xy, size, radius))
+    }
+
+    fn draw_gradient_rect(
+        &mut self,
+        xy: PixelsXY,
+        size: SizeInPixels,
+        from: RGB,
+        to: RGB,
+        direction: GradientDir,
+    ) -> io::Result<()> {
+        let (clipped_xy, clipped_size) = match self.clip_rect(xy, size) {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+        let x1y1 = self.clamp_xy(clipped_xy);
+        let x2y2 = match self.clip_x2y2(clipped_xy, clipped_size) {
+            Some(x2y2) => x2y2,
+            None => return Ok(()),
+        };
+
+        // Colors are computed relative to the unclipped rectangle so that the gradient does not
+        // shift when only part of it is visible.
+        let steps = match direction {
+            GradientDir::Horizontal => usize::from(size.width),
+            GradientDir::Vertical => usize::from(size.height),
+        };
+        let origin = match direction {
+            GradientDir::Horizontal => i32::from(xy.x),
+            GradientDir::Vertical => i32::from(xy.y),
+        };
+        let color_at = |pos: usize| {
+            let i = usize::try_from(pos as i32 - origin).expect("Position must be within the rect");
+            drawing::gradient_color(from, to, i, steps)
+        };
+
+        let rowlen = (x2y2.x - x1y1.x + 1) * self.stride;
+        let mut data = LcdSize::between(x1y1, x2y2).new_buffer(self.stride);
+        for y in x1y1.y..(x2y2.y + 1) {
+            self.row_buffer.clear();
+            match direction {
+                GradientDir::Horizontal => {
+                    for x in x1y1.x..(x2y2.x + 1) {
+                        self.lcd.encode_into(color_at(x), &mut self.row_buffer);
+                    }
+                }
+                GradientDir::Vertical => {
+                    let pixel = self.lcd.encode(color_at(y));
+                    for _ in x1y1.x..(x2y2.x + 1) {
+                        self.row_buffer.extend_from_slice(pixel.as_slice());
+                    }
+                }
+            }
+            debug_assert_eq!(rowlen, self.row_buffer.len());
+
+            let offset = self.fb_addr(x1y1.x, y);
+            self.fb[offset..offset + rowlen].copy_from_slice(&self.row_buffer);
+            if self.sync {
+                data.extend(&self.row_buffer);
+            }
+        }
+
+        if self.sync {
+            self.lcd.set_data(x1y1, x2y2, &data)
+        } else {
+            self.damage(x1y1, x2y2);
+            Ok(())
+        }
+    }
+
     fn draw_rect_filled(&mut self, xy: PixelsXY, size: SizeInPixels) -> io::Result<()> {
+        let (xy, size) = match self.clip_rect(xy, size) {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+
         let x1y1 = self.clamp_xy(xy);
         let x2y2 = self.clip_x2y2(xy, size);
         match x2y2 {
-            Some(x2y2) => self.fill(x1y1, x2y2),
+            Some(x2y2) => self.draw_area(x1y1, x2y2),
             _ => Ok(()),
         }
     }
@@ -18,7 +18,7 @@
 use super::testutils::*;
 use super::*;
 use crate::console::graphics::RasterOps;
-use crate::console::{CharsXY, PixelsXY, SizeInPixels};
+use crate::console::{CharsXY, FontId, GradientDir, PixelsXY, SizeInPixels};
 use crate::gfx::lcd::fonts::{FONT_16X16, FONT_5X8};
 
 #[test]
@@ -193,6 +193,67 @@ fn test_force_present_canvas_damage() {
         .check();
 }
 
+#[test]
+fn test_take_damage_no_damage() {
+    Tester::new(size(10, 12)).op(|l| assert!(l.take_damage().is_none())).check();
+}
+
+#[test]
+fn test_take_damage() {
+    Tester::new(size(10, 12))
+        .op(|l| l.set_sync(false))
+        .op(|l| l.set_draw_color((120, 40, 180)))
+        .op(|l| l.fill(xy(2, 3), xy(3, 3)).unwrap())
+        .op(|l| {
+            let (x1y1, x2y2, data) = l.take_damage().unwrap();
+            assert_eq!((xy(2, 3), xy(3, 3)), (x1y1, x2y2));
+            assert_eq!(vec![120, 40, 180, 120, 40, 180], data);
+        })
+        .op(|l| l.force_present_canvas().unwrap())
+        .expect_pixel(xy(2, 3), (120, 40, 180))
+        .expect_pixel(xy(3, 3), (120, 40, 180))
+        .check();
+}
+
+#[test]
+fn test_restore_damage() {
+    Tester::new(size(10, 12))
+        .op(|l| l.set_sync(false))
+        .op(|l| l.set_draw_color((120, 40, 180)))
+        .op(|l| l.fill(xy(2, 3), xy(3, 3)).unwrap())
+        .op(|l| {
+            let (x1y1, x2y2, _data) = l.take_damage().unwrap();
+            l.restore_damage(x1y1, x2y2);
+        })
+        .op(|l| l.fill(xy(5, 1), xy(5, 1)).unwrap())
+        .op(|l| {
+            let (x1y1, x2y2, _data) = l.take_damage().unwrap();
+            assert_eq!((xy(2, 1), xy(5, 3)), (x1y1, x2y2));
+            assert!(l.take_damage().is_none());
+        })
+        .ignore_pixels()
+        .check();
+}
+
+#[test]
+fn test_frame_buffer() {
+    Tester::new(size(10, 12))
+        .op(|l| l.set_sync(false))
+        .op(|l| l.set_draw_color((120, 40, 180)))
+        .op(|l| l.fill(xy(2, 3), xy(2, 3)).unwrap())
+        .op(|l| {
+            let (size, stride) = l.frame_dimensions();
+            assert_eq!((10, 12, 3), (size.width, size.height, stride));
+            let fb = l.frame_buffer();
+            assert_eq!(size.width * size.height * stride, fb.len());
+            let offset = (3 * size.width + 2) * stride;
+            assert_eq!(&[120, 40, 180], &fb[offset..offset + stride]);
+        })
+        .ignore_pixels()
+        .expect_damage(xy(2, 3), xy(2, 3))
+        .check();
+}
+
 #[test]
 fn test_get_info() {
     let lcd = BufferedLcd::new(LcdRecorder::new(size(100, 200)), &FONT_5X8);
@@ -202,6 +263,22 @@ fn test_get_info() {
     assert_eq!(info.size_chars, CharsXY { x: 20, y: 25 });
 }
 
+#[test]
+fn test_set_font() {
+    let mut lcd = BufferedLcd::new(LcdRecorder::new(size(100, 200)), &FONT_5X8);
+
+    lcd.set_font(FontId::Font16x16).unwrap();
+    let info = lcd.get_info();
+    assert_eq!(info.size_pixels, SizeInPixels::new(100, 200));
+    assert_eq!(info.glyph_size, SizeInPixels::new(16, 16));
+    assert_eq!(info.size_chars, CharsXY { x: 6, y: 12 });
+
+    lcd.set_font(FontId::Font5x8).unwrap();
+    let info = lcd.get_info();
+    assert_eq!(info.glyph_size, SizeInPixels::new(5, 8));
+    assert_eq!(info.size_chars, CharsXY { x: 20, y: 25 });
+}
+
 #[test]
 fn test_clear() {
     Tester::new(size(2, 3))
@@ -783,6 +860,44 @@ fn test_draw_pixel_out_of_bounds() {
         .check();
 }
 
+#[test]
+fn test_draw_pixels_sync_flushes_once() {
+    Tester::new(size(20, 30))
+        .op(|l| l.set_draw_color((50, 51, 52)))
+        .op(|l| {
+            l.draw_pixels(&[PixelsXY::new(4, 5), PixelsXY::new(-1, 3), PixelsXY::new(5, 6)])
+                .unwrap()
+        })
+        .expect_pixel(xy(4, 5), (50, 51, 52))
+        .expect_pixel(xy(5, 6), (50, 51, 52))
+        .expect_op(
+            "set_data: from=(4, 5), to=(5, 6), data=[50, 51, 52, 0, 0, 0, 0, 0, 0, 50, 51, 52]",
+        )
+        .check();
+}
+
+#[test]
+fn test_draw_pixels_no_sync() {
+    Tester::new(size(20, 30))
+        .op(|l| l.set_draw_color((50, 51, 52)))
+        .op(|l| {
+            l.set_sync(false);
+            l.draw_pixels(&[PixelsXY::new(10, 2), PixelsXY::new(3, 7)]).unwrap();
+        })
+        .expect_damage(xy(3, 2), xy(10, 7))
+        .expect_pixel(xy(10, 2), (50, 51, 52))
+        .expect_pixel(xy(3, 7), (50, 51, 52))
+        .check();
+}
+
+#[test]
+fn test_draw_pixels_all_out_of_bounds() {
+    Tester::new(size(20, 30))
+        .op(|l| l.set_draw_color((50, 51, 52)))
+        .op(|l| l.draw_pixels(&[PixelsXY::new(-5, 10), PixelsXY::new(20, 30)]).unwrap())
+        .check();
+}
+
 #[test]
 fn test_draw_rect_sync() {
     Tester::new(size(20, 30))
@@ -928,3 +1043,129 @@ fn test_draw_rect_filled_clip() {
         .expect_op("set_data: from=(0, 28), to=(0, 29), data=[50, 51, 52, 50, 51, 52]")
         .check();
 }
+
+#[test]
+fn test_set_clip_draw_line() {
+    Tester::new(size(20, 30))
+        .op(|l| l.set_draw_color((50, 51, 52)))
+        .op(|l| {
+            l.set_sync(false);
+            l.set_clip(Some((PixelsXY::new(5, 0), SizeInPixels::new(4, 30)))).unwrap();
+            l.draw_line(PixelsXY::new(2, 10), PixelsXY::new(12, 10)).unwrap()
+        })
+        .expect_damage(xy(5, 10), xy(8, 10))
+        .expect_pixel(xy(5, 10), (50, 51, 52))
+        .expect_pixel(xy(6, 10), (50, 51, 52))
+        .expect_pixel(xy(7, 10), (50, 51, 52))
+        .expect_pixel(xy(8, 10), (50, 51, 52))
+        .check();
+}
+
+#[test]
+fn test_set_clip_draw_rect_filled() {
+    Tester::new(size(20, 30))
+        .op(|l| {
+            l.set_draw_color((50, 51, 52));
+            l.set_clip(Some((PixelsXY::new(1, 1), SizeInPixels::new(2, 2)))).unwrap();
+            l.draw_rect_filled(PixelsXY::new(0, 0), SizeInPixels::new(10, 10)).unwrap()
+        })
+        .expect_pixel(xy(1, 1), (50, 51, 52))
+        .expect_pixel(xy(1, 2), (50, 51, 52))
+        .expect_pixel(xy(2, 1), (50, 51, 52))
+        .expect_pixel(xy(2, 2), (50, 51, 52))
+        .expect_op("set_data: from=(1, 1), to=(2, 2), data=[50, 51, 52, 50, 51, 52, 50, 51, 52, 50, 51, 52]")
+        .check();
+}
+
+#[test]
+fn test_set_clip_draw_outside() {
+    Tester::new(size(20, 30))
+        .op(|l| {
+            l.set_draw_color((50, 51, 52));
+            l.set_clip(Some((PixelsXY::new(10, 10), SizeInPixels::new(5, 5)))).unwrap();
+            l.draw_pixel(PixelsXY::new(9, 10)).unwrap();
+            l.draw_pixel(PixelsXY::new(15, 10)).unwrap();
+            l.draw_rect_filled(PixelsXY::new(0, 0), SizeInPixels::new(10, 10)).unwrap()
+        })
+        .check();
+}
+
+#[test]
+fn test_set_clip_none_restores() {
+    Tester::new(size(20, 30))
+        .op(|l| {
+            l.set_draw_color((50, 51, 52));
+            l.set_clip(Some((PixelsXY::new(10, 10), SizeInPixels::new(5, 5)))).unwrap();
+            l.set_clip(None).unwrap();
+            l.draw_pixel(PixelsXY::new(0, 0)).unwrap();
+        })
+        .expect_pixel(xy(0, 0), (50, 51, 52))
+        .expect_op("set_data: from=(0, 0), to=(0, 0), data=[50, 51, 52]")
+        .check();
+}
+
+#[test]
+fn test_draw_gradient_rect_horizontal_sync() {
+    Tester::new(size(20, 30))
+        .op(|l| {
+            l.draw_gradient_rect(
+                PixelsXY::new(2, 3),
+                SizeInPixels::new(5, 1),
+                (0, 20, 30),
+                (200, 100, 0),
+                GradientDir::Horizontal,
+            )
+            .unwrap()
+        })
+        .expect_pixel(xy(2, 3), (0, 20, 30))
+        .expect_pixel(xy(3, 3), (50, 40, 23))
+        .expect_pixel(xy(4, 3), (100, 60, 15))
+        .expect_pixel(xy(5, 3), (150, 80, 8))
+        .expect_pixel(xy(6, 3), (200, 100, 0))
+        .expect_op("set_data: from=(2, 3), to=(6, 3), data=[0, 20, 30, 50, 40, 23, 100, 60, 15, 150, 80, 8, 200, 100, 0]")
+        .check();
+}
+
+#[test]
+fn test_draw_gradient_rect_vertical_no_sync() {
+    Tester::new(size(20, 30))
+        .op(|l| {
+            l.set_sync(false);
+            l.draw_gradient_rect(
+                PixelsXY::new(1, 1),
+                SizeInPixels::new(2, 3),
+                (10, 10, 10),
+                (30, 50, 70),
+                GradientDir::Vertical,
+            )
+            .unwrap()
+        })
+        .expect_damage(xy(1, 1), xy(2, 3))
+        .expect_pixel(xy(1, 1), (10, 10, 10))
+        .expect_pixel(xy(2, 1), (10, 10, 10))
+        .expect_pixel(xy(1, 2), (20, 30, 40))
+        .expect_pixel(xy(2, 2), (20, 30, 40))
+        .expect_pixel(xy(1, 3), (30, 50, 70))
+        .expect_pixel(xy(2, 3), (30, 50, 70))
+        .check();
+}
+
+#[test]
+fn test_draw_gradient_rect_clip() {
+    Tester::new(size(20, 30))
+        .op(|l| {
+            l.draw_gradient_rect(
+                PixelsXY::new(-2, 0),
+                SizeInPixels::new(5, 1),
+                (0, 20, 30),
+                (200, 100, 0),
+                GradientDir::Horizontal,
+            )
+            .unwrap()
+        })
+        .expect_pixel(xy(0, 0), (100, 60, 15))
+        .expect_pixel(xy(1, 0), (150, 80, 8))
+        .expect_pixel(xy(2, 0), (200, 100, 0))
+        .expect_op("set_data: from=(0, 0), to=(2, 0), data=[100, 60, 15, 150, 80, 8, 200, 100, 0]")
+        .check();
+}
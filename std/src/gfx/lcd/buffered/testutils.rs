@@ -17,23 +17,11 @@
 
 use crate::console::RGB;
 use crate::gfx::lcd::fonts::Font;
-use crate::gfx::lcd::{AsByteSlice, BufferedLcd, Lcd, LcdSize, LcdXY};
+use crate::gfx::lcd::{BufferedLcd, Lcd, LcdSize, LcdXY, RGB888Pixel};
 use std::io;
 
-/// Data for one pixel encoded as RGB888.
-#[cfg(test)]
-#[derive(Clone, Copy)]
-pub(super) struct RGB888Pixel(pub(super) [u8; 3]);
-
-#[cfg(test)]
-impl AsByteSlice for RGB888Pixel {
-    fn as_slice(&self) -> &[u8] {
-        &self.0
-    }
-}
-
 /// Syntactic sugar to instantiate a coordinate in the LCD space.
-pub(super) fn xy(x: usize, y: usize) -> LcdXY {
+pub(in crate::gfx::lcd) fn xy(x: usize, y: usize) -> LcdXY {
     LcdXY { x, y }
 }
 
@@ -15,6 +15,7 @@
 
 //! Support for bitmap fonts directly rendered onto an LCD.
 
+use crate::console::FontId;
 use crate::gfx::lcd::LcdSize;
 use std::collections::HashMap;
 
@@ -57,6 +58,14 @@ impl Font {
     }
 }
 
+/// Returns the font identified by `id`.
+pub fn font_by_id(id: FontId) -> &'static Font {
+    match id {
+        FontId::Font5x8 => &FONT_5X8,
+        FontId::Font16x16 => &FONT_16X16,
+    }
+}
+
 /// Registry of all available fonts.
 pub type Fonts = HashMap<&'static str, &'static Font>;
 
@@ -0,0 +1,145 @@
+// EndBASIC
+// Copyright 2024 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Headless LCD with 8-bit grayscale pixels.
+
+use crate::console::RGB;
+use crate::gfx::lcd::{AsByteSlice, Lcd, LcdSize, LcdXY};
+use std::convert::TryFrom;
+use std::io;
+
+/// Data for one pixel encoded as an 8-bit luminance value.
+#[derive(Clone, Copy)]
+pub struct GrayscalePixel(pub [u8; 1]);
+
+impl AsByteSlice for GrayscalePixel {
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Computes the luminance of `rgb` using the Rec. 601 weights.
+pub fn luminance(rgb: RGB) -> u8 {
+    let y = (299 * u32::from(rgb.0) + 587 * u32::from(rgb.1) + 114 * u32::from(rgb.2) + 500) / 1000;
+    u8::try_from(y).expect("Luminance must fit in a byte")
+}
+
+/// LCD with one luminance byte per pixel that keeps its pixels in memory.
+///
+/// This shows how to back a `BufferedLcd` with a panel that does not use RGB pixels, such as a
+/// monochrome OLED: drivers for those devices can start from this type and replace `set_data` with
+/// the transfer of the luminance values to the device, possibly after reducing their depth.
+pub struct GrayscaleLcd {
+    size: LcdSize,
+    pixels: Vec<u8>,
+}
+
+impl GrayscaleLcd {
+    /// Creates a new grayscale LCD of `size` pixels with all pixels set to black.
+    pub fn new(size: LcdSize) -> Self {
+        Self { size, pixels: vec![0; size.width * size.height] }
+    }
+
+    /// Returns the luminance of the pixel at `xy`.
+    ///
+    /// Panics if `xy` is outside of the LCD.
+    pub fn luminance_at(&self, xy: LcdXY) -> u8 {
+        assert!(xy.x < self.size.width && xy.y < self.size.height, "Pixel out of range");
+        self.pixels[xy.y * self.size.width + xy.x]
+    }
+
+    /// Returns the contents of the LCD as a sequence of luminance values in row-major order.
+    pub fn as_luminance_buffer(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+impl Lcd for GrayscaleLcd {
+    type Pixel = GrayscalePixel;
+
+    fn info(&self) -> (LcdSize, usize) {
+        (self.size, 1)
+    }
+
+    fn encode(&self, rgb: RGB) -> Self::Pixel {
+        GrayscalePixel([luminance(rgb)])
+    }
+
+    fn set_data(&mut self, x1y1: LcdXY, x2y2: LcdXY, data: &[u8]) -> io::Result<()> {
+        let size = self.size.check_window(x1y1, x2y2, data, 1)?;
+        for (i, row) in data.chunks(size.width).enumerate() {
+            let offset = (x1y1.y + i) * self.size.width + x1y1.x;
+            self.pixels[offset..offset + size.width].copy_from_slice(row);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::graphics::RasterOps;
+    use crate::console::{FontId, PixelsXY, SizeInPixels};
+    use crate::gfx::lcd::buffered::testutils::xy;
+    use crate::gfx::lcd::fonts::font_by_id;
+    use crate::gfx::lcd::BufferedLcd;
+
+    #[test]
+    fn test_luminance() {
+        assert_eq!(0, luminance((0, 0, 0)));
+        assert_eq!(255, luminance((255, 255, 255)));
+        assert_eq!(128, luminance((128, 128, 128)));
+        assert_eq!(76, luminance((255, 0, 0)));
+        assert_eq!(150, luminance((0, 255, 0)));
+        assert_eq!(29, luminance((0, 0, 255)));
+        assert_eq!(226, luminance((255, 255, 0)));
+    }
+
+    #[test]
+    fn test_grayscale_lcd_info() {
+        let lcd = GrayscaleLcd::new(LcdSize { width: 4, height: 3 });
+        assert_eq!((LcdSize { width: 4, height: 3 }, 1), lcd.info());
+    }
+
+    #[test]
+    fn test_grayscale_lcd_set_data() {
+        let mut lcd = GrayscaleLcd::new(LcdSize { width: 4, height: 3 });
+        lcd.set_data(xy(1, 1), xy(2, 2), &[1, 2, 3, 4]).unwrap();
+
+        assert_eq!(0, lcd.luminance_at(xy(0, 0)));
+        assert_eq!(1, lcd.luminance_at(xy(1, 1)));
+        assert_eq!(2, lcd.luminance_at(xy(2, 1)));
+        assert_eq!(3, lcd.luminance_at(xy(1, 2)));
+        assert_eq!(4, lcd.luminance_at(xy(2, 2)));
+        assert_eq!(&[0, 0, 0, 0, 0, 1, 2, 0, 0, 3, 4, 0], lcd.as_luminance_buffer());
+    }
+
+    #[test]
+    fn test_grayscale_lcd_buffered_fill() {
+        let mut buffered = BufferedLcd::new(
+            GrayscaleLcd::new(LcdSize { width: 20, height: 10 }),
+            font_by_id(FontId::Font5x8),
+        );
+        buffered.set_draw_color((255, 0, 0));
+        buffered.draw_rect_filled(PixelsXY::new(2, 3), SizeInPixels::new(5, 4)).unwrap();
+
+        let lcd = buffered.lcd();
+        for corner in [xy(2, 3), xy(6, 3), xy(2, 6), xy(6, 6)] {
+            assert_eq!(76, lcd.luminance_at(corner));
+        }
+        assert_eq!(0, lcd.luminance_at(xy(1, 3)));
+        assert_eq!(0, lcd.luminance_at(xy(7, 7)));
+    }
+}
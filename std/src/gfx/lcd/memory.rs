@@ -0,0 +1,187 @@
+// EndBASIC
+// Copyright 2024 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Headless LCD backed by an in-memory framebuffer.
+
+use crate::console::RGB;
+use crate::gfx::lcd::{Lcd, LcdSize, LcdXY, RGB888Pixel};
+use std::io;
+
+/// LCD that keeps its pixels in memory instead of sending them to a device.
+///
+/// This is useful to run graphics programs without real hardware, such as in tests, and to
+/// inspect the rendered pixels afterwards.  Wrap it in a `BufferedLcd` to use it as the raster
+/// backend of a `GraphicsConsole`.
+pub struct MemoryLcd {
+    size: LcdSize,
+    pixels: Vec<u8>,
+}
+
+impl MemoryLcd {
+    /// Creates a new in-memory LCD of `size` pixels with all pixels set to black.
+    pub fn new(size: LcdSize) -> Self {
+        Self { size, pixels: vec![0; size.width * size.height * 3] }
+    }
+
+    /// Returns the color of the pixel at `xy`.
+    ///
+    /// Panics if `xy` is outside of the LCD.
+    pub fn pixel_at(&self, xy: LcdXY) -> RGB {
+        assert!(xy.x < self.size.width && xy.y < self.size.height, "Pixel out of range");
+        let offset = (xy.y * self.size.width + xy.x) * 3;
+        (self.pixels[offset], self.pixels[offset + 1], self.pixels[offset + 2])
+    }
+
+    /// Returns the contents of the LCD as a sequence of RGB888 pixels in row-major order.
+    pub fn as_rgb_buffer(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+impl Lcd for MemoryLcd {
+    type Pixel = RGB888Pixel;
+
+    fn info(&self) -> (LcdSize, usize) {
+        (self.size, 3)
+    }
+
+    fn encode(&self, rgb: RGB) -> Self::Pixel {
+        RGB888Pixel([rgb.0, rgb.1, rgb.2])
+    }
+
+    fn set_data(&mut self, x1y1: LcdXY, x2y2: LcdXY, data: &[u8]) -> io::Result<()> {
+        let size = self.size.check_window(x1y1, x2y2, data, 3)?;
+        let row_len = size.width * 3;
+        for (i, row) in data.chunks(row_len).enumerate() {
+            let offset = ((x1y1.y + i) * self.size.width + x1y1.x) * 3;
+            self.pixels[offset..offset + row_len].copy_from_slice(row);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::graphics::RasterOps;
+    use crate::console::{FontId, PixelsXY, SizeInPixels};
+    use crate::gfx::lcd::buffered::testutils::xy;
+    use crate::gfx::lcd::fonts::font_by_id;
+    use crate::gfx::lcd::BufferedLcd;
+
+    #[test]
+    fn test_memory_lcd_set_data() {
+        let mut lcd = MemoryLcd::new(LcdSize { width: 4, height: 3 });
+        lcd.set_data(xy(1, 1), xy(2, 2), &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]).unwrap();
+
+        assert_eq!((0, 0, 0), lcd.pixel_at(xy(0, 0)));
+        assert_eq!((1, 2, 3), lcd.pixel_at(xy(1, 1)));
+        assert_eq!((4, 5, 6), lcd.pixel_at(xy(2, 1)));
+        assert_eq!((7, 8, 9), lcd.pixel_at(xy(1, 2)));
+        assert_eq!((10, 11, 12), lcd.pixel_at(xy(2, 2)));
+        assert_eq!((0, 0, 0), lcd.pixel_at(xy(3, 2)));
+        assert_eq!(4 * 3 * 3, lcd.as_rgb_buffer().len());
+        assert_eq!(&[1, 2, 3], &lcd.as_rgb_buffer()[15..18]);
+    }
+
+    #[test]
+    fn test_memory_lcd_set_data_errors() {
+        let mut lcd = MemoryLcd::new(LcdSize { width: 4, height: 3 });
+        assert_eq!(
+            "Window (3, 0) to (4, 0) does not fit in the 4x3 LCD",
+            lcd.set_data(xy(3, 0), xy(4, 0), &[0; 6]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Expected 6 bytes of pixel data",
+            lcd.set_data(xy(0, 0), xy(1, 0), &[0; 3]).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_memory_lcd_draw_rect_corners() {
+        let mut buffered = BufferedLcd::new(
+            MemoryLcd::new(LcdSize { width: 20, height: 10 }),
+            font_by_id(FontId::Font5x8),
+        );
+        buffered.set_draw_color((10, 20, 30));
+        buffered.draw_rect(PixelsXY::new(2, 3), SizeInPixels::new(5, 4)).unwrap();
+
+        let lcd = buffered.lcd();
+        for corner in [xy(2, 3), xy(6, 3), xy(2, 6), xy(6, 6)] {
+            assert_eq!((10, 20, 30), lcd.pixel_at(corner));
+        }
+        assert_eq!((0, 0, 0), lcd.pixel_at(xy(4, 5)));
+        assert_eq!((0, 0, 0), lcd.pixel_at(xy(7, 7)));
+    }
+
+    #[test]
+    fn test_memory_lcd_draw_rect_filled_corners() {
+        let mut buffered = BufferedLcd::new(
+            MemoryLcd::new(LcdSize { width: 20, height: 10 }),
+            font_by_id(FontId::Font5x8),
+        );
+        buffered.set_draw_color((200, 100, 50));
+        buffered.draw_rect_filled(PixelsXY::new(10, 0), SizeInPixels::new(10, 10)).unwrap();
+
+        let lcd = buffered.lcd();
+        for corner in [xy(10, 0), xy(19, 0), xy(10, 9), xy(19, 9), xy(15, 5)] {
+            assert_eq!((200, 100, 50), lcd.pixel_at(corner));
+        }
+        assert_eq!((0, 0, 0), lcd.pixel_at(xy(9, 0)));
+    }
+
+    #[test]
+    fn test_memory_lcd_draw_rounded_rect_corners() {
+        let mut buffered = BufferedLcd::new(
+            MemoryLcd::new(LcdSize { width: 20, height: 10 }),
+            font_by_id(FontId::Font5x8),
+        );
+        buffered.set_draw_color((10, 20, 30));
+        buffered.draw_rounded_rect(PixelsXY::new(2, 1), SizeInPixels::new(12, 8), 4).unwrap();
+
+        let lcd = buffered.lcd();
+        for corner in [xy(2, 1), xy(13, 1), xy(2, 8), xy(13, 8)] {
+            assert_eq!((0, 0, 0), lcd.pixel_at(corner));
+        }
+        for arc in [xy(2, 2), xy(3, 1), xy(12, 1), xy(13, 2), xy(2, 7), xy(13, 7)] {
+            assert_eq!((10, 20, 30), lcd.pixel_at(arc));
+        }
+        for edge in [xy(7, 1), xy(13, 4), xy(7, 8), xy(2, 5)] {
+            assert_eq!((10, 20, 30), lcd.pixel_at(edge));
+        }
+        assert_eq!((0, 0, 0), lcd.pixel_at(xy(3, 2)));
+        assert_eq!((0, 0, 0), lcd.pixel_at(xy(7, 4)));
+    }
+
+    #[test]
+    fn test_memory_lcd_draw_rounded_rect_filled_corners() {
+        let mut buffered = BufferedLcd::new(
+            MemoryLcd::new(LcdSize { width: 20, height: 10 }),
+            font_by_id(FontId::Font5x8),
+        );
+        buffered.set_draw_color((200, 100, 50));
+        buffered
+            .draw_rounded_rect_filled(PixelsXY::new(2, 1), SizeInPixels::new(12, 8), 4)
+            .unwrap();
+
+        let lcd = buffered.lcd();
+        for corner in [xy(2, 1), xy(13, 1), xy(2, 8), xy(13, 8)] {
+            assert_eq!((0, 0, 0), lcd.pixel_at(corner));
+        }
+        for inside in [xy(3, 2), xy(12, 2), xy(3, 7), xy(12, 7), xy(7, 4), xy(2, 4), xy(3, 1)] {
+            assert_eq!((200, 100, 50), lcd.pixel_at(inside));
+        }
+    }
+}
@@ -21,8 +21,14 @@ use std::io;
 
 mod buffered;
 pub mod fonts;
+mod grayscale;
+mod memory;
+mod paletted;
 
 pub use buffered::BufferedLcd;
+pub use grayscale::{luminance, GrayscaleLcd, GrayscalePixel};
+pub use memory::MemoryLcd;
+pub use paletted::{PaletteIndex, PalettedLcd};
 
 /// Trait to convert a pixel to a sequence of bytes.
 pub trait AsByteSlice {
@@ -40,6 +46,16 @@ impl AsByteSlice for RGB565Pixel {
     }
 }
 
+/// Data for one pixel encoded as RGB888.
+#[derive(Clone, Copy)]
+pub struct RGB888Pixel(pub [u8; 3]);
+
+impl AsByteSlice for RGB888Pixel {
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 /// Primitives that an LCD must define.
 pub trait Lcd {
     /// The primitive type of the pixel data.
@@ -51,6 +67,11 @@ pub trait Lcd {
     /// Encodes an `rgb` color into the `Pixel` expected by the LCD.
     fn encode(&self, rgb: RGB) -> Self::Pixel;
 
+    /// Encodes an `rgb` color and appends the resulting pixel data to `out`.
+    fn encode_into(&self, rgb: RGB, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.encode(rgb).as_slice());
+    }
+
     /// Fills the area expressed by `x1y1` to `x2y2` by the pixel `data`.  The length of `data`
     /// should be the size of the window in pixels multiplied by the `Pixel` size.
     fn set_data(&mut self, x1y1: LcdXY, x2y2: LcdXY, data: &[u8]) -> io::Result<()>;
@@ -86,6 +107,36 @@ impl LcdSize {
         Self { width: x2y2.x - x1y1.x + 1, height: x2y2.y - x1y1.y + 1 }
     }
 
+    /// Checks that the window `x1y1` to `x2y2` fits in an LCD of this size and that `data` holds
+    /// exactly its pixels for the given `stride`.  Returns the size of the window.
+    fn check_window(
+        &self,
+        x1y1: LcdXY,
+        x2y2: LcdXY,
+        data: &[u8],
+        stride: usize,
+    ) -> io::Result<LcdSize> {
+        if x1y1.x > x2y2.x || x1y1.y > x2y2.y || x2y2.x >= self.width || x2y2.y >= self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Window ({}, {}) to ({}, {}) does not fit in the {}x{} LCD",
+                    x1y1.x, x1y1.y, x2y2.x, x2y2.y, self.width, self.height
+                ),
+            ));
+        }
+
+        let size = LcdSize::between(x1y1, x2y2);
+        let len = size.width * size.height * stride;
+        if data.len() != len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Expected {} bytes of pixel data", len),
+            ));
+        }
+        Ok(size)
+    }
+
     /// Creates a new buffer with enough capacity to hold the content of this LCD size for the given
     /// `stride``.  The returned buffer is of zero size.
     fn new_buffer(&self, stride: usize) -> Vec<u8> {
@@ -127,6 +178,34 @@ mod tests {
         LcdSize { width, height }
     }
 
+    /// LCD with 16-bit pixels that only provides the required encoding primitives.
+    struct DefaultEncodingLcd;
+
+    impl Lcd for DefaultEncodingLcd {
+        type Pixel = RGB565Pixel;
+
+        fn info(&self) -> (LcdSize, usize) {
+            (size(10, 5), 2)
+        }
+
+        fn encode(&self, rgb: RGB) -> Self::Pixel {
+            RGB565Pixel([rgb.0, rgb.2])
+        }
+
+        fn set_data(&mut self, _x1y1: LcdXY, _x2y2: LcdXY, _data: &[u8]) -> io::Result<()> {
+            unreachable!("Not needed in tests");
+        }
+    }
+
+    #[test]
+    fn test_lcd_encode_into_default() {
+        let lcd = DefaultEncodingLcd;
+        let mut out = vec![9];
+        lcd.encode_into((1, 2, 3), &mut out);
+        lcd.encode_into((4, 5, 6), &mut out);
+        assert_eq!(vec![9, 1, 3, 4, 6], out);
+    }
+
     #[test]
     fn test_lcdsize_between_one_pixel() {
         assert_eq!(size(1, 1), LcdSize::between(xy(15, 16), xy(15, 16)));
@@ -143,6 +222,27 @@ mod tests {
         assert_eq!(10 * 20 * 3, buffer.capacity());
     }
 
+    #[test]
+    fn test_lcdsize_check_window() {
+        let lcd_size = size(4, 3);
+        assert_eq!(size(2, 2), lcd_size.check_window(xy(1, 1), xy(2, 2), &[0; 8], 2).unwrap());
+        assert_eq!(size(4, 3), lcd_size.check_window(xy(0, 0), xy(3, 2), &[0; 12], 1).unwrap());
+
+        for (x1y1, x2y2) in [(xy(3, 0), xy(4, 0)), (xy(0, 1), xy(0, 3)), (xy(2, 0), xy(1, 0))] {
+            assert_eq!(
+                format!(
+                    "Window ({}, {}) to ({}, {}) does not fit in the 4x3 LCD",
+                    x1y1.x, x1y1.y, x2y2.x, x2y2.y
+                ),
+                lcd_size.check_window(x1y1, x2y2, &[], 1).unwrap_err().to_string()
+            );
+        }
+        assert_eq!(
+            "Expected 6 bytes of pixel data",
+            lcd_size.check_window(xy(0, 0), xy(1, 0), &[0; 2], 3).unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn test_to_xy_size_one_pixel() {
         assert_eq!(
@@ -0,0 +1,199 @@
+// EndBASIC
+// Copyright 2024 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Palette-indexed wrapper for LCDs.
+
+use crate::console::{ansi_color_to_rgb, RGB};
+use crate::gfx::lcd::{AsByteSlice, Lcd, LcdSize, LcdXY};
+use std::convert::TryFrom;
+use std::io;
+
+/// Number of entries in the palette of a `PalettedLcd`.
+const PALETTE_SIZE: usize = 256;
+
+/// Data for one pixel encoded as an index into the palette of a `PalettedLcd`.
+#[derive(Clone, Copy)]
+pub struct PaletteIndex(pub [u8; 1]);
+
+impl AsByteSlice for PaletteIndex {
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Wraps an `L` LCD so that pixel data is expressed as 8-bit indices into a 256-entry palette.
+///
+/// Wrapping an LCD in this type and then wrapping the result in a `BufferedLcd` makes the
+/// framebuffer store one byte per pixel instead of the native pixel size of the LCD, which halves
+/// the memory footprint of 16-bit panels.  The price is that colors are restricted to those in
+/// the palette and that pixel data must be translated to the native format when it is sent to
+/// the LCD.
+///
+/// Mapping an `RGB` color to a palette index involves a linear scan of the whole palette, so
+/// `encode` costs 256 distance computations per call.  Drawing primitives only encode a color
+/// when the drawing color changes, so this is not in the hot path.  Translating indices to native
+/// pixels in `set_data` is a table lookup per pixel.
+///
+/// The palette starts as the standard 256-color ANSI palette.
+pub struct PalettedLcd<L: Lcd> {
+    lcd: L,
+    palette: Vec<RGB>,
+    native: Vec<L::Pixel>,
+    buffer: Vec<u8>,
+}
+
+impl<L> PalettedLcd<L>
+where
+    L: Lcd,
+{
+    /// Creates a new paletted LCD backed by `lcd`.
+    pub fn new(lcd: L) -> Self {
+        let palette: Vec<RGB> = (0..PALETTE_SIZE)
+            .map(|i| ansi_color_to_rgb(u8::try_from(i).expect("Palette index must fit")))
+            .collect();
+        let native = palette.iter().map(|rgb| lcd.encode(*rgb)).collect();
+        Self { lcd, palette, native, buffer: vec![] }
+    }
+
+    /// Returns the color at palette `index`.
+    pub fn palette_entry(&self, index: u8) -> RGB {
+        self.palette[usize::from(index)]
+    }
+
+    /// Sets the palette entry at `index` to `rgb`.
+    ///
+    /// Pixels already sent to the LCD are not updated: the new color only applies to subsequent
+    /// `set_data` calls.
+    pub fn set_palette_entry(&mut self, index: u8, rgb: RGB) {
+        self.palette[usize::from(index)] = rgb;
+        self.native[usize::from(index)] = self.lcd.encode(rgb);
+    }
+
+    /// Finds the palette index whose color is closest to `rgb`.
+    fn nearest(&self, rgb: RGB) -> u8 {
+        let distance = |other: RGB| {
+            let dr = i32::from(rgb.0) - i32::from(other.0);
+            let dg = i32::from(rgb.1) - i32::from(other.1);
+            let db = i32::from(rgb.2) - i32::from(other.2);
+            dr * dr + dg * dg + db * db
+        };
+
+        let mut best = 0;
+        let mut best_distance = i32::MAX;
+        for (i, entry) in self.palette.iter().enumerate() {
+            let d = distance(*entry);
+            if d < best_distance {
+                best = i;
+                best_distance = d;
+                if d == 0 {
+                    break;
+                }
+            }
+        }
+        u8::try_from(best).expect("Palette index must fit")
+    }
+}
+
+impl<L> Lcd for PalettedLcd<L>
+where
+    L: Lcd,
+{
+    type Pixel = PaletteIndex;
+
+    fn info(&self) -> (LcdSize, usize) {
+        let (size, _stride) = self.lcd.info();
+        (size, 1)
+    }
+
+    fn encode(&self, rgb: RGB) -> Self::Pixel {
+        PaletteIndex([self.nearest(rgb)])
+    }
+
+    fn set_data(&mut self, x1y1: LcdXY, x2y2: LcdXY, data: &[u8]) -> io::Result<()> {
+        let (_size, stride) = self.lcd.info();
+        self.buffer.clear();
+        self.buffer.reserve(data.len() * stride);
+        for index in data {
+            self.buffer.extend_from_slice(self.native[usize::from(*index)].as_slice());
+        }
+        self.lcd.set_data(x1y1, x2y2, &self.buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::lcd::RGB565Pixel;
+
+    /// Mock LCD with 16-bit pixels that records the data it receives.
+    struct LcdRecorder {
+        data: Vec<Vec<u8>>,
+    }
+
+    impl Lcd for LcdRecorder {
+        type Pixel = RGB565Pixel;
+
+        fn info(&self) -> (LcdSize, usize) {
+            (LcdSize { width: 10, height: 5 }, 2)
+        }
+
+        fn encode(&self, rgb: RGB) -> Self::Pixel {
+            RGB565Pixel([rgb.0, rgb.1])
+        }
+
+        fn set_data(&mut self, _x1y1: LcdXY, _x2y2: LcdXY, data: &[u8]) -> io::Result<()> {
+            self.data.push(data.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_paletted_info() {
+        let lcd = PalettedLcd::new(LcdRecorder { data: vec![] });
+        let (size, stride) = lcd.info();
+        assert_eq!(LcdSize { width: 10, height: 5 }, size);
+        assert_eq!(1, stride);
+    }
+
+    #[test]
+    fn test_paletted_default_palette() {
+        let lcd = PalettedLcd::new(LcdRecorder { data: vec![] });
+        for i in 0..=255 {
+            assert_eq!(ansi_color_to_rgb(i), lcd.palette_entry(i));
+        }
+    }
+
+    #[test]
+    fn test_paletted_encode_nearest() {
+        let mut lcd = PalettedLcd::new(LcdRecorder { data: vec![] });
+        assert_eq!(0, lcd.encode((0, 0, 0)).0[0]);
+        assert_eq!(15, lcd.encode((255, 255, 255)).0[0]);
+
+        lcd.set_palette_entry(200, (1, 2, 3));
+        assert_eq!(200, lcd.encode((1, 2, 3)).0[0]);
+        assert_eq!(200, lcd.encode((2, 2, 2)).0[0]);
+    }
+
+    #[test]
+    fn test_paletted_set_data_translates() {
+        let mut lcd = PalettedLcd::new(LcdRecorder { data: vec![] });
+        lcd.set_palette_entry(0, (10, 11, 12));
+        lcd.set_palette_entry(7, (20, 21, 22));
+
+        let xy = LcdXY { x: 0, y: 0 };
+        lcd.set_data(xy, LcdXY { x: 2, y: 0 }, &[7, 0, 7]).unwrap();
+        assert_eq!(vec![vec![20, 21, 10, 11, 20, 21]], lcd.lcd.data);
+    }
+}
@@ -18,7 +18,7 @@
 use std::io::Write;
 
 /// Defines the SPI clock polarity and phase.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SpiMode {
     /// CPOL 0, CPHA 0
     Mode0 = 0,
@@ -30,6 +30,15 @@ pub enum SpiMode {
     Mode3 = 3,
 }
 
+/// Defines the level of the slave select line that marks the device as selected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SsPolarity {
+    /// The device is selected when the line is low.
+    ActiveLow,
+    /// The device is selected when the line is high.
+    ActiveHigh,
+}
+
 /// A trait abstracting access to an SPI bus.
 pub trait SpiBus: Write {
     /// Returns the maximum transfer size for the bus.
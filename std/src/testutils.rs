@@ -215,6 +215,10 @@ impl Console for MockConsole {
         self.interactive
     }
 
+    fn supports_graphics(&self) -> bool {
+        self.size_pixels.is_some()
+    }
+
     fn leave_alt(&mut self) -> io::Result<()> {
         self.captured_out.push(CapturedOut::LeaveAlt);
         Ok(())
@@ -253,6 +257,12 @@ impl Console for MockConsole {
         }
     }
 
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        let before = self.golden_in.len();
+        self.golden_in.retain(|k| matches!(k, Key::Eof | Key::Interrupt));
+        Ok(before - self.golden_in.len())
+    }
+
     fn show_cursor(&mut self) -> io::Result<()> {
         self.captured_out.push(CapturedOut::ShowCursor);
         Ok(())
@@ -24,359 +24,1895 @@
 
 use async_channel::{Receiver, Sender, TryRecvError};
 use async_trait::async_trait;
-use crossterm::event::{self, KeyEventKind};
+use crossterm::event::{self, KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::tty::IsTty;
 use crossterm::{cursor, style, terminal, QueueableCommand};
 use endbasic_core::exec::Signal;
 use endbasic_std::console::graphics::InputOps;
 use endbasic_std::console::{
-    get_env_var_as_u16, read_key_from_stdin, remove_control_chars, CharsXY, ClearType, Console, Key,
+    ansi_color_to_16, filter_control_chars, parse_dimension, read_key_from_stdin, CharsXY,
+    ClearType, ColorCapability, Console, Key, PixelsXY, SizeInPixels,
 };
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::VecDeque;
-use std::io::{self, StdoutLock, Write};
+use std::convert::TryFrom;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, StdoutLock, Write};
+#[cfg(unix)]
+use std::os::unix::io::AsFd;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
 
-/// Implementation of the EndBASIC console to interact with stdin and stdout.
-pub struct TerminalConsole {
-    /// Whether stdin and stdout are attached to a TTY.  When this is true, the console is put in
-    /// raw mode for finer-grained control.
-    is_tty: bool,
+mod script;
+pub use script::play_input_script;
+mod shadow;
+use shadow::ShadowScreen;
 
-    /// Current foreground color.
-    fg_color: Option<u8>,
+/// Frequency at which the key handler checks for changes to the raw mode while in raw mode.
+const RAW_MODE_POLL_PERIOD: Duration = Duration::from_millis(100);
 
-    /// Current background color.
-    bg_color: Option<u8>,
+/// Default value for `TerminalConsoleOptions::esc_timeout`.
+const DEFAULT_ESC_TIMEOUT: Duration = Duration::from_millis(50);
 
-    /// Whether the cursor is visible or not.
-    cursor_visible: bool,
+/// Frequency at which the key handler checks if it can resume reading after being paused.
+const INPUT_PAUSE_POLL_PERIOD: Duration = Duration::from_millis(1);
 
-    /// Whether we are in the alternate console or not.
-    alt_active: bool,
+/// Maximum time to wait for the terminal to report the cursor position.
+const CURSOR_POSITION_TIMEOUT: Duration = Duration::from_millis(500);
 
-    /// Whether video syncing is enabled or not.
-    sync_enabled: bool,
+/// Returns true if both stdin and stdout are attached to a TTY.
+///
+/// This is the same check that `TerminalConsole` does at construction time to decide whether to
+/// put the terminal in raw mode or not.
+pub fn stdio_is_tty() -> bool {
+    io::stdin().is_tty() && io::stdout().is_tty()
+}
 
-    /// Channel to receive key presses from the terminal.
-    on_key_rx: Receiver<Key>,
+/// Queries the size of the terminal attached to stdout.
+///
+/// The `LINES` and `COLUMNS` environment variables, if set to positive numbers, override the
+/// corresponding dimensions reported by the terminal.  Variables that are malformed or zero are
+/// ignored as if they were unset.
+pub fn detect_size() -> io::Result<CharsXY> {
+    detect_size_with(|name| env::var(name).ok(), terminal::size)
 }
 
-impl Drop for TerminalConsole {
-    fn drop(&mut self) {
-        if self.is_tty {
-            terminal::disable_raw_mode().unwrap();
+/// Computes the size of the terminal by looking up the `LINES` and `COLUMNS` overrides with
+/// `getenv` and by calling `query` to obtain the columns and lines reported by the terminal for
+/// those dimensions that are not overridden.
+fn detect_size_with<E, Q>(getenv: E, query: Q) -> io::Result<CharsXY>
+where
+    E: Fn(&str) -> Option<String>,
+    Q: FnOnce() -> io::Result<(u16, u16)>,
+{
+    // Must be careful to not query the terminal size if both LINES and COLUMNS are set, because
+    // the query fails when we don't have a PTY and we still need to run under these conditions
+    // for testing purposes.
+    let lines = getenv("LINES").as_deref().and_then(parse_dimension);
+    let columns = getenv("COLUMNS").as_deref().and_then(parse_dimension);
+    let size = match (lines, columns) {
+        (Some(l), Some(c)) => CharsXY::new(c, l),
+        (l, c) => {
+            let (actual_columns, actual_lines) = query()?;
+            CharsXY::new(c.unwrap_or(actual_columns), l.unwrap_or(actual_lines))
         }
+    };
+    Ok(size)
+}
+
+/// Validates that `pos` falls within a console of `size`.
+fn check_locate_pos(pos: CharsXY, size: CharsXY) -> io::Result<()> {
+    if pos.x >= size.x || pos.y >= size.y {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Position {},{} is out of the {}x{} console", pos.x, pos.y, size.x, size.y),
+        ));
     }
+    Ok(())
 }
 
-impl TerminalConsole {
-    /// Creates a new console based on the properties of stdin/stdout.
-    ///
-    /// This spawns a background task to handle console input so this must be run in the context of
-    /// an Tokio runtime.
-    pub fn from_stdio(signals_tx: Sender<Signal>) -> io::Result<Self> {
-        let (terminal, _on_key_tx) = Self::from_stdio_with_injector(signals_tx)?;
-        Ok(terminal)
+/// Computes the size in pixels of `text` when written to a terminal of `window` size, assuming
+/// that every character occupies one cell.
+fn text_pixel_size_in(window: &terminal::WindowSize, text: &str) -> io::Result<SizeInPixels> {
+    if window.columns == 0 || window.rows == 0 || window.width == 0 || window.height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Cannot determine the pixel size of this terminal",
+        ));
     }
+    let cell_width = window.width / window.columns;
+    let cell_height = window.height / window.rows;
 
-    /// Creates a new console based on the properties of stdin/stdout.
-    ///
-    /// This spawns a background task to handle console input so this must be run in the context of
-    /// an Tokio runtime.
-    ///
-    /// Compared to `from_stdio`, this also returns a key sender to inject extra events into the
-    /// queue maintained by the terminal.
-    pub fn from_stdio_with_injector(signals_tx: Sender<Signal>) -> io::Result<(Self, Sender<Key>)> {
-        let (on_key_tx, on_key_rx) = async_channel::unbounded();
+    let len = filter_control_chars(text).chars().count();
+    if len == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot measure empty text"));
+    }
+    let width = u16::try_from(len)
+        .ok()
+        .and_then(|len| len.checked_mul(cell_width))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Text too long"))?;
+    Ok(SizeInPixels::new(width, cell_height))
+}
 
-        let is_tty = io::stdin().is_tty() && io::stdout().is_tty();
+/// Computes the character cells of a terminal of `window` size that cover the pixels in the
+/// rectangle from `x1y1` to `x2y2`, whose corners can be given in any order.
+///
+/// Returns the top-left cell and the size of the area in cells, or `None` if the rectangle lies
+/// outside of the terminal.
+fn rect_cells_in(
+    window: &terminal::WindowSize,
+    x1y1: PixelsXY,
+    x2y2: PixelsXY,
+) -> io::Result<Option<(CharsXY, CharsXY)>> {
+    if window.columns == 0
+        || window.rows == 0
+        || window.width < window.columns
+        || window.height < window.rows
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Cannot determine the pixel size of this terminal",
+        ));
+    }
+    let cell_width = i32::from(window.width / window.columns);
+    let cell_height = i32::from(window.height / window.rows);
 
-        if is_tty {
-            terminal::enable_raw_mode()?;
-            tokio::task::spawn(TerminalConsole::raw_key_handler(on_key_tx.clone(), signals_tx));
+    let cells = |a: i16, b: i16, cell_size: i32, count: u16| {
+        let (min, max) =
+            if a < b { (i32::from(a), i32::from(b)) } else { (i32::from(b), i32::from(a)) };
+        let first = min.max(0) / cell_size;
+        let last = (max / cell_size).min(i32::from(count) - 1);
+        if max < 0 || first > last {
+            None
         } else {
-            tokio::task::spawn(TerminalConsole::stdio_key_handler(on_key_tx.clone()));
+            let first = u16::try_from(first).expect("Clamped to the window size");
+            let last = u16::try_from(last).expect("Clamped to the window size");
+            Some((first, last - first + 1))
         }
+    };
 
-        Ok((
-            Self {
-                is_tty,
-                fg_color: None,
-                bg_color: None,
-                cursor_visible: true,
-                alt_active: false,
-                sync_enabled: true,
-                on_key_rx,
-            },
-            on_key_tx,
-        ))
+    let columns = cells(x1y1.x, x2y2.x, cell_width, window.columns);
+    let rows = cells(x1y1.y, x2y2.y, cell_height, window.rows);
+    match (columns, rows) {
+        (Some((x, width)), Some((y, height))) => {
+            Ok(Some((CharsXY::new(x, y), CharsXY::new(width, height))))
+        }
+        _ => Ok(None),
     }
+}
 
-    /// Async task to wait for key events on a raw terminal and translate them into events for the
-    /// console or the machine.
-    async fn raw_key_handler(on_key_tx: Sender<Key>, signals_tx: Sender<Signal>) {
-        use event::{KeyCode, KeyModifiers};
-
-        let mut done = false;
-        while !done {
-            let key = match event::read() {
-                Ok(event::Event::Key(ev)) => {
-                    if ev.kind != KeyEventKind::Press {
-                        continue;
-                    }
+/// Determines the color support of the terminal given whether stdio is attached to a TTY
+/// (`is_tty`) and the values of the `TERM` (`term`) and `COLORTERM` (`colorterm`) variables.
+fn detect_color_capability(
+    is_tty: bool,
+    term: Option<&str>,
+    colorterm: Option<&str>,
+) -> ColorCapability {
+    if !is_tty {
+        return ColorCapability::None;
+    }
 
-                    match ev.code {
-                        KeyCode::Backspace => Key::Backspace,
-                        KeyCode::End => Key::End,
-                        KeyCode::Esc => Key::Escape,
-                        KeyCode::Home => Key::Home,
-                        KeyCode::Tab => Key::Tab,
-                        KeyCode::Up => Key::ArrowUp,
-                        KeyCode::Down => Key::ArrowDown,
-                        KeyCode::Left => Key::ArrowLeft,
-                        KeyCode::Right => Key::ArrowRight,
-                        KeyCode::PageDown => Key::PageDown,
-                        KeyCode::PageUp => Key::PageUp,
-                        KeyCode::Char('a') if ev.modifiers == KeyModifiers::CONTROL => Key::Home,
-                        KeyCode::Char('b') if ev.modifiers == KeyModifiers::CONTROL => {
-                            Key::ArrowLeft
-                        }
-                        KeyCode::Char('c') if ev.modifiers == KeyModifiers::CONTROL => {
-                            Key::Interrupt
-                        }
-                        KeyCode::Char('d') if ev.modifiers == KeyModifiers::CONTROL => Key::Eof,
-                        KeyCode::Char('e') if ev.modifiers == KeyModifiers::CONTROL => Key::End,
-                        KeyCode::Char('f') if ev.modifiers == KeyModifiers::CONTROL => {
-                            Key::ArrowRight
-                        }
-                        KeyCode::Char('j') if ev.modifiers == KeyModifiers::CONTROL => Key::NewLine,
-                        KeyCode::Char('m') if ev.modifiers == KeyModifiers::CONTROL => Key::NewLine,
-                        KeyCode::Char('n') if ev.modifiers == KeyModifiers::CONTROL => {
-                            Key::ArrowDown
-                        }
-                        KeyCode::Char('p') if ev.modifiers == KeyModifiers::CONTROL => Key::ArrowUp,
-                        KeyCode::Char(ch) => Key::Char(ch),
-                        KeyCode::Enter => Key::NewLine,
-                        _ => Key::Unknown,
-                    }
-                }
-                Ok(_) => {
-                    // Not a key event; ignore and try again.
-                    continue;
-                }
-                Err(_) => {
-                    // There is not much we can do if we get an error from crossterm.
-                    Key::Unknown
-                }
-            };
+    match term {
+        Some("dumb") | Some("") => return ColorCapability::None,
+        _ => (),
+    }
 
-            done = key == Key::Eof;
-            if key == Key::Interrupt {
-                // Handling CTRL+C in this way isn't great because this is not the same as handling
-                // SIGINT on Unix builds.  First, we are unable to stop long-running operations like
-                // sleeps; and second, a real SIGINT will kill the interpreter completely instead of
-                // coming this way.  We need a real signal handler and we probably should not be
-                // running in raw mode all the time.
-                signals_tx
-                    .send(Signal::Break)
-                    .await
-                    .expect("Send to unbounded channel should not have failed")
-            }
+    match colorterm {
+        Some("truecolor") | Some("24bit") => ColorCapability::TrueColor,
+        _ => match term {
+            Some(term) if term.contains("256color") => ColorCapability::Ansi256,
+            _ => ColorCapability::Ansi16,
+        },
+    }
+}
 
-            // This should never fail but can if the receiver outruns the console because we
-            // don't await for the handler to terminate (which we cannot do safely because
-            // `Drop` is not async).
-            let _ = on_key_tx.send(key).await;
+/// Converts an ANSI `color` to a crossterm color that can be represented in a console with the
+/// given `capability`, or `None` if the console cannot display colors at all.
+fn downgrade_color(color: Option<u8>, capability: ColorCapability) -> Option<style::Color> {
+    match (capability, color) {
+        (ColorCapability::None, _) => None,
+        (_, None) => Some(style::Color::Reset),
+        (ColorCapability::Ansi16, Some(color)) => Some(match ansi_color_to_16(color) {
+            0 => style::Color::Black,
+            1 => style::Color::DarkRed,
+            2 => style::Color::DarkGreen,
+            3 => style::Color::DarkYellow,
+            4 => style::Color::DarkBlue,
+            5 => style::Color::DarkMagenta,
+            6 => style::Color::DarkCyan,
+            7 => style::Color::Grey,
+            8 => style::Color::DarkGrey,
+            9 => style::Color::Red,
+            10 => style::Color::Green,
+            11 => style::Color::Yellow,
+            12 => style::Color::Blue,
+            13 => style::Color::Magenta,
+            14 => style::Color::Cyan,
+            _ => style::Color::White,
+        }),
+        (ColorCapability::Ansi256, Some(color)) | (ColorCapability::TrueColor, Some(color)) => {
+            Some(style::Color::AnsiValue(color))
         }
+    }
+}
 
-        signals_tx.close();
-        on_key_tx.close();
+/// Translates the newlines in `text` to `\r\n` sequences if the console is attached to a TTY
+/// (`is_tty`), given that raw mode disables the terminal's own translation.
+fn translate_newlines(text: &str, is_tty: bool) -> Cow<'_, str> {
+    if is_tty && text.contains('\n') {
+        Cow::Owned(text.replace('\n', "\r\n"))
+    } else {
+        Cow::Borrowed(text)
     }
+}
 
-    /// Async task to wait for key events on a non-raw terminal and translate them into events for
-    /// the console or the machine.
-    async fn stdio_key_handler(on_key_tx: Sender<Key>) {
-        // TODO(jmmv): We should probably install a signal handler here to capture SIGINT and
-        // funnel it to the Machine via signals_rx, as we do in the raw_key_handler.  This would
-        // help ensure both consoles behave in the same way, but there is strictly no need for this
-        // because, when we do not configure the terminal in raw mode, we aren't capturing CTRL+C
-        // and the default system handler will work.
+/// State of the escape sequence parser of a `Tee`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EscapeState {
+    /// Outside of any escape sequence.
+    Text,
 
-        let mut buffer = VecDeque::default();
+    /// After an ESC character.
+    Esc,
 
-        let mut done = false;
-        while !done {
-            let key = match read_key_from_stdin(&mut buffer) {
-                Ok(key) => key,
-                Err(_) => {
-                    // There is not much we can do if we get an error from stdin.
-                    Key::Unknown
+    /// Within a control sequence (`ESC [`), which ends with a byte in the `@` to `~` range.
+    Csi,
+
+    /// Within a control string (`ESC P`, `ESC ]`, etc.), which ends with BEL or `ESC \`.
+    String,
+
+    /// After an ESC character within a control string.
+    StringEsc,
+}
+
+/// Copy of the output of a console into a file.
+struct Tee {
+    /// File that receives the copy of the output.
+    file: BufWriter<File>,
+
+    /// State of the escape sequence parser if escape sequences have to be stripped, or `None` if
+    /// they have to be copied verbatim.
+    strip: Option<EscapeState>,
+}
+
+impl Tee {
+    /// Copies `buf` into the file, stripping escape sequences if requested.
+    ///
+    /// Escape sequences can span multiple calls to this function.
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        let mut state = match self.strip {
+            Some(state) => state,
+            None => return self.file.write_all(buf),
+        };
+
+        let mut text = Vec::with_capacity(buf.len());
+        for b in buf {
+            state = match (state, *b) {
+                (EscapeState::Text, 0x1b) => EscapeState::Esc,
+                (EscapeState::Text, b) => {
+                    text.push(b);
+                    EscapeState::Text
                 }
+                (EscapeState::Esc, b'[') => EscapeState::Csi,
+                (EscapeState::Esc, b'P' | b']' | b'X' | b'^' | b'_') => EscapeState::String,
+                (EscapeState::Esc, _) => EscapeState::Text,
+                (EscapeState::Csi, 0x40..=0x7e) => EscapeState::Text,
+                (EscapeState::Csi, _) => EscapeState::Csi,
+                (EscapeState::String, 0x07) => EscapeState::Text,
+                (EscapeState::String, 0x1b) => EscapeState::StringEsc,
+                (EscapeState::String, _) => EscapeState::String,
+                (EscapeState::StringEsc, b'\\') => EscapeState::Text,
+                (EscapeState::StringEsc, _) => EscapeState::String,
             };
+        }
+        self.strip = Some(state);
+        self.file.write_all(&text)
+    }
+}
 
-            done = key == Key::Eof;
+/// Counters describing the output sent to the terminal by a `TerminalConsole`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ConsoleIoStats {
+    /// Number of bytes written to stdout, including escape sequences.
+    pub bytes_written: u64,
 
-            // This should never fail but can if the receiver outruns the console because we don't
-            // await for the handler to terminate (which we cannot do safely because `Drop` is not
-            // async).
-            let _ = on_key_tx.send(key).await;
-        }
+    /// Number of times stdout was flushed.
+    pub flushes: u64,
+}
 
-        on_key_tx.close();
+/// Snapshot of the visible state of a `TerminalConsole` as captured by `save_state`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConsoleState {
+    /// Nesting level of the alternate console.
+    alt_depth: usize,
+
+    /// Whether the cursor is visible.
+    cursor_visible: bool,
+
+    /// Foreground color.
+    fg_color: Option<u8>,
+
+    /// Background color.
+    bg_color: Option<u8>,
+
+    /// Whether video syncing is enabled.
+    sync_enabled: bool,
+}
+
+/// Destination of the console output.
+enum Sink {
+    /// Locked stdout.
+    Stdout(StdoutLock<'static>),
+
+    /// In-memory buffer that replaces stdout in tests.
+    #[cfg(test)]
+    Memory(Arc<Mutex<Vec<u8>>>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Stdout(stdout) => stdout.write(buf),
+            #[cfg(test)]
+            Sink::Memory(memory) => memory.lock().unwrap().write(buf),
+        }
     }
 
-    /// Flushes the console, which has already been written to via `lock`, if syncing is enabled.
-    fn maybe_flush(&self, mut lock: StdoutLock<'_>) -> io::Result<()> {
-        if self.sync_enabled {
-            lock.flush()
-        } else {
-            Ok(())
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => stdout.flush(),
+            #[cfg(test)]
+            Sink::Memory(_memory) => Ok(()),
         }
     }
 }
 
-#[async_trait(?Send)]
-impl InputOps for TerminalConsole {
-    async fn poll_key(&mut self) -> io::Result<Option<Key>> {
-        match self.on_key_rx.try_recv() {
-            Ok(k) => Ok(Some(k)),
-            Err(TryRecvError::Empty) => Ok(None),
-            Err(TryRecvError::Closed) => Ok(Some(Key::Eof)),
+/// Writer for the console output that sends a copy of everything it writes to an optional `Tee`.
+struct Output {
+    /// Destination of the output.
+    sink: Sink,
+
+    /// Destination of the copy of the output, if any.
+    tee: Option<Arc<Mutex<Tee>>>,
+
+    /// Counters to update with the writes and flushes done via this writer.
+    stats: Arc<Mutex<ConsoleIoStats>>,
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.sink.write(buf)?;
+        self.stats.lock().unwrap().bytes_written += n as u64;
+        if let Some(tee) = self.tee.as_ref() {
+            tee.lock().unwrap().write_all(&buf[..n])?;
         }
+        Ok(n)
     }
 
-    async fn read_key(&mut self) -> io::Result<Key> {
-        match self.on_key_rx.recv().await {
-            Ok(k) => Ok(k),
-            Err(_) => Ok(Key::Eof),
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()?;
+        self.stats.lock().unwrap().flushes += 1;
+        if let Some(tee) = self.tee.as_ref() {
+            tee.lock().unwrap().file.flush()?;
         }
+        Ok(())
     }
 }
 
-#[async_trait(?Send)]
-impl Console for TerminalConsole {
-    fn clear(&mut self, how: ClearType) -> io::Result<()> {
-        let how = match how {
-            ClearType::All => terminal::ClearType::All,
-            ClearType::CurrentLine => terminal::ClearType::CurrentLine,
-            ClearType::PreviousChar => {
-                let stdout = io::stdout();
-                let mut stdout = stdout.lock();
-                stdout.write_all(b"\x08 \x08")?;
-                return self.maybe_flush(stdout);
-            }
-            ClearType::UntilNewLine => terminal::ClearType::UntilNewLine,
-        };
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
-        stdout.queue(terminal::Clear(how))?;
-        if how == terminal::ClearType::All {
-            stdout.queue(cursor::MoveTo(0, 0))?;
-        }
-        self.maybe_flush(stdout)
-    }
+/// Enables or disables echo on the TTY `fd` and returns the previous state.
+#[cfg(unix)]
+fn set_tty_echo<Fd: AsFd>(fd: Fd, enabled: bool) -> io::Result<bool> {
+    use rustix::termios::{self, LocalModes, OptionalActions};
 
-    fn color(&self) -> (Option<u8>, Option<u8>) {
-        (self.fg_color, self.bg_color)
+    if !termios::isatty(&fd) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Cannot change the echo of an input not attached to a TTY",
+        ));
     }
 
-    fn set_color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()> {
-        if fg == self.fg_color && bg == self.bg_color {
-            return Ok(());
+    let mut attrs = termios::tcgetattr(&fd)?;
+    let previous = attrs.local_modes.contains(LocalModes::ECHO);
+    attrs.local_modes.set(LocalModes::ECHO, enabled);
+    termios::tcsetattr(&fd, OptionalActions::Now, &attrs)?;
+    Ok(previous)
+}
+
+/// Enables or disables echo on the TTY `fd` and returns the previous state.
+#[cfg(not(unix))]
+fn set_tty_echo<Fd>(_fd: Fd, _enabled: bool) -> io::Result<bool> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Cannot change the echo on this platform"))
+}
+
+/// Reads bytes from the TTY `fd` until `terminator` is found or until `timeout` expires.
+///
+/// Returns the bytes read including the `terminator`.  Reaching the timeout or the end of the
+/// input before finding the terminator is an error.
+#[cfg(unix)]
+fn read_tty_reply<Fd: AsFd>(fd: Fd, terminator: u8, timeout: Duration) -> io::Result<Vec<u8>> {
+    use rustix::event::{poll, PollFd, PollFlags, Timespec};
+
+    let deadline = Instant::now() + timeout;
+    let mut reply = vec![];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let remaining = Timespec::try_from(remaining)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let mut fds = [PollFd::new(&fd, PollFlags::IN)];
+        if poll(&mut fds, Some(&remaining))? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Terminal did not reply to the query in time",
+            ));
         }
 
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
-        if fg != self.fg_color {
-            let ct_fg = match fg {
-                None => style::Color::Reset,
-                Some(color) => style::Color::AnsiValue(color),
-            };
-            stdout.queue(style::SetForegroundColor(ct_fg))?;
-            self.fg_color = fg;
+        let mut byte = [0u8; 1];
+        if rustix::io::read(&fd, &mut byte)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Terminal closed the input before replying to the query",
+            ));
         }
-        if bg != self.bg_color {
-            let ct_bg = match bg {
-                None => style::Color::Reset,
-                Some(color) => style::Color::AnsiValue(color),
-            };
-            stdout.queue(style::SetBackgroundColor(ct_bg))?;
-            self.bg_color = bg;
+        reply.push(byte[0]);
+        if byte[0] == terminator {
+            return Ok(reply);
         }
-        self.maybe_flush(stdout)
     }
+}
 
-    fn enter_alt(&mut self) -> io::Result<()> {
-        if !self.alt_active {
-            let stdout = io::stdout();
-            let mut stdout = stdout.lock();
-            stdout.queue(terminal::EnterAlternateScreen)?;
-            self.alt_active = true;
-            self.maybe_flush(stdout)
-        } else {
-            Ok(())
-        }
+/// Reads bytes from the TTY `fd` until `terminator` is found or until `timeout` expires.
+#[cfg(not(unix))]
+fn read_tty_reply<Fd>(_fd: Fd, _terminator: u8, _timeout: Duration) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Cannot query the terminal on this platform"))
+}
+
+/// Parses the `ESC [ row ; column R` reply of the terminal to a cursor position request.
+///
+/// The terminal reports 1-based coordinates, which are converted to 0-based.  Any bytes before the
+/// last `ESC [` are ignored, as they come from keys typed while the query was in progress.
+fn parse_cursor_position(reply: &[u8]) -> io::Result<CharsXY> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid cursor position reply {:?}", String::from_utf8_lossy(reply)),
+        )
+    };
+
+    let start = reply.windows(2).rposition(|w| w == b"\x1b[").ok_or_else(invalid)?;
+    let body = reply[start + 2..].strip_suffix(b"R").ok_or_else(invalid)?;
+    let body = std::str::from_utf8(body).map_err(|_| invalid())?;
+    let (row, column) = body.split_once(';').ok_or_else(invalid)?;
+    let row = row.parse::<u16>().map_err(|_| invalid())?;
+    let column = column.parse::<u16>().map_err(|_| invalid())?;
+    if row == 0 || column == 0 {
+        return Err(invalid());
     }
+    Ok(CharsXY::new(column - 1, row - 1))
+}
 
-    fn hide_cursor(&mut self) -> io::Result<()> {
-        if self.cursor_visible {
-            let stdout = io::stdout();
-            let mut stdout = stdout.lock();
-            stdout.queue(cursor::Hide)?;
-            self.cursor_visible = false;
-            self.maybe_flush(stdout)
-        } else {
-            Ok(())
+/// Handshake to stop the raw key handler from reading the terminal while `TerminalConsole::query`
+/// waits for a reply.
+///
+/// The console raises `requested` and awaits a message on the `acknowledged` channel, which the
+/// handler only sends right before it goes to sleep without reading.  The handler checks
+/// `requested` again every time it wakes up, so once the console receives the acknowledgement,
+/// the handler does not touch the terminal until the console lowers `requested`.  Awaiting the
+/// acknowledgement instead of blocking lets the handler run even on a single-threaded runtime.
+struct InputPause {
+    /// Whether the console wants the handler to stop reading from the terminal.
+    requested: AtomicBool,
+
+    /// Sender used by the handler to acknowledge the request.
+    ack_tx: Sender<()>,
+
+    /// Receiver used by the console to wait for the acknowledgement.
+    ack_rx: Receiver<()>,
+}
+
+impl Default for InputPause {
+    fn default() -> Self {
+        let (ack_tx, ack_rx) = async_channel::bounded(1);
+        Self { requested: AtomicBool::new(false), ack_tx, ack_rx }
+    }
+}
+
+impl InputPause {
+    /// Asks the handler to pause and waits until it does or until `timeout` expires.
+    ///
+    /// On error, the request is withdrawn so that the handler continues reading.
+    async fn pause(&self, timeout: Duration) -> io::Result<()> {
+        while self.ack_rx.try_recv().is_ok() {}
+        self.requested.store(true, atomic::Ordering::SeqCst);
+
+        match tokio::time::timeout(timeout, self.ack_rx.recv()).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) | Err(_) => {
+                self.resume();
+                Err(io::Error::new(io::ErrorKind::TimedOut, "Key handler did not pause in time"))
+            }
         }
     }
 
-    fn is_interactive(&self) -> bool {
-        self.is_tty
+    /// Lets the handler resume reading.
+    fn resume(&self) {
+        self.requested.store(false, atomic::Ordering::SeqCst);
+        while self.ack_rx.try_recv().is_ok() {}
     }
 
-    fn leave_alt(&mut self) -> io::Result<()> {
-        if self.alt_active {
-            let stdout = io::stdout();
-            let mut stdout = stdout.lock();
-            stdout.queue(terminal::LeaveAlternateScreen)?;
-            self.alt_active = false;
-            self.maybe_flush(stdout)
+    /// Checks if the handler must stay away from the terminal, acknowledging the request if so.
+    ///
+    /// The handler must call this before every read and must not read if this returns true.
+    fn check(&self) -> bool {
+        if self.requested.load(atomic::Ordering::SeqCst) {
+            // The channel is full if the console has not collected a previous acknowledgement
+            // yet, which carries the same information.
+            let _ = self.ack_tx.try_send(());
+            true
         } else {
-            Ok(())
+            false
         }
     }
+}
 
-    fn locate(&mut self, pos: CharsXY) -> io::Result<()> {
-        #[cfg(debug_assertions)]
-        {
-            let size = self.size_chars()?;
-            assert!(pos.x < size.x);
-            assert!(pos.y < size.y);
-        }
+/// Enables or disables the reporting of keys via the Kitty keyboard protocol.
+fn set_keyboard_enhancement(enabled: bool) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if enabled {
+        stdout.queue(event::PushKeyboardEnhancementFlags(
+            event::KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES,
+        ))?;
+    } else {
+        stdout.queue(event::PopKeyboardEnhancementFlags)?;
+    }
+    stdout.flush()
+}
 
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
-        stdout.queue(cursor::MoveTo(pos.x, pos.y))?;
-        self.maybe_flush(stdout)
+/// Enables or disables the reporting of mouse events, which stops the terminal from acting on
+/// them (e.g. to select text).
+fn set_mouse_capture(enabled: bool) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if enabled {
+        stdout.queue(event::EnableMouseCapture)?;
+    } else {
+        stdout.queue(event::DisableMouseCapture)?;
     }
+    stdout.flush()
+}
 
-    fn move_within_line(&mut self, off: i16) -> io::Result<()> {
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
-        match off.cmp(&0) {
-            Ordering::Less => stdout.queue(cursor::MoveLeft(-off as u16)),
-            Ordering::Equal => return Ok(()),
-            Ordering::Greater => stdout.queue(cursor::MoveRight(off as u16)),
-        }?;
-        self.maybe_flush(stdout)
+/// Returns the text attribute that stands in for the `fg` and `bg` colors on a terminal without
+/// color support, if any.  See `TerminalConsoleBuilder::monochrome_attributes` for details.
+fn monochrome_attribute(fg: Option<u8>, bg: Option<u8>) -> Option<style::Attribute> {
+    match (fg, bg) {
+        (_, Some(_)) => Some(style::Attribute::Reverse),
+        (Some(_), None) => Some(style::Attribute::Bold),
+        (None, None) => None,
     }
+}
 
-    fn print(&mut self, text: &str) -> io::Result<()> {
-        let text = remove_control_chars(text.to_owned());
+/// Settings to customize the behavior of a `TerminalConsole` at construction time.
+#[derive(Clone, Debug)]
+pub struct TerminalConsoleOptions {
+    /// Whether to deliver repeated key events generated by holding a key down in addition to the
+    /// initial press.  Only has an effect on terminals that report key repeats, such as Kitty or
+    /// the Windows console.
+    pub repeat_keys: bool,
 
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
-        stdout.write_all(text.as_bytes())?;
+    /// Key and modifiers that generate `Key::Interrupt` and thus stop the running program.
+    ///
+    /// When this is not CTRL+C, pressing CTRL+C delivers the literal ETX character.  This only has
+    /// an effect when the console is attached to a TTY: in non-interactive mode, the interrupt is
+    /// handled by the system as a signal.
+    pub interrupt_key: (KeyCode, KeyModifiers),
+
+    /// Key and modifiers that generate `Key::Eof` and thus close the console input, or `None` to
+    /// never treat a key press as the end of the input.
+    ///
+    /// When this is not CTRL+D, pressing CTRL+D delivers the literal EOT character.  When the
+    /// console is not attached to a TTY, the key is recognized if it maps to a single character
+    /// (such as CTRL plus a letter), and the real end of the input stream is always delivered as
+    /// `Key::Eof`.
+    ///
+    /// Keys that cannot be told apart from Enter, such as CTRL+J and CTRL+M, are rejected when
+    /// building the console because they would turn every line break into the end of the input.
+    pub eof_key: Option<(KeyCode, KeyModifiers)>,
+
+    /// Whether to ask the terminal to report keys unambiguously via the Kitty keyboard protocol.
+    ///
+    /// When this is active, CTRL+J and CTRL+M are delivered as `Key::Char('\n')` and
+    /// `Key::Char('\r')` respectively so that they can be told apart from Enter, which is still
+    /// delivered as `Key::NewLine`.  Terminals that do not support the protocol keep delivering
+    /// all three as `Key::NewLine`: use `TerminalConsole::enhanced_keys` to check which mode is in
+    /// effect.
+    pub enhanced_keys: bool,
+
+    /// Maximum time to wait for the rest of an escape sequence after reading an ESC character
+    /// when the console is not attached to a TTY.
+    ///
+    /// If no more input arrives within this period, the ESC is delivered as `Key::Escape`.
+    /// Otherwise, sequences like `ESC [ A` are delivered as the key they represent.
+    pub esc_timeout: Duration,
+}
+
+impl Default for TerminalConsoleOptions {
+    fn default() -> Self {
+        Self {
+            repeat_keys: false,
+            interrupt_key: (KeyCode::Char('c'), KeyModifiers::CONTROL),
+            eof_key: Some((KeyCode::Char('d'), KeyModifiers::CONTROL)),
+            enhanced_keys: false,
+            esc_timeout: DEFAULT_ESC_TIMEOUT,
+        }
+    }
+}
+
+impl TerminalConsoleOptions {
+    /// Returns the character that `eof_key` produces on a non-TTY input, if any.
+    fn eof_char(&self) -> Option<char> {
+        match self.eof_key? {
+            (KeyCode::Char(ch), KeyModifiers::CONTROL) if ch.is_ascii_alphabetic() => {
+                Some(char::from(ch.to_ascii_lowercase() as u8 - b'a' + 1))
+            }
+            (KeyCode::Char(ch), KeyModifiers::NONE) => Some(ch),
+            _ => None,
+        }
+    }
+
+    /// Checks that the options are consistent with each other.
+    fn validate(&self) -> io::Result<()> {
+        let collides_with_enter = match self.eof_key {
+            Some((KeyCode::Enter, _)) => true,
+            Some(_) => matches!(self.eof_char(), Some('\n' | '\r')),
+            None => false,
+        };
+        if collides_with_enter {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "EOF key cannot be a key that also produces a line break",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Builder pattern to construct a `TerminalConsole` with non-default settings.
+///
+/// All settings start with the same values that `TerminalConsole::from_stdio` uses.
+#[must_use]
+pub struct TerminalConsoleBuilder {
+    signals_tx: Sender<Signal>,
+    options: TerminalConsoleOptions,
+    sync_enabled: bool,
+    cursor_visible: bool,
+    mouse_capture: bool,
+    monochrome_attributes: bool,
+    size_override: Option<CharsXY>,
+    track_contents: bool,
+}
+
+impl TerminalConsoleBuilder {
+    /// Creates a new builder for a console that delivers signals via `signals_tx`.
+    pub fn new(signals_tx: Sender<Signal>) -> Self {
+        Self {
+            signals_tx,
+            options: TerminalConsoleOptions::default(),
+            sync_enabled: true,
+            cursor_visible: true,
+            mouse_capture: false,
+            monochrome_attributes: false,
+            size_override: None,
+            track_contents: false,
+        }
+    }
+
+    /// Sets whether to deliver repeated key events.  See `TerminalConsoleOptions::repeat_keys`.
+    ///
+    /// Defaults to false.
+    pub fn repeat_keys(mut self, enabled: bool) -> Self {
+        self.options.repeat_keys = enabled;
+        self
+    }
+
+    /// Sets the key `code` and `modifiers` that generate `Key::Interrupt`.  See
+    /// `TerminalConsoleOptions::interrupt_key`.
+    ///
+    /// Defaults to CTRL+C.
+    pub fn interrupt_key(mut self, code: KeyCode, modifiers: KeyModifiers) -> Self {
+        self.options.interrupt_key = (code, modifiers);
+        self
+    }
+
+    /// Sets the key and modifiers that generate `Key::Eof`, or `None` to disable it.  See
+    /// `TerminalConsoleOptions::eof_key`, which also describes the keys that make `build` fail.
+    ///
+    /// Defaults to CTRL+D.
+    pub fn eof_key(mut self, key: Option<(KeyCode, KeyModifiers)>) -> Self {
+        self.options.eof_key = key;
+        self
+    }
+
+    /// Sets whether to ask the terminal to report keys via the Kitty keyboard protocol.  See
+    /// `TerminalConsoleOptions::enhanced_keys`.
+    ///
+    /// Defaults to false.
+    pub fn enhanced_keys(mut self, enabled: bool) -> Self {
+        self.options.enhanced_keys = enabled;
+        self
+    }
+
+    /// Sets the maximum time to wait for the rest of an escape sequence.  See
+    /// `TerminalConsoleOptions::esc_timeout`.
+    ///
+    /// Defaults to 50 milliseconds.
+    pub fn esc_timeout(mut self, timeout: Duration) -> Self {
+        self.options.esc_timeout = timeout;
+        self
+    }
+
+    /// Sets whether video syncing starts enabled, which is the state that `Console::set_sync`
+    /// later changes.
+    ///
+    /// Defaults to true.
+    pub fn sync_enabled(mut self, enabled: bool) -> Self {
+        self.sync_enabled = enabled;
+        self
+    }
+
+    /// Sets whether the cursor starts visible.  If false, the cursor is hidden at construction
+    /// time and shown again when the console is dropped.
+    ///
+    /// Defaults to true.
+    pub fn cursor_visible(mut self, visible: bool) -> Self {
+        self.cursor_visible = visible;
+        self
+    }
+
+    /// Sets whether to capture mouse events while the console is in raw mode so that the
+    /// terminal does not act on them.  Mouse events are not delivered as keys.
+    ///
+    /// This only has an effect when the console is attached to a TTY.  Defaults to false.
+    pub fn mouse_capture(mut self, enabled: bool) -> Self {
+        self.mouse_capture = enabled;
+        self
+    }
+
+    /// Sets whether to render colors as text attributes on terminals without color support.
+    ///
+    /// When enabled and the detected color capability is `ColorCapability::None`, text written
+    /// with a background color is shown in reverse video and text written with only a foreground
+    /// color is shown in bold, so that colored emphasis remains visible.  The specific colors do
+    /// not matter, so all foreground colors look the same and so do all background colors.  Text
+    /// written with the default colors has no attributes.
+    ///
+    /// This has no effect on terminals that support colors nor when the output is not a TTY, as
+    /// the escape sequences would only clutter redirected output.  Defaults to false.
+    pub fn monochrome_attributes(mut self, enabled: bool) -> Self {
+        self.monochrome_attributes = enabled;
+        self
+    }
+
+    /// Sets the size that the console reports instead of the real size of the terminal.  See
+    /// `TerminalConsole::set_size_override`.
+    ///
+    /// Defaults to none.
+    pub fn size_override(mut self, size: Option<CharsXY>) -> Self {
+        self.size_override = size;
+        self
+    }
+
+    /// Sets whether to keep a copy of the text shown on the screen so that it can be read back
+    /// with `TerminalConsole::screen_text`.
+    ///
+    /// The copy holds one character per cell of the screen, which costs about 4 bytes per cell
+    /// (some 8 KB for an 80x25 terminal), and follows the size of the terminal whenever the
+    /// console writes to it.  Only the text sent via `print` and `write` is tracked, along with
+    /// the cursor movements, clears, scrolling and alternate screen switches that affect it: the
+    /// output of `write_raw` is passed through to the terminal as is and is not tracked.
+    ///
+    /// Defaults to false.
+    pub fn track_contents(mut self, enabled: bool) -> Self {
+        self.track_contents = enabled;
+        self
+    }
+
+    /// Creates the console based on the properties of stdin/stdout and the settings in this
+    /// builder.
+    ///
+    /// This spawns a background task to handle console input so this must be run in the context of
+    /// an Tokio runtime.
+    ///
+    /// Returns the console and a key sender to inject extra events into the queue maintained by
+    /// the console.
+    pub fn build(self) -> io::Result<(TerminalConsole, Sender<Key>)> {
+        let mut options = self.options;
+        options.validate()?;
+        let (on_key_tx, on_key_rx) = async_channel::unbounded();
+
+        let is_tty = stdio_is_tty();
+        let raw_mode = Arc::from(AtomicBool::new(is_tty));
+        let input_pause = Arc::from(InputPause::default());
+
+        let shadow = if self.track_contents {
+            let size = match self.size_override {
+                Some(size) => size,
+                None => detect_size()?,
+            };
+            Some(ShadowScreen::new(size))
+        } else {
+            None
+        };
+
+        let mut enhanced_keys = false;
+        let mut mouse_capture = false;
+        let input_task = if is_tty {
+            terminal::enable_raw_mode()?;
+            if options.enhanced_keys {
+                enhanced_keys = terminal::supports_keyboard_enhancement().unwrap_or(false);
+                if enhanced_keys {
+                    set_keyboard_enhancement(true)?;
+                }
+            }
+            options.enhanced_keys = enhanced_keys;
+            if self.mouse_capture {
+                set_mouse_capture(true)?;
+                mouse_capture = true;
+            }
+            tokio::task::spawn(TerminalConsole::raw_key_handler(
+                on_key_tx.clone(),
+                self.signals_tx,
+                options,
+                raw_mode.clone(),
+                input_pause.clone(),
+            ))
+        } else {
+            tokio::task::spawn(TerminalConsole::stdio_key_handler(
+                on_key_tx.clone(),
+                options.esc_timeout,
+                options.eof_char(),
+            ))
+        };
+
+        if !self.cursor_visible {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            stdout.queue(cursor::Hide)?;
+            stdout.flush()?;
+        }
+
+        Ok((
+            TerminalConsole {
+                is_tty,
+                raw_mode,
+                input_pause,
+                fg_color: None,
+                bg_color: None,
+                cursor_visible: self.cursor_visible,
+                alt_depth: 0,
+                sync_enabled: self.sync_enabled,
+                color_capability: detect_color_capability(
+                    is_tty,
+                    env::var("TERM").ok().as_deref(),
+                    env::var("COLORTERM").ok().as_deref(),
+                ),
+                enhanced_keys,
+                mouse_capture,
+                monochrome_attributes: self.monochrome_attributes,
+                size_override: self.size_override,
+                shadow,
+                original_echo: None,
+                tee: None,
+                stats: Arc::default(),
+                #[cfg(test)]
+                captured: None,
+                on_key_rx,
+                on_key_tx: on_key_tx.clone(),
+                pending_keys: VecDeque::default(),
+                input_task: Some(input_task),
+            },
+            on_key_tx,
+        ))
+    }
+}
+
+/// Peeks at the next byte in `input` without consuming it, waiting for at most `timeout` for it to
+/// arrive.
+///
+/// Returns `None` if the input reached EOF or if the timeout expired.
+async fn peek_byte<R: AsyncBufRead + Unpin>(
+    input: &mut R,
+    timeout: Duration,
+) -> io::Result<Option<u8>> {
+    match tokio::time::timeout(timeout, input.fill_buf()).await {
+        Ok(Ok(buf)) => Ok(buf.first().copied()),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Reads the rest of an escape sequence from `input` after an ESC character has been consumed.
+///
+/// An ESC that is not followed by a `[` within `esc_timeout` is a standalone escape key press,
+/// in which case any byte that follows it is left in the `input` for the next read.
+async fn read_escape_sequence<R: AsyncBufRead + Unpin>(
+    input: &mut R,
+    esc_timeout: Duration,
+) -> io::Result<Key> {
+    if peek_byte(input, esc_timeout).await? != Some(b'[') {
+        return Ok(Key::Escape);
+    }
+    input.consume(1);
+
+    let key = match peek_byte(input, esc_timeout).await? {
+        Some(b'A') => Key::ArrowUp,
+        Some(b'B') => Key::ArrowDown,
+        Some(b'C') => Key::ArrowRight,
+        Some(b'D') => Key::ArrowLeft,
+        Some(b'F') => Key::End,
+        Some(b'H') => Key::Home,
+        Some(_) => Key::Unknown,
+        None => return Ok(Key::Unknown),
+    };
+    input.consume(1);
+    Ok(key)
+}
+
+/// Reads a single key from the non-TTY `input`.
+///
+/// Unlike `read_key_from_stdin`, this does not wait for complete lines, which means that escape
+/// sequences can arrive split across reads.  `esc_timeout` specifies how long to wait for the rest
+/// of a sequence after an ESC character before treating it as a standalone escape key press.
+/// `eof_char`, if not none, is the character that signals the end of the input in addition to
+/// the real end of the stream.
+async fn read_key_from<R: AsyncBufRead + Unpin>(
+    input: &mut R,
+    esc_timeout: Duration,
+    eof_char: Option<char>,
+) -> io::Result<Key> {
+    loop {
+        let first = match input.read_u8().await {
+            Ok(byte) => byte,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Key::Eof),
+            Err(e) => return Err(e),
+        };
+
+        let len = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return Ok(Key::Unknown),
+        };
+        let mut bytes = [first, 0, 0, 0];
+        if len > 1 {
+            match input.read_exact(&mut bytes[1..len]).await {
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Key::Unknown),
+                Err(e) => return Err(e),
+            }
+        }
+        let ch = match std::str::from_utf8(&bytes[..len]) {
+            Ok(s) => s.chars().next().expect("Decoded exactly one character"),
+            Err(_) => return Ok(Key::Unknown),
+        };
+
+        return match ch {
+            ch if Some(ch) == eof_char => Ok(Key::Eof),
+            '\x1b' => read_escape_sequence(input, esc_timeout).await,
+            '\n' => Ok(Key::NewLine),
+            '\r' => {
+                // Ignore for consistency with `read_key_from_stdin`, which drops the CR in CRLF
+                // line terminators.
+                continue;
+            }
+            ch if !ch.is_control() => Ok(Key::Char(ch)),
+            _ => Ok(Key::Unknown),
+        };
+    }
+}
+
+/// Translates the crossterm key event `ev` into a `Key` according to `options`.
+///
+/// Returns `None` if the event must be ignored.
+fn key_from_event(ev: event::KeyEvent, options: &TerminalConsoleOptions) -> Option<Key> {
+    match ev.kind {
+        KeyEventKind::Press => (),
+        KeyEventKind::Repeat if options.repeat_keys => (),
+        KeyEventKind::Repeat | KeyEventKind::Release => return None,
+    }
+
+    if (ev.code, ev.modifiers) == options.interrupt_key {
+        return Some(Key::Interrupt);
+    }
+    if Some((ev.code, ev.modifiers)) == options.eof_key {
+        return Some(Key::Eof);
+    }
+
+    let key = match ev.code {
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::End => Key::End,
+        KeyCode::Esc => Key::Escape,
+        KeyCode::Home => Key::Home,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Up => Key::ArrowUp,
+        KeyCode::Down => Key::ArrowDown,
+        KeyCode::Left => Key::ArrowLeft,
+        KeyCode::Right => Key::ArrowRight,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::Char('a') if ev.modifiers == KeyModifiers::CONTROL => Key::Home,
+        KeyCode::Char('b') if ev.modifiers == KeyModifiers::CONTROL => Key::ArrowLeft,
+        KeyCode::Char('c') if ev.modifiers == KeyModifiers::CONTROL => Key::Char('\u{3}'),
+        KeyCode::Char('d') if ev.modifiers == KeyModifiers::CONTROL => Key::Char('\u{4}'),
+        KeyCode::Char('e') if ev.modifiers == KeyModifiers::CONTROL => Key::End,
+        KeyCode::Char('f') if ev.modifiers == KeyModifiers::CONTROL => Key::ArrowRight,
+        KeyCode::Char('j') if ev.modifiers == KeyModifiers::CONTROL && options.enhanced_keys => {
+            Key::Char('\n')
+        }
+        KeyCode::Char('m') if ev.modifiers == KeyModifiers::CONTROL && options.enhanced_keys => {
+            Key::Char('\r')
+        }
+        KeyCode::Char('j') if ev.modifiers == KeyModifiers::CONTROL => Key::NewLine,
+        KeyCode::Char('m') if ev.modifiers == KeyModifiers::CONTROL => Key::NewLine,
+        KeyCode::Char('n') if ev.modifiers == KeyModifiers::CONTROL => Key::ArrowDown,
+        KeyCode::Char('p') if ev.modifiers == KeyModifiers::CONTROL => Key::ArrowUp,
+        KeyCode::Char(ch) => Key::Char(ch),
+        KeyCode::Enter => Key::NewLine,
+        _ => Key::Unknown,
+    };
+    Some(key)
+}
+
+/// Implementation of the EndBASIC console to interact with stdin and stdout.
+pub struct TerminalConsole {
+    /// Whether stdin and stdout are attached to a TTY.  When this is true, the console is put in
+    /// raw mode for finer-grained control.
+    is_tty: bool,
+
+    /// Whether the terminal is currently in raw mode.  Shared with the key handler so that it
+    /// reads keys in the matching way.  Can only be true if `is_tty` is true.
+    raw_mode: Arc<AtomicBool>,
+
+    /// Handshake to pause the key handler while `query` reads from the terminal.
+    input_pause: Arc<InputPause>,
+
+    /// Current foreground color.
+    fg_color: Option<u8>,
+
+    /// Current background color.
+    bg_color: Option<u8>,
+
+    /// Whether the cursor is visible or not.
+    cursor_visible: bool,
+
+    /// Number of times the alternate console has been entered and not yet left.  The terminal is
+    /// only switched back to the main console when this drops to zero.
+    alt_depth: usize,
+
+    /// Whether video syncing is enabled or not.
+    sync_enabled: bool,
+
+    /// Level of color support of the terminal.
+    color_capability: ColorCapability,
+
+    /// Whether the terminal reports keys via the Kitty keyboard protocol while in raw mode.  Can
+    /// only be true if `is_tty` is true.
+    enhanced_keys: bool,
+
+    /// Whether the terminal captures mouse events while in raw mode.  Can only be true if
+    /// `is_tty` is true.
+    mouse_capture: bool,
+
+    /// Whether to render colors as text attributes when `color_capability` is `None`.  See
+    /// `TerminalConsoleBuilder::monochrome_attributes`.
+    monochrome_attributes: bool,
+
+    /// Size to report from `size_chars` instead of detecting the real size of the terminal.
+    size_override: Option<CharsXY>,
+
+    /// Copy of the text on the screen, if tracking was requested at construction time.
+    shadow: Option<ShadowScreen>,
+
+    /// Echo state of the TTY before the first call to `set_echo`, to restore it on exit.
+    original_echo: Option<bool>,
+
+    /// Destination of the copy of the output, if any.
+    tee: Option<Arc<Mutex<Tee>>>,
+
+    /// Counters of the output sent to the terminal.
+    stats: Arc<Mutex<ConsoleIoStats>>,
+
+    /// Buffer that receives the output instead of stdout, if any.
+    #[cfg(test)]
+    captured: Option<Arc<Mutex<Vec<u8>>>>,
+
+    /// Channel to receive key presses from the terminal.
+    on_key_rx: Receiver<Key>,
+
+    /// Channel to inject key presses into the terminal's queue.
+    on_key_tx: Sender<Key>,
+
+    /// Keys kept by `drain_keys` that must be delivered before any others in `on_key_rx`.
+    pending_keys: VecDeque<Key>,
+
+    /// Handle to the background task that reads keys from the terminal, if any.
+    input_task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for TerminalConsole {
+    fn drop(&mut self) {
+        // Errors are ignored because there is nothing we can do about them at this point, and we
+        // may be running as part of a panic.
+        let _ = self.restore_terminal();
+    }
+}
+
+impl TerminalConsole {
+    /// Restores the terminal to a sane state and flushes any output that may have been buffered
+    /// while syncing was disabled.
+    ///
+    /// All steps are attempted even if some fail, and the first error is returned.  Calling this
+    /// again after a successful restore only flushes the output.
+    fn restore_terminal(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        {
+            let mut stdout = self.lock_output();
+            if !self.cursor_visible {
+                result = result.and(stdout.queue(cursor::Show).map(|_| ()));
+            }
+            if self.alt_depth > 0 {
+                result = result.and(stdout.queue(terminal::LeaveAlternateScreen).map(|_| ()));
+            }
+            if self.uses_monochrome_attributes()
+                && monochrome_attribute(self.fg_color, self.bg_color).is_some()
+            {
+                let reset = style::SetAttribute(style::Attribute::Reset);
+                result = result.and(stdout.queue(reset).map(|_| ()));
+            }
+            result = result.and(stdout.flush());
+        }
+        self.cursor_visible = true;
+        self.alt_depth = 0;
+
+        if self.raw_mode.swap(false, atomic::Ordering::SeqCst) {
+            if self.enhanced_keys {
+                result = result.and(set_keyboard_enhancement(false));
+            }
+            if self.mouse_capture {
+                result = result.and(set_mouse_capture(false));
+            }
+            result = result.and(terminal::disable_raw_mode());
+        }
+        self.enhanced_keys = false;
+        self.mouse_capture = false;
+
+        if let Some(echo) = self.original_echo.take() {
+            result = result.and(set_tty_echo(io::stdin(), echo).map(|_| ()));
+        }
+
+        result
+    }
+
+    /// Stops the background task that reads keys and restores the terminal to its original state.
+    ///
+    /// Dropping the console also restores the terminal but cannot wait for the input task to exit
+    /// because `Drop` is not async.  This waits for the task, which makes teardown deterministic,
+    /// and reports any errors found while restoring the terminal.  Keys that were not yet read are
+    /// discarded.
+    pub async fn shutdown(mut self) -> io::Result<()> {
+        self.on_key_rx.close();
+        if let Some(task) = self.input_task.take() {
+            // The task may be blocked reading from stdin, which only completes once there is more
+            // input, so cancel it instead of waiting for it to notice that the channel is closed.
+            task.abort();
+            match task.await {
+                Ok(()) => (),
+                Err(e) if e.is_cancelled() => (),
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Input task failed: {}", e),
+                    ))
+                }
+            }
+        }
+        self.restore_terminal()
+    }
+
+    /// Creates a new console based on the properties of stdin/stdout.
+    ///
+    /// This spawns a background task to handle console input so this must be run in the context of
+    /// an Tokio runtime.
+    pub fn from_stdio(signals_tx: Sender<Signal>) -> io::Result<Self> {
+        let (terminal, _on_key_tx) = Self::from_stdio_with_injector(signals_tx)?;
+        Ok(terminal)
+    }
+
+    /// Creates a new console based on the properties of stdin/stdout.
+    ///
+    /// This spawns a background task to handle console input so this must be run in the context of
+    /// an Tokio runtime.
+    ///
+    /// Compared to `from_stdio`, this also returns a key sender to inject extra events into the
+    /// queue maintained by the terminal.
+    pub fn from_stdio_with_injector(signals_tx: Sender<Signal>) -> io::Result<(Self, Sender<Key>)> {
+        Self::from_stdio_with_options(signals_tx, TerminalConsoleOptions::default())
+    }
+
+    /// Creates a new console based on the properties of stdin/stdout and customized by `options`.
+    ///
+    /// This spawns a background task to handle console input so this must be run in the context of
+    /// an Tokio runtime.
+    ///
+    /// Like `from_stdio_with_injector`, this also returns a key sender to inject extra events into
+    /// the queue maintained by the terminal.  Use `TerminalConsoleBuilder` to customize settings
+    /// beyond those in `options`.
+    pub fn from_stdio_with_options(
+        signals_tx: Sender<Signal>,
+        options: TerminalConsoleOptions,
+    ) -> io::Result<(Self, Sender<Key>)> {
+        let mut builder = TerminalConsoleBuilder::new(signals_tx);
+        builder.options = options;
+        builder.build()
+    }
+
+    /// Returns true if the terminal reports keys via the Kitty keyboard protocol, which happens
+    /// when `TerminalConsoleOptions::enhanced_keys` was requested and the terminal supports it.
+    ///
+    /// The protocol is only in effect while the console is in raw mode.
+    pub fn enhanced_keys(&self) -> bool {
+        self.enhanced_keys
+    }
+
+    /// Makes `size_chars` return `size` instead of the real size of the terminal.
+    ///
+    /// While an override is set, neither the `LINES` and `COLUMNS` environment variables nor the
+    /// terminal are consulted, which makes the reported size deterministic.  This only affects
+    /// this console, unlike changing the environment, so it is safe to use from concurrent tests.
+    /// Passing `None` restores the detection of the size from the environment and the terminal.
+    pub fn set_size_override(&mut self, size: Option<CharsXY>) {
+        self.size_override = size;
+        if let (Some(shadow), Some(size)) = (self.shadow.as_mut(), size) {
+            shadow.resize(size);
+        }
+    }
+
+    /// Copies all output written to the terminal from now on into `file`, replacing any previous
+    /// copy.
+    ///
+    /// The copy receives the same bytes as the terminal after the removal of control characters.
+    /// If `strip_escapes` is true, the escape sequences emitted to change colors, move the cursor,
+    /// and the like are not copied so that the file only contains the printed text.  The file is
+    /// flushed whenever the terminal is, which means that the copy may lag behind while video
+    /// syncing is disabled.
+    pub fn tee_to(&mut self, file: File, strip_escapes: bool) {
+        let strip = if strip_escapes { Some(EscapeState::Text) } else { None };
+        self.tee = Some(Arc::from(Mutex::from(Tee { file: BufWriter::new(file), strip })));
+    }
+
+    /// Returns the counters of the output sent to the terminal since construction or since the
+    /// last call to `reset_stats`.
+    pub fn io_stats(&self) -> ConsoleIoStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Captures the alternate console, cursor visibility, colors and video syncing settings so
+    /// that they can be reinstated later with `restore_state`.
+    ///
+    /// This is useful to hand the console over to code that may change these settings and to
+    /// undo its changes afterwards.
+    pub fn save_state(&self) -> ConsoleState {
+        ConsoleState {
+            alt_depth: self.alt_depth,
+            cursor_visible: self.cursor_visible,
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            sync_enabled: self.sync_enabled,
+        }
+    }
+
+    /// Reinstates the settings captured in `state` by `save_state`.
+    ///
+    /// Only the escape sequences needed to reconcile the differences between the current and the
+    /// saved settings are emitted, and the output is flushed afterwards.
+    pub fn restore_state(&mut self, state: ConsoleState) -> io::Result<()> {
+        let mut stdout = self.lock_output();
+        self.queue_restore_state(&mut stdout, state)?;
+        stdout.flush()
+    }
+
+    /// Queues the commands to go from the current settings to those in `state` into `out`.
+    fn queue_restore_state<W: Write>(
+        &mut self,
+        out: &mut W,
+        state: ConsoleState,
+    ) -> io::Result<()> {
+        while self.alt_depth < state.alt_depth {
+            self.queue_enter_alt(out)?;
+        }
+        while self.alt_depth > state.alt_depth {
+            self.queue_leave_alt(out)?;
+        }
+
+        if self.cursor_visible != state.cursor_visible {
+            if state.cursor_visible {
+                out.queue(cursor::Show)?;
+            } else {
+                out.queue(cursor::Hide)?;
+            }
+            self.cursor_visible = state.cursor_visible;
+        }
+
+        self.queue_color(out, state.fg_color, state.bg_color)?;
+        self.sync_enabled = state.sync_enabled;
+        Ok(())
+    }
+
+    /// Returns the text currently shown on the screen, one string per line and without trailing
+    /// whitespace.
+    ///
+    /// This is only available if the console was built with `TerminalConsoleBuilder::
+    /// track_contents` enabled, and returns an empty vector otherwise.  See the builder for the
+    /// limitations of the tracking.
+    pub fn screen_text(&self) -> Vec<String> {
+        match self.shadow.as_ref() {
+            Some(shadow) => shadow.text(),
+            None => vec![],
+        }
+    }
+
+    /// Writes the escape sequence in `request` to the terminal and returns its reply, which is
+    /// read from the terminal until the `terminator` byte is found.
+    ///
+    /// This is meant to probe the capabilities of the terminal, such as sending `ESC [ c` to
+    /// obtain the device attributes, which the terminal answers on the input stream.  The returned
+    /// bytes include the `terminator`.  If the terminal does not reply within `timeout`, this
+    /// fails with `io::ErrorKind::TimedOut` and any partial reply is lost.  The timeout covers the
+    /// whole operation, so it should span a few hundred milliseconds to give the key handler a
+    /// chance to pause: the handler checks for input in periods of 100 milliseconds.
+    ///
+    /// The key handler runs in the background and reads everything that the terminal sends, so
+    /// this pauses it before writing the request and resumes it once the reply has been read.
+    /// Keys that were already read remain queued and are delivered later as usual.  However, keys
+    /// typed while the query is in progress reach the input stream interleaved with the reply and
+    /// become part of it, and keys typed right before the request may remain buffered inside the
+    /// key handler.  Replies that don't arrive before the timeout are delivered as keys once the
+    /// handler resumes.
+    ///
+    /// This is only possible on Unix systems and when the console is attached to a TTY in raw
+    /// mode: in line-buffered mode, the key handler blocks waiting for a full line and cannot be
+    /// paused.  Queries must not be issued concurrently from different threads.  The key handler
+    /// is awaited while pausing it, so this works on any Tokio runtime, but the wait for the reply
+    /// blocks the calling thread.
+    pub async fn query(
+        &mut self,
+        request: &[u8],
+        terminator: u8,
+        timeout: Duration,
+    ) -> io::Result<Vec<u8>> {
+        if !self.is_tty || !self.raw_mode.load(atomic::Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Cannot query a terminal that is not attached to a TTY in raw mode",
+            ));
+        }
+
+        let deadline = Instant::now() + timeout;
+        self.input_pause.pause(timeout).await?;
+        let result = (|| {
+            let mut stdout = self.lock_output();
+            stdout.write_all(request)?;
+            stdout.flush()?;
+            drop(stdout);
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            read_tty_reply(io::stdin(), terminator, remaining)
+        })();
+        self.input_pause.resume();
+        result
+    }
+
+    /// Changes the colors to `fg` and `bg` and clears the console as described by `how`.
+    ///
+    /// This is equivalent to calling `set_color` followed by `clear` but sends all commands to the
+    /// terminal in a single write, which avoids briefly showing the old background color when
+    /// repainting the whole screen.
+    pub fn set_color_and_clear(
+        &mut self,
+        fg: Option<u8>,
+        bg: Option<u8>,
+        how: ClearType,
+    ) -> io::Result<()> {
+        let mut stdout = self.lock_output();
+        self.queue_color(&mut stdout, fg, bg)?;
+        self.queue_clear(&mut stdout, how)?;
+        self.maybe_flush(stdout)
+    }
+
+    /// Resets the counters returned by `io_stats` to zero.
+    pub fn reset_stats(&mut self) {
+        *self.stats.lock().unwrap() = ConsoleIoStats::default();
+    }
+
+    /// Returns a key sender to inject extra events into the queue maintained by the terminal.
+    ///
+    /// Injected keys share the same unbounded channel as the keys read from the terminal, so both
+    /// are interleaved and delivered in the order in which they arrive.
+    pub fn key_injector(&self) -> Sender<Key> {
+        self.on_key_tx.clone()
+    }
+
+    /// Async task to wait for key events on a raw terminal and translate them into events for the
+    /// console or the machine.
+    ///
+    /// While `raw_mode` is false, keys are instead read from the line-buffered stdin.  Changes to
+    /// `raw_mode` take effect once the read in progress completes, which in line-buffered mode
+    /// means after the user presses Enter.
+    async fn raw_key_handler(
+        on_key_tx: Sender<Key>,
+        signals_tx: Sender<Signal>,
+        options: TerminalConsoleOptions,
+        raw_mode: Arc<AtomicBool>,
+        input_pause: Arc<InputPause>,
+    ) {
+        let mut buffer = VecDeque::default();
+
+        let mut done = false;
+        while !done && !on_key_tx.is_closed() {
+            if input_pause.check() {
+                tokio::time::sleep(INPUT_PAUSE_POLL_PERIOD).await;
+                continue;
+            }
+
+            // Drain any partially-consumed line before going back to raw input so that keys are
+            // delivered in the order in which they were typed.
+            if !raw_mode.load(atomic::Ordering::SeqCst) || !buffer.is_empty() {
+                let key = read_key_from_stdin(&mut buffer).unwrap_or(Key::Unknown);
+                done = key == Key::Eof;
+                let _ = on_key_tx.send(key).await;
+                continue;
+            }
+
+            // Wait for events with a timeout so that we notice when raw mode is disabled.
+            if let Ok(false) = event::poll(RAW_MODE_POLL_PERIOD) {
+                continue;
+            }
+            let key = match event::read() {
+                Ok(event::Event::Key(ev)) => match key_from_event(ev, &options) {
+                    Some(key) => key,
+                    None => continue,
+                },
+                Ok(_) => {
+                    // Not a key event; ignore and try again.
+                    continue;
+                }
+                Err(_) => {
+                    // There is not much we can do if we get an error from crossterm.
+                    Key::Unknown
+                }
+            };
+
+            done = key == Key::Eof;
+            if key == Key::Interrupt {
+                // Handling CTRL+C in this way isn't great because this is not the same as handling
+                // SIGINT on Unix builds.  First, we are unable to stop long-running operations like
+                // sleeps; and second, a real SIGINT will kill the interpreter completely instead of
+                // coming this way.  We need a real signal handler and we probably should not be
+                // running in raw mode all the time.
+                signals_tx
+                    .send(Signal::Break)
+                    .await
+                    .expect("Send to unbounded channel should not have failed")
+            }
+
+            // This should never fail but can if the receiver outruns the console because we
+            // don't await for the handler to terminate (which we cannot do safely because
+            // `Drop` is not async).
+            let _ = on_key_tx.send(key).await;
+        }
+
+        signals_tx.close();
+        on_key_tx.close();
+    }
+
+    /// Async task to wait for key events on a non-raw terminal and translate them into events for
+    /// the console or the machine.
+    ///
+    /// `esc_timeout` specifies how long to wait for the rest of an escape sequence after reading
+    /// an ESC character, and `eof_char` is the character that terminates the input in addition to
+    /// the real end of the stream.
+    async fn stdio_key_handler(
+        on_key_tx: Sender<Key>,
+        esc_timeout: Duration,
+        eof_char: Option<char>,
+    ) {
+        // TODO(jmmv): We should probably install a signal handler here to capture SIGINT and
+        // funnel it to the Machine via signals_rx, as we do in the raw_key_handler.  This would
+        // help ensure both consoles behave in the same way, but there is strictly no need for this
+        // because, when we do not configure the terminal in raw mode, we aren't capturing CTRL+C
+        // and the default system handler will work.
+
+        let mut input = tokio::io::BufReader::new(tokio::io::stdin());
+
+        let mut done = false;
+        while !done && !on_key_tx.is_closed() {
+            let key = match read_key_from(&mut input, esc_timeout, eof_char).await {
+                Ok(key) => key,
+                Err(_) => {
+                    // There is not much we can do if we get an error from stdin.
+                    Key::Unknown
+                }
+            };
+
+            done = key == Key::Eof;
+
+            // This should never fail but can if the receiver outruns the console because we don't
+            // await for the handler to terminate (which we cannot do safely because `Drop` is not
+            // async).
+            let _ = on_key_tx.send(key).await;
+        }
+
+        on_key_tx.close();
+    }
+
+    /// Returns true if colors have to be rendered as text attributes, which only happens when
+    /// requested for a TTY without color support.
+    fn uses_monochrome_attributes(&self) -> bool {
+        self.is_tty && self.monochrome_attributes && self.color_capability == ColorCapability::None
+    }
+
+    /// Queues the commands to change the colors to `fg` and `bg` into `out`, only emitting the
+    /// commands for the colors that actually change.
+    fn queue_color<W: Write>(
+        &mut self,
+        out: &mut W,
+        fg: Option<u8>,
+        bg: Option<u8>,
+    ) -> io::Result<()> {
+        if self.uses_monochrome_attributes() {
+            let old = monochrome_attribute(self.fg_color, self.bg_color);
+            let new = monochrome_attribute(fg, bg);
+            if old != new {
+                if old.is_some() {
+                    out.queue(style::SetAttribute(style::Attribute::Reset))?;
+                }
+                if let Some(attribute) = new {
+                    out.queue(style::SetAttribute(attribute))?;
+                }
+            }
+        }
+        if fg != self.fg_color {
+            if let Some(ct_fg) = downgrade_color(fg, self.color_capability) {
+                out.queue(style::SetForegroundColor(ct_fg))?;
+            }
+            self.fg_color = fg;
+        }
+        if bg != self.bg_color {
+            if let Some(ct_bg) = downgrade_color(bg, self.color_capability) {
+                out.queue(style::SetBackgroundColor(ct_bg))?;
+            }
+            self.bg_color = bg;
+        }
+        Ok(())
+    }
+
+    /// Returns the shadow copy of the screen, if contents are being tracked, after resizing it to
+    /// the current size of the terminal.
+    fn shadow_mut(&mut self) -> Option<&mut ShadowScreen> {
+        let size = match self.shadow {
+            Some(_) => self.size_chars().ok(),
+            None => None,
+        };
+        let shadow = self.shadow.as_mut()?;
+        if let Some(size) = size {
+            shadow.resize(size);
+        }
+        Some(shadow)
+    }
+
+    /// Queues the commands to clear the console as described by `how` into `out`.
+    fn queue_clear<W: Write>(&mut self, out: &mut W, how: ClearType) -> io::Result<()> {
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.clear(&how);
+        }
+        let how = match how {
+            ClearType::All => terminal::ClearType::All,
+            ClearType::CurrentLine => terminal::ClearType::CurrentLine,
+            ClearType::PreviousChar => return out.write_all(b"\x08 \x08"),
+            ClearType::UntilNewLine => terminal::ClearType::UntilNewLine,
+            ClearType::UntilEndOfScreen => terminal::ClearType::FromCursorDown,
+            ClearType::UntilBeginningOfScreen => terminal::ClearType::FromCursorUp,
+        };
+        out.queue(terminal::Clear(how))?;
+        if how == terminal::ClearType::All {
+            out.queue(cursor::MoveTo(0, 0))?;
+        }
+        Ok(())
+    }
+
+    /// Queues the command to enter the alternate console into `out` unless we are already in it,
+    /// and records one more nesting level.
+    fn queue_enter_alt<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.alt_depth == 0 {
+            out.queue(terminal::EnterAlternateScreen)?;
+            if let Some(shadow) = self.shadow_mut() {
+                shadow.enter_alt();
+            }
+        }
+        self.alt_depth += 1;
+        Ok(())
+    }
+
+    /// Records that one nesting level of the alternate console has been left and, if this was
+    /// the outermost one, queues the command to return to the main console into `out`.
+    fn queue_leave_alt<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match self.alt_depth {
+            0 => (),
+            1 => {
+                out.queue(terminal::LeaveAlternateScreen)?;
+                if let Some(shadow) = self.shadow_mut() {
+                    shadow.leave_alt();
+                }
+                self.alt_depth = 0;
+            }
+            _ => self.alt_depth -= 1,
+        }
+        Ok(())
+    }
+
+    /// Locks stdout for writing and sets up the copy of the output if one was requested.
+    fn lock_output(&self) -> Output {
+        #[cfg(test)]
+        let sink = match self.captured.as_ref() {
+            Some(captured) => Sink::Memory(captured.clone()),
+            None => Sink::Stdout(io::stdout().lock()),
+        };
+        #[cfg(not(test))]
+        let sink = Sink::Stdout(io::stdout().lock());
+        Output { sink, tee: self.tee.clone(), stats: self.stats.clone() }
+    }
+
+    /// Flushes the console, which has already been written to via `lock`, if syncing is enabled.
+    fn maybe_flush(&self, mut lock: Output) -> io::Result<()> {
+        if self.sync_enabled {
+            lock.flush()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl InputOps for TerminalConsole {
+    async fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        if let Some(k) = self.pending_keys.pop_front() {
+            return Ok(Some(k));
+        }
+        match self.on_key_rx.try_recv() {
+            Ok(k) => Ok(Some(k)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Closed) => Ok(Some(Key::Eof)),
+        }
+    }
+
+    async fn read_key(&mut self) -> io::Result<Key> {
+        if let Some(k) = self.pending_keys.pop_front() {
+            return Ok(k);
+        }
+        match self.on_key_rx.recv().await {
+            Ok(k) => Ok(k),
+            Err(_) => Ok(Key::Eof),
+        }
+    }
+
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        // `pending_keys` only ever holds keys that must survive draining so there is no need to
+        // inspect it.
+        let mut discarded = 0;
+        while let Ok(key) = self.on_key_rx.try_recv() {
+            match key {
+                Key::Eof | Key::Interrupt => self.pending_keys.push_back(key),
+                _ => discarded += 1,
+            }
+        }
+        Ok(discarded)
+    }
+}
+
+#[async_trait(?Send)]
+impl Console for TerminalConsole {
+    fn clear(&mut self, how: ClearType) -> io::Result<()> {
+        let mut stdout = self.lock_output();
+        self.queue_clear(&mut stdout, how)?;
+        self.maybe_flush(stdout)
+    }
+
+    /// Terminals cannot clear arbitrary pixels, so this overwrites all character cells that
+    /// cover the rectangle with spaces in the current background color.  This relies on the
+    /// terminal reporting its size in pixels, which not all terminals do.
+    ///
+    /// The position of the cursor is preserved via the same mechanism used by `save_cursor`, so
+    /// this discards any position saved with it.
+    fn clear_rect(&mut self, x1y1: PixelsXY, x2y2: PixelsXY) -> io::Result<()> {
+        let (origin, size) = match rect_cells_in(&terminal::window_size()?, x1y1, x2y2)? {
+            Some(cells) => cells,
+            None => return Ok(()),
+        };
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.clear_rect(origin, size);
+        }
+
+        let spaces = " ".repeat(usize::from(size.x));
+        let mut stdout = self.lock_output();
+        stdout.queue(cursor::SavePosition)?;
+        for y in origin.y..origin.y + size.y {
+            stdout.queue(cursor::MoveTo(origin.x, y))?;
+            stdout.write_all(spaces.as_bytes())?;
+        }
+        stdout.queue(cursor::RestorePosition)?;
+        self.maybe_flush(stdout)
+    }
+
+    fn color(&self) -> (Option<u8>, Option<u8>) {
+        (self.fg_color, self.bg_color)
+    }
+
+    fn color_capability(&self) -> ColorCapability {
+        self.color_capability
+    }
+
+    fn set_color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()> {
+        if fg == self.fg_color && bg == self.bg_color {
+            return Ok(());
+        }
+
+        let mut stdout = self.lock_output();
+        self.queue_color(&mut stdout, fg, bg)?;
+        self.maybe_flush(stdout)
+    }
+
+    fn enter_alt(&mut self) -> io::Result<()> {
+        let mut stdout = self.lock_output();
+        self.queue_enter_alt(&mut stdout)?;
+        self.maybe_flush(stdout)
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        if self.cursor_visible {
+            let mut stdout = self.lock_output();
+            stdout.queue(cursor::Hide)?;
+            self.cursor_visible = false;
+            self.maybe_flush(stdout)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.is_tty
+    }
+
+    /// Queries the terminal for the cursor position.
+    ///
+    /// This is only possible when the console is attached to a TTY in raw mode.  The position is
+    /// obtained with `query`, which pauses the key handler while waiting for the reply of the
+    /// terminal, so the reply is never mistaken for keystrokes.  This fails if the terminal does
+    /// not reply in time.
+    async fn cursor_position(&mut self) -> io::Result<CharsXY> {
+        if !self.is_tty {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Cannot query the cursor position of a console not attached to a TTY",
+            ));
+        }
+
+        let reply = self.query(b"\x1b[6n", b'R', CURSOR_POSITION_TIMEOUT).await?;
+        parse_cursor_position(&reply)
+    }
+
+    /// Switches the terminal between raw and line-buffered input.
+    ///
+    /// This is only possible when the console is attached to a TTY.  While raw mode is disabled,
+    /// the terminal echoes typed characters by itself and the system handles CTRL+C as a signal,
+    /// which terminates the interpreter instead of stopping the program.  Dropping the console
+    /// leaves raw mode if it is enabled at that time, so the terminal is always restored to
+    /// line-buffered input on exit.
+    fn set_raw_mode(&mut self, enabled: bool) -> io::Result<bool> {
+        if !self.is_tty {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Cannot change the input mode of a console not attached to a TTY",
+            ));
+        }
+
+        let previous = self.raw_mode.load(atomic::Ordering::SeqCst);
+        if enabled != previous {
+            if enabled {
+                terminal::enable_raw_mode()?;
+                if self.enhanced_keys {
+                    set_keyboard_enhancement(true)?;
+                }
+                if self.mouse_capture {
+                    set_mouse_capture(true)?;
+                }
+            } else {
+                if self.mouse_capture {
+                    set_mouse_capture(false)?;
+                }
+                if self.enhanced_keys {
+                    set_keyboard_enhancement(false)?;
+                }
+                terminal::disable_raw_mode()?;
+            }
+            self.raw_mode.store(enabled, atomic::Ordering::SeqCst);
+        }
+        Ok(previous)
+    }
+
+    /// Controls whether the terminal echoes typed characters while in line-buffered mode.
+    ///
+    /// This is only supported on Unix systems and when stdin is attached to a TTY, which may be
+    /// the case even if stdout is not.  Input is never echoed in raw mode so this does nothing and
+    /// reports echo as disabled while raw mode is enabled.
+    ///
+    /// The echo state that the TTY had before the first change is restored when the console is
+    /// shut down or dropped.
+    fn set_echo(&mut self, enabled: bool) -> io::Result<bool> {
+        if self.raw_mode.load(atomic::Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let previous = set_tty_echo(io::stdin(), enabled)?;
+        if self.original_echo.is_none() {
+            self.original_echo = Some(previous);
+        }
+        Ok(previous)
+    }
+
+    fn leave_alt(&mut self) -> io::Result<()> {
+        let mut stdout = self.lock_output();
+        self.queue_leave_alt(&mut stdout)?;
+        self.maybe_flush(stdout)
+    }
+
+    fn locate(&mut self, pos: CharsXY) -> io::Result<()> {
+        check_locate_pos(pos, self.size_chars()?)?;
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.locate(pos);
+        }
+
+        let mut stdout = self.lock_output();
+        stdout.queue(cursor::MoveTo(pos.x, pos.y))?;
+        self.maybe_flush(stdout)
+    }
+
+    /// Terminals stop the relative cursor movements used here at the margins of the screen, so
+    /// there is no need to know the current column to keep the cursor within the line.
+    fn move_within_line(&mut self, off: i16) -> io::Result<()> {
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.move_within_line(off);
+        }
+        let mut stdout = self.lock_output();
+        match off.cmp(&0) {
+            Ordering::Less => stdout.queue(cursor::MoveLeft(-off as u16)),
+            Ordering::Equal => return Ok(()),
+            Ordering::Greater => stdout.queue(cursor::MoveRight(off as u16)),
+        }?;
+        self.maybe_flush(stdout)
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        let text = filter_control_chars(text);
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.print(&text);
+        }
+        let text = translate_newlines(&text, self.is_tty);
+
+        let mut stdout = self.lock_output();
+        stdout.write_all(text.as_bytes())?;
         if self.is_tty {
             stdout.write_all(b"\r\n")?;
         } else {
@@ -389,14 +1925,62 @@ impl Console for TerminalConsole {
         (self as &mut dyn InputOps).poll_key().await
     }
 
-    async fn read_key(&mut self) -> io::Result<Key> {
-        (self as &mut dyn InputOps).read_key().await
+    async fn read_key(&mut self) -> io::Result<Key> {
+        (self as &mut dyn InputOps).read_key().await
+    }
+
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        (self as &mut dyn InputOps).drain_keys()
+    }
+
+    fn restore_cursor(&mut self) -> io::Result<()> {
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.restore_cursor();
+        }
+        let mut stdout = self.lock_output();
+        stdout.queue(cursor::RestorePosition)?;
+        self.maybe_flush(stdout)
+    }
+
+    fn save_cursor(&mut self) -> io::Result<()> {
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.save_cursor();
+        }
+        let mut stdout = self.lock_output();
+        stdout.queue(cursor::SavePosition)?;
+        self.maybe_flush(stdout)
+    }
+
+    fn scroll_up(&mut self, lines: u16) -> io::Result<()> {
+        if lines == 0 {
+            return Ok(());
+        }
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.scroll_up(lines);
+        }
+
+        let mut stdout = self.lock_output();
+        stdout.queue(terminal::ScrollUp(lines))?;
+        self.maybe_flush(stdout)
+    }
+
+    fn set_scroll_region(&mut self, top: u16, bottom: u16) -> io::Result<()> {
+        let size = self.size_chars()?;
+        if top >= bottom || bottom >= size.y {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid scroll region"));
+        }
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.set_scroll_region(top, bottom);
+        }
+
+        let mut stdout = self.lock_output();
+        write!(stdout, "\x1b[{};{}r", top + 1, bottom + 1)?;
+        self.maybe_flush(stdout)
     }
 
     fn show_cursor(&mut self) -> io::Result<()> {
         if !self.cursor_visible {
-            let stdout = io::stdout();
-            let mut stdout = stdout.lock();
+            let mut stdout = self.lock_output();
             stdout.queue(cursor::Show)?;
             self.cursor_visible = true;
             self.maybe_flush(stdout)
@@ -406,44 +1990,1160 @@ impl Console for TerminalConsole {
     }
 
     fn size_chars(&self) -> io::Result<CharsXY> {
-        // Must be careful to not query the terminal size if both LINES and COLUMNS are set, because
-        // the query fails when we don't have a PTY and we still need to run under these conditions
-        // for testing purposes.
-        let lines = get_env_var_as_u16("LINES");
-        let columns = get_env_var_as_u16("COLUMNS");
-        let size = match (lines, columns) {
-            (Some(l), Some(c)) => CharsXY::new(c, l),
-            (l, c) => {
-                let (actual_columns, actual_lines) = terminal::size()?;
-                CharsXY::new(c.unwrap_or(actual_columns), l.unwrap_or(actual_lines))
-            }
-        };
-        Ok(size)
+        match self.size_override {
+            Some(size) => Ok(size),
+            None => detect_size(),
+        }
+    }
+
+    /// Terminals do not support drawing text at arbitrary pixel positions, but knowing the size
+    /// of the text in pixels is still useful to lay out images shown via `write_raw`.  This relies
+    /// on the terminal reporting its size in pixels, which not all terminals do.
+    fn text_pixel_size(&self, text: &str) -> io::Result<SizeInPixels> {
+        text_pixel_size_in(&terminal::window_size()?, text)
     }
 
     fn write(&mut self, text: &str) -> io::Result<()> {
-        let text = remove_control_chars(text.to_owned());
+        let text = filter_control_chars(text);
+        if let Some(shadow) = self.shadow_mut() {
+            shadow.write(&text);
+        }
+        let text = translate_newlines(&text, self.is_tty);
 
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
+        let mut stdout = self.lock_output();
         stdout.write_all(text.as_bytes())?;
         self.maybe_flush(stdout)
     }
 
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut stdout = self.lock_output();
+        stdout.write_all(bytes)?;
+        self.maybe_flush(stdout)
+    }
+
     fn sync_now(&mut self) -> io::Result<()> {
         if self.sync_enabled {
             Ok(())
         } else {
-            io::stdout().flush()
+            self.lock_output().flush()
         }
     }
 
     fn set_sync(&mut self, enabled: bool) -> io::Result<bool> {
         if !self.sync_enabled {
-            io::stdout().flush()?;
+            self.lock_output().flush()?;
         }
         let previous = self.sync_enabled;
         self.sync_enabled = enabled;
         Ok(previous)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use event::KeyEvent;
+    use tokio::io::AsyncWriteExt;
+
+    /// Creates a console that is not attached to a TTY and that supports the given `capability`.
+    fn new_console(color_capability: ColorCapability) -> TerminalConsole {
+        let (on_key_tx, on_key_rx) = async_channel::unbounded();
+        TerminalConsole {
+            is_tty: false,
+            raw_mode: Arc::from(AtomicBool::new(false)),
+            input_pause: Arc::default(),
+            fg_color: None,
+            bg_color: None,
+            cursor_visible: true,
+            alt_depth: 0,
+            sync_enabled: true,
+            color_capability,
+            enhanced_keys: false,
+            mouse_capture: false,
+            monochrome_attributes: false,
+            size_override: None,
+            shadow: None,
+            original_echo: None,
+            tee: None,
+            stats: Arc::default(),
+            captured: Some(Arc::default()),
+            on_key_rx,
+            on_key_tx,
+            pending_keys: VecDeque::default(),
+            input_task: None,
+        }
+    }
+
+    /// Returns the output that `console`, created by `new_console`, would have sent to stdout.
+    fn captured_output(console: &TerminalConsole) -> String {
+        let captured = console.captured.as_ref().expect("Console must capture its output");
+        String::from_utf8(captured.lock().unwrap().clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_drain_keys() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        for key in [Key::Char('a'), Key::Eof, Key::Char('b'), Key::Interrupt, Key::Char('c')] {
+            console.on_key_tx.try_send(key).unwrap();
+        }
+
+        assert_eq!(3, Console::drain_keys(&mut console).unwrap());
+        assert_eq!(Err(TryRecvError::Empty), console.on_key_rx.try_recv());
+
+        // Keys that arrive after draining must not overtake the ones that were kept.
+        console.on_key_tx.try_send(Key::Char('d')).unwrap();
+        assert_eq!(Some(Key::Eof), Console::poll_key(&mut console).await.unwrap());
+        assert_eq!(Key::Interrupt, Console::read_key(&mut console).await.unwrap());
+        assert_eq!(Some(Key::Char('d')), Console::poll_key(&mut console).await.unwrap());
+        assert_eq!(None, Console::poll_key(&mut console).await.unwrap());
+
+        assert_eq!(0, Console::drain_keys(&mut console).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_input_task() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        let marker = Arc::new(());
+        let task_marker = marker.clone();
+        let on_key_tx = console.on_key_tx.clone();
+        console.input_task = Some(tokio::task::spawn(async move {
+            let _marker = task_marker;
+            while !on_key_tx.is_closed() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }));
+        assert_eq!(2, Arc::strong_count(&marker));
+
+        console.shutdown().await.unwrap();
+        assert_eq!(1, Arc::strong_count(&marker));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_blocked_input_task() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        let marker = Arc::new(());
+        let task_marker = marker.clone();
+        console.input_task = Some(tokio::task::spawn(async move {
+            let _marker = task_marker;
+            std::future::pending::<()>().await;
+        }));
+
+        console.shutdown().await.unwrap();
+        assert_eq!(1, Arc::strong_count(&marker));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_input_channel() {
+        let console = new_console(ColorCapability::Ansi16);
+        let on_key_tx = console.on_key_tx.clone();
+        console.shutdown().await.unwrap();
+        assert!(on_key_tx.is_closed());
+    }
+
+    /// Writes some text with colors to a new console that copies its output into a temporary
+    /// file, and returns the contents of the file.
+    fn tee_output(strip_escapes: bool) -> String {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        {
+            let mut console = new_console(ColorCapability::Ansi16);
+            console.tee_to(file.reopen().unwrap(), strip_escapes);
+            console.write("hello ").unwrap();
+            console.set_color(Some(1), None).unwrap();
+            console.print("world").unwrap();
+        }
+        std::fs::read_to_string(file.path()).unwrap()
+    }
+
+    #[test]
+    fn test_io_stats() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        assert_eq!(ConsoleIoStats::default(), console.io_stats());
+
+        console.print("abc").unwrap();
+        assert_eq!(ConsoleIoStats { bytes_written: 4, flushes: 0 }, console.io_stats());
+
+        console.write("de").unwrap();
+        assert_eq!(ConsoleIoStats { bytes_written: 6, flushes: 1 }, console.io_stats());
+
+        console.set_sync(false).unwrap();
+        console.write("f").unwrap();
+        assert_eq!(ConsoleIoStats { bytes_written: 7, flushes: 1 }, console.io_stats());
+        console.sync_now().unwrap();
+        assert_eq!(ConsoleIoStats { bytes_written: 7, flushes: 2 }, console.io_stats());
+
+        console.reset_stats();
+        assert_eq!(ConsoleIoStats::default(), console.io_stats());
+
+        assert_eq!("abc\ndef", captured_output(&console));
+    }
+
+    #[test]
+    fn test_console_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<TerminalConsole>();
+    }
+
+    #[tokio::test]
+    async fn test_query_requires_tty() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        let e = console.query(b"\x1b[c", b'c', Duration::from_millis(10)).await.unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, e.kind());
+        assert!(!console.input_pause.requested.load(atomic::Ordering::SeqCst));
+    }
+
+    /// Checks that the handler can acknowledge the pause on a single-threaded runtime.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_input_pause_acknowledged() {
+        let pause = Arc::from(InputPause::default());
+        assert!(!pause.check());
+
+        let handler = {
+            let pause = pause.clone();
+            tokio::task::spawn(async move {
+                while !pause.check() {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+                while pause.check() {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+            })
+        };
+        pause.pause(Duration::from_secs(10)).await.unwrap();
+        assert!(pause.check());
+        pause.resume();
+        handler.await.unwrap();
+        assert!(!pause.check());
+    }
+
+    #[tokio::test]
+    async fn test_input_pause_timeout() {
+        let pause = InputPause::default();
+        let e = pause.pause(Duration::from_millis(5)).await.unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, e.kind());
+        assert!(!pause.requested.load(atomic::Ordering::SeqCst));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_tty_reply_until_terminator() {
+        use std::os::unix::net::UnixStream;
+
+        let (mut writer, reader) = UnixStream::pair().unwrap();
+        writer.write_all(b"\x1b[12;5Rextra").unwrap();
+        let reply = read_tty_reply(&reader, b'R', Duration::from_secs(10)).unwrap();
+        assert_eq!(b"\x1b[12;5R".as_ref(), reply.as_slice());
+
+        writer.write_all(b"?").unwrap();
+        let reply = read_tty_reply(&reader, b'?', Duration::from_secs(10)).unwrap();
+        assert_eq!(b"extra?".as_ref(), reply.as_slice());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_tty_reply_errors() {
+        use std::os::unix::net::UnixStream;
+
+        let (mut writer, reader) = UnixStream::pair().unwrap();
+        writer.write_all(b"\x1b[12").unwrap();
+        let e = read_tty_reply(&reader, b'R', Duration::from_millis(10)).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, e.kind());
+
+        drop(writer);
+        let e = read_tty_reply(&reader, b'R', Duration::from_secs(10)).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, e.kind());
+    }
+
+    #[test]
+    fn test_screen_text_not_tracked() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        console.write("abc").unwrap();
+        assert!(console.screen_text().is_empty());
+    }
+
+    #[test]
+    fn test_screen_text_tracked() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        console.shadow = Some(ShadowScreen::new(CharsXY::new(10, 3)));
+        console.set_size_override(Some(CharsXY::new(10, 3)));
+        console.print("first\u{7}").unwrap();
+        console.write("second line").unwrap();
+        console.write_raw(b"raw").unwrap();
+        assert_eq!(vec!["first", "second lin", "e"], console.screen_text());
+    }
+
+    #[test]
+    fn test_screen_text_tracks_alt_screen_and_resizes() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        console.shadow = Some(ShadowScreen::new(CharsXY::new(5, 2)));
+        console.set_size_override(Some(CharsXY::new(5, 2)));
+        console.write("abcd").unwrap();
+        console.enter_alt().unwrap();
+        console.write("x").unwrap();
+        assert_eq!(vec!["    x", ""], console.screen_text());
+        console.leave_alt().unwrap();
+        assert_eq!(vec!["abcd", ""], console.screen_text());
+
+        console.set_size_override(Some(CharsXY::new(3, 1)));
+        assert_eq!(vec!["abc"], console.screen_text());
+    }
+
+    /// Replays the escape sequences used by `set_color_and_clear` in `output` on a screen of
+    /// `size` and returns the foreground and background colors of each cell in row-major order.
+    ///
+    /// Only the sequences emitted by these tests are understood: anything else panics.
+    fn replay_cell_colors(output: &str, size: CharsXY) -> Vec<(Option<u8>, Option<u8>)> {
+        let width = usize::from(size.x);
+        let mut cells = vec![(None, None); width * usize::from(size.y)];
+        let mut current = (None, None);
+        let (mut x, mut y) = (0, 0);
+        let mut chars = output.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\x1b' => {
+                    assert_eq!(Some('['), chars.next());
+                    let mut params = String::new();
+                    let cmd = loop {
+                        match chars.next().expect("Incomplete escape sequence") {
+                            ch if ch.is_ascii_alphabetic() => break ch,
+                            ch => params.push(ch),
+                        }
+                    };
+                    let color = |p: &str| Some(p.parse::<u8>().unwrap());
+                    match (cmd, params.as_str()) {
+                        ('m', "") | ('m', "0") => current = (None, None),
+                        ('m', p) if p.starts_with("38;5;") => current.0 = color(&p[5..]),
+                        ('m', p) if p.starts_with("48;5;") => current.1 = color(&p[5..]),
+                        ('J', "2") => cells.iter_mut().for_each(|cell| *cell = current),
+                        ('H', p) => {
+                            let (row, col) = p.split_once(';').unwrap();
+                            y = row.parse::<usize>().unwrap() - 1;
+                            x = col.parse::<usize>().unwrap() - 1;
+                        }
+                        (cmd, p) => panic!("Unsupported sequence {:?} {:?}", p, cmd),
+                    }
+                }
+                '\r' => x = 0,
+                '\n' => (x, y) = (0, y + 1),
+                ch => {
+                    assert!(!ch.is_control(), "Unsupported control character {:?}", ch);
+                    cells[y * width + x] = current;
+                    x += 1;
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn test_set_color_and_clear() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut console = new_console(ColorCapability::Ansi256);
+        console.shadow = Some(ShadowScreen::new(CharsXY::new(5, 2)));
+        console.set_size_override(Some(CharsXY::new(5, 2)));
+        console.tee_to(file.reopen().unwrap(), false);
+        console.print("old").unwrap();
+        console.set_color_and_clear(Some(3), Some(4), ClearType::All).unwrap();
+        console.write("new").unwrap();
+        assert_eq!((Some(3), Some(4)), console.color());
+        assert_eq!(vec!["new", ""], console.screen_text());
+        assert_eq!(
+            vec![(Some(3), Some(4)); 10],
+            replay_cell_colors(&captured_output(&console), CharsXY::new(5, 2))
+        );
+        drop(console);
+
+        assert_eq!(
+            "old\n\x1b[38;5;3m\x1b[48;5;4m\x1b[2J\x1b[1;1Hnew",
+            std::fs::read_to_string(file.path()).unwrap()
+        );
+    }
+
+    /// Restores `state` into `console` via `queue_restore_state` and returns the emitted commands.
+    fn queue_restore_state_to_string(console: &mut TerminalConsole, state: ConsoleState) -> String {
+        let mut out = vec![];
+        console.queue_restore_state(&mut out, state).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_save_restore_state_round_trip() {
+        let mut console = new_console(ColorCapability::Ansi256);
+        let saved = console.save_state();
+
+        queue_enter_alt_to_string(&mut console);
+        queue_enter_alt_to_string(&mut console);
+        console.cursor_visible = false;
+        queue_color_to_string(&mut console, Some(3), Some(4));
+        console.sync_enabled = false;
+        let changed = console.save_state();
+        assert_ne!(saved, changed);
+
+        assert_eq!(
+            "\x1b[?1049l\x1b[?25h\x1b[39m\x1b[49m",
+            queue_restore_state_to_string(&mut console, saved)
+        );
+        assert_eq!(saved, console.save_state());
+
+        assert_eq!(
+            "\x1b[?1049h\x1b[?25l\x1b[38;5;3m\x1b[48;5;4m",
+            queue_restore_state_to_string(&mut console, changed)
+        );
+        assert_eq!(changed, console.save_state());
+        assert_eq!(2, console.alt_depth);
+
+        // Avoid emitting escape sequences to the test output when the console is dropped.
+        queue_restore_state_to_string(&mut console, saved);
+    }
+
+    #[test]
+    fn test_restore_state_emits_only_differences() {
+        let mut console = new_console(ColorCapability::Ansi256);
+        queue_color_to_string(&mut console, Some(3), None);
+        let saved = console.save_state();
+        assert_eq!("", queue_restore_state_to_string(&mut console, saved));
+
+        queue_color_to_string(&mut console, Some(3), Some(5));
+        assert_eq!("\x1b[49m", queue_restore_state_to_string(&mut console, saved));
+        assert_eq!(saved, console.save_state());
+    }
+
+    #[test]
+    fn test_tee_to_verbatim() {
+        assert_eq!("hello \x1b[38;5;1mworld\n", tee_output(false));
+    }
+
+    #[test]
+    fn test_tee_to_strip_escapes() {
+        assert_eq!("hello world\n", tee_output(true));
+    }
+
+    #[test]
+    fn test_tee_strip_escapes_across_writes() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        {
+            let mut tee = Tee {
+                file: BufWriter::new(file.reopen().unwrap()),
+                strip: Some(EscapeState::Text),
+            };
+            tee.write_all(b"a\x1b[").unwrap();
+            tee.write_all(b"1;2Hb\x1b]0;title\x07c\x1b_Gdata\x1b").unwrap();
+            tee.write_all(b"\\d\x1b7e").unwrap();
+        }
+        assert_eq!("abcde", std::fs::read_to_string(file.path()).unwrap());
+    }
+
+    #[test]
+    fn test_translate_newlines() {
+        assert!(matches!(translate_newlines("a\nb", false), Cow::Borrowed("a\nb")));
+        assert!(matches!(translate_newlines("ab", true), Cow::Borrowed("ab")));
+        assert_eq!("a\r\nb\r\n", translate_newlines("a\nb\n", true));
+    }
+
+    /// Syntactic sugar to instantiate a key event of the given `kind` without modifiers.
+    fn key_event(code: KeyCode, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind)
+    }
+
+    #[test]
+    fn test_key_from_event_press_only_by_default() {
+        let options = TerminalConsoleOptions::default();
+        assert_eq!(
+            Some(Key::ArrowUp),
+            key_from_event(key_event(KeyCode::Up, KeyEventKind::Press), &options)
+        );
+        assert_eq!(None, key_from_event(key_event(KeyCode::Up, KeyEventKind::Repeat), &options));
+        assert_eq!(None, key_from_event(key_event(KeyCode::Up, KeyEventKind::Release), &options));
+    }
+
+    #[test]
+    fn test_key_from_event_repeat_keys() {
+        let options = TerminalConsoleOptions { repeat_keys: true, ..Default::default() };
+        assert_eq!(
+            Some(Key::ArrowUp),
+            key_from_event(key_event(KeyCode::Up, KeyEventKind::Press), &options)
+        );
+        assert_eq!(
+            Some(Key::ArrowUp),
+            key_from_event(key_event(KeyCode::Up, KeyEventKind::Repeat), &options)
+        );
+        assert_eq!(None, key_from_event(key_event(KeyCode::Up, KeyEventKind::Release), &options));
+    }
+
+    #[test]
+    fn test_key_from_event_control_keys() {
+        let options = TerminalConsoleOptions::default();
+        let ev = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Interrupt), key_from_event(ev, &options));
+        let ev = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+        assert_eq!(Some(Key::Char('c')), key_from_event(ev, &options));
+    }
+
+    #[test]
+    fn test_key_from_event_newlines_legacy() {
+        let options = TerminalConsoleOptions::default();
+        let ev = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(Some(Key::NewLine), key_from_event(ev, &options));
+        let ev = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::NewLine), key_from_event(ev, &options));
+        let ev = KeyEvent::new(KeyCode::Char('m'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::NewLine), key_from_event(ev, &options));
+    }
+
+    #[test]
+    fn test_key_from_event_newlines_enhanced() {
+        let options = TerminalConsoleOptions { enhanced_keys: true, ..Default::default() };
+        let ev = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(Some(Key::NewLine), key_from_event(ev, &options));
+        let ev = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Char('\n')), key_from_event(ev, &options));
+        let ev = KeyEvent::new(KeyCode::Char('m'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Char('\r')), key_from_event(ev, &options));
+    }
+
+    #[test]
+    fn test_enhanced_keys_not_a_tty() {
+        let console = new_console(ColorCapability::None);
+        assert!(!console.enhanced_keys());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_tty_echo() {
+        use rustix::pty::{self, OpenptFlags};
+        use rustix::termios::{tcgetattr, LocalModes};
+        use std::ffi::OsStr;
+        use std::fs::OpenOptions;
+        use std::os::unix::ffi::OsStrExt;
+
+        let master = match pty::openpt(OpenptFlags::RDWR | OpenptFlags::NOCTTY) {
+            Ok(master) => master,
+            Err(e) => {
+                eprintln!("Skipping test because a PTY cannot be opened: {}", e);
+                return;
+            }
+        };
+        pty::grantpt(&master).unwrap();
+        pty::unlockpt(&master).unwrap();
+        let name = pty::ptsname(&master, vec![]).unwrap();
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(OsStr::from_bytes(name.as_bytes()))
+            .unwrap();
+
+        set_tty_echo(&tty, true).unwrap();
+        assert!(set_tty_echo(&tty, false).unwrap());
+        assert!(!tcgetattr(&tty).unwrap().local_modes.contains(LocalModes::ECHO));
+        assert!(!set_tty_echo(&tty, true).unwrap());
+        assert!(tcgetattr(&tty).unwrap().local_modes.contains(LocalModes::ECHO));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_tty_echo_not_a_tty() {
+        let file = File::open("/dev/null").unwrap();
+        let e = set_tty_echo(&file, false).unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, e.kind());
+    }
+
+    #[test]
+    fn test_check_locate_pos_ok() {
+        check_locate_pos(CharsXY::new(0, 0), CharsXY::new(80, 24)).unwrap();
+        check_locate_pos(CharsXY::new(79, 23), CharsXY::new(80, 24)).unwrap();
+    }
+
+    #[test]
+    fn test_check_locate_pos_out_of_range() {
+        for pos in [CharsXY::new(80, 0), CharsXY::new(0, 24), CharsXY::new(100, 100)] {
+            let e = check_locate_pos(pos, CharsXY::new(80, 24)).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, e.kind());
+        }
+    }
+
+    #[test]
+    fn test_detect_color_capability_not_a_tty() {
+        assert_eq!(
+            ColorCapability::None,
+            detect_color_capability(false, Some("xterm-256color"), Some("truecolor"))
+        );
+    }
+
+    #[test]
+    fn test_detect_color_capability_dumb() {
+        assert_eq!(ColorCapability::None, detect_color_capability(true, Some("dumb"), None));
+        assert_eq!(ColorCapability::None, detect_color_capability(true, Some(""), None));
+    }
+
+    #[test]
+    fn test_detect_color_capability_levels() {
+        assert_eq!(ColorCapability::Ansi16, detect_color_capability(true, None, None));
+        assert_eq!(ColorCapability::Ansi16, detect_color_capability(true, Some("xterm"), None));
+        assert_eq!(
+            ColorCapability::Ansi256,
+            detect_color_capability(true, Some("xterm-256color"), None)
+        );
+        assert_eq!(
+            ColorCapability::TrueColor,
+            detect_color_capability(true, Some("xterm"), Some("truecolor"))
+        );
+        assert_eq!(
+            ColorCapability::TrueColor,
+            detect_color_capability(true, Some("xterm-256color"), Some("24bit"))
+        );
+    }
+
+    #[test]
+    fn test_downgrade_color() {
+        assert_eq!(None, downgrade_color(Some(3), ColorCapability::None));
+        assert_eq!(None, downgrade_color(None, ColorCapability::None));
+
+        assert_eq!(Some(style::Color::Reset), downgrade_color(None, ColorCapability::Ansi16));
+        assert_eq!(
+            Some(style::Color::DarkYellow),
+            downgrade_color(Some(3), ColorCapability::Ansi16)
+        );
+        assert_eq!(Some(style::Color::White), downgrade_color(Some(231), ColorCapability::Ansi16));
+
+        assert_eq!(
+            Some(style::Color::AnsiValue(231)),
+            downgrade_color(Some(231), ColorCapability::Ansi256)
+        );
+        assert_eq!(
+            Some(style::Color::AnsiValue(231)),
+            downgrade_color(Some(231), ColorCapability::TrueColor)
+        );
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let (signals_tx, _signals_rx) = async_channel::unbounded();
+        let builder = TerminalConsoleBuilder::new(signals_tx);
+        let defaults = TerminalConsoleOptions::default();
+        assert_eq!(defaults.repeat_keys, builder.options.repeat_keys);
+        assert_eq!(defaults.interrupt_key, builder.options.interrupt_key);
+        assert_eq!(defaults.eof_key, builder.options.eof_key);
+        assert_eq!(defaults.enhanced_keys, builder.options.enhanced_keys);
+        assert_eq!(defaults.esc_timeout, builder.options.esc_timeout);
+        assert!(builder.sync_enabled);
+        assert!(builder.cursor_visible);
+        assert!(!builder.mouse_capture);
+        assert!(!builder.monochrome_attributes);
+        assert_eq!(None, builder.size_override);
+        assert!(!builder.track_contents);
+    }
+
+    #[test]
+    fn test_builder_setters() {
+        let (signals_tx, _signals_rx) = async_channel::unbounded();
+        let builder = TerminalConsoleBuilder::new(signals_tx)
+            .repeat_keys(true)
+            .interrupt_key(KeyCode::Esc, KeyModifiers::NONE)
+            .eof_key(None)
+            .enhanced_keys(true)
+            .esc_timeout(Duration::from_millis(5))
+            .sync_enabled(false)
+            .cursor_visible(false)
+            .mouse_capture(true)
+            .monochrome_attributes(true)
+            .size_override(Some(CharsXY::new(40, 10)))
+            .track_contents(true);
+        assert!(builder.options.repeat_keys);
+        assert_eq!((KeyCode::Esc, KeyModifiers::NONE), builder.options.interrupt_key);
+        assert_eq!(None, builder.options.eof_key);
+        assert!(builder.options.enhanced_keys);
+        assert_eq!(Duration::from_millis(5), builder.options.esc_timeout);
+        assert!(!builder.sync_enabled);
+        assert!(!builder.cursor_visible);
+        assert!(builder.mouse_capture);
+        assert!(builder.monochrome_attributes);
+        assert_eq!(Some(CharsXY::new(40, 10)), builder.size_override);
+        assert!(builder.track_contents);
+    }
+
+    #[test]
+    fn test_key_from_event_custom_interrupt_key() {
+        let options = TerminalConsoleOptions {
+            interrupt_key: (KeyCode::Char('\\'), KeyModifiers::CONTROL),
+            ..Default::default()
+        };
+        let ev = KeyEvent::new(KeyCode::Char('\\'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Interrupt), key_from_event(ev, &options));
+        let ev = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Char('\u{3}')), key_from_event(ev, &options));
+
+        let options = TerminalConsoleOptions {
+            interrupt_key: (KeyCode::Esc, KeyModifiers::NONE),
+            ..Default::default()
+        };
+        let ev = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(Some(Key::Interrupt), key_from_event(ev, &options));
+    }
+
+    #[test]
+    fn test_key_from_event_custom_eof_key() {
+        let options = TerminalConsoleOptions::default();
+        let ev = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Eof), key_from_event(ev, &options));
+
+        let options = TerminalConsoleOptions {
+            eof_key: Some((KeyCode::Char('q'), KeyModifiers::CONTROL)),
+            ..Default::default()
+        };
+        let ev = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Eof), key_from_event(ev, &options));
+        let ev = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Char('\u{4}')), key_from_event(ev, &options));
+
+        let options = TerminalConsoleOptions { eof_key: None, ..Default::default() };
+        let ev = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(Some(Key::Char('\u{4}')), key_from_event(ev, &options));
+    }
+
+    #[test]
+    fn test_options_eof_char() {
+        assert_eq!(Some('\u{4}'), TerminalConsoleOptions::default().eof_char());
+
+        let eof_char =
+            |eof_key| TerminalConsoleOptions { eof_key, ..Default::default() }.eof_char();
+        assert_eq!(Some('\u{11}'), eof_char(Some((KeyCode::Char('Q'), KeyModifiers::CONTROL))));
+        assert_eq!(Some('~'), eof_char(Some((KeyCode::Char('~'), KeyModifiers::NONE))));
+        assert_eq!(None, eof_char(Some((KeyCode::Char('1'), KeyModifiers::CONTROL))));
+        assert_eq!(None, eof_char(Some((KeyCode::F(1), KeyModifiers::NONE))));
+        assert_eq!(None, eof_char(None));
+    }
+
+    #[test]
+    fn test_options_validate_eof_key() {
+        let validate =
+            |eof_key| TerminalConsoleOptions { eof_key, ..Default::default() }.validate();
+        validate(None).unwrap();
+        validate(Some((KeyCode::Char('d'), KeyModifiers::CONTROL))).unwrap();
+        validate(Some((KeyCode::Char('q'), KeyModifiers::NONE))).unwrap();
+        validate(Some((KeyCode::F(1), KeyModifiers::NONE))).unwrap();
+
+        for eof_key in [
+            (KeyCode::Enter, KeyModifiers::NONE),
+            (KeyCode::Enter, KeyModifiers::CONTROL),
+            (KeyCode::Char('j'), KeyModifiers::CONTROL),
+            (KeyCode::Char('M'), KeyModifiers::CONTROL),
+            (KeyCode::Char('\n'), KeyModifiers::NONE),
+            (KeyCode::Char('\r'), KeyModifiers::NONE),
+        ] {
+            let e = validate(Some(eof_key)).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, e.kind());
+            assert_eq!("EOF key cannot be a key that also produces a line break", e.to_string());
+        }
+    }
+
+    /// Queues a color change from the current state of `console` to `fg` and `bg` and returns
+    /// the emitted escape sequences.
+    fn queue_color_to_string(
+        console: &mut TerminalConsole,
+        fg: Option<u8>,
+        bg: Option<u8>,
+    ) -> String {
+        let mut out = vec![];
+        console.queue_color(&mut out, fg, bg).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cursor_position_requires_tty() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        assert_eq!(io::ErrorKind::Unsupported, console.cursor_position().await.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_parse_cursor_position() {
+        assert_eq!(CharsXY::new(0, 0), parse_cursor_position(b"\x1b[1;1R").unwrap());
+        assert_eq!(CharsXY::new(4, 11), parse_cursor_position(b"\x1b[12;5R").unwrap());
+        assert_eq!(CharsXY::new(79, 23), parse_cursor_position(b"ab\x1b[A\x1b[24;80R").unwrap());
+
+        for reply in [
+            b"".as_ref(),
+            b"12;5R",
+            b"\x1b[12;5",
+            b"\x1b[12R",
+            b"\x1b[0;5R",
+            b"\x1b[12;0R",
+            b"\x1b[a;5R",
+            b"\x1b[12;99999R",
+        ] {
+            let e = parse_cursor_position(reply).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, e.kind(), "Reply {:?}", reply);
+        }
+    }
+
+    #[test]
+    fn test_set_raw_mode_requires_tty() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        for enabled in [false, true] {
+            assert_eq!(
+                io::ErrorKind::Unsupported,
+                console.set_raw_mode(enabled).unwrap_err().kind()
+            );
+        }
+        assert!(!console.raw_mode.load(atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_queue_color_only_emits_changes() {
+        let mut console = new_console(ColorCapability::Ansi256);
+        assert_eq!(
+            "\x1b[38;5;3m\x1b[48;5;4m",
+            queue_color_to_string(&mut console, Some(3), Some(4))
+        );
+        assert_eq!("", queue_color_to_string(&mut console, Some(3), Some(4)));
+        assert_eq!("\x1b[38;5;5m", queue_color_to_string(&mut console, Some(5), Some(4)));
+    }
+
+    #[test]
+    fn test_monochrome_attribute() {
+        assert_eq!(None, monochrome_attribute(None, None));
+        assert_eq!(Some(style::Attribute::Bold), monochrome_attribute(Some(1), None));
+        assert_eq!(Some(style::Attribute::Reverse), monochrome_attribute(None, Some(2)));
+        assert_eq!(Some(style::Attribute::Reverse), monochrome_attribute(Some(1), Some(2)));
+    }
+
+    #[test]
+    fn test_queue_color_monochrome_attributes() {
+        let mut console = new_console(ColorCapability::None);
+        console.is_tty = true;
+        console.monochrome_attributes = true;
+        assert_eq!("\x1b[1m", queue_color_to_string(&mut console, Some(3), None));
+        assert_eq!("", queue_color_to_string(&mut console, Some(4), None));
+        assert_eq!("\x1b[0m\x1b[7m", queue_color_to_string(&mut console, Some(4), Some(1)));
+        assert_eq!("", queue_color_to_string(&mut console, None, Some(2)));
+        assert_eq!("\x1b[0m", queue_color_to_string(&mut console, None, None));
+        assert_eq!("", queue_color_to_string(&mut console, None, None));
+        // Avoid restoring the terminal state on drop, which would write to stdout.
+        console.is_tty = false;
+    }
+
+    #[test]
+    fn test_queue_color_monochrome_attributes_not_tty() {
+        let mut console = new_console(ColorCapability::None);
+        console.monochrome_attributes = true;
+        assert_eq!("", queue_color_to_string(&mut console, Some(3), Some(4)));
+        assert_eq!((Some(3), Some(4)), console.color());
+    }
+
+    #[test]
+    fn test_queue_color_monochrome_attributes_disabled() {
+        let mut console = new_console(ColorCapability::None);
+        assert_eq!("", queue_color_to_string(&mut console, Some(3), Some(4)));
+        assert_eq!((Some(3), Some(4)), console.color());
+    }
+
+    #[test]
+    fn test_queue_color_monochrome_attributes_with_colors() {
+        let mut console = new_console(ColorCapability::Ansi256);
+        console.monochrome_attributes = true;
+        assert_eq!("\x1b[38;5;3m", queue_color_to_string(&mut console, Some(3), None));
+    }
+
+    #[test]
+    fn test_queue_color_reset_background() {
+        let mut console = new_console(ColorCapability::Ansi256);
+        queue_color_to_string(&mut console, Some(3), Some(4));
+        let (fg, _bg) = console.color();
+        assert_eq!("\x1b[49m", queue_color_to_string(&mut console, fg, None));
+        assert_eq!((Some(3), None), console.color());
+    }
+
+    #[test]
+    fn test_queue_color_reset_foreground() {
+        let mut console = new_console(ColorCapability::Ansi256);
+        queue_color_to_string(&mut console, Some(3), Some(4));
+        let (_fg, bg) = console.color();
+        assert_eq!("\x1b[39m", queue_color_to_string(&mut console, None, bg));
+        assert_eq!((None, Some(4)), console.color());
+    }
+
+    #[test]
+    fn test_queue_color_no_capability_tracks_state() {
+        let mut console = new_console(ColorCapability::None);
+        assert_eq!("", queue_color_to_string(&mut console, Some(3), Some(4)));
+        assert_eq!((Some(3), Some(4)), console.color());
+    }
+
+    /// Enters the alternate console via `queue_enter_alt` and returns the emitted commands.
+    fn queue_enter_alt_to_string(console: &mut TerminalConsole) -> String {
+        let mut out = vec![];
+        console.queue_enter_alt(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Leaves the alternate console via `queue_leave_alt` and returns the emitted commands.
+    fn queue_leave_alt_to_string(console: &mut TerminalConsole) -> String {
+        let mut out = vec![];
+        console.queue_leave_alt(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_alt_enter_leave() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        assert_eq!("\x1b[?1049h", queue_enter_alt_to_string(&mut console));
+        assert_eq!(1, console.alt_depth);
+        assert_eq!("\x1b[?1049l", queue_leave_alt_to_string(&mut console));
+        assert_eq!(0, console.alt_depth);
+    }
+
+    #[test]
+    fn test_alt_nested() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        assert_eq!("\x1b[?1049h", queue_enter_alt_to_string(&mut console));
+        assert_eq!("", queue_enter_alt_to_string(&mut console));
+        assert_eq!("", queue_enter_alt_to_string(&mut console));
+        assert_eq!(3, console.alt_depth);
+
+        assert_eq!("", queue_leave_alt_to_string(&mut console));
+        assert_eq!("", queue_leave_alt_to_string(&mut console));
+        assert_eq!(1, console.alt_depth);
+        assert_eq!("\x1b[?1049l", queue_leave_alt_to_string(&mut console));
+        assert_eq!(0, console.alt_depth);
+
+        assert_eq!("\x1b[?1049h", queue_enter_alt_to_string(&mut console));
+        assert_eq!(1, console.alt_depth);
+    }
+
+    #[test]
+    fn test_alt_leave_without_enter() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        assert_eq!("", queue_leave_alt_to_string(&mut console));
+        assert_eq!(0, console.alt_depth);
+        assert_eq!("\x1b[?1049h", queue_enter_alt_to_string(&mut console));
+        assert_eq!(1, console.alt_depth);
+    }
+
+    #[test]
+    fn test_text_pixel_size_in() {
+        let window = terminal::WindowSize { rows: 25, columns: 80, width: 640, height: 400 };
+        assert_eq!(
+            io::ErrorKind::InvalidInput,
+            text_pixel_size_in(&window, "").unwrap_err().kind()
+        );
+        assert_eq!(SizeInPixels::new(40, 16), text_pixel_size_in(&window, "Hello").unwrap());
+        assert_eq!(
+            io::ErrorKind::InvalidInput,
+            text_pixel_size_in(&window, &"x".repeat(10000)).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn test_rect_cells_in() {
+        let window = terminal::WindowSize { rows: 25, columns: 80, width: 640, height: 400 };
+        assert_eq!(
+            Some((CharsXY::new(1, 0), CharsXY::new(2, 2))),
+            rect_cells_in(&window, PixelsXY::new(8, 0), PixelsXY::new(23, 16)).unwrap()
+        );
+        assert_eq!(
+            Some((CharsXY::new(1, 0), CharsXY::new(2, 2))),
+            rect_cells_in(&window, PixelsXY::new(23, 16), PixelsXY::new(8, 0)).unwrap()
+        );
+        assert_eq!(
+            Some((CharsXY::new(0, 0), CharsXY::new(1, 1))),
+            rect_cells_in(&window, PixelsXY::new(3, 4), PixelsXY::new(3, 4)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rect_cells_in_clipped() {
+        let window = terminal::WindowSize { rows: 25, columns: 80, width: 640, height: 400 };
+        assert_eq!(
+            Some((CharsXY::new(0, 24), CharsXY::new(80, 1))),
+            rect_cells_in(&window, PixelsXY::new(-100, 390), PixelsXY::new(1000, 1000)).unwrap()
+        );
+        assert_eq!(
+            None,
+            rect_cells_in(&window, PixelsXY::new(-10, -10), PixelsXY::new(-1, 5)).unwrap()
+        );
+        assert_eq!(
+            None,
+            rect_cells_in(&window, PixelsXY::new(640, 0), PixelsXY::new(700, 5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rect_cells_in_unknown_pixels() {
+        let window = terminal::WindowSize { rows: 25, columns: 80, width: 0, height: 0 };
+        assert_eq!(
+            io::ErrorKind::Unsupported,
+            rect_cells_in(&window, PixelsXY::new(0, 0), PixelsXY::new(5, 5)).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn test_text_pixel_size_in_unknown_pixels() {
+        let window = terminal::WindowSize { rows: 25, columns: 80, width: 0, height: 0 };
+        assert_eq!(
+            io::ErrorKind::Unsupported,
+            text_pixel_size_in(&window, "Hello").unwrap_err().kind()
+        );
+    }
+
+    /// Calls `detect_size_with` with an environment that only contains `vars` and with a terminal
+    /// that reports a size of 100x50.
+    fn detect_size_with_env(vars: &[(&str, &str)]) -> CharsXY {
+        let getenv =
+            |name: &str| vars.iter().find(|(k, _v)| *k == name).map(|(_k, v)| (*v).to_owned());
+        detect_size_with(getenv, || Ok((100, 50))).unwrap()
+    }
+
+    #[test]
+    fn test_detect_size_overrides() {
+        assert_eq!(CharsXY::new(100, 50), detect_size_with_env(&[]));
+        assert_eq!(CharsXY::new(80, 50), detect_size_with_env(&[("COLUMNS", "80")]));
+        assert_eq!(CharsXY::new(100, 24), detect_size_with_env(&[("LINES", "24")]));
+        assert_eq!(
+            CharsXY::new(80, 24),
+            detect_size_with(
+                |name| match name {
+                    "COLUMNS" => Some("80".to_owned()),
+                    "LINES" => Some("24".to_owned()),
+                    _ => None,
+                },
+                || panic!("Terminal must not be queried"),
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_detect_size_ignores_zero() {
+        assert_eq!(CharsXY::new(100, 50), detect_size_with_env(&[("COLUMNS", "0")]));
+        assert_eq!(
+            CharsXY::new(100, 24),
+            detect_size_with_env(&[("COLUMNS", "0"), ("LINES", "24")])
+        );
+        assert_eq!(
+            CharsXY::new(80, 50),
+            detect_size_with_env(&[("COLUMNS", "80"), ("LINES", "0")])
+        );
+    }
+
+    #[test]
+    fn test_detect_size_ignores_garbage() {
+        assert_eq!(CharsXY::new(100, 50), detect_size_with_env(&[("COLUMNS", "abc")]));
+        assert_eq!(
+            CharsXY::new(100, 50),
+            detect_size_with_env(&[("COLUMNS", "abc"), ("LINES", "-3")])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_key_from_lone_escape_then_eof() {
+        let mut input: &[u8] = b"\x1b";
+        assert_eq!(
+            Key::Escape,
+            read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap()
+        );
+        assert_eq!(Key::Eof, read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_read_key_from_escape_sequences() {
+        let mut input: &[u8] = b"\x1b[A\x1b[B\x1b[C\x1b[D\x1b[H\x1b[F\x1b[Z";
+        for exp_key in [
+            Key::ArrowUp,
+            Key::ArrowDown,
+            Key::ArrowRight,
+            Key::ArrowLeft,
+            Key::Home,
+            Key::End,
+            Key::Unknown,
+            Key::Eof,
+        ] {
+            assert_eq!(
+                exp_key,
+                read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_key_from_escape_then_char() {
+        let mut input: &[u8] = b"\x1bx";
+        assert_eq!(
+            Key::Escape,
+            read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap()
+        );
+        assert_eq!(
+            Key::Char('x'),
+            read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap()
+        );
+        assert_eq!(Key::Eof, read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_read_key_from_chars() {
+        let mut input: &[u8] = "añ\r\n\x01".as_bytes();
+        for exp_key in [Key::Char('a'), Key::Char('ñ'), Key::NewLine, Key::Unknown, Key::Eof] {
+            assert_eq!(
+                exp_key,
+                read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_key_from_eof_char() {
+        let mut input: &[u8] = b"a\x04b";
+        let eof_char = Some('\u{4}');
+        for exp_key in [Key::Char('a'), Key::Eof, Key::Char('b'), Key::Eof] {
+            assert_eq!(
+                exp_key,
+                read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, eof_char).await.unwrap()
+            );
+        }
+
+        let mut input: &[u8] = b"a\x04";
+        for exp_key in [Key::Char('a'), Key::Unknown, Key::Eof] {
+            assert_eq!(
+                exp_key,
+                read_key_from(&mut input, DEFAULT_ESC_TIMEOUT, None).await.unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_key_from_escape_timeout() {
+        let (mut tx, rx) = tokio::io::duplex(16);
+        let mut input = tokio::io::BufReader::new(rx);
+        let timeout = Duration::from_millis(10);
+
+        tx.write_all(b"\x1b").await.unwrap();
+        assert_eq!(Key::Escape, read_key_from(&mut input, timeout, None).await.unwrap());
+
+        tx.write_all(b"[A\x1b").await.unwrap();
+        assert_eq!(Key::Char('['), read_key_from(&mut input, timeout, None).await.unwrap());
+        assert_eq!(Key::Char('A'), read_key_from(&mut input, timeout, None).await.unwrap());
+
+        let writer = tokio::task::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            tx.write_all(b"[B").await.unwrap();
+        });
+        assert_eq!(
+            Key::ArrowDown,
+            read_key_from(&mut input, Duration::from_secs(10), None).await.unwrap()
+        );
+        writer.await.unwrap();
+        assert_eq!(Key::Eof, read_key_from(&mut input, timeout, None).await.unwrap());
+    }
+
+    #[test]
+    fn test_size_override() {
+        let mut console = new_console(ColorCapability::Ansi16);
+        console.set_size_override(Some(CharsXY::new(30, 10)));
+        assert_eq!(CharsXY::new(30, 10), console.size_chars().unwrap());
+
+        assert_eq!(
+            "Invalid scroll region",
+            console.set_scroll_region(5, 10).unwrap_err().to_string()
+        );
+
+        console.set_size_override(Some(CharsXY::new(7, 3)));
+        assert_eq!(CharsXY::new(7, 3), console.size_chars().unwrap());
+    }
+}
@@ -0,0 +1,192 @@
+// EndBASIC
+// Copyright 2026 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Playback of canned keystrokes from a script file.
+
+use async_channel::Sender;
+use endbasic_core::exec::Signal;
+use endbasic_std::console::Key;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// A single step of an input script.
+#[derive(Debug, Eq, PartialEq)]
+enum Step {
+    /// Sends the given keys in order.
+    Keys(Vec<Key>),
+
+    /// Pauses playback for the given duration.
+    Sleep(Duration),
+}
+
+/// Parses the contents of an input script into the steps to execute.
+///
+/// Each line of the script holds one directive:
+///
+/// *   `key NAME` sends the key named `NAME`, as accepted by `Key::from_name`.
+/// *   `text STRING` types every character in `STRING`, which extends until the end of the line.
+/// *   `sleep N` pauses playback for `N` milliseconds.
+///
+/// Empty lines and lines starting with `#` are ignored.
+fn parse_script(script: &str) -> io::Result<Vec<Step>> {
+    let mut steps = vec![];
+    for (i, line) in script.lines().enumerate() {
+        let err = |message: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Line {}: {}", i + 1, message))
+        };
+
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let (directive, arg) = match line.split_once(' ') {
+            Some((directive, arg)) => (directive, arg),
+            None => (line, ""),
+        };
+        match directive {
+            "key" => match Key::from_name(arg.trim()) {
+                Some(key) => steps.push(Step::Keys(vec![key])),
+                None => return Err(err(format!("Unknown key name '{}'", arg.trim()))),
+            },
+            "text" => steps.push(Step::Keys(arg.chars().map(Key::Char).collect())),
+            "sleep" => match arg.trim().parse::<u64>() {
+                Ok(ms) => steps.push(Step::Sleep(Duration::from_millis(ms))),
+                Err(_) => return Err(err(format!("Invalid sleep duration '{}'", arg.trim()))),
+            },
+            _ => return Err(err(format!("Unknown directive '{}'", directive))),
+        }
+    }
+    Ok(steps)
+}
+
+/// Reads the input script at `path` and feeds its keystrokes into `sender`, honoring any pauses.
+///
+/// `sender` is meant to be the key injector returned by `TerminalConsole::from_stdio_with_injector`
+/// so that the scripted keys are interleaved with any live input.  See `parse_script` for the
+/// format of the script.  The whole script is validated before any key is sent.
+///
+/// Like a live CTRL+C, a scripted `Key::Interrupt` also raises `Signal::Break` via `signals_tx`
+/// so that it can stop a running program.
+pub async fn play_input_script(
+    sender: Sender<Key>,
+    signals_tx: Sender<Signal>,
+    path: &Path,
+) -> io::Result<()> {
+    let script = fs::read_to_string(path)?;
+    for step in parse_script(&script)? {
+        match step {
+            Step::Keys(keys) => {
+                for key in keys {
+                    if key == Key::Interrupt && signals_tx.send(Signal::Break).await.is_err() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::BrokenPipe,
+                            "Interpreter stopped accepting signals",
+                        ));
+                    }
+                    if sender.send(key).await.is_err() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::BrokenPipe,
+                            "Console stopped accepting keys",
+                        ));
+                    }
+                }
+            }
+            Step::Sleep(duration) => tokio::time::sleep(duration).await,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_script_ok() {
+        let script = "# Demo\ntext PRINT 1\nkey NewLine\n\nsleep 250\nkey ctrl+c\ntext  a \n";
+        assert_eq!(
+            vec![
+                Step::Keys("PRINT 1".chars().map(Key::Char).collect()),
+                Step::Keys(vec![Key::NewLine]),
+                Step::Sleep(Duration::from_millis(250)),
+                Step::Keys(vec![Key::Interrupt]),
+                Step::Keys(vec![Key::Char(' '), Key::Char('a'), Key::Char(' ')]),
+            ],
+            parse_script(script).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_script_errors() {
+        for (script, exp_error) in [
+            ("key Foo", "Line 1: Unknown key name 'Foo'"),
+            ("text a\nsleep", "Line 2: Invalid sleep duration ''"),
+            ("sleep -1", "Line 1: Invalid sleep duration '-1'"),
+            ("\npress x", "Line 2: Unknown directive 'press'"),
+        ] {
+            assert_eq!(exp_error, parse_script(script).unwrap_err().to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_play_input_script() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"text ab\nsleep 1\nkey Escape\n").unwrap();
+
+        let (tx, rx) = async_channel::unbounded();
+        let (signals_tx, signals_rx) = async_channel::unbounded();
+        play_input_script(tx, signals_tx, file.path()).await.unwrap();
+        assert_eq!(Key::Char('a'), rx.recv().await.unwrap());
+        assert_eq!(Key::Char('b'), rx.recv().await.unwrap());
+        assert_eq!(Key::Escape, rx.recv().await.unwrap());
+        assert!(rx.is_empty());
+        assert!(signals_rx.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_play_input_script_interrupt_raises_break() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            b"text a
+key ctrl+c
+",
+        )
+        .unwrap();
+
+        let (tx, rx) = async_channel::unbounded();
+        let (signals_tx, signals_rx) = async_channel::unbounded();
+        play_input_script(tx, signals_tx, file.path()).await.unwrap();
+        assert_eq!(Key::Char('a'), rx.recv().await.unwrap());
+        assert_eq!(Key::Interrupt, rx.recv().await.unwrap());
+        assert!(rx.is_empty());
+        assert_eq!(Signal::Break, signals_rx.recv().await.unwrap());
+        assert!(signals_rx.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_play_input_script_closed_channel() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"text a\n").unwrap();
+
+        let (tx, rx) = async_channel::unbounded();
+        drop(rx);
+        let (signals_tx, _signals_rx) = async_channel::unbounded();
+        let err = play_input_script(tx, signals_tx, file.path()).await.unwrap_err();
+        assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+    }
+}
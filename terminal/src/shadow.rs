@@ -0,0 +1,435 @@
+// EndBASIC
+// Copyright 2026 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Shadow copy of the text shown by the terminal.
+
+use endbasic_std::console::{CharsXY, ClearType};
+
+/// In-memory copy of the characters on the screen of a terminal.
+///
+/// Terminals cannot be read back, so this replays the operations that the console sends to the
+/// terminal on a grid of characters.  The grid mimics the behavior of a terminal that wraps long
+/// lines and scrolls the scroll region when writing past its last line, and keeps the contents of
+/// the main screen aside while the alternate screen is in use.
+pub(crate) struct ShadowScreen {
+    /// Size of the grid.
+    size: CharsXY,
+
+    /// Characters on the screen in row-major order.
+    cells: Vec<char>,
+
+    /// Position of the cursor.  The column can be equal to the width of the grid after writing
+    /// the last character of a line, in which case the line wraps on the next write.
+    cursor: CharsXY,
+
+    /// Position of the cursor recorded by the last call to `save_cursor`.
+    saved_cursor: Option<CharsXY>,
+
+    /// First and last rows, both inclusive, of the scroll region.
+    region: (u16, u16),
+
+    /// Contents and cursor position of the main screen while the alternate screen is in use.
+    main: Option<(Vec<char>, CharsXY)>,
+}
+
+/// Copies the `cells` of a grid of `old` size into a new grid of `new` size, keeping the contents
+/// anchored at the top-left corner.
+fn resize_cells(cells: &[char], old: CharsXY, new: CharsXY) -> Vec<char> {
+    let mut resized = vec![' '; usize::from(new.x) * usize::from(new.y)];
+    let width = usize::from(old.x.min(new.x));
+    for y in 0..usize::from(old.y.min(new.y)) {
+        let src = y * usize::from(old.x);
+        let dst = y * usize::from(new.x);
+        resized[dst..dst + width].copy_from_slice(&cells[src..src + width]);
+    }
+    resized
+}
+
+impl ShadowScreen {
+    /// Creates a new empty screen of `size`.
+    pub(crate) fn new(size: CharsXY) -> Self {
+        let cells = vec![' '; usize::from(size.x) * usize::from(size.y)];
+        Self {
+            size,
+            cells,
+            cursor: CharsXY::new(0, 0),
+            saved_cursor: None,
+            region: (0, size.y.saturating_sub(1)),
+            main: None,
+        }
+    }
+
+    /// Records that the terminal was resized to `size`.
+    ///
+    /// The contents are kept anchored at the top-left corner, the cursor is moved within the new
+    /// bounds and the scroll region is reset to the whole screen.
+    pub(crate) fn resize(&mut self, size: CharsXY) {
+        if size == self.size {
+            return;
+        }
+
+        self.cells = resize_cells(&self.cells, self.size, size);
+        if let Some((cells, _cursor)) = self.main.as_mut() {
+            *cells = resize_cells(cells, self.size, size);
+        }
+        let clamp =
+            |pos: CharsXY| CharsXY::new(pos.x.min(size.x), pos.y.min(size.y.saturating_sub(1)));
+        self.cursor = clamp(self.cursor);
+        self.saved_cursor = self.saved_cursor.map(clamp);
+        if let Some((_cells, cursor)) = self.main.as_mut() {
+            *cursor = clamp(*cursor);
+        }
+        self.region = (0, size.y.saturating_sub(1));
+        self.size = size;
+    }
+
+    /// Returns the index into `cells` of the character at `x` and `y`.
+    fn offset(&self, x: u16, y: u16) -> usize {
+        usize::from(y) * usize::from(self.size.x) + usize::from(x)
+    }
+
+    /// Blanks the cells from the `start` offset up to, but not including, the `end` offset.
+    fn blank(&mut self, start: usize, end: usize) {
+        let end = end.min(self.cells.len());
+        if start < end {
+            self.cells[start..end].iter_mut().for_each(|cell| *cell = ' ');
+        }
+    }
+
+    /// Moves the cursor to the beginning of the next line, scrolling the scroll region if the
+    /// cursor is on its last line.
+    fn newline(&mut self) {
+        self.cursor.x = 0;
+        if self.cursor.y == self.region.1 {
+            self.scroll_up(1);
+        } else if self.cursor.y + 1 < self.size.y {
+            self.cursor.y += 1;
+        }
+    }
+
+    /// Records that `text` was written at the cursor position.  `text` must not contain control
+    /// characters other than newlines.
+    pub(crate) fn write(&mut self, text: &str) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        for ch in text.chars() {
+            if ch == '\n' {
+                self.newline();
+                continue;
+            }
+
+            if self.cursor.x >= self.size.x {
+                self.newline();
+            }
+            let offset = self.offset(self.cursor.x, self.cursor.y);
+            self.cells[offset] = ch;
+            self.cursor.x += 1;
+        }
+    }
+
+    /// Records that `text` was printed at the cursor position, followed by a newline.
+    pub(crate) fn print(&mut self, text: &str) {
+        self.write(text);
+        if !self.cells.is_empty() {
+            self.newline();
+        }
+    }
+
+    /// Records that the cursor was moved to `pos`, which must be within the screen.
+    pub(crate) fn locate(&mut self, pos: CharsXY) {
+        self.cursor = pos;
+    }
+
+    /// Records that the cursor was moved `off` cells within the current line.
+    pub(crate) fn move_within_line(&mut self, off: i16) {
+        let x = i32::from(self.cursor.x.min(self.size.x.saturating_sub(1))) + i32::from(off);
+        let max = i32::from(self.size.x.saturating_sub(1));
+        self.cursor.x = x.clamp(0, max) as u16;
+    }
+
+    /// Records that the screen was cleared as described by `how`.
+    pub(crate) fn clear(&mut self, how: &ClearType) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        let x = self.cursor.x.min(self.size.x - 1);
+        let line_start = self.offset(0, self.cursor.y);
+        let line_end = line_start + usize::from(self.size.x);
+        let cursor = self.offset(x, self.cursor.y);
+        match how {
+            ClearType::All => {
+                self.blank(0, self.cells.len());
+                self.cursor = CharsXY::new(0, 0);
+            }
+            ClearType::CurrentLine => self.blank(line_start, line_end),
+            ClearType::PreviousChar => {
+                if self.cursor.x > 0 {
+                    self.cursor.x = x.min(self.cursor.x - 1);
+                    let offset = self.offset(self.cursor.x, self.cursor.y);
+                    self.cells[offset] = ' ';
+                }
+            }
+            ClearType::UntilNewLine => self.blank(cursor, line_end),
+            ClearType::UntilEndOfScreen => self.blank(cursor, self.cells.len()),
+            ClearType::UntilBeginningOfScreen => self.blank(0, cursor + 1),
+        }
+    }
+
+    /// Records that the rectangle of `size` cells starting at `origin` was blanked.
+    ///
+    /// The console preserves the cursor position while doing so by saving and restoring it, which
+    /// overwrites any position saved with `save_cursor`.
+    pub(crate) fn clear_rect(&mut self, origin: CharsXY, size: CharsXY) {
+        self.saved_cursor = Some(self.cursor);
+        let x1 = origin.x.min(self.size.x);
+        let x2 = origin.x.saturating_add(size.x).min(self.size.x);
+        for y in origin.y..origin.y.saturating_add(size.y).min(self.size.y) {
+            let start = self.offset(x1, y);
+            let end = self.offset(x2, y);
+            self.blank(start, end);
+        }
+    }
+
+    /// Records that the cursor position was saved.
+    pub(crate) fn save_cursor(&mut self) {
+        self.saved_cursor = Some(self.cursor);
+    }
+
+    /// Records that the cursor was moved to the saved position, or to the top-left corner if
+    /// none was saved.
+    pub(crate) fn restore_cursor(&mut self) {
+        self.cursor = self.saved_cursor.unwrap_or_else(|| CharsXY::new(0, 0));
+    }
+
+    /// Records that the scroll region was set to the rows between `top` and `bottom`, both
+    /// inclusive.  Terminals move the cursor to the top-left corner when doing so.
+    pub(crate) fn set_scroll_region(&mut self, top: u16, bottom: u16) {
+        debug_assert!(top < bottom && bottom < self.size.y);
+        self.region = (top, bottom);
+        self.cursor = CharsXY::new(0, 0);
+    }
+
+    /// Records that the contents of the scroll region were scrolled up by `lines`.
+    pub(crate) fn scroll_up(&mut self, lines: u16) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        let (top, bottom) = self.region;
+        let width = usize::from(self.size.x);
+        let start = self.offset(0, top);
+        let end = self.offset(0, bottom) + width;
+        let shift = (usize::from(lines) * width).min(end - start);
+        self.cells[start..end].rotate_left(shift);
+        self.blank(end - shift, end);
+    }
+
+    /// Records that the terminal switched to the alternate screen, which starts blank.
+    pub(crate) fn enter_alt(&mut self) {
+        if self.main.is_some() {
+            return;
+        }
+        let blank = vec![' '; self.cells.len()];
+        self.main = Some((std::mem::replace(&mut self.cells, blank), self.cursor));
+    }
+
+    /// Records that the terminal returned to the main screen, restoring its contents.
+    pub(crate) fn leave_alt(&mut self) {
+        if let Some((cells, cursor)) = self.main.take() {
+            self.cells = cells;
+            self.cursor = cursor;
+        }
+    }
+
+    /// Returns the contents of the screen as one string per line, without trailing whitespace.
+    pub(crate) fn text(&self) -> Vec<String> {
+        if self.size.x == 0 {
+            return vec![String::new(); usize::from(self.size.y)];
+        }
+        self.cells
+            .chunks(usize::from(self.size.x))
+            .map(|row| row.iter().collect::<String>().trim_end().to_owned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadow_write_and_wrap() {
+        let mut screen = ShadowScreen::new(CharsXY::new(4, 3));
+        screen.write("abcdef");
+        assert_eq!(vec!["abcd", "ef", ""], screen.text());
+
+        screen.write("\nxy");
+        assert_eq!(vec!["abcd", "ef", "xy"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_full_line_does_not_wrap_until_next_write() {
+        let mut screen = ShadowScreen::new(CharsXY::new(4, 2));
+        screen.write("abcd");
+        screen.locate(CharsXY::new(0, 1));
+        screen.write("e");
+        assert_eq!(vec!["abcd", "e"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_print_scrolls() {
+        let mut screen = ShadowScreen::new(CharsXY::new(5, 2));
+        screen.print("one");
+        screen.print("two");
+        screen.print("three");
+        assert_eq!(vec!["three", ""], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_locate_overwrites() {
+        let mut screen = ShadowScreen::new(CharsXY::new(6, 2));
+        screen.write("hello");
+        screen.locate(CharsXY::new(1, 0));
+        screen.write("EL");
+        screen.locate(CharsXY::new(3, 1));
+        screen.write("x");
+        assert_eq!(vec!["hELlo", "   x"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_move_within_line() {
+        let mut screen = ShadowScreen::new(CharsXY::new(6, 1));
+        screen.write("abc");
+        screen.move_within_line(-2);
+        screen.write("X");
+        screen.move_within_line(10);
+        screen.write("Y");
+        screen.move_within_line(-10);
+        screen.write("Z");
+        assert_eq!(vec!["ZXc  Y"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_clear() {
+        let fill = || {
+            let mut screen = ShadowScreen::new(CharsXY::new(3, 3));
+            screen.write("abcdefghi");
+            screen.locate(CharsXY::new(1, 1));
+            screen
+        };
+
+        let mut screen = fill();
+        screen.clear(&ClearType::All);
+        assert_eq!(vec!["", "", ""], screen.text());
+        screen.write("x");
+        assert_eq!(vec!["x", "", ""], screen.text());
+
+        let mut screen = fill();
+        screen.clear(&ClearType::CurrentLine);
+        assert_eq!(vec!["abc", "", "ghi"], screen.text());
+
+        let mut screen = fill();
+        screen.clear(&ClearType::PreviousChar);
+        assert_eq!(vec!["abc", " ef", "ghi"], screen.text());
+
+        let mut screen = fill();
+        screen.clear(&ClearType::UntilNewLine);
+        assert_eq!(vec!["abc", "d", "ghi"], screen.text());
+
+        let mut screen = fill();
+        screen.clear(&ClearType::UntilEndOfScreen);
+        assert_eq!(vec!["abc", "d", ""], screen.text());
+
+        let mut screen = fill();
+        screen.clear(&ClearType::UntilBeginningOfScreen);
+        assert_eq!(vec!["", "  f", "ghi"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_clear_rect() {
+        let mut screen = ShadowScreen::new(CharsXY::new(4, 3));
+        screen.write("abcdefghijkl");
+        screen.locate(CharsXY::new(3, 2));
+        screen.clear_rect(CharsXY::new(1, 0), CharsXY::new(2, 2));
+        screen.clear_rect(CharsXY::new(3, 2), CharsXY::new(5, 5));
+        screen.write("x");
+        assert_eq!(vec!["a  d", "e  h", "ijkx"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_save_and_restore_cursor() {
+        let mut screen = ShadowScreen::new(CharsXY::new(4, 2));
+        screen.restore_cursor();
+        screen.write("a");
+        screen.locate(CharsXY::new(2, 1));
+        screen.save_cursor();
+        screen.locate(CharsXY::new(0, 0));
+        screen.write("b");
+        screen.restore_cursor();
+        screen.write("c");
+        assert_eq!(vec!["b", "  c"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_scroll_region() {
+        let mut screen = ShadowScreen::new(CharsXY::new(3, 4));
+        screen.write("aaabbbcccddd");
+        screen.set_scroll_region(1, 2);
+        screen.locate(CharsXY::new(0, 2));
+        screen.print("x");
+        assert_eq!(vec!["aaa", "xcc", "", "ddd"], screen.text());
+
+        screen.scroll_up(5);
+        assert_eq!(vec!["aaa", "", "", "ddd"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_alt_screen() {
+        let mut screen = ShadowScreen::new(CharsXY::new(3, 2));
+        screen.write("abc");
+        screen.enter_alt();
+        assert_eq!(vec!["", ""], screen.text());
+        screen.locate(CharsXY::new(0, 1));
+        screen.write("xyz");
+        assert_eq!(vec!["", "xyz"], screen.text());
+        screen.leave_alt();
+        assert_eq!(vec!["abc", ""], screen.text());
+        screen.write("d");
+        assert_eq!(vec!["abc", "d"], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_resize() {
+        let mut screen = ShadowScreen::new(CharsXY::new(3, 3));
+        screen.write("abcdefgh");
+        screen.resize(CharsXY::new(2, 2));
+        assert_eq!(vec!["ab", "de"], screen.text());
+        screen.resize(CharsXY::new(4, 3));
+        assert_eq!(vec!["ab", "de", ""], screen.text());
+        screen.print("x");
+        assert_eq!(vec!["ab", "dex", ""], screen.text());
+    }
+
+    #[test]
+    fn test_shadow_empty_screen() {
+        let mut screen = ShadowScreen::new(CharsXY::new(0, 0));
+        screen.print("foo");
+        screen.clear(&ClearType::All);
+        assert!(screen.text().is_empty());
+    }
+}
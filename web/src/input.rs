@@ -21,6 +21,7 @@ use async_trait::async_trait;
 use endbasic_core::exec::Signal;
 use endbasic_std::console::{graphics::InputOps, Key};
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::io;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
@@ -144,6 +145,7 @@ impl OnScreenKeyboard {
 pub struct WebInput {
     on_key_rx: Receiver<Key>,
     on_key_tx: Sender<Key>,
+    pending_keys: VecDeque<Key>,
     signals_tx: Sender<Signal>,
     yielder: Rc<RefCell<Yielder>>,
 }
@@ -152,7 +154,7 @@ impl WebInput {
     /// Creates a new `WebInput` that can inject events into the interpreter via `signals_tx`.
     pub(crate) fn new(signals_tx: Sender<Signal>, yielder: Rc<RefCell<Yielder>>) -> Self {
         let (on_key_tx, on_key_rx) = async_channel::unbounded();
-        Self { on_key_rx, on_key_tx, signals_tx, yielder }
+        Self { on_key_rx, on_key_tx, pending_keys: VecDeque::default(), signals_tx, yielder }
     }
 
     /// Generates a new `OnScreenKeyboard` that can inject key events.
@@ -162,6 +164,10 @@ impl WebInput {
 
     /// Gets the next key event, if one is available.
     pub(crate) async fn try_recv(&mut self) -> io::Result<Option<Key>> {
+        if let Some(k) = self.pending_keys.pop_front() {
+            self.yielder.borrow_mut().reset();
+            return Ok(Some(k));
+        }
         match self.on_key_rx.try_recv() {
             Ok(k) => {
                 self.yielder.borrow_mut().reset();
@@ -174,10 +180,26 @@ impl WebInput {
 
     /// Gets the next key event, waiting until one is available.
     pub(crate) async fn recv(&mut self) -> io::Result<Key> {
-        let key = self.on_key_rx.recv().await.unwrap();
+        let key = match self.pending_keys.pop_front() {
+            Some(k) => k,
+            None => self.on_key_rx.recv().await.unwrap(),
+        };
         self.yielder.borrow_mut().reset();
         Ok(key)
     }
+
+    /// Discards all pending key events except for those that request termination or interruption,
+    /// and returns how many were discarded.
+    pub(crate) fn drain(&mut self) -> io::Result<usize> {
+        let mut discarded = 0;
+        while let Ok(key) = self.on_key_rx.try_recv() {
+            match key {
+                Key::Eof | Key::Interrupt => self.pending_keys.push_back(key),
+                _ => discarded += 1,
+            }
+        }
+        Ok(discarded)
+    }
 }
 
 pub struct WebInputOps(pub WebInput);
@@ -191,4 +213,8 @@ impl InputOps for WebInputOps {
     async fn read_key(&mut self) -> io::Result<Key> {
         self.0.recv().await
     }
+
+    fn drain_keys(&mut self) -> io::Result<usize> {
+        self.0.drain()
+    }
 }